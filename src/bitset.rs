@@ -0,0 +1,163 @@
+use std::fs::File;
+use std::path::Path;
+
+use crate::{Extent, Flush, MapMut, Result};
+
+/// Fixed-size set of bits backed by a mapping.
+///
+/// The bits are stored least significant bit first within each byte, so bit
+/// `i` is `(byte[i / 8] >> (i % 8)) & 1`. When opened from a file, the bits
+/// are read from and written to the file, and [`.flush()`] makes changes
+/// durable. An anonymous bitset starts with every bit cleared.
+///
+/// # Examples
+///
+/// ```
+/// use vmap::MapBitset;
+/// use std::path::PathBuf;
+///
+/// # fn main() -> vmap::Result<()> {
+/// # let tmp = tempdir::TempDir::new("vmap")?;
+/// let path: PathBuf = /* path to file */
+/// # tmp.path().join("example");
+/// {
+///     let mut bits = MapBitset::open(&path, 100_000)?;
+///     bits.set(3, true);
+///     bits.set(99_999, true);
+///     bits.flush()?;
+/// }
+///
+/// let bits = MapBitset::open(&path, 100_000)?;
+/// assert!(bits.get(3));
+/// assert!(!bits.get(4));
+/// assert_eq!(bits.count_ones(), 2);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`.flush()`]: #method.flush
+#[derive(Debug)]
+pub struct MapBitset {
+    map: MapMut,
+    file: Option<File>,
+    len: usize,
+}
+
+impl MapBitset {
+    /// Creates an anonymous bitset of `len` bits, all initially cleared.
+    pub fn new(len: usize) -> Result<Self> {
+        Ok(Self {
+            map: MapMut::with_options().len(bytes(len)).alloc()?,
+            file: None,
+            len,
+        })
+    }
+
+    /// Opens or creates a file-backed bitset of `len` bits.
+    ///
+    /// The file is extended to hold `len` bits if it is shorter, and any
+    /// new bits are cleared. A longer file is left as is, and only the
+    /// first `len` bits are mapped.
+    pub fn open<P: AsRef<Path>>(path: P, len: usize) -> Result<Self> {
+        let (map, file) = MapMut::with_options()
+            .create(true)
+            .resize(Extent::Min(bytes(len)))
+            .len(bytes(len))
+            .open(path)?;
+        Ok(Self {
+            map,
+            file: Some(file),
+            len,
+        })
+    }
+
+    /// Gets the number of bits in the set.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Tests if the set holds zero bits.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Gets the value of bit `i`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` is not less than [`.len()`].
+    ///
+    /// [`.len()`]: #method.len
+    #[inline]
+    pub fn get(&self, i: usize) -> bool {
+        assert!(i < self.len, "bit index out of range");
+        self.map[i / 8] & (1 << (i % 8)) != 0
+    }
+
+    /// Sets the value of bit `i` and returns the previous value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` is not less than [`.len()`].
+    ///
+    /// [`.len()`]: #method.len
+    #[inline]
+    pub fn set(&mut self, i: usize, value: bool) -> bool {
+        assert!(i < self.len, "bit index out of range");
+        let byte = &mut self.map[i / 8];
+        let mask = 1 << (i % 8);
+        let old = *byte & mask != 0;
+        if value {
+            *byte |= mask;
+        } else {
+            *byte &= !mask;
+        }
+        old
+    }
+
+    /// Counts the number of set bits.
+    pub fn count_ones(&self) -> usize {
+        let full = self.len / 8;
+        let mut n: usize = self.map[..full]
+            .iter()
+            .map(|b| b.count_ones() as usize)
+            .sum();
+        let rem = self.len % 8;
+        if rem != 0 {
+            n += (self.map[full] & ((1 << rem) - 1)).count_ones() as usize;
+        }
+        n
+    }
+
+    /// Writes modified bits back to the file and waits for completion.
+    ///
+    /// This does nothing for an anonymous bitset.
+    pub fn flush(&self) -> Result<()> {
+        match self.file {
+            Some(ref file) => self.map.flush(file, Flush::Sync),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the modified bits in a range back to the file.
+    ///
+    /// Only the pages containing bits `start..start + len` are written.
+    /// This does nothing for an anonymous bitset.
+    pub fn flush_range(&self, start: usize, len: usize, mode: Flush) -> Result<()> {
+        match self.file {
+            Some(ref file) if len > 0 => {
+                let first = start / 8;
+                self.map
+                    .flush_range(file, first, bytes(start + len) - first, mode)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[inline]
+fn bytes(bits: usize) -> usize {
+    bits.div_ceil(8)
+}
@@ -0,0 +1,191 @@
+use std::ops::Range;
+
+use crate::{Error, Input, Map, MapMut, Operation, Result, Size};
+
+/// Read-only map with private copies of the pages that are written.
+///
+/// The base map is never modified. The first [`.write_page()`] of a page
+/// copies it into a private anonymous overlay, and every later access to that
+/// page is served from the copy, while unmodified pages continue to share the
+/// file pages of the base map. This suits versioned stores that start from a
+/// snapshot on disk and only pay for the pages a new version touches.
+///
+/// The overlay spans the whole map but is only allocated on the first write,
+/// and its pages are only committed as they are copied.
+///
+/// # Access Cost
+///
+/// A page from [`.page()`] is a direct slice of either the base map or the
+/// overlay, so access within a page costs a single bit test. The base and
+/// overlay pages live at different addresses, so there is no contiguous slice
+/// of the whole map. Reads that may cross pages use [`.read_at()`], which
+/// copies each page in turn into the caller's buffer.
+///
+/// # Examples
+///
+/// ```
+/// use vmap::{CowMap, Map};
+///
+/// # fn main() -> vmap::Result<()> {
+/// let (map, _) = Map::with_options().open("README.md")?;
+/// let first = map[0];
+/// let mut cow = CowMap::new(map);
+///
+/// cow.write_page(0)?[0] = b'!';
+/// assert_eq!(b'!', cow.page(0)[0]);
+/// assert_eq!(first, cow.base()[0]);
+/// assert!(cow.is_page_copied(0));
+/// assert_eq!(1, cow.copied_pages());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`.write_page()`]: #method.write_page
+/// [`.page()`]: #method.page
+/// [`.read_at()`]: #method.read_at
+#[derive(Debug)]
+pub struct CowMap {
+    base: Map,
+    overlay: Option<MapMut>,
+    copied: Vec<u64>,
+    page: usize,
+}
+
+impl CowMap {
+    /// Creates a copy-on-write view over a read-only map.
+    ///
+    /// No memory beyond the set of copied pages is allocated until the
+    /// first write.
+    pub fn new(base: Map) -> Self {
+        let page = Size::page().size(1);
+        let count = base.len().div_ceil(page);
+        Self {
+            base,
+            overlay: None,
+            copied: vec![0; count.div_ceil(64)],
+            page,
+        }
+    }
+
+    /// Gets the unmodified base map.
+    #[inline]
+    pub fn base(&self) -> &Map {
+        &self.base
+    }
+
+    /// Gets the length of the map in bytes.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.base.len()
+    }
+
+    /// Tests if the map is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.base.is_empty()
+    }
+
+    /// Gets the size of each page.
+    ///
+    /// Only the last page may hold fewer bytes, when the length of the map is
+    /// not a multiple of the page size.
+    #[inline]
+    pub fn page_size(&self) -> usize {
+        self.page
+    }
+
+    /// Gets the number of pages in the map.
+    #[inline]
+    pub fn page_count(&self) -> usize {
+        self.base.len().div_ceil(self.page)
+    }
+
+    /// Tests if page `idx` has been copied into the overlay.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is not less than [`.page_count()`].
+    ///
+    /// [`.page_count()`]: #method.page_count
+    #[inline]
+    pub fn is_page_copied(&self, idx: usize) -> bool {
+        assert!(idx < self.page_count(), "page index out of range");
+        self.copied[idx / 64] & (1 << (idx % 64)) != 0
+    }
+
+    /// Counts the pages that have been copied into the overlay.
+    pub fn copied_pages(&self) -> usize {
+        self.copied.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Gets the current contents of page `idx`.
+    ///
+    /// This is the overlay copy once the page has been written, and the base
+    /// page otherwise.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is not less than [`.page_count()`].
+    ///
+    /// [`.page_count()`]: #method.page_count
+    pub fn page(&self, idx: usize) -> &[u8] {
+        let range = self.page_range(idx);
+        match self.overlay {
+            Some(ref overlay) if self.is_page_copied(idx) => &overlay[range],
+            _ => &self.base[range],
+        }
+    }
+
+    /// Gets a writable copy of page `idx`.
+    ///
+    /// The first call for a page copies it from the base map into the
+    /// overlay, allocating the overlay if this is the first write. Later
+    /// calls return the same copy without copying again. An
+    /// [`Input::InvalidRange`] error is returned if `idx` is not less than
+    /// [`.page_count()`].
+    ///
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    /// [`.page_count()`]: #method.page_count
+    pub fn write_page(&mut self, idx: usize) -> Result<&mut [u8]> {
+        if idx >= self.page_count() {
+            return Err(Error::input(Operation::Write, Input::InvalidRange));
+        }
+        let range = self.page_range(idx);
+        if self.overlay.is_none() {
+            self.overlay = Some(MapMut::with_options().len(self.len()).alloc()?);
+        }
+        let copied = self.is_page_copied(idx);
+        let overlay = self.overlay.as_mut().unwrap();
+        if !copied {
+            overlay[range.clone()].copy_from_slice(&self.base[range.clone()]);
+            self.copied[idx / 64] |= 1 << (idx % 64);
+        }
+        Ok(&mut overlay[range])
+    }
+
+    /// Copies the current contents starting at byte `off` into `buf`.
+    ///
+    /// Each page is read from the overlay or the base map as in
+    /// [`.page()`]. Returns the number of bytes copied, which is less than
+    /// the length of `buf` only when the end of the map is reached.
+    ///
+    /// [`.page()`]: #method.page
+    pub fn read_at(&self, off: usize, buf: &mut [u8]) -> usize {
+        let end = self.len().min(off.saturating_add(buf.len()));
+        let mut pos = off;
+        while pos < end {
+            let idx = pos / self.page;
+            let start = pos - idx * self.page;
+            let n = (self.page - start).min(end - pos);
+            buf[pos - off..pos - off + n].copy_from_slice(&self.page(idx)[start..start + n]);
+            pos += n;
+        }
+        end.saturating_sub(off)
+    }
+
+    fn page_range(&self, idx: usize) -> Range<usize> {
+        assert!(idx < self.page_count(), "page index out of range");
+        let start = idx * self.page;
+        start..self.len().min(start + self.page)
+    }
+}
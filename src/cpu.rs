@@ -0,0 +1,117 @@
+//! Processor-level cache maintenance used by the mapping types.
+
+#[cfg(target_arch = "x86_64")]
+mod x86_64 {
+    use std::arch::asm;
+    use std::arch::x86_64::{
+        __cpuid_count, __m128i, _mm_clflush, _mm_loadu_si128, _mm_sfence, _mm_stream_si128,
+    };
+    use std::sync::atomic::{AtomicU8, Ordering};
+    use std::{cmp, ptr};
+
+    const LINE: usize = 64;
+
+    const UNKNOWN: u8 = 0;
+    const CLFLUSH: u8 = 1;
+    const CLFLUSHOPT: u8 = 2;
+    const CLWB: u8 = 3;
+
+    static WRITEBACK: AtomicU8 = AtomicU8::new(UNKNOWN);
+
+    fn writeback_kind() -> u8 {
+        match WRITEBACK.load(Ordering::Relaxed) {
+            UNKNOWN => {
+                // CPUID leaf 7 reports CLFLUSHOPT in EBX bit 23 and CLWB
+                // in EBX bit 24. CLFLUSH is part of the x86_64 baseline.
+                let ebx = __cpuid_count(7, 0).ebx;
+                let kind = if ebx & (1 << 24) != 0 {
+                    CLWB
+                } else if ebx & (1 << 23) != 0 {
+                    CLFLUSHOPT
+                } else {
+                    CLFLUSH
+                };
+                WRITEBACK.store(kind, Ordering::Relaxed);
+                kind
+            }
+            kind => kind,
+        }
+    }
+
+    pub unsafe fn writeback(ptr: *const u8, len: usize) -> bool {
+        let start = ptr as usize & !(LINE - 1);
+        let end = ptr as usize + len;
+        match writeback_kind() {
+            CLWB => {
+                for p in (start..end).step_by(LINE) {
+                    asm!("clwb [{}]", in(reg) p, options(nostack, preserves_flags));
+                }
+            }
+            CLFLUSHOPT => {
+                for p in (start..end).step_by(LINE) {
+                    asm!("clflushopt [{}]", in(reg) p, options(nostack, preserves_flags));
+                }
+            }
+            _ => {
+                for p in (start..end).step_by(LINE) {
+                    _mm_clflush(p as *const u8);
+                }
+            }
+        }
+        _mm_sfence();
+        true
+    }
+
+    pub unsafe fn copy_nontemporal(dst: *mut u8, src: *const u8, len: usize) {
+        // Streaming stores require a 16-byte aligned destination, so the
+        // unaligned head and any partial tail are copied normally.
+        let head = cmp::min(dst.align_offset(16), len);
+        ptr::copy_nonoverlapping(src, dst, head);
+        let mut off = head;
+        while len - off >= 16 {
+            let v = _mm_loadu_si128(src.add(off) as *const __m128i);
+            _mm_stream_si128(dst.add(off) as *mut __m128i, v);
+            off += 16;
+        }
+        ptr::copy_nonoverlapping(src.add(off), dst.add(off), len - off);
+        _mm_sfence();
+    }
+}
+
+/// Writes back the cache lines covering a byte range and fences the stores.
+///
+/// Returns `false` if the processor has no suitable instructions, in which
+/// case nothing was done and the caller must fall back to an OS flush.
+///
+/// # Safety
+///
+/// The range must be mapped memory.
+pub unsafe fn writeback(ptr: *const u8, len: usize) -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        self::x86_64::writeback(ptr, len)
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        let _ = (ptr, len);
+        false
+    }
+}
+
+/// Copies bytes using non-temporal stores that bypass the cache.
+///
+/// On processors without streaming stores this is a plain copy.
+///
+/// # Safety
+///
+/// Both ranges must be valid for `len` bytes and must not overlap.
+pub unsafe fn copy_nontemporal(dst: *mut u8, src: *const u8, len: usize) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        self::x86_64::copy_nontemporal(dst, src, len)
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        std::ptr::copy_nonoverlapping(src, dst, len)
+    }
+}
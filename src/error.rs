@@ -2,6 +2,8 @@
 
 use std::{fmt, io};
 
+use crate::Protect;
+
 /// A specialized `Result` type for map operations.
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -49,6 +51,40 @@ impl<F> From<(Error, F)> for Error {
 pub struct Error {
     repr: Repr,
     op: Operation,
+    ctx: Context,
+}
+
+// Each recorded value is only present when its bit is set in `has`, rather
+// than wrapping each in an `Option`, which keeps the error small enough to
+// return alongside a map in a `ConvertResult`.
+#[derive(Clone, Copy, Default)]
+struct Context {
+    has: u8,
+    protect: Option<Protect>,
+    offset: u64,
+    length: usize,
+    file_len: u64,
+}
+
+const HAS_OFFSET: u8 = 1;
+const HAS_LENGTH: u8 = 2;
+const HAS_FILE_LEN: u8 = 4;
+
+impl Context {
+    #[inline]
+    fn offset(&self) -> Option<u64> {
+        (self.has & HAS_OFFSET != 0).then_some(self.offset)
+    }
+
+    #[inline]
+    fn length(&self) -> Option<usize> {
+        (self.has & HAS_LENGTH != 0).then_some(self.length)
+    }
+
+    #[inline]
+    fn file_len(&self) -> Option<u64> {
+        (self.has & HAS_FILE_LEN != 0).then_some(self.file_len)
+    }
 }
 
 enum Repr {
@@ -77,6 +113,7 @@ impl Error {
         Self {
             repr: Repr::Io(err),
             op,
+            ctx: Context::default(),
         }
     }
 
@@ -99,6 +136,7 @@ impl Error {
         Self {
             repr: Repr::Input(input),
             op,
+            ctx: Context::default(),
         }
     }
 
@@ -122,6 +160,7 @@ impl Error {
         Self {
             repr: Repr::System(err),
             op,
+            ctx: Context::default(),
         }
     }
 
@@ -212,11 +251,34 @@ impl Error {
     pub fn kind(&self) -> io::ErrorKind {
         match self.repr {
             Repr::Io(ref err) => err.kind(),
-            Repr::Input(_) => io::ErrorKind::InvalidInput,
+            Repr::Input(ref input) => input.kind(),
             Repr::System(ref err) => err.kind(),
         }
     }
 
+    /// Returns the [`Input`] that caused the error, if it was not an OS error.
+    ///
+    /// This distinguishes errors in the requested input, such as a range that
+    /// lies outside of the file, from failures reported by the system.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Error, Input, Operation};
+    ///
+    /// let err = Error::input(Operation::MapFile, Input::InvalidRange);
+    /// assert_eq!(err.input_kind(), Some(Input::InvalidRange));
+    /// assert_eq!(Error::last_os_error(Operation::MapFile).input_kind(), None);
+    /// ```
+    ///
+    /// [`Input`]: enum.Input.html
+    pub fn input_kind(&self) -> Option<Input> {
+        match self.repr {
+            Repr::Input(input) => Some(input),
+            _ => None,
+        }
+    }
+
     /// Returns the corresponding [`Operation`] that cuased the error.
     ///
     /// # Examples
@@ -236,6 +298,124 @@ impl Error {
     pub fn operation(&self) -> Operation {
         self.op
     }
+
+    /// Records the offset involved in the failed operation.
+    ///
+    /// For file mappings this is the offset into the file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Error, Operation};
+    ///
+    /// let err = Error::last_os_error(Operation::MapFile).with_offset(4096);
+    /// assert_eq!(err.offset(), Some(4096));
+    /// ```
+    pub fn with_offset(mut self, offset: u64) -> Self {
+        self.ctx.offset = offset;
+        self.ctx.has |= HAS_OFFSET;
+        self
+    }
+
+    /// Records the length involved in the failed operation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Error, Operation};
+    ///
+    /// let err = Error::last_os_error(Operation::MapFile).with_length(100);
+    /// assert_eq!(err.length(), Some(100));
+    /// ```
+    pub fn with_length(mut self, length: usize) -> Self {
+        self.ctx.length = length;
+        self.ctx.has |= HAS_LENGTH;
+        self
+    }
+
+    /// Records the length of the file involved in the failed operation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Error, Input, Operation};
+    ///
+    /// let err = Error::input(Operation::MapFile, Input::InvalidRange).with_file_len(8);
+    /// assert_eq!(err.file_len(), Some(8));
+    /// ```
+    pub fn with_file_len(mut self, file_len: u64) -> Self {
+        self.ctx.file_len = file_len;
+        self.ctx.has |= HAS_FILE_LEN;
+        self
+    }
+
+    /// Records the [`Protect`] level involved in the failed operation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Error, Operation, Protect};
+    ///
+    /// let err = Error::last_os_error(Operation::Protect).with_protect(Protect::ReadWrite);
+    /// assert_eq!(err.protect(), Some(Protect::ReadWrite));
+    /// ```
+    ///
+    /// [`Protect`]: enum.Protect.html
+    pub fn with_protect(mut self, protect: Protect) -> Self {
+        self.ctx.protect = Some(protect);
+        self
+    }
+
+    /// Returns the offset involved in the failed operation, if recorded.
+    ///
+    /// Errors from mapping a file record the offset into the file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::Map;
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// # std::fs::write(&path, b"this is a test")?;
+    /// // Mapping a file that is not open for writing as writable fails.
+    /// let file = std::fs::File::open(&path)?;
+    /// let err = Map::with_options().offset(5).write().map(&file).unwrap_err();
+    /// assert_eq!(err.offset(), Some(0));
+    /// assert_eq!(err.length(), Some(14));
+    /// println!("{}", err);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn offset(&self) -> Option<u64> {
+        self.ctx.offset()
+    }
+
+    /// Returns the length involved in the failed operation, if recorded.
+    pub fn length(&self) -> Option<usize> {
+        self.ctx.length()
+    }
+
+    /// Returns the length of the file involved in the failed operation, if
+    /// recorded.
+    ///
+    /// Mapping a range that lies outside of a file records the length of the
+    /// file along with the requested offset and length, so the file may be
+    /// extended before trying again.
+    pub fn file_len(&self) -> Option<u64> {
+        self.ctx.file_len()
+    }
+
+    /// Returns the [`Protect`] level involved in the failed operation, if
+    /// recorded.
+    ///
+    /// [`Protect`]: enum.Protect.html
+    pub fn protect(&self) -> Option<Protect> {
+        self.ctx.protect
+    }
 }
 
 impl std::error::Error for Error {
@@ -253,6 +433,7 @@ impl From<std::io::Error> for Error {
         Self {
             repr: Repr::Io(err),
             op: Operation::None,
+            ctx: Context::default(),
         }
     }
 }
@@ -261,7 +442,7 @@ impl From<Error> for std::io::Error {
     fn from(err: Error) -> Self {
         match err.repr {
             Repr::Io(io) => io,
-            Repr::Input(v) => Self::new(io::ErrorKind::InvalidInput, v.as_str()),
+            Repr::Input(v) => Self::new(v.kind(), v.as_str()),
             Repr::System(sys) => sys.into(),
         }
     }
@@ -274,11 +455,23 @@ impl fmt::Debug for Error {
             Repr::Input(ref input) => ("input", input as &dyn fmt::Debug),
             Repr::System(ref err) => ("system", err as &dyn fmt::Debug),
         };
-        fmt.debug_struct("Error")
-            .field("op", &self.op)
+        let mut dbg = fmt.debug_struct("Error");
+        dbg.field("op", &self.op)
             .field("kind", &self.kind())
-            .field(field, value)
-            .finish()
+            .field(field, value);
+        if let Some(offset) = self.ctx.offset() {
+            dbg.field("offset", &offset);
+        }
+        if let Some(length) = self.ctx.length() {
+            dbg.field("length", &length);
+        }
+        if let Some(file_len) = self.ctx.file_len() {
+            dbg.field("file_len", &file_len);
+        }
+        if let Some(protect) = self.ctx.protect {
+            dbg.field("protect", &protect);
+        }
+        dbg.finish()
     }
 }
 
@@ -290,10 +483,37 @@ impl fmt::Display for Error {
             Repr::System(ref err) => err as &dyn fmt::Display,
         };
         if let Some(op) = self.op.as_str() {
-            write!(fmt, "failed to {}, {}", op, value)
+            write!(fmt, "failed to {}, {}", op, value)?;
         } else {
-            value.fmt(fmt)
+            value.fmt(fmt)?;
         }
+        self.ctx.fmt(fmt)
+    }
+}
+
+impl fmt::Display for Context {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut sep = " (";
+        if let Some(offset) = self.offset() {
+            write!(fmt, "{}offset {}", sep, offset)?;
+            sep = ", ";
+        }
+        if let Some(length) = self.length() {
+            write!(fmt, "{}length {}", sep, length)?;
+            sep = ", ";
+        }
+        if let Some(file_len) = self.file_len() {
+            write!(fmt, "{}file length {}", sep, file_len)?;
+            sep = ", ";
+        }
+        if let Some(protect) = self.protect {
+            write!(fmt, "{}protect {:?}", sep, protect)?;
+            sep = ", ";
+        }
+        if sep == ", " {
+            fmt.write_str(")")?;
+        }
+        Ok(())
     }
 }
 
@@ -330,12 +550,28 @@ pub enum Operation {
     ///
     /// [`Advise`]: ../enum.Advise.html
     Advise,
+    /// The [`FAdvise`] could not be applied to the provided file range.
+    ///
+    /// [`FAdvise`]: ../enum.FAdvise.html
+    AdviseFile,
+    /// Readahead could not be initiated for the provided file range.
+    Readahead,
     /// The physical page could not be locked into memory.
     Lock,
     /// The physical page could not be unlocked from memory.
     Unlock,
+    /// The physical pages could not be bound to a NUMA node.
+    Bind,
+    /// An advisory lock could not be acquired for a file.
+    LockFile,
+    /// An advisory lock could not be released for a file.
+    UnlockFile,
     /// A flush cannot be perfomed for the provided input.
     Flush,
+    /// The bytes could not be read from the mapped memory.
+    Read,
+    /// The bytes could not be written to the mapped memory.
+    Write,
     /// The full address space for a ring could not be allocated.
     RingAllocate,
     /// The full address space for a ring could not be deallocated.
@@ -348,6 +584,32 @@ pub enum Operation {
     RingSecondary,
     /// A temporary memory file descriptor failed to open.
     MemoryFd,
+    /// The block size for a file could not be determined.
+    BlockSize,
+    /// A signal handler could not be installed.
+    Signal,
+    /// The soft-dirty bits of the process pages could not be read or cleared.
+    SoftDirty,
+    /// The map could not be viewed as a slice of a type.
+    Cast,
+    /// The address space for a growable map could not be reserved.
+    Reserve,
+    /// Pages of a growable map could not be committed.
+    Commit,
+    /// The address space of a growable map could not be released.
+    Release,
+    /// The residency of pages in memory could not be queried.
+    Residency,
+    /// The page fault counts of the process could not be queried.
+    PageFaults,
+    /// The huge page size of the system could not be determined.
+    HugePageSize,
+    /// A named shared memory object could not be created, opened, or removed.
+    SharedMemory,
+    /// Writes to a map could not be tracked.
+    TrackWrites,
+    /// A temporary file could not be created.
+    TempFile,
     /// Used for pure I/O errors to simplify wrapping a `std::io::Error` into an
     ///
     /// [`Error`]: struct.Error.html
@@ -382,15 +644,35 @@ impl Operation {
             Operation::Unmap => Some("unmap"),
             Operation::Protect => Some("protect mapped memory"),
             Operation::Advise => Some("advise mapped memory"),
+            Operation::AdviseFile => Some("advise file"),
+            Operation::Readahead => Some("read ahead file"),
             Operation::Lock => Some("lock mapped memory"),
             Operation::Unlock => Some("unlock mapped memory"),
+            Operation::Bind => Some("bind mapped memory to node"),
+            Operation::LockFile => Some("lock file"),
+            Operation::UnlockFile => Some("unlock file"),
             Operation::Flush => Some("flush mapped memory"),
+            Operation::Read => Some("read mapped memory"),
+            Operation::Write => Some("write mapped memory"),
             Operation::RingAllocate => Some("allocate full ring"),
             Operation::RingDeallocate => Some("deallocate full ring"),
             Operation::RingEntry => Some("make ring memory entry"),
             Operation::RingPrimary => Some("map ring first half"),
             Operation::RingSecondary => Some("map ring second half"),
             Operation::MemoryFd => Some("open memory fd"),
+            Operation::BlockSize => Some("query block size"),
+            Operation::Signal => Some("install signal handler"),
+            Operation::SoftDirty => Some("track dirty pages"),
+            Operation::Cast => Some("cast map to typed slice"),
+            Operation::Reserve => Some("reserve address space"),
+            Operation::Commit => Some("commit reserved pages"),
+            Operation::Release => Some("release address space"),
+            Operation::Residency => Some("query resident pages"),
+            Operation::PageFaults => Some("query page faults"),
+            Operation::HugePageSize => Some("query huge page size"),
+            Operation::SharedMemory => Some("open shared memory"),
+            Operation::TrackWrites => Some("track writes"),
+            Operation::TempFile => Some("create temporary file"),
             Operation::None => None,
         }
     }
@@ -415,6 +697,20 @@ impl fmt::Display for Operation {
 pub enum Input {
     /// The range of the requested file or bytes is invalid.
     InvalidRange,
+    /// The requested offset or length is not aligned to the required size.
+    Unaligned,
+    /// The requested behavior is not supported by the platform or resource.
+    Unsupported,
+    /// The underlying file was truncated while mapped.
+    Truncated,
+    /// The system returned a null or failed pointer for a mapping.
+    InvalidPointer,
+    /// The map is shared with other clones and cannot be replaced.
+    Shared,
+    /// The requested address is already in use by another mapping.
+    AddressInUse,
+    /// The name of a shared memory object is empty or holds a separator.
+    InvalidName,
 }
 
 impl Input {
@@ -437,6 +733,22 @@ impl Input {
     pub fn as_str(&self) -> &'static str {
         match *self {
             Input::InvalidRange => "invalid range",
+            Input::Unaligned => "unaligned range",
+            Input::Unsupported => "unsupported",
+            Input::Truncated => "file truncated",
+            Input::InvalidPointer => "invalid pointer",
+            Input::Shared => "map is shared",
+            Input::AddressInUse => "address in use",
+            Input::InvalidName => "invalid name",
+        }
+    }
+
+    fn kind(&self) -> io::ErrorKind {
+        match *self {
+            Input::Unsupported => io::ErrorKind::Unsupported,
+            Input::Truncated => io::ErrorKind::UnexpectedEof,
+            Input::AddressInUse => io::ErrorKind::AddrInUse,
+            _ => io::ErrorKind::InvalidInput,
         }
     }
 }
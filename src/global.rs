@@ -0,0 +1,173 @@
+use std::alloc::{GlobalAlloc, Layout};
+use std::hint;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+
+use crate::os::{map_anon, unmap};
+use crate::{track_map, track_unmap, Protect, Size};
+
+// Small allocations are served from size classes of 16 bytes up to 2048
+// bytes, each a power of two, so that every block is aligned to its size.
+const MIN_SHIFT: u32 = 4;
+const CLASSES: usize = 8;
+const MAX_SMALL: usize = 1 << (MIN_SHIFT as usize + CLASSES - 1);
+
+struct Class {
+    lock: AtomicBool,
+    free: AtomicPtr<u8>,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY: Class = Class {
+    lock: AtomicBool::new(false),
+    free: AtomicPtr::new(ptr::null_mut()),
+};
+
+/// Global allocator that takes memory directly from the system with
+/// anonymous mappings.
+///
+/// Allocations larger than 2048 bytes each receive their own mapping, which
+/// is returned to the system when deallocated. Smaller allocations are
+/// rounded up to a power of two and carved from shared mappings, one
+/// allocation unit at a time. Freed small blocks are reused for
+/// allocations of the same size but are never returned to the system.
+///
+/// Large allocations are aligned to the allocation granularity of the system
+/// and small allocations are aligned to their rounded size. A layout with an
+/// alignment larger than the allocation granularity cannot be satisfied and
+/// yields a null pointer.
+///
+/// This is intended for coarse allocations in embedded or sandboxed
+/// environments where memory should come straight from the system. Each
+/// large allocation costs at least a whole page and a system call, so this
+/// is much slower than the default allocator for workloads with many small
+/// or short-lived allocations.
+///
+/// This is only defined with the `alloc` feature, which is not enabled by
+/// default.
+///
+/// # Examples
+///
+/// ```
+/// use vmap::MmapAlloc;
+///
+/// #[global_allocator]
+/// static GLOBAL: MmapAlloc = MmapAlloc::new();
+///
+/// let small = vec![1u8; 100];
+/// let large = vec![2u64; 100_000];
+/// assert_eq!(small.as_ptr() as usize % 128, 0);
+/// assert_eq!(large.as_ptr() as usize % vmap::page_size(), 0);
+/// ```
+pub struct MmapAlloc {
+    classes: [Class; CLASSES],
+}
+
+impl MmapAlloc {
+    /// Creates a new allocator.
+    pub const fn new() -> Self {
+        Self {
+            classes: [EMPTY; CLASSES],
+        }
+    }
+
+    fn class(layout: &Layout) -> Option<usize> {
+        let size = layout.size().max(layout.align());
+        if size > MAX_SMALL {
+            None
+        } else {
+            let shift = size.next_power_of_two().trailing_zeros().max(MIN_SHIFT);
+            Some((shift - MIN_SHIFT) as usize)
+        }
+    }
+
+    unsafe fn alloc_small(&self, idx: usize) -> *mut u8 {
+        let class = &self.classes[idx];
+        class.lock();
+        let head = class.free.load(Ordering::Relaxed);
+        if !head.is_null() {
+            class.free.store(*(head as *mut *mut u8), Ordering::Relaxed);
+            class.unlock();
+            return head;
+        }
+        class.unlock();
+
+        // Refill with a new chunk, keeping the first block and pushing the
+        // rest onto the free list.
+        let chunk = Size::alloc().size(1);
+        let pg = match map_anon(chunk, Protect::ReadWrite) {
+            Ok(pg) => pg,
+            Err(_) => return ptr::null_mut(),
+        };
+        track_map(chunk);
+        let block = 1 << (idx as u32 + MIN_SHIFT);
+        class.lock();
+        for off in (block..chunk).step_by(block) {
+            let p = pg.add(off);
+            *(p as *mut *mut u8) = class.free.load(Ordering::Relaxed);
+            class.free.store(p, Ordering::Relaxed);
+        }
+        class.unlock();
+        pg
+    }
+
+    unsafe fn dealloc_small(&self, ptr: *mut u8, idx: usize) {
+        let class = &self.classes[idx];
+        class.lock();
+        *(ptr as *mut *mut u8) = class.free.load(Ordering::Relaxed);
+        class.free.store(ptr, Ordering::Relaxed);
+        class.unlock();
+    }
+}
+
+impl Default for MmapAlloc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Class {
+    #[inline]
+    fn lock(&self) {
+        while self
+            .lock
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            hint::spin_loop();
+        }
+    }
+
+    #[inline]
+    fn unlock(&self) {
+        self.lock.store(false, Ordering::Release);
+    }
+}
+
+unsafe impl GlobalAlloc for MmapAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if let Some(idx) = Self::class(&layout) {
+            return self.alloc_small(idx);
+        }
+        if layout.align() > Size::alloc().size(1) {
+            return ptr::null_mut();
+        }
+        let len = Size::page().round(layout.size());
+        match map_anon(len, Protect::ReadWrite) {
+            Ok(pg) => {
+                track_map(Size::alloc().round(len));
+                pg
+            }
+            Err(_) => ptr::null_mut(),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if let Some(idx) = Self::class(&layout) {
+            return self.dealloc_small(ptr, idx);
+        }
+        let len = Size::page().round(layout.size());
+        unmap(ptr, len).unwrap_or_default();
+        track_unmap(Size::alloc().round(len));
+    }
+}
@@ -0,0 +1,210 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+use std::{fmt, mem, slice};
+
+use crate::{Error, Input, MapMut, Operation, Result, Size};
+
+/// Group of fixed-size slots carved from a single anonymous mapping.
+///
+/// Creating thousands of small maps gives the kernel thousands of separate
+/// mappings to track, which slows down every later mapping change, `fork`,
+/// and process teardown. A group instead maps one private region, so the
+/// whole group is a single kernel mapping no matter how many slots are in
+/// use, and dropping the group unmaps all of it at once. The slots are
+/// borrowed from the group rather than mapped independently, so they cannot
+/// be protected, remapped, or outlive the group.
+///
+/// Slots are taken with [`.alloc()`] from a shared reference, so a group
+/// may hand out slots to several threads. A slot is returned to the group
+/// when it is dropped, and its contents are not cleared. Slots that have
+/// never been handed out are not touched, so their memory is only committed
+/// as it is used.
+///
+/// # Examples
+///
+/// ```
+/// use vmap::MapGroup;
+///
+/// # fn main() -> vmap::Result<()> {
+/// let group = MapGroup::new(100, 1000)?;
+/// let mut a = group.alloc().unwrap();
+/// let mut b = group.alloc().unwrap();
+/// a[..4].copy_from_slice(b"ping");
+/// b[..4].copy_from_slice(b"pong");
+/// assert_eq!(b"ping", &a[..4]);
+/// assert_eq!(2, group.used());
+///
+/// drop(a);
+/// assert_eq!(1, group.used());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`.alloc()`]: #method.alloc
+pub struct MapGroup {
+    map: MapMut,
+    ptr: *mut u8,
+    slot_size: usize,
+    slot_count: usize,
+    state: Mutex<State>,
+}
+
+struct State {
+    // Slots that were handed out and returned.
+    free: Vec<usize>,
+    // Index of the first slot that has never been handed out.
+    fresh: usize,
+}
+
+// The slots handed out never overlap, and the free list is only changed
+// while holding the lock.
+unsafe impl Send for MapGroup {}
+unsafe impl Sync for MapGroup {}
+
+impl MapGroup {
+    /// Creates a group of `slot_count` slots of `slot_size` bytes each.
+    ///
+    /// The slot size is rounded up to a multiple of the pointer size so that
+    /// every slot is aligned. The mapping is rounded up to the allocation
+    /// size.
+    pub fn new(slot_size: usize, slot_count: usize) -> Result<Self> {
+        let align = mem::size_of::<usize>();
+        let slot_size = match slot_size.max(1).checked_add(align - 1) {
+            Some(sz) => sz & !(align - 1),
+            None => return Err(Error::input(Operation::MapAnonymous, Input::InvalidRange)),
+        };
+        let len = match slot_size.checked_mul(slot_count) {
+            Some(len) => Size::alloc().round(len),
+            None => return Err(Error::input(Operation::MapAnonymous, Input::InvalidRange)),
+        };
+        let mut map = MapMut::with_options().copy().len(len).alloc()?;
+        Ok(Self {
+            ptr: map.as_mut_ptr(),
+            map,
+            slot_size,
+            slot_count,
+            state: Mutex::new(State {
+                free: Vec::new(),
+                fresh: 0,
+            }),
+        })
+    }
+
+    /// Gets the size in bytes of each slot.
+    #[inline]
+    pub fn slot_size(&self) -> usize {
+        self.slot_size
+    }
+
+    /// Gets the total number of slots in the group.
+    #[inline]
+    pub fn slot_count(&self) -> usize {
+        self.slot_count
+    }
+
+    /// Gets the number of slots currently handed out.
+    pub fn used(&self) -> usize {
+        let state = self.state();
+        state.fresh - state.free.len()
+    }
+
+    /// Gets the pointer to the start of the first slot.
+    ///
+    /// Slot `i` starts `i * slot_size()` bytes after this pointer.
+    #[inline]
+    pub fn as_ptr(&self) -> *const u8 {
+        self.ptr
+    }
+
+    /// Takes a slot from the group.
+    ///
+    /// Returns `None` when every slot is in use. The slot is returned to the
+    /// group when dropped.
+    pub fn alloc(&self) -> Option<MapGroupSlot<'_>> {
+        let mut state = self.state();
+        let idx = match state.free.pop() {
+            Some(idx) => idx,
+            None if state.fresh < self.slot_count => {
+                state.fresh += 1;
+                state.fresh - 1
+            }
+            None => return None,
+        };
+        Some(MapGroupSlot {
+            group: self,
+            idx,
+            ptr: unsafe { self.ptr.add(idx * self.slot_size) },
+        })
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl fmt::Debug for MapGroup {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("MapGroup")
+            .field("map", &self.map)
+            .field("slot_size", &self.slot_size)
+            .field("slot_count", &self.slot_count)
+            .field("used", &self.used())
+            .finish()
+    }
+}
+
+/// Slot borrowed from a [`MapGroup`].
+///
+/// This dereferences to the bytes of the slot and returns the slot to its
+/// group when dropped.
+///
+/// [`MapGroup`]: struct.MapGroup.html
+pub struct MapGroupSlot<'a> {
+    group: &'a MapGroup,
+    idx: usize,
+    ptr: *mut u8,
+}
+
+// A slot is an exclusive borrow of its bytes, like `&mut [u8]`.
+unsafe impl Send for MapGroupSlot<'_> {}
+unsafe impl Sync for MapGroupSlot<'_> {}
+
+impl MapGroupSlot<'_> {
+    /// Gets the index of the slot within its group.
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.idx
+    }
+}
+
+impl Deref for MapGroupSlot<'_> {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr, self.group.slot_size) }
+    }
+}
+
+impl DerefMut for MapGroupSlot<'_> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.ptr, self.group.slot_size) }
+    }
+}
+
+impl Drop for MapGroupSlot<'_> {
+    fn drop(&mut self) {
+        self.group.state().free.push(self.idx);
+    }
+}
+
+impl fmt::Debug for MapGroupSlot<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("MapGroupSlot")
+            .field("index", &self.idx)
+            .field("ptr", &self.ptr)
+            .field("len", &self.group.slot_size)
+            .finish()
+    }
+}
@@ -0,0 +1,52 @@
+use std::fmt;
+
+/// Hexdump of a byte range in the classic offset, hex, and ASCII format.
+///
+/// Each line shows the offset of its first byte, up to 16 bytes in hex split
+/// into two groups of 8, and the same bytes as ASCII with non-printable bytes
+/// shown as `.`. The lines are written one at a time as the value is
+/// formatted, so a dump of a large map is never built up in memory.
+///
+/// This is created with [`Span::hexdump()`] or [`Span::hexdump_range()`].
+///
+/// [`Span::hexdump()`]: trait.Span.html#method.hexdump
+/// [`Span::hexdump_range()`]: trait.Span.html#method.hexdump_range
+#[derive(Clone, Copy, Debug)]
+pub struct Hexdump<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Hexdump<'a> {
+    pub(crate) fn new(bytes: &'a [u8], offset: usize) -> Self {
+        Self { bytes, offset }
+    }
+}
+
+impl fmt::Display for Hexdump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (n, line) in self.bytes.chunks(16).enumerate() {
+            write!(f, "{:08x}  ", self.offset + n * 16)?;
+            for i in 0..16 {
+                match line.get(i) {
+                    Some(b) => write!(f, "{:02x} ", b)?,
+                    None => f.write_str("   ")?,
+                }
+                if i == 7 {
+                    f.write_str(" ")?;
+                }
+            }
+            f.write_str(" |")?;
+            for &b in line {
+                let c = if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                };
+                write!(f, "{}", c)?;
+            }
+            f.write_str("|\n")?;
+        }
+        Ok(())
+    }
+}
@@ -89,7 +89,7 @@ impl<R: Read> BufReader<R> {
     /// Returns a reference to the internally buffered data.
     #[inline]
     pub fn buffer(&self) -> &[u8] {
-        self.buf.as_read_slice(std::usize::MAX)
+        self.buf.as_read_slice(usize::MAX)
     }
 
     /// Unwraps this `BufReader`, returning the underlying reader.
@@ -180,7 +180,7 @@ impl<R: Read + Write> Write for BufReader<R> {
 impl<R: Read> BufRead for BufReader<R> {
     fn fill_buf(&mut self) -> io::Result<&[u8]> {
         if self.buf.read_len() <= self.lowat {
-            let n = self.inner.read(self.buf.as_write_slice(std::usize::MAX))?;
+            let n = self.inner.read(self.buf.as_write_slice(usize::MAX))?;
             self.buf.feed(n);
         }
         Ok(self.buffer())
@@ -360,7 +360,7 @@ impl<W: Write> BufWriter<W> {
             }
 
             self.panicked = true;
-            let r = self.inner.write(self.buf.as_read_slice(std::usize::MAX));
+            let r = self.inner.write(self.buf.as_read_slice(usize::MAX));
             self.panicked = false;
 
             match r {
@@ -0,0 +1,189 @@
+use crate::{Span, SpanMut};
+
+use std::cmp;
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
+
+/// Seekable reader and writer over the bytes of a map.
+///
+/// This is like [`std::io::Cursor`], but the length is fixed at the mapped
+/// length of the inner map. Reads stop at the end of the map, returning
+/// `Ok(0)` once it is reached. Writes never extend the map: a write that
+/// crosses the end is cut short, and a write at or past the end returns
+/// `Ok(0)`, so `write_all` fails with [`io::ErrorKind::WriteZero`]. The
+/// position may be seeked past the end, after which reads and writes
+/// transfer nothing, but seeking before the start is an error.
+///
+/// The inner value may be any [`Span`], such as a [`Map`], a
+/// [`MapSegment`], or a borrowed slice, and writing requires a [`SpanMut`]
+/// such as a [`MapMut`]. Use a reference to the map, such as
+/// `MapCursor::new(&mut *map)`, to keep ownership of it. With the `bytes`
+/// feature, the cursor also implements `bytes::Buf`, reading from the
+/// mapped bytes without copying them.
+///
+/// # Examples
+///
+/// ```
+/// use vmap::MapMut;
+/// use vmap::io::MapCursor;
+/// use std::io::{Read, Seek, SeekFrom, Write};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let map = MapMut::with_options().len(16).alloc()?;
+/// let mut cur = MapCursor::new(map);
+/// write!(cur, "hello, world")?;
+///
+/// cur.seek(SeekFrom::Start(7))?;
+/// let mut word = [0; 5];
+/// cur.read_exact(&mut word)?;
+/// assert_eq!(b"world", &word);
+///
+/// let end = cur.seek(SeekFrom::End(0))?;
+/// assert_eq!(16, end);
+/// assert!(cur.write_all(b"!").is_err());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`std::io::Cursor`]: https://doc.rust-lang.org/std/io/struct.Cursor.html
+/// [`io::ErrorKind::WriteZero`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.WriteZero
+/// [`Span`]: ../trait.Span.html
+/// [`SpanMut`]: ../trait.SpanMut.html
+/// [`Map`]: ../struct.Map.html
+/// [`MapMut`]: ../struct.MapMut.html
+/// [`MapSegment`]: ../struct.MapSegment.html
+#[derive(Debug, Clone)]
+pub struct MapCursor<T> {
+    inner: T,
+    pos: u64,
+}
+
+impl<T: Span> MapCursor<T> {
+    /// Creates a cursor over the inner map starting at position `0`.
+    pub fn new(inner: T) -> Self {
+        Self { inner, pos: 0 }
+    }
+
+    /// Transfers ownership of the inner map back to the caller.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Gets a reference to the inner map.
+    #[inline]
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the inner map.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Gets the current position of the cursor.
+    #[inline]
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// Sets the position of the cursor.
+    ///
+    /// The position may be past the end of the map.
+    #[inline]
+    pub fn set_position(&mut self, pos: u64) {
+        self.pos = pos;
+    }
+
+    /// Gets the bytes from the current position to the end of the map.
+    ///
+    /// This is empty when the position is at or past the end.
+    #[inline]
+    pub fn remaining_slice(&self) -> &[u8] {
+        &self.inner[self.offset()..]
+    }
+
+    // Gets the position as an offset clamped to the length of the map.
+    #[inline]
+    fn offset(&self) -> usize {
+        cmp::min(self.pos, self.inner.len() as u64) as usize
+    }
+}
+
+impl<T: Span> Read for MapCursor<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let src = self.remaining_slice();
+        let len = cmp::min(src.len(), buf.len());
+        buf[..len].copy_from_slice(&src[..len]);
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+impl<T: Span> BufRead for MapCursor<T> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        Ok(self.remaining_slice())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt as u64;
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl<T: Span> bytes::Buf for MapCursor<T> {
+    #[inline]
+    fn remaining(&self) -> usize {
+        self.inner.len() - self.offset()
+    }
+
+    #[inline]
+    fn chunk(&self) -> &[u8] {
+        self.remaining_slice()
+    }
+
+    fn advance(&mut self, cnt: usize) {
+        assert!(
+            cnt <= self.remaining(),
+            "cannot advance past the end of the map"
+        );
+        self.pos = (self.offset() + cnt) as u64;
+    }
+}
+
+impl<T: SpanMut> Write for MapCursor<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let off = self.offset();
+        let dst = &mut self.inner[off..];
+        let len = cmp::min(dst.len(), buf.len());
+        dst[..len].copy_from_slice(&buf[..len]);
+        self.pos += len as u64;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<T: Span> Seek for MapCursor<T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, off) = match pos {
+            SeekFrom::Start(n) => {
+                self.pos = n;
+                return Ok(n);
+            }
+            SeekFrom::End(n) => (self.inner.len() as u64, n),
+            SeekFrom::Current(n) => (self.pos, n),
+        };
+        match base.checked_add_signed(off) {
+            Some(n) => {
+                self.pos = n;
+                Ok(n)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
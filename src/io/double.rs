@@ -0,0 +1,74 @@
+use crate::{MapMut, Result};
+
+use std::mem;
+
+/// Pair of equally sized buffers where one is filled while the other is read.
+///
+/// The "back" buffer is written to by a producer while the "front" buffer is
+/// read by a consumer. Once the back buffer is complete, a [`.swap()`]
+/// exchanges the roles of the two buffers. The swap only exchanges the
+/// mappings, so no bytes are copied.
+///
+/// # Examples
+///
+/// ```
+/// use vmap::io::DoubleBuffer;
+///
+/// # fn main() -> vmap::Result<()> {
+/// let mut buf = DoubleBuffer::new(4)?;
+/// buf.back_mut().copy_from_slice(b"test");
+/// assert_eq!(b"\0\0\0\0", buf.front());
+///
+/// buf.swap();
+/// assert_eq!(b"test", buf.front());
+///
+/// buf.back_mut().copy_from_slice(b"next");
+/// buf.swap();
+/// assert_eq!(b"next", buf.front());
+/// assert_eq!(b"test", buf.back());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`.swap()`]: #method.swap
+#[derive(Debug)]
+pub struct DoubleBuffer {
+    front: MapMut,
+    back: MapMut,
+}
+
+impl DoubleBuffer {
+    /// Constructs a new double buffer with two anonymous allocations.
+    ///
+    /// Each buffer is exactly `len` bytes, and both are initially zeroed.
+    pub fn new(len: usize) -> Result<Self> {
+        Ok(Self {
+            front: MapMut::with_options().len(len).alloc()?,
+            back: MapMut::with_options().len(len).alloc()?,
+        })
+    }
+
+    /// Get an immutable slice of the front buffer.
+    #[inline]
+    pub fn front(&self) -> &[u8] {
+        &self.front
+    }
+
+    /// Get an immutable slice of the back buffer.
+    #[inline]
+    pub fn back(&self) -> &[u8] {
+        &self.back
+    }
+
+    /// Get a mutable slice of the back buffer.
+    #[inline]
+    pub fn back_mut(&mut self) -> &mut [u8] {
+        &mut self.back
+    }
+
+    /// Exchanges the front and back buffers.
+    #[inline]
+    pub fn swap(&mut self) {
+        mem::swap(&mut self.front, &mut self.back);
+    }
+}
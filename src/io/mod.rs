@@ -7,6 +7,13 @@
 //! the [`Ring`] may only written to as readable space is consumed, whereas
 //! the [`InfiniteRing`] is always writable and will overwrite unconsumed
 //! space as needed.
+//!
+//! The [`DoubleBuffer`] pairs two anonymous allocations so one may be filled
+//! while the other is read, exchanging the two without copying. The
+//! [`Slab`] hands out fixed-size blocks from a single anonymous allocation.
+//!
+//! The [`MapCursor`] instead implements `Read`, `Write`, and `Seek`
+//! over the fixed bytes of an existing map.
 
 mod ring;
 pub use self::ring::*;
@@ -14,6 +21,15 @@ pub use self::ring::*;
 mod buffer;
 pub use self::buffer::*;
 
+mod cursor;
+pub use self::cursor::*;
+
+mod double;
+pub use self::double::*;
+
+mod slab;
+pub use self::slab::*;
+
 use std::cmp;
 use std::io::{self, BufRead};
 use std::slice;
@@ -115,8 +131,8 @@ mod tests {
     use crate::os;
 
     use super::super::Size;
-    use super::{InfiniteRing, Ring, SeqRead, SeqWrite};
-    use std::io::{BufRead, Write};
+    use super::{InfiniteRing, MapCursor, Ring, SeqRead, SeqWrite, Slab};
+    use std::io::{BufRead, ErrorKind, Read, Seek, SeekFrom, Write};
 
     #[test]
     fn size() {
@@ -224,4 +240,66 @@ mod tests {
         let end = bytes.len() - (ring.write_capacity() % bytes.len());
         assert_eq!(ring.as_read_slice(10), &cmp[end..(end + 10)]);
     }
+
+    #[test]
+    fn ring_slice_wraps() {
+        let mut buf = Ring::new(1000).expect("failed to create ring buffer");
+        let cap = buf.write_capacity();
+        for (i, b) in buf.as_mut_slice(0, cap).iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        let tail = buf.as_slice(cap - 3, 6);
+        let expect: Vec<u8> = (cap - 3..cap + 3).map(|i| (i % cap) as u8).collect();
+        assert_eq!(tail, &expect[..]);
+        assert_eq!(buf.as_slice(cap + 5, 1), &[5]);
+
+        buf.as_mut_slice(cap - 1, 2).copy_from_slice(b"xy");
+        assert_eq!(buf.as_slice(0, 1), b"y");
+        assert_eq!(buf.read_len(), 0);
+    }
+
+    #[test]
+    fn cursor_eof() {
+        let map = crate::MapMut::with_options().len(10).alloc().unwrap();
+        let mut cur = MapCursor::new(map);
+        assert_eq!(cur.write(b"0123456789abc").unwrap(), 10);
+        assert_eq!(cur.write(b"x").unwrap(), 0);
+        assert_eq!(
+            cur.write_all(b"x").unwrap_err().kind(),
+            ErrorKind::WriteZero
+        );
+
+        assert_eq!(cur.seek(SeekFrom::End(-4)).unwrap(), 6);
+        let mut buf = [0; 8];
+        assert_eq!(cur.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf[..4], b"6789");
+        assert_eq!(cur.read(&mut buf).unwrap(), 0);
+        assert!(cur.fill_buf().unwrap().is_empty());
+
+        assert_eq!(cur.seek(SeekFrom::Current(5)).unwrap(), 15);
+        assert_eq!(cur.read(&mut buf).unwrap(), 0);
+        assert_eq!(cur.write(b"x").unwrap(), 0);
+        assert!(cur.seek(SeekFrom::Current(-16)).is_err());
+        assert_eq!(cur.position(), 15);
+
+        cur.set_position(2);
+        let mut rest = Vec::new();
+        cur.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"23456789");
+        assert_eq!(&cur.into_inner()[..], b"0123456789");
+    }
+
+    #[test]
+    fn slab_overwritten_free_list() {
+        let mut slab = Slab::new(64, 4).expect("failed to create slab");
+        let a = slab.alloc().unwrap();
+        let b = slab.alloc().unwrap();
+        slab.free(b);
+        slab.free(a);
+        slab.block_mut(a).fill(0xff);
+        assert_eq!(Some(a), slab.alloc());
+        assert_eq!(Some(2), slab.alloc());
+        assert_eq!(Some(3), slab.alloc());
+        assert_eq!(None, slab.alloc());
+    }
 }
@@ -1,10 +1,10 @@
 use super::{SeqRead, SeqWrite};
 use crate::os::{map_ring, unmap_ring};
-use crate::{Result, Size};
+use crate::{track_map, track_unmap, Result, Size};
 
-use std::{cmp, slice};
 use std::io::{self, BufRead, Read, Write};
 use std::ops::Deref;
+use std::{cmp, slice};
 
 /// Fixed-size reliable read/write buffer with sequential address mapping.
 ///
@@ -72,6 +72,7 @@ impl Ring {
     pub fn new(hint: usize) -> Result<Self> {
         let len = Size::alloc().round(hint);
         let ptr = map_ring(len)?;
+        track_map(2 * len);
         Ok(Self {
             ptr,
             len,
@@ -94,18 +95,59 @@ impl Ring {
         let offset = self.read_offset();
         let len = cmp::min(self.read_len(), max);
         self.rpos += len as u64; // consume
-        unsafe {
-            slice::from_raw_parts(
-                self.as_read_ptr().add(offset),
-                len,
-            )
-        }
+        unsafe { slice::from_raw_parts(self.as_read_ptr().add(offset), len) }
+    }
+
+    /// Get an immutable slice of `len` bytes starting at `offset` in the buffer.
+    ///
+    /// The offset is taken modulo the capacity, and the slice is contiguous
+    /// even when it runs past the end of the buffer, as it continues into the
+    /// mirror mapping of the start. This ignores the read and write positions,
+    /// so any byte of the buffer may be accessed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` exceeds the capacity of the buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::io::{Ring, SeqWrite};
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let mut buf = Ring::new(4000)?;
+    /// let cap = buf.write_capacity();
+    /// buf.as_mut_slice(cap - 2, 4).copy_from_slice(b"wrap");
+    /// assert_eq!(b"ap", buf.as_slice(0, 2));
+    /// assert_eq!(b"wrap", buf.as_slice(cap - 2, 4));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn as_slice(&self, offset: usize, len: usize) -> &[u8] {
+        assert!(len <= self.len, "slice length exceeds ring capacity");
+        unsafe { slice::from_raw_parts(self.ptr.add(offset % self.len), len) }
+    }
+
+    /// Get a mutable slice of `len` bytes starting at `offset` in the buffer.
+    ///
+    /// This is the mutable form of [`.as_slice()`]. Writes through the slice
+    /// do not change the read or write positions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` exceeds the capacity of the buffer.
+    ///
+    /// [`.as_slice()`]: #method.as_slice
+    pub fn as_mut_slice(&mut self, offset: usize, len: usize) -> &mut [u8] {
+        assert!(len <= self.len, "slice length exceeds ring capacity");
+        unsafe { slice::from_raw_parts_mut(self.ptr.add(offset % self.len), len) }
     }
 }
 
 impl Drop for Ring {
     fn drop(&mut self) {
         unsafe { unmap_ring(self.ptr, self.write_capacity()) }.unwrap_or_default();
+        track_unmap(2 * self.len);
     }
 }
 
@@ -147,7 +189,7 @@ impl SeqWrite for Ring {
 
 impl BufRead for Ring {
     fn fill_buf(&mut self) -> io::Result<&[u8]> {
-        Ok(self.as_read_slice(std::usize::MAX))
+        Ok(self.as_read_slice(usize::MAX))
     }
 
     fn consume(&mut self, len: usize) {
@@ -247,6 +289,7 @@ impl InfiniteRing {
     pub fn new(hint: usize) -> Result<Self> {
         let len = Size::alloc().round(hint);
         let ptr = map_ring(len)?;
+        track_map(2 * len);
         Ok(Self {
             ptr,
             len,
@@ -258,7 +301,8 @@ impl InfiniteRing {
 
 impl Drop for InfiniteRing {
     fn drop(&mut self) {
-        unsafe { unmap_ring(self.ptr, self.write_capacity()) }.unwrap_or_default()
+        unsafe { unmap_ring(self.ptr, self.write_capacity()) }.unwrap_or_default();
+        track_unmap(2 * self.len);
     }
 }
 
@@ -295,7 +339,7 @@ impl SeqWrite for InfiniteRing {
 
 impl BufRead for InfiniteRing {
     fn fill_buf(&mut self) -> io::Result<&[u8]> {
-        Ok(self.as_read_slice(std::usize::MAX))
+        Ok(self.as_read_slice(usize::MAX))
     }
 
     fn consume(&mut self, len: usize) {
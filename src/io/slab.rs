@@ -0,0 +1,162 @@
+use crate::{Error, Input, MapMut, Operation, Result, Size};
+
+use std::{mem, ptr};
+
+const END: usize = usize::MAX;
+
+/// Pool of fixed-size blocks carved from a single anonymous allocation.
+///
+/// Blocks are identified by their index and are handed out and returned in
+/// constant time. Returned blocks are kept in a free list that is stored in
+/// the first bytes of the free blocks themselves, so the pool requires no
+/// memory beyond the mapping. Writing to a block after freeing it overwrites
+/// its entry, so [`.alloc()`] ignores an entry that does not name a block
+/// already handed out, and drops the rest of the free list rather than follow
+/// it out of bounds. The blocks left on the list are then lost until the pool
+/// is dropped. Blocks that have never been handed out are not
+/// touched, so the physical memory of the mapping is only committed as it is
+/// used.
+///
+/// # Examples
+///
+/// ```
+/// use vmap::io::Slab;
+///
+/// # fn main() -> vmap::Result<()> {
+/// let mut slab = Slab::new(1500, 4)?;
+/// let a = slab.alloc().unwrap();
+/// let b = slab.alloc().unwrap();
+/// slab.block_mut(a)[..4].copy_from_slice(b"ping");
+/// slab.block_mut(b)[..4].copy_from_slice(b"pong");
+/// assert_eq!(b"ping", &slab.block(a)[..4]);
+///
+/// slab.free(a);
+/// assert_eq!(Some(a), slab.alloc());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`.alloc()`]: #method.alloc
+#[derive(Debug)]
+pub struct Slab {
+    map: MapMut,
+    block_size: usize,
+    block_count: usize,
+    // Head of the free list of returned blocks.
+    free: usize,
+    // Index of the first block that has never been handed out.
+    fresh: usize,
+    used: usize,
+}
+
+impl Slab {
+    /// Constructs a new pool of `block_count` blocks.
+    ///
+    /// The block size is rounded up to a multiple of the pointer size so
+    /// that every block is aligned and can hold a free list entry. The
+    /// allocation is rounded up to the allocation size.
+    pub fn new(block_size: usize, block_count: usize) -> Result<Self> {
+        let align = mem::size_of::<usize>();
+        let block_size = match block_size.max(1).checked_add(align - 1) {
+            Some(sz) => sz & !(align - 1),
+            None => return Err(Error::input(Operation::MapAnonymous, Input::InvalidRange)),
+        };
+        let len = match block_size.checked_mul(block_count) {
+            Some(len) => Size::alloc().round(len),
+            None => return Err(Error::input(Operation::MapAnonymous, Input::InvalidRange)),
+        };
+        Ok(Self {
+            map: MapMut::with_options().len(len).alloc()?,
+            block_size,
+            block_count,
+            free: END,
+            fresh: 0,
+            used: 0,
+        })
+    }
+
+    /// Gets the size in bytes of each block.
+    #[inline]
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Gets the total number of blocks in the pool.
+    #[inline]
+    pub fn block_count(&self) -> usize {
+        self.block_count
+    }
+
+    /// Gets the number of blocks currently handed out.
+    #[inline]
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    /// Takes a free block from the pool and returns its index.
+    ///
+    /// Returns `None` when every block is in use. The contents of a block
+    /// that was previously freed are not cleared.
+    pub fn alloc(&mut self) -> Option<usize> {
+        let idx = if self.free != END {
+            let idx = self.free;
+            let next = unsafe { ptr::read(self.block_ptr(idx) as *const usize) };
+            // The entry may have been overwritten through `block_mut`.
+            self.free = if next < self.fresh { next } else { END };
+            idx
+        } else if self.fresh < self.block_count {
+            self.fresh += 1;
+            self.fresh - 1
+        } else {
+            return None;
+        };
+        self.used += 1;
+        Some(idx)
+    }
+
+    /// Returns a block to the pool.
+    ///
+    /// The block must have been taken with [`.alloc()`] and not already
+    /// freed. Freeing a block twice corrupts the free list.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is not a block of this pool.
+    ///
+    /// [`.alloc()`]: #method.alloc
+    pub fn free(&mut self, idx: usize) {
+        assert!(idx < self.fresh, "block index out of range");
+        unsafe { ptr::write(self.block_ptr(idx) as *mut usize, self.free) };
+        self.free = idx;
+        self.used -= 1;
+    }
+
+    /// Get an immutable slice of a block.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is not a block of this pool.
+    #[inline]
+    pub fn block(&self, idx: usize) -> &[u8] {
+        assert!(idx < self.block_count, "block index out of range");
+        let off = idx * self.block_size;
+        &self.map[off..off + self.block_size]
+    }
+
+    /// Get a mutable slice of a block.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is not a block of this pool.
+    #[inline]
+    pub fn block_mut(&mut self, idx: usize) -> &mut [u8] {
+        assert!(idx < self.block_count, "block index out of range");
+        let off = idx * self.block_size;
+        &mut self.map[off..off + self.block_size]
+    }
+
+    #[inline]
+    fn block_ptr(&mut self, idx: usize) -> *mut u8 {
+        unsafe { self.map.as_mut_ptr().add(idx * self.block_size) }
+    }
+}
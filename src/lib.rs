@@ -144,9 +144,12 @@
 
 #![deny(missing_docs)]
 
-use std::ops::{Deref, DerefMut};
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::{mem, ptr};
+use std::ffi::CStr;
+use std::fs::File;
+use std::ops::{BitOr, BitOrAssign, Deref, DerefMut};
+use std::sync::atomic::{self, AtomicUsize, Ordering};
+use std::time::Duration;
+use std::{mem, ptr, slice};
 
 #[cfg(feature = "os")]
 pub mod os;
@@ -154,16 +157,65 @@ pub mod os;
 #[cfg(not(feature = "os"))]
 mod os;
 
+mod bitset;
+pub use self::bitset::MapBitset;
+
+mod cow;
+pub use self::cow::CowMap;
+
+mod cpu;
+
 mod error;
 pub use self::error::{ConvertResult, Error, Input, Operation, Result};
 
+#[cfg(feature = "hexdump")]
+mod hexdump;
+#[cfg(feature = "hexdump")]
+pub use self::hexdump::Hexdump;
+
+#[cfg(feature = "alloc")]
+mod global;
+#[cfg(feature = "alloc")]
+pub use self::global::MmapAlloc;
+
+mod group;
+pub use self::group::{MapGroup, MapGroupSlot};
+
 mod map;
-pub use self::map::{Map, MapMut, Options};
+pub use self::map::{FileLock, FileRangeLock, Map, MapMut, Options, Pages, PagesMut};
+
+mod region;
+pub use self::region::MapRegion;
+
+mod reserve;
+pub use self::reserve::{Reservation, ReservationRange};
+
+mod segment;
+pub use self::segment::MapSegment;
+
+pub mod shm;
+
+mod stack;
+pub use self::stack::Stack;
+
+mod typed;
+pub use self::typed::TypedMap;
+
+mod vec;
+pub use self::vec::{GrowthPolicy, MapVec};
+
+pub use self::os::install_sigbus_handler;
 
 #[cfg(feature = "io")]
 pub mod io;
 
 /// Protection level for a page.
+///
+/// No level is both writable and executable, so every mapping created or
+/// protected through this crate is either writable or executable but never
+/// both at once. Code that generates instructions, such as a JIT, writes them
+/// with `ReadWrite` and then switches the pages to `ReadExec` before running
+/// them.
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub enum Protect {
     /// The page(s) may only be read from.
@@ -185,7 +237,31 @@ pub enum Flush {
     Async,
 }
 
+/// Policy for flushing a writable file map to its retained file.
+///
+/// See [`.set_sync_policy()`] for how each policy is applied.
+///
+/// [`.set_sync_policy()`]: struct.MapMut.html#method.set_sync_policy
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SyncPolicy {
+    /// Never flush automatically.
+    None,
+    /// Flush with the mode when the map is dropped or closed.
+    OnDrop(Flush),
+    /// Flush with the mode from [`.maybe_flush()`] once the interval has
+    /// passed since the last flush, and when the map is dropped or closed.
+    ///
+    /// [`.maybe_flush()`]: struct.MapMut.html#method.maybe_flush
+    Periodic(Flush, Duration),
+}
+
 /// Hint for the access pattern of the underlying mapping.
+///
+/// Hints that are not supported by the current platform are ignored, so any
+/// variant may be used without conditional compilation. See [`.advise()`]
+/// for the platforms on which each hint takes effect.
+///
+/// [`.advise()`]: struct.MapMut.html#method.advise
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub enum Advise {
     /// Use the system default behavior.
@@ -197,7 +273,190 @@ pub enum Advise {
     /// The map is expected to be accessed soon.
     WillNeed,
     /// The map is not expected to be accessed soon.
+    ///
+    /// This uses `MADV_DONTNEED`, which on Linux discards the pages of a
+    /// private map at once. Any writes to those pages are lost, and they read
+    /// back as the file contents, or as zero for an anonymous map. Use
+    /// [`Cold`] or [`PageOut`] to have the pages reclaimed without losing
+    /// their contents.
+    ///
+    /// [`Cold`]: #variant.Cold
+    /// [`PageOut`]: #variant.PageOut
     WillNotNeed,
+    /// The map is not expected to be accessed soon and its pages should be
+    /// reclaimed before others.
+    ///
+    /// Unlike [`WillNotNeed`], this never discards data. The pages keep their
+    /// contents, including dirty pages of a private or anonymous map, and are
+    /// only moved to the inactive list so they are reclaimed first under
+    /// memory pressure. This uses `MADV_COLD` on Linux 5.4 and later, and is
+    /// a no-op elsewhere.
+    ///
+    /// [`WillNotNeed`]: #variant.WillNotNeed
+    Cold,
+    /// The pages of the map should be reclaimed now.
+    ///
+    /// Like [`Cold`], this never discards data: clean file pages are dropped
+    /// from memory and dirty or anonymous pages are written back or swapped
+    /// out, to be faulted back in on the next access. This uses
+    /// `MADV_PAGEOUT` on Linux 5.4 and later, and is a no-op elsewhere.
+    ///
+    /// [`Cold`]: #variant.Cold
+    PageOut,
+    /// The map should be backed by transparent huge pages where possible.
+    ///
+    /// This uses `MADV_HUGEPAGE` on Linux kernels built with transparent
+    /// huge page support, and is a no-op elsewhere.
+    HugePage,
+    /// The contents of the map are no longer needed and may be freed lazily.
+    ///
+    /// Unlike [`WillNotNeed`], the pages are only freed once the system
+    /// needs the memory, and writing to a page before then keeps it and its
+    /// contents. A page that was freed reads back as zero.
+    ///
+    /// This uses `MADV_FREE` on Linux 4.5 and later, macOS, and the BSDs.
+    /// Linux only applies it to private anonymous maps, such as an anonymous
+    /// map created with [`.copy()`], and it is a no-op there for any other
+    /// map and on older kernels. macOS and the BSDs accept it for any map,
+    /// but only free the anonymous pages in it. It is a no-op on Windows and
+    /// on other platforms.
+    ///
+    /// [`WillNotNeed`]: #variant.WillNotNeed
+    /// [`.copy()`]: struct.Options.html#method.copy
+    Free,
+}
+
+/// Hint for the access pattern of a range of a file in the page cache.
+///
+/// Unlike [`Advise`], which applies to a mapped memory region, this applies
+/// to a file and may be used before the file is mapped. See the
+/// [`.fadvise()`] method of [`Map`] and [`MapMut`].
+///
+/// [`Advise`]: enum.Advise.html
+/// [`.fadvise()`]: struct.Map.html#method.fadvise
+/// [`Map`]: struct.Map.html
+/// [`MapMut`]: struct.MapMut.html
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum FAdvise {
+    /// Use the system default behavior.
+    Normal,
+    /// The file range will be accessed in a sequential manner.
+    Sequential,
+    /// The file range will be accessed in a random manner.
+    Random,
+    /// The file range is expected to be accessed soon.
+    WillNeed,
+    /// The file range is not expected to be accessed soon. Clean pages of
+    /// the range may be dropped from the page cache.
+    DontNeed,
+    /// The file range will only be accessed once.
+    NoReuse,
+}
+
+/// Visibility of modifications to a file mapping.
+///
+/// For usage information, see the [`.sharing()`] method of the [`Options`]
+/// builder type.
+///
+/// [`.sharing()`]: struct.Options.html#method.sharing
+/// [`Options`]: struct.Options.html
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Sharing {
+    /// Modifications are written back to the file and are visible to all
+    /// other mappings of it (`MAP_SHARED`).
+    Shared,
+    /// Modifications are private to the mapping and never written back to
+    /// the file (`MAP_PRIVATE`). The file is still opened with the requested
+    /// access.
+    Private,
+    /// Like `Private`, but the mapping is writable while the file is opened
+    /// read-only. This is the same as [`.copy()`].
+    ///
+    /// [`.copy()`]: struct.Options.html#method.copy
+    Copy,
+}
+
+/// Additional platform-specific behaviors requested when creating a mapping.
+///
+/// Flags may be combined using the `|` operator. Not every flag is supported
+/// on every platform; mapping with an unsupported flag fails with an
+/// [`Input::Unsupported`] error rather than silently ignoring the request.
+///
+/// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct MapFlags(u32);
+
+impl MapFlags {
+    /// No additional behaviors.
+    pub const NONE: Self = Self(0);
+
+    /// Use synchronous page faults for a shared file mapping.
+    ///
+    /// This uses `MAP_SYNC | MAP_SHARED_VALIDATE` and is only supported on
+    /// Linux for files on a DAX-capable file system (i.e. persistent memory).
+    /// Once a store has been flushed from the CPU cache, it is durable without
+    /// a call to [`.flush()`].
+    ///
+    /// [`.flush()`]: struct.MapMut.html#method.flush
+    pub const SYNC: Self = Self(1 << 0);
+
+    /// Map a file privately regardless of the protection.
+    ///
+    /// Modifications are copied on write and never reach the file. This is
+    /// implied by [`Protect::ReadCopy`] and [`Protect::ReadExec`].
+    ///
+    /// [`Protect::ReadCopy`]: enum.Protect.html#variant.ReadCopy
+    /// [`Protect::ReadExec`]: enum.Protect.html#variant.ReadExec
+    pub const PRIVATE: Self = Self(1 << 1);
+
+    /// Populate the page tables when the mapping is created.
+    ///
+    /// This uses `MAP_POPULATE` and is only supported on Linux and Android.
+    /// File pages are read ahead, and the pages of a writable private
+    /// mapping are faulted in for writing, so no access to the new mapping
+    /// incurs a page fault. [`Options::populate()`] uses this where it is
+    /// supported and touches each page elsewhere.
+    ///
+    /// [`Options::populate()`]: struct.Options.html#method.populate
+    pub const POPULATE: Self = Self(1 << 2);
+
+    /// Tests if all of the flags in `other` are set.
+    #[inline]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Tests if no flags are set.
+    #[inline]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Sets or clears all of the flags in `other`.
+    #[inline]
+    pub fn set(&mut self, other: Self, value: bool) {
+        if value {
+            self.0 |= other.0;
+        } else {
+            self.0 &= !other.0;
+        }
+    }
+}
+
+impl BitOr for MapFlags {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for MapFlags {
+    #[inline]
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
 }
 
 /// Byte extent type used for length and resize options.
@@ -230,6 +489,30 @@ impl From<usize> for Extent {
 
 /// Gets a cached version of the system page size.
 ///
+/// With the `const-page-size` feature, this is instead the constant page
+/// size chosen at build time, and the system is never queried. See
+/// [`FIXED_PAGE_SIZE`] for details.
+///
+/// # Examples
+///
+/// ```
+/// let page_size = vmap::page_size();
+/// println!("the system page size is {} bytes", page_size);
+/// assert!(page_size >= 4096);
+/// ```
+///
+/// [`FIXED_PAGE_SIZE`]: constant.FIXED_PAGE_SIZE.html
+#[cfg(feature = "const-page-size")]
+#[inline]
+pub const fn page_size() -> usize {
+    FIXED_PAGE_SIZE
+}
+
+/// Gets a cached version of the system page size.
+///
+/// With the `const-page-size` feature, this is instead the constant page
+/// size chosen at build time, and the system is never queried.
+///
 /// # Examples
 ///
 /// ```
@@ -237,6 +520,7 @@ impl From<usize> for Extent {
 /// println!("the system page size is {} bytes", page_size);
 /// assert!(page_size >= 4096);
 /// ```
+#[cfg(not(feature = "const-page-size"))]
 pub fn page_size() -> usize {
     let size = PAGE_SIZE.load(Ordering::Relaxed);
     if size == 0 {
@@ -262,6 +546,29 @@ pub fn page_size() -> usize {
 ///     assert!(alloc_size >= 4096);
 /// }
 /// ```
+#[cfg(all(feature = "const-page-size", not(windows)))]
+#[inline]
+pub const fn allocation_size() -> usize {
+    FIXED_PAGE_SIZE
+}
+
+/// Gets a cached version of the system allocation granularity size.
+///
+/// On Windows this value is typically 64k. Otherwise it is the same as the
+/// page size.
+///
+/// # Examples
+///
+/// ```
+/// let alloc_size = vmap::allocation_size();
+/// println!("the system allocation granularity is {} bytes", alloc_size);
+/// if cfg!(windows) {
+///     assert!(alloc_size >= 65536);
+/// } else {
+///     assert!(alloc_size >= 4096);
+/// }
+/// ```
+#[cfg(not(all(feature = "const-page-size", not(windows))))]
 pub fn allocation_size() -> usize {
     let size = ALLOC_SIZE.load(Ordering::Relaxed);
     if size == 0 {
@@ -271,9 +578,254 @@ pub fn allocation_size() -> usize {
     }
 }
 
+static MAPPED_BYTES: AtomicUsize = AtomicUsize::new(0);
+static MAPPING_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Gets the number of bytes of address space currently mapped by this crate.
+///
+/// This includes every live [`Map`], [`MapMut`], and ring buffer, rounded
+/// to the allocation size that was actually mapped. Converting between map
+/// types does not change the total, and a map that is leaked with
+/// `mem::forget` remains counted as it is never unmapped.
+///
+/// # Examples
+///
+/// ```
+/// use vmap::MapMut;
+///
+/// # fn main() -> vmap::Result<()> {
+/// let before = vmap::mapped_bytes();
+/// let map = MapMut::new(1)?;
+/// assert_eq!(vmap::mapped_bytes(), before + vmap::allocation_size());
+/// drop(map);
+/// assert_eq!(vmap::mapped_bytes(), before);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Map`]: struct.Map.html
+/// [`MapMut`]: struct.MapMut.html
+pub fn mapped_bytes() -> usize {
+    MAPPED_BYTES.load(Ordering::Relaxed)
+}
+
+/// Gets the number of mappings currently created by this crate.
+///
+/// See [`mapped_bytes()`] for which mappings are counted.
+///
+/// # Examples
+///
+/// ```
+/// use vmap::MapMut;
+///
+/// # fn main() -> vmap::Result<()> {
+/// let before = vmap::mapping_count();
+/// let map = MapMut::new(1)?.into_map()?;
+/// assert_eq!(vmap::mapping_count(), before + 1);
+/// drop(map);
+/// assert_eq!(vmap::mapping_count(), before);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`mapped_bytes()`]: fn.mapped_bytes.html
+pub fn mapping_count() -> usize {
+    MAPPING_COUNT.load(Ordering::Relaxed)
+}
+
+/// Counts of page faults taken by the process.
+///
+/// This is returned by [`fault_stats()`].
+///
+/// [`fault_stats()`]: fn.fault_stats.html
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct FaultStats {
+    /// Faults served without I/O, such as a page already in the page cache
+    /// or a new zero-filled anonymous page.
+    pub minor: u64,
+    /// Faults that waited for a page to be read from disk.
+    pub major: u64,
+}
+
+impl FaultStats {
+    /// Gets the faults taken between an `earlier` sample and this one.
+    #[inline]
+    pub fn since(&self, earlier: &FaultStats) -> FaultStats {
+        FaultStats {
+            minor: self.minor.saturating_sub(earlier.minor),
+            major: self.major.saturating_sub(earlier.major),
+        }
+    }
+}
+
+/// Gets the number of page faults taken by the process so far.
+///
+/// The system does not count faults per mapping, so these are the totals
+/// across every thread and mapping of the process. They are best used as a
+/// before and after delta around the access being measured, with
+/// [`FaultStats::since()`], to estimate its fault cost. This uses
+/// `getrusage` on Unix. On Windows, `GetProcessMemoryInfo` only reports a
+/// single count, which is given as minor faults with zero major faults.
+///
+/// # Examples
+///
+/// ```
+/// use vmap::MapMut;
+///
+/// # fn main() -> vmap::Result<()> {
+/// let mut map = MapMut::new(16 * vmap::page_size())?;
+/// let before = vmap::fault_stats()?;
+/// for i in (0..map.len()).step_by(vmap::page_size()) {
+///     map[i] = 1;
+/// }
+/// let faults = vmap::fault_stats()?.since(&before);
+/// println!("{} minor, {} major", faults.minor, faults.major);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`FaultStats::since()`]: struct.FaultStats.html#method.since
+pub fn fault_stats() -> Result<FaultStats> {
+    let (minor, major) = os::page_faults()?;
+    Ok(FaultStats { minor, major })
+}
+
+#[inline]
+pub(crate) fn track_map(len: usize) {
+    MAPPED_BYTES.fetch_add(len, Ordering::Relaxed);
+    MAPPING_COUNT.fetch_add(1, Ordering::Relaxed);
+}
+
+#[inline]
+pub(crate) fn track_unmap(len: usize) {
+    MAPPED_BYTES.fetch_sub(len, Ordering::Relaxed);
+    MAPPING_COUNT.fetch_sub(1, Ordering::Relaxed);
+}
+
+/// Issues a release fence for publishing writes to shared memory.
+///
+/// No reads or writes before the fence may be reordered after any atomic
+/// store that follows it. In a producer/consumer protocol over a shared
+/// mapping, the producer writes the data, calls `fence_release()`, and then
+/// sets a ready flag with an atomic store. The consumer pairs this with
+/// [`fence_acquire()`].
+///
+/// This is a convenience for `std::sync::atomic::fence(Ordering::Release)`.
+///
+/// # Examples
+///
+/// ```
+/// use vmap::MapMut;
+/// use std::sync::atomic::{AtomicU32, Ordering};
+///
+/// # fn main() -> vmap::Result<()> {
+/// let mut map = MapMut::new(4096)?;
+/// let ready = unsafe { &*(map.as_ptr() as *const AtomicU32) };
+///
+/// // producer
+/// map[64..68].clone_from_slice(b"test");
+/// vmap::fence_release();
+/// ready.store(1, Ordering::Relaxed);
+///
+/// // consumer
+/// if ready.load(Ordering::Relaxed) == 1 {
+///     vmap::fence_acquire();
+///     assert_eq!(b"test", &map[64..68]);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`fence_acquire()`]: fn.fence_acquire.html
+#[inline]
+pub fn fence_release() {
+    atomic::fence(Ordering::Release)
+}
+
+/// Issues an acquire fence for observing writes to shared memory.
+///
+/// No reads or writes after the fence may be reordered before any atomic
+/// load that precedes it. This pairs with a [`fence_release()`] from the
+/// writer of the shared memory.
+///
+/// This is a convenience for `std::sync::atomic::fence(Ordering::Acquire)`.
+///
+/// [`fence_release()`]: fn.fence_release.html
+#[inline]
+pub fn fence_acquire() {
+    atomic::fence(Ordering::Acquire)
+}
+
+/// Issues a sequentially consistent fence.
+///
+/// This combines the guarantees of [`fence_acquire()`] and
+/// [`fence_release()`], and additionally participates in a single total
+/// order with all other sequentially consistent operations.
+///
+/// This is a convenience for `std::sync::atomic::fence(Ordering::SeqCst)`.
+///
+/// [`fence_acquire()`]: fn.fence_acquire.html
+/// [`fence_release()`]: fn.fence_release.html
+#[inline]
+pub fn fence_seqcst() {
+    atomic::fence(Ordering::SeqCst)
+}
+
+/// The page size used in place of the system page size.
+///
+/// This is only defined with the `const-page-size` feature, for targets
+/// where the page size is known ahead of time. The value is read from the
+/// `VMAP_PAGE_SIZE` environment variable when the crate is built, and is
+/// 4096 when the variable is not set. A value that is not a power of two
+/// fails the build. Outside of Windows, where the allocation granularity
+/// differs, the value is also used as the [`allocation_size()`].
+///
+/// The value must match the page size of the system that runs the program,
+/// otherwise mappings and page operations are misaligned.
+///
+/// ```text
+/// VMAP_PAGE_SIZE=16384 cargo build --features const-page-size
+/// ```
+///
+/// [`allocation_size()`]: fn.allocation_size.html
+#[cfg(feature = "const-page-size")]
+pub const FIXED_PAGE_SIZE: usize = {
+    let size = match option_env!("VMAP_PAGE_SIZE") {
+        Some(val) => parse_size(val),
+        None => 4096,
+    };
+    assert!(
+        size.is_power_of_two(),
+        "VMAP_PAGE_SIZE must be a power of two"
+    );
+    size
+};
+
+// Parses a decimal size at compile time.
+#[cfg(feature = "const-page-size")]
+const fn parse_size(val: &str) -> usize {
+    let bytes = val.as_bytes();
+    assert!(!bytes.is_empty(), "VMAP_PAGE_SIZE must be a decimal number");
+    let mut size: usize = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        assert!(
+            b.is_ascii_digit(),
+            "VMAP_PAGE_SIZE must be a decimal number"
+        );
+        size = size * 10 + (b - b'0') as usize;
+        i += 1;
+    }
+    size
+}
+
+#[cfg(not(all(feature = "const-page-size", not(windows))))]
 static PAGE_SIZE: AtomicUsize = AtomicUsize::new(0);
+#[cfg(not(all(feature = "const-page-size", not(windows))))]
 static ALLOC_SIZE: AtomicUsize = AtomicUsize::new(0);
 
+#[cfg(not(all(feature = "const-page-size", not(windows))))]
 #[inline]
 fn load_system_info() -> (u32, u32) {
     let (page, alloc) = self::os::system_info();
@@ -284,6 +836,15 @@ fn load_system_info() -> (u32, u32) {
 
 /// Type for calculation system page or allocation size information.
 ///
+/// The [`.round()`], [`.truncate()`], and [`.bounds()`] methods are the same
+/// calculations used internally to map and operate on pages, so they may be
+/// used to apply other low-level system calls to a map correctly.
+///
+/// Use [`Size::page()`] for calls that operate on pages, such as protecting
+/// or advising a range, and [`Size::alloc()`] for the address and file
+/// offset of a mapping. The two are the same on Unix, but on Windows the
+/// allocation granularity is 64KiB while pages are 4KiB.
+///
 /// # Examples
 ///
 /// ```
@@ -300,6 +861,12 @@ fn load_system_info() -> (u32, u32) {
 /// let size = size.size(3);
 /// println!("3 pages are {} bytes", size);
 /// ```
+///
+/// [`.round()`]: #method.round
+/// [`.truncate()`]: #method.truncate
+/// [`.bounds()`]: #method.bounds
+/// [`Size::page()`]: #method.page
+/// [`Size::alloc()`]: #method.alloc
 #[derive(Copy, Clone)]
 pub struct Size(usize);
 
@@ -322,6 +889,75 @@ impl Size {
         unsafe { Self::with_size(allocation_size()) }
     }
 
+    /// Creates a type for calculating block numbers and byte offsets for
+    /// direct I/O on a file.
+    ///
+    /// Files opened for direct I/O (e.g. `O_DIRECT`) require offsets and
+    /// lengths to be aligned to the block size of the underlying device
+    /// rather than the page size. The [`.truncate()`] and [`.round()`]
+    /// methods align a position down or up to that block size. This size
+    /// may be passed to [`Options::align()`] to validate a mapping range.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::Size;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let file = std::fs::File::open("README.md")?;
+    /// let block = Size::block(&file)?;
+    /// println!("direct I/O requires {} byte blocks", block.round(1));
+    ///
+    /// let off = block.truncate(1000); // align down
+    /// let len = block.round(1000);    // align up
+    /// assert_eq!(block.offset(off), 0);
+    /// assert_eq!(block.offset(len), 0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.truncate()`]: #method.truncate
+    /// [`.round()`]: #method.round
+    /// [`Options::align()`]: struct.Options.html#method.align
+    pub fn block(file: &File) -> Result<Self> {
+        let size = self::os::block_size(file)?;
+        if size.is_power_of_two() {
+            Ok(Size(size))
+        } else {
+            Err(Error::input(Operation::BlockSize, Input::Unaligned))
+        }
+    }
+
+    /// Creates a type for calculating huge page numbers and byte offsets.
+    ///
+    /// This is the default huge page size of the system, as used by
+    /// [`MapMut::new_huge()`]. On Linux it is read from `/proc/meminfo`, and
+    /// on Windows it is the minimum large page size. Other platforms, and
+    /// systems without huge page support, yield an [`Input::Unsupported`]
+    /// error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::Size;
+    ///
+    /// if let Ok(huge) = Size::huge() {
+    ///     println!("huge pages are {} bytes", huge.size(1));
+    ///     assert_eq!(huge.offset(huge.round(1)), 0);
+    /// }
+    /// ```
+    ///
+    /// [`MapMut::new_huge()`]: struct.MapMut.html#method.new_huge
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    pub fn huge() -> Result<Self> {
+        let size = self::os::huge_page_size()?;
+        if size.is_power_of_two() {
+            Ok(Size(size))
+        } else {
+            Err(Error::input(Operation::HugePageSize, Input::Unaligned))
+        }
+    }
+
     /// Creates a type for calculating allocations numbers and byte offsets
     /// using a known size.
     ///
@@ -404,6 +1040,57 @@ impl Size {
         len & (self.0 - 1)
     }
 
+    /// Calculate the number of bytes from a position to the next unit
+    /// boundary.
+    ///
+    /// This is zero for a position already on a boundary, so adding it to
+    /// the position gives the same result as [`.round()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::Size;
+    ///
+    /// let sys = vmap::page_size();
+    /// let size = Size::page();
+    /// assert_eq!(size.offset_to(0), 0);
+    /// assert_eq!(size.offset_to(1), sys-1);
+    /// assert_eq!(size.offset_to(sys*2 - 123), 123);
+    /// ```
+    ///
+    /// [`.round()`]: #method.round
+    #[inline]
+    pub const fn offset_to(&self, len: usize) -> usize {
+        len.wrapping_neg() & (self.0 - 1)
+    }
+
+    /// Test if a position or byte size is a multiple of the unit size.
+    ///
+    /// Addresses passed to system calls on pages, and the offsets of file
+    /// mappings, must be aligned to [`Size::page()`] or [`Size::alloc()`]
+    /// respectively. These differ on Windows, where the allocation
+    /// granularity is 64KiB, so check against the one the call requires.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::Size;
+    ///
+    /// let sys = vmap::allocation_size();
+    /// let size = Size::alloc();
+    /// assert!(size.is_aligned(0));
+    /// assert!(size.is_aligned(sys*3));
+    /// assert!(!size.is_aligned(sys + 1));
+    /// assert!(Size::page().is_aligned(vmap::page_size()));
+    /// ```
+    ///
+    /// [`Size::page()`]: #method.page
+    /// [`Size::alloc()`]: #method.alloc
+    #[inline]
+    pub const fn is_aligned(&self, len: usize) -> bool {
+        self.offset(len) == 0
+    }
+
     /// Convert a unit count into a byte size.
     ///
     /// # Examples
@@ -445,14 +1132,48 @@ impl Size {
 
     /// Calculates the unit bounds for a pointer and length.
     ///
+    /// The pointer is aligned down to the start of its unit, and the length
+    /// is extended by the same amount and rounded up to a whole number of
+    /// units. The result is the span that a system call operating on pages,
+    /// such as `madvise`, `mprotect`, or `msync`, requires in place of a
+    /// range that starts or ends partway through a page.
+    ///
+    /// A [`Map`] or [`MapMut`] created with an unaligned offset points into
+    /// its first page rather than at the start of it. This method computes
+    /// the span the map uses with [`Size::page()`] for page operations, and
+    /// with [`Size::alloc()`] for the underlying mapping.
+    ///
     /// # Safety
     ///
     /// There is no verification that the pointer is a mapped page nor that
     /// the calculated offset may be dereferenced.
-    #[inline]
-    pub unsafe fn bounds(&self, ptr: *mut u8, len: usize) -> (*mut u8, usize) {
-        let off = self.offset(ptr as usize);
-        (ptr.offset(-(off as isize)), self.round(len + off))
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{MapMut, Size};
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let page = vmap::page_size();
+    /// let mut map = MapMut::new(4 * page)?;
+    ///
+    /// // A range starting 10 bytes into the second page and spanning 2 pages.
+    /// let ptr = unsafe { map.as_mut_ptr().add(page + 10) };
+    /// let (pg, len) = unsafe { Size::page().bounds(ptr, 2 * page) };
+    /// assert_eq!(pg, unsafe { map.as_mut_ptr().add(page) });
+    /// assert_eq!(len, 3 * page);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Map`]: struct.Map.html
+    /// [`MapMut`]: struct.MapMut.html
+    /// [`Size::page()`]: #method.page
+    /// [`Size::alloc()`]: #method.alloc
+    #[inline]
+    pub unsafe fn bounds(&self, ptr: *mut u8, len: usize) -> (*mut u8, usize) {
+        let off = self.offset(ptr as usize);
+        (ptr.offset(-(off as isize)), self.round(len + off))
     }
 }
 
@@ -495,6 +1216,328 @@ pub trait Span: Deref<Target = [u8]> + Sized + sealed::Span {
         assert_capacity::<T>(offset, self.len());
         unsafe { ptr::read_unaligned(self.as_ptr().add(offset) as *const T) }
     }
+
+    /// Gets a reference to exactly `N` bytes at a given offset.
+    ///
+    /// Unlike a slice, the fixed-size array may be destructured or matched
+    /// without any further length checks. An error is returned when the
+    /// bytes lie outside of the span.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Map, Span};
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// # std::fs::write(&path, b"\x7fELF\x02\x01")?;
+    /// let (map, file) = Map::with_options().open(&path)?;
+    /// let [class, data] = *map.as_array::<2>(4)?;
+    /// assert_eq!(b"\x7fELF", map.as_array::<4>(0)?);
+    /// assert_eq!((2, 1), (class, data));
+    /// assert!(map.as_array::<4>(4).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    fn as_array<const N: usize>(&self, offset: usize) -> Result<&[u8; N]> {
+        offset
+            .checked_add(N)
+            .and_then(|end| self.deref().get(offset..end))
+            .and_then(|s| s.try_into().ok())
+            .ok_or_else(|| Error::input(Operation::Read, Input::InvalidRange))
+    }
+
+    /// Gets the NUL-terminated C string that starts at a given offset.
+    ///
+    /// The NUL is searched for with [`.find()`] between `offset` and the end
+    /// of the span, so the string can never extend past the span. An error
+    /// is returned when `offset` lies outside of the span or no NUL follows
+    /// it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Map, Span};
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// # std::fs::write(&path, b"\0.text\0.data\0.bss")?;
+    /// let (map, file) = Map::with_options().open(&path)?;
+    /// assert_eq!(c".text", map.c_str(1)?);
+    /// assert_eq!(c"", map.c_str(0)?);
+    /// assert!(map.c_str(15).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.find()`]: #method.find
+    fn c_str(&self, offset: usize) -> Result<&CStr> {
+        let bytes = self
+            .deref()
+            .get(offset..)
+            .ok_or_else(|| Error::input(Operation::Read, Input::InvalidRange))?;
+        match bytes.find(0) {
+            Some(n) => Ok(CStr::from_bytes_with_nul(&bytes[..=n]).unwrap()),
+            None => Err(Error::input(Operation::Read, Input::InvalidRange)),
+        }
+    }
+
+    /// Formats the span as a hexdump for debugging.
+    ///
+    /// The dump shows the offset, hex, and ASCII of each 16 bytes on a line,
+    /// and is written line by line as it is displayed. This requires the
+    /// `hexdump` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Map, Span};
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// # std::fs::write(&path, b"\x7fELF\x02\x01")?;
+    /// let (map, file) = Map::with_options().open(&path)?;
+    /// assert_eq!(
+    ///     "00000000  7f 45 4c 46 02 01                                 |.ELF..|\n",
+    ///     map.hexdump().to_string(),
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "hexdump")]
+    #[inline]
+    fn hexdump(&self) -> Hexdump<'_> {
+        Hexdump::new(self, 0)
+    }
+
+    /// Formats a range of the span as a hexdump for debugging.
+    ///
+    /// The offsets shown are relative to the start of the span rather than
+    /// the range. An [`Input::InvalidRange`] error is returned when the
+    /// range lies outside of the span. See [`.hexdump()`] for details.
+    ///
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    /// [`.hexdump()`]: #method.hexdump
+    #[cfg(feature = "hexdump")]
+    #[inline]
+    fn hexdump_range(&self, range: std::ops::Range<usize>) -> Result<Hexdump<'_>> {
+        let start = range.start;
+        self.deref()
+            .get(range)
+            .map(|bytes| Hexdump::new(bytes, start))
+            .ok_or_else(|| Error::input(Operation::Read, Input::InvalidRange))
+    }
+
+    /// Returns the offset of the first occurrence of a byte.
+    ///
+    /// With the `memchr` feature enabled this uses the vectorized routines
+    /// from the [`memchr`] crate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Map, Span};
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// # std::fs::write(&path, b"first line\nsecond line\n")?;
+    /// let (map, file) = Map::with_options().open(&path)?;
+    /// assert_eq!(Some(10), map.find(b'\n'));
+    /// assert_eq!(Some(22), map.rfind(b'\n'));
+    /// assert_eq!(None, map.find(b'x'));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`memchr`]: https://docs.rs/memchr
+    #[inline]
+    fn find(&self, needle: u8) -> Option<usize> {
+        #[cfg(feature = "memchr")]
+        {
+            memchr::memchr(needle, self)
+        }
+        #[cfg(not(feature = "memchr"))]
+        {
+            self.iter().position(|&b| b == needle)
+        }
+    }
+
+    /// Returns the offset of the last occurrence of a byte.
+    ///
+    /// See [`.find()`] for details.
+    ///
+    /// [`.find()`]: #method.find
+    #[inline]
+    fn rfind(&self, needle: u8) -> Option<usize> {
+        #[cfg(feature = "memchr")]
+        {
+            memchr::memrchr(needle, self)
+        }
+        #[cfg(not(feature = "memchr"))]
+        {
+            self.iter().rposition(|&b| b == needle)
+        }
+    }
+
+    /// Returns the offset of the first occurrence of a byte sequence.
+    ///
+    /// An empty needle matches at offset `0`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Map, Span};
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// # std::fs::write(&path, b"first line\nsecond line\n")?;
+    /// let (map, file) = Map::with_options().open(&path)?;
+    /// assert_eq!(Some(6), map.find_slice(b"line"));
+    /// assert_eq!(Some(18), map.rfind_slice(b"line"));
+    /// assert_eq!(None, map.find_slice(b"third"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    fn find_slice(&self, needle: &[u8]) -> Option<usize> {
+        #[cfg(feature = "memchr")]
+        {
+            memchr::memmem::find(self, needle)
+        }
+        #[cfg(not(feature = "memchr"))]
+        {
+            if needle.is_empty() {
+                Some(0)
+            } else {
+                self.windows(needle.len()).position(|w| w == needle)
+            }
+        }
+    }
+
+    /// Returns the offset of the last occurrence of a byte sequence.
+    ///
+    /// An empty needle matches at the end of the span.
+    #[inline]
+    fn rfind_slice(&self, needle: &[u8]) -> Option<usize> {
+        #[cfg(feature = "memchr")]
+        {
+            memchr::memmem::rfind(self, needle)
+        }
+        #[cfg(not(feature = "memchr"))]
+        {
+            if needle.is_empty() {
+                Some(self.len())
+            } else {
+                self.windows(needle.len()).rposition(|w| w == needle)
+            }
+        }
+    }
+
+    /// Views the span as a slice of scalar values with any unaligned bytes.
+    ///
+    /// This mirrors `slice::align_to` and returns the unaligned prefix, the
+    /// aligned middle as a `&[T]`, and the remaining bytes that do not fill a
+    /// whole `T`. As the values are restricted to plain scalar types, any bit
+    /// pattern is valid and the view is safe. For a map of a page-aligned
+    /// file offset the prefix is usually empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Map, MapMut, Span};
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let map = MapMut::with_options().len(20).alloc()?;
+    /// let (head, body, tail) = map.align_to::<u64>();
+    /// assert_eq!(0, head.len());
+    /// assert_eq!(&[0u64, 0], body);
+    /// assert_eq!(4, tail.len());
+    ///
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// # std::fs::write(&path, [0u8; 20])?;
+    /// let (map, file) = Map::with_options().offset(1).open(&path)?;
+    /// let (head, body, tail) = map.align_to::<u32>();
+    /// assert_eq!((3, 4, 0), (head.len(), body.len(), tail.len()));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    fn align_to<T: sealed::Scalar>(&self) -> (&[u8], &[T], &[u8]) {
+        unsafe { self.deref().align_to::<T>() }
+    }
+
+    /// Gets a reference to the value of type `T` at a given offset.
+    ///
+    /// This suits fixed binary headers of mapped files. The value must lie
+    /// within the span, or an [`Input::InvalidRange`] error is returned, and
+    /// its address must be aligned for `T`, or an [`Input::Unaligned`] error
+    /// is returned. Use [`.read_unaligned()`] to copy out a scalar value
+    /// that is not aligned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{FromBytes, MapMut, Span, SpanMut};
+    ///
+    /// #[derive(Clone, Copy)]
+    /// #[repr(C)]
+    /// struct Header {
+    ///     magic: [u8; 4],
+    ///     count: u32,
+    /// }
+    ///
+    /// unsafe impl FromBytes for Header {}
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let mut map = MapMut::new(4096)?;
+    /// map.as_type_mut::<Header>(0)?.magic = *b"VMAP";
+    /// let header = map.as_type::<Header>(0)?;
+    /// assert_eq!(b"VMAP", &header.magic);
+    /// assert_eq!(0, header.count);
+    /// assert!(map.as_type::<Header>(2).is_err());
+    /// assert!(map.as_type::<Header>(4092).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    /// [`Input::Unaligned`]: enum.Input.html#variant.Unaligned
+    /// [`.read_unaligned()`]: #method.read_unaligned
+    #[inline]
+    fn as_type<T: FromBytes>(&self, offset: usize) -> Result<&T> {
+        let ptr = cast_ptr::<T>(self.as_ptr(), self.len(), offset, 1)?;
+        Ok(unsafe { &*(ptr as *const T) })
+    }
+
+    /// Gets a slice of `count` values of type `T` at a given offset.
+    ///
+    /// The values are checked as in [`.as_type()`].
+    ///
+    /// [`.as_type()`]: #method.as_type
+    #[inline]
+    fn as_type_slice<T: FromBytes>(&self, offset: usize, count: usize) -> Result<&[T]> {
+        let ptr = cast_ptr::<T>(self.as_ptr(), self.len(), offset, count)?;
+        Ok(unsafe { slice::from_raw_parts(ptr as *const T, count) })
+    }
 }
 
 /// General trait for working with any memory-safe representation of a
@@ -521,9 +1564,120 @@ pub trait SpanMut: Span + DerefMut {
         assert_capacity::<T>(offset, self.len());
         unsafe { ptr::write_unaligned(self.as_mut_ptr().add(offset) as *mut T, value) }
     }
+
+    /// Gets a mutable reference to exactly `N` bytes at a given offset.
+    ///
+    /// An error is returned when the bytes lie outside of the span.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{MapMut, SpanMut};
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let mut map = MapMut::with_options().len(8).alloc()?;
+    /// *map.as_array_mut::<4>(2)? = *b"test";
+    /// assert_eq!(b"\0\0test\0\0", &map[..]);
+    /// assert!(map.as_array_mut::<4>(6).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    fn as_array_mut<const N: usize>(&mut self, offset: usize) -> Result<&mut [u8; N]> {
+        offset
+            .checked_add(N)
+            .and_then(|end| self.deref_mut().get_mut(offset..end))
+            .and_then(|s| s.try_into().ok())
+            .ok_or_else(|| Error::input(Operation::Write, Input::InvalidRange))
+    }
+
+    /// Views the span as a mutable slice of scalar values with any unaligned
+    /// bytes.
+    ///
+    /// See [`Span::align_to()`] for details.
+    ///
+    /// [`Span::align_to()`]: trait.Span.html#method.align_to
+    #[inline]
+    fn align_to_mut<T: sealed::Scalar>(&mut self) -> (&mut [u8], &mut [T], &mut [u8]) {
+        unsafe { self.deref_mut().align_to_mut::<T>() }
+    }
+
+    /// Gets a mutable reference to the value of type `T` at a given offset.
+    ///
+    /// See [`Span::as_type()`] for details.
+    ///
+    /// [`Span::as_type()`]: trait.Span.html#method.as_type
+    #[inline]
+    fn as_type_mut<T: FromBytes>(&mut self, offset: usize) -> Result<&mut T> {
+        cast_ptr::<T>(self.as_ptr(), self.len(), offset, 1)?;
+        Ok(unsafe { &mut *(self.as_mut_ptr().add(offset) as *mut T) })
+    }
+
+    /// Gets a mutable slice of `count` values of type `T` at a given offset.
+    ///
+    /// See [`Span::as_type()`] for details.
+    ///
+    /// [`Span::as_type()`]: trait.Span.html#method.as_type
+    #[inline]
+    fn as_type_slice_mut<T: FromBytes>(&mut self, offset: usize, count: usize) -> Result<&mut [T]> {
+        cast_ptr::<T>(self.as_ptr(), self.len(), offset, count)?;
+        Ok(unsafe { slice::from_raw_parts_mut(self.as_mut_ptr().add(offset) as *mut T, count) })
+    }
+}
+
+/// Marker for types that may be read from any bytes of the right size.
+///
+/// This allows a type to be viewed in place within a map with
+/// [`Span::as_type()`] and [`SpanMut::as_type_mut()`], and is implemented for
+/// the primitive integer and floating point types and arrays of them.
+///
+/// # Safety
+///
+/// Every bit pattern of the size of the type must be a valid value of it.
+/// This holds for a `#[repr(C)]` or `#[repr(transparent)]` struct whose
+/// fields all implement `FromBytes` and that has no padding, whose bytes
+/// would be uninitialized when written. It excludes types such as `bool`,
+/// `char`, references, and most enums.
+///
+/// [`Span::as_type()`]: trait.Span.html#method.as_type
+/// [`SpanMut::as_type_mut()`]: trait.SpanMut.html#method.as_type_mut
+pub unsafe trait FromBytes: Copy {}
+
+unsafe impl FromBytes for u8 {}
+unsafe impl FromBytes for i8 {}
+unsafe impl FromBytes for u16 {}
+unsafe impl FromBytes for i16 {}
+unsafe impl FromBytes for u32 {}
+unsafe impl FromBytes for i32 {}
+unsafe impl FromBytes for u64 {}
+unsafe impl FromBytes for i64 {}
+unsafe impl FromBytes for u128 {}
+unsafe impl FromBytes for i128 {}
+unsafe impl FromBytes for usize {}
+unsafe impl FromBytes for isize {}
+unsafe impl FromBytes for f32 {}
+unsafe impl FromBytes for f64 {}
+unsafe impl<T: FromBytes, const N: usize> FromBytes for [T; N] {}
+
+// Checks that `count` values of `T` at `offset` lie within a span and are
+// aligned, and gets the address of the first.
+#[inline]
+fn cast_ptr<T>(ptr: *const u8, len: usize, offset: usize, count: usize) -> Result<*const u8> {
+    let end = count
+        .checked_mul(mem::size_of::<T>())
+        .and_then(|n| n.checked_add(offset));
+    match end {
+        Some(end) if end <= len => {}
+        _ => return Err(Error::input(Operation::Cast, Input::InvalidRange)),
+    }
+    let ptr = ptr.wrapping_add(offset);
+    if !(ptr as usize).is_multiple_of(mem::align_of::<T>()) {
+        return Err(Error::input(Operation::Cast, Input::Unaligned));
+    }
+    Ok(ptr)
 }
 
-impl<'a> Span for &'a [u8] {
+impl Span for &[u8] {
     #[inline]
     fn len(&self) -> usize {
         <[u8]>::len(self)
@@ -535,7 +1689,7 @@ impl<'a> Span for &'a [u8] {
     }
 }
 
-impl<'a> Span for &'a mut [u8] {
+impl Span for &mut [u8] {
     #[inline]
     fn len(&self) -> usize {
         <[u8]>::len(self)
@@ -547,7 +1701,7 @@ impl<'a> Span for &'a mut [u8] {
     }
 }
 
-impl<'a> SpanMut for &'a mut [u8] {
+impl SpanMut for &mut [u8] {
     #[inline]
     fn as_mut_ptr(&mut self) -> *mut u8 {
         <[u8]>::as_mut_ptr(self)
@@ -559,11 +1713,69 @@ mod sealed {
 
     impl Span for super::Map {}
     impl Span for super::MapMut {}
-    impl<'a> Span for &'a [u8] {}
-    impl<'a> Span for &'a mut [u8] {}
+    impl Span for super::MapSegment {}
+    impl Span for &[u8] {}
+    impl Span for &mut [u8] {}
 
     pub trait FromPtr {
-        unsafe fn from_ptr(ptr: *mut u8, len: usize) -> Self;
+        unsafe fn from_ptr(ptr: *mut u8, len: usize, prot: super::Protect) -> Self;
+        unsafe fn from_file_ptr(
+            ptr: *mut u8,
+            len: usize,
+            off: u64,
+            prot: super::Protect,
+            flags: super::MapFlags,
+        ) -> Self;
+        fn set_file(&mut self, file: std::fs::File);
+        fn set_huge(&mut self, size: usize);
+
+        /// Checked form of `from_ptr` that rejects a null or `MAP_FAILED`
+        /// pointer rather than constructing a map from it.
+        unsafe fn try_from_ptr(
+            ptr: *mut u8,
+            len: usize,
+            prot: super::Protect,
+        ) -> super::Result<Self>
+        where
+            Self: Sized,
+        {
+            check_ptr(super::Operation::MapAnonymous, ptr)?;
+            Ok(Self::from_ptr(ptr, len, prot))
+        }
+
+        /// Checked form of `from_file_ptr` that rejects a null or
+        /// `MAP_FAILED` pointer rather than constructing a map from it.
+        unsafe fn try_from_file_ptr(
+            ptr: *mut u8,
+            len: usize,
+            off: u64,
+            prot: super::Protect,
+            flags: super::MapFlags,
+        ) -> super::Result<Self>
+        where
+            Self: Sized,
+        {
+            check_ptr(super::Operation::MapFile, ptr)?;
+            Ok(Self::from_file_ptr(ptr, len, off, prot, flags))
+        }
+    }
+
+    fn check_ptr(op: super::Operation, ptr: *mut u8) -> super::Result<()> {
+        // Maps may start partway into their first allocation unit, so this
+        // rejects null or `MAP_FAILED` (`(void *)-1`) advanced by less than
+        // the allocation size. No mapping is placed in either of those units.
+        let gran = super::allocation_size();
+        let addr = ptr as usize;
+        #[cfg(test)]
+        if super::map::FAIL_FROM_PTR.with(|f| f.replace(false)) {
+            super::map::REJECTED_PTR.with(|p| p.set(addr));
+            return Err(super::Error::input(op, super::Input::InvalidPointer));
+        }
+        if addr < gran || addr > usize::MAX - gran {
+            Err(super::Error::input(op, super::Input::InvalidPointer))
+        } else {
+            Ok(())
+        }
     }
 
     pub trait Scalar: Default {}
@@ -582,11 +1794,34 @@ mod sealed {
     impl Scalar for isize {}
     impl Scalar for f32 {}
     impl Scalar for f64 {}
+
+    pub trait Atomic {}
+
+    #[cfg(target_has_atomic = "8")]
+    impl Atomic for std::sync::atomic::AtomicU8 {}
+    #[cfg(target_has_atomic = "8")]
+    impl Atomic for std::sync::atomic::AtomicI8 {}
+    #[cfg(target_has_atomic = "16")]
+    impl Atomic for std::sync::atomic::AtomicU16 {}
+    #[cfg(target_has_atomic = "16")]
+    impl Atomic for std::sync::atomic::AtomicI16 {}
+    #[cfg(target_has_atomic = "32")]
+    impl Atomic for std::sync::atomic::AtomicU32 {}
+    #[cfg(target_has_atomic = "32")]
+    impl Atomic for std::sync::atomic::AtomicI32 {}
+    #[cfg(target_has_atomic = "64")]
+    impl Atomic for std::sync::atomic::AtomicU64 {}
+    #[cfg(target_has_atomic = "64")]
+    impl Atomic for std::sync::atomic::AtomicI64 {}
+    #[cfg(target_has_atomic = "ptr")]
+    impl Atomic for std::sync::atomic::AtomicUsize {}
+    #[cfg(target_has_atomic = "ptr")]
+    impl Atomic for std::sync::atomic::AtomicIsize {}
 }
 
 #[inline]
 fn assert_alignment<T>(offset: usize, ptr: *const u8) {
-    if unsafe { ptr.add(offset) } as usize % mem::align_of::<T>() != 0 {
+    if !(unsafe { ptr.add(offset) } as usize).is_multiple_of(mem::align_of::<T>()) {
         panic!(
             "offset improperly aligned: the requirement is {} but the offset is +{}/-{}",
             mem::align_of::<T>(),
@@ -686,6 +1921,194 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn alloc_empty() -> Result<()> {
+        let map = MapMut::with_options().len(0).alloc()?;
+        assert!(map.is_empty());
+        assert_eq!(&map[..], &[] as &[u8]);
+        map.advise(Advise::Sequential)?;
+
+        let map = MapMut::with_options().offset(5).len(0).alloc()?;
+        assert!(map.is_empty());
+        let map = map.into_map()?;
+        assert!(map.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn file_empty() -> Result<()> {
+        let (_tmp, path, len) = write_default("file_empty")?;
+        let (map, _) = Map::with_options().offset(len).open(&path)?;
+        assert!(map.is_empty());
+        assert_eq!(&map[..], &[] as &[u8]);
+
+        let (map, file) = MapMut::with_options().truncate(true).open(&path)?;
+        assert!(map.is_empty());
+        map.flush(&file, Flush::Sync)?;
+        let map = map.into_map()?.into_map_mut()?;
+        assert_eq!(&map[..], &[] as &[u8]);
+        Ok(())
+    }
+
+    #[test]
+    fn file_shared_writeback() -> Result<()> {
+        let (_tmp, path, len) = write_default("file_shared_writeback")?;
+        {
+            let (mut map, file) = MapMut::with_options()
+                .sharing(Sharing::Shared)
+                .open(&path)?;
+            map[2..7].clone_from_slice(b"CROSS");
+
+            // A separate handle reads the page cache before any flush.
+            let (map2, _) = Map::with_options().open(&path)?;
+            assert_eq!(b"CROSS", &map2[2..7]);
+
+            map.flush(&file, Flush::Sync)?;
+        }
+        let data = fs::read(&path)?;
+        assert_eq!(data.len(), len);
+        assert_eq!(Ok("A CROSS-platform"), from_utf8(&data[..16]));
+        Ok(())
+    }
+
+    #[test]
+    fn file_copy_private() -> Result<()> {
+        let (_tmp, path, len) = write_default("file_copy_private")?;
+        let before = fs::read(&path)?;
+        {
+            let (mut map, file) = MapMut::with_options().copy().open(&path)?;
+            assert!(map.is_private());
+            map[2..7].clone_from_slice(b"CROSS");
+            assert_eq!(b"CROSS", &map[2..7]);
+            map.flush(&file, Flush::Sync)?;
+
+            let map = map.into_map()?;
+            assert!(map.is_private());
+            assert_eq!(b"CROSS", &map[2..7]);
+        }
+        let (map, _) = MapMut::with_options().open(&path)?;
+        assert!(!map.is_private());
+        let data = fs::read(&path)?;
+        assert_eq!(data.len(), len);
+        assert_eq!(data, before);
+        Ok(())
+    }
+
+    #[test]
+    fn protect_redundant() -> Result<()> {
+        let calls = || map::PROTECT_CALLS.with(|n| n.get());
+
+        let mut map = MapMut::new(100)?;
+        map.set_protect(Protect::ReadWrite)?;
+        assert_eq!(calls(), 0);
+        let map = map.into_map()?.into_map_mut()?;
+        assert_eq!(calls(), 2);
+
+        let (_tmp, path, _) = write_default("protect_redundant")?;
+        let file = fs::OpenOptions::new().read(true).write(true).open(&path)?;
+        let map2 = Map::with_options().map(&file)?;
+        let map2 = map2.with_protection(Protect::ReadOnly)?;
+        assert_eq!(calls(), 2);
+        let map2 = map2.into_map_mut()?;
+        assert_eq!(calls(), 3);
+        let mut map2 = map2.into_map()?.into_map_mut()?;
+        map2.set_protect(Protect::ReadWrite)?;
+        assert_eq!(calls(), 5);
+
+        drop((map, map2));
+        Ok(())
+    }
+
+    #[test]
+    fn bitset_partial() -> Result<()> {
+        let (_tmp, path, _) = write_tmp("bitset_partial", "\u{7f}\u{7f}\u{7f}")?;
+        let mut bits = MapBitset::open(&path, 13)?;
+        assert_eq!(bits.count_ones(), 12);
+        assert!(!bits.set(7, true));
+        assert!(bits.set(12, false));
+        assert_eq!(bits.count_ones(), 12);
+        bits.flush_range(7, 1, Flush::Sync)?;
+        assert_eq!(fs::read(&path)?, b"\xff\x6f\x7f");
+
+        let mut bits = MapBitset::new(13)?;
+        assert_eq!(bits.count_ones(), 0);
+        bits.set(12, true);
+        assert_eq!(bits.count_ones(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn flush_partial_page() -> Result<()> {
+        let tmp = tempdir::TempDir::new("vmap")?;
+        let path: PathBuf = tmp.path().join("flush_partial_page");
+        fs::write(&path, [0u8; 100])?;
+
+        let (mut map, file) = MapMut::with_options().open(&path)?;
+        assert_eq!(map.len(), 100);
+        map.clone_from_slice(&[0xa5; 100]);
+        map.flush(&file, Flush::Sync)?;
+        map.flush_range(&file, 99, 1, Flush::Async)?;
+        drop(map);
+
+        assert_eq!(fs::read(&path)?, vec![0xa5; 100]);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(all(windows, target_pointer_width = "64"))]
+    fn file_offset_above_4gb() -> Result<()> {
+        use std::os::windows::fs::FileExt;
+
+        let tmp = tempdir::TempDir::new("vmap")?;
+        let path: PathBuf = tmp.path().join("file_offset_above_4gb");
+        let gran = crate::allocation_size();
+        let high = (1usize << 32) + gran;
+
+        // Only the low offset is written so the file is never filled to 4GB.
+        // A view that dropped the high 32 bits of the offset would see it.
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        file.set_len((high + gran) as u64)?;
+        file.seek_write(b"low", gran as u64)?;
+
+        let map = Map::with_options().offset(high).len(gran).map(&file)?;
+        assert_eq!(map.len(), gran);
+        assert_eq!(&map[..3], b"\0\0\0");
+
+        let map = Map::with_options().offset(gran).len(gran).map(&file)?;
+        assert_eq!(&map[..3], b"low");
+        Ok(())
+    }
+
+    #[test]
+    fn remap_window() -> Result<()> {
+        let tmp = tempdir::TempDir::new("vmap")?;
+        let path: PathBuf = tmp.path().join("remap_window");
+        let page = Size::alloc().size(1);
+        let data: Vec<u8> = (0..4 * page).map(|i| (i / page) as u8).collect();
+        fs::write(&path, &data)?;
+
+        let (mut map, file) = Map::with_options().len(2 * page).open(&path)?;
+        let ptr = map.as_ptr();
+
+        map.remap(&file, page + 10, page - 10)?;
+        assert_eq!(&map[..], &data[page + 10..2 * page]);
+        if cfg!(unix) {
+            assert_eq!(map.as_ptr(), ptr.wrapping_add(10));
+        }
+
+        map.remap(&file, 0, 4 * page)?;
+        assert_eq!(&map[..], &data[..]);
+
+        map.remap(&file, 4 * page, 0)?;
+        assert!(map.is_empty());
+        assert!(map.remap(&file, 4 * page, 1).is_err());
+        Ok(())
+    }
+
     #[test]
     fn read_end() -> Result<()> {
         let (_tmp, path, len) = write_default("read_end")?;
@@ -791,6 +2214,124 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn sync_private() -> Result<()> {
+        let (_tmp, path, _len) = write_default("sync_private")?;
+        let err = MapMut::with_options()
+            .copy()
+            .sync(true)
+            .open(&path)
+            .unwrap_err();
+        assert_eq!(std::io::ErrorKind::Unsupported, err.kind());
+        Ok(())
+    }
+
+    #[test]
+    fn persist() -> Result<()> {
+        let (_tmp, path, _len) = write_default("persist")?;
+        let (mut map, file) = MapMut::with_options().open(&path)?;
+        map[29..33].clone_from_slice(b"nice");
+        map.persist(&file, 29, 4)?;
+        assert!(map.persist(&file, 60, 100).is_err());
+        assert!(map.persist(&file, usize::MAX, 2).is_err());
+
+        // Without `MAP_SYNC`, the cache writeback alone is not durable, so
+        // an anonymous map cannot be persisted.
+        let err = MapMut::new(100)?.persist(&file, 0, 4).unwrap_err();
+        assert_eq!(Some(Input::Unsupported), err.input_kind());
+
+        let (map, _) = Map::with_options().offset(29).len(30).open(&path)?;
+        assert_eq!(Ok("nice and safe memory-mapped IO"), from_utf8(&map[..]));
+        Ok(())
+    }
+
+    #[test]
+    fn lock_file() -> Result<()> {
+        let (_tmp, path, _len) = write_default("lock_file")?;
+        let (map1, file1) = Map::with_options().open(&path)?;
+        let (map2, file2) = Map::with_options().open(&path)?;
+        {
+            let _a = map1.lock_file(&file1, false)?;
+            let _b = map2.lock_file(&file2, false)?;
+        }
+        let held = map1.lock_file(&file1, true)?;
+
+        // A second open of the file cannot take a lock while the first is held.
+        let other = fs::File::open(&path)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::io::AsRawFd;
+            let rc = unsafe { libc::flock(other.as_raw_fd(), libc::LOCK_SH | libc::LOCK_NB) };
+            assert_eq!(-1, rc);
+            let err = std::io::Error::last_os_error();
+            assert_eq!(Some(libc::EWOULDBLOCK), err.raw_os_error());
+        }
+        #[cfg(windows)]
+        {
+            use std::os::windows::io::AsRawHandle;
+            use winapi::um::fileapi::LockFileEx;
+            use winapi::um::minwinbase::{LOCKFILE_FAIL_IMMEDIATELY, OVERLAPPED};
+            let ok = unsafe {
+                let mut overlapped = mem::zeroed::<OVERLAPPED>();
+                let flags = LOCKFILE_FAIL_IMMEDIATELY;
+                LockFileEx(other.as_raw_handle(), flags, 0, !0, !0, &mut overlapped)
+            };
+            assert_eq!(0, ok);
+        }
+        drop(held);
+        let _d = map2.lock_file(&other, false)?;
+        Ok(())
+    }
+
+    // Writes a sentinel from a forked child and returns what the parent
+    // observes afterward. The child only touches the mapping and exits to
+    // remain safe with the other test threads.
+    #[cfg(unix)]
+    fn fork_write(map: &mut MapMut) -> u8 {
+        unsafe {
+            let pid = libc::fork();
+            assert!(pid >= 0, "fork failed");
+            if pid == 0 {
+                ptr::write_volatile(map.as_mut_ptr(), 0xa5);
+                libc::_exit(0);
+            }
+            let mut status = 0;
+            assert_eq!(pid, libc::waitpid(pid, &mut status, 0));
+            assert!(libc::WIFEXITED(status));
+            ptr::read_volatile(map.as_ptr())
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn alloc_shared_fork() -> Result<()> {
+        let mut map = MapMut::new(100)?;
+        assert_eq!(0xa5, fork_write(&mut map));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn alloc_copy_fork() -> Result<()> {
+        let mut map = MapMut::with_options().copy().len(100).alloc()?;
+        assert_eq!(0, fork_write(&mut map));
+        Ok(())
+    }
+
+    #[test]
+    fn find() {
+        let s: &[u8] = b"abcabc";
+        assert_eq!(Some(0), s.find_slice(b""));
+        assert_eq!(Some(6), s.rfind_slice(b""));
+        assert_eq!(Some(1), s.find_slice(b"bc"));
+        assert_eq!(Some(4), s.rfind_slice(b"bc"));
+        assert_eq!(None, s.find_slice(b"abcabca"));
+        assert_eq!(None, s.rfind_slice(b"abcabca"));
+        let e: &[u8] = b"";
+        assert_eq!(None, e.find(b'a'));
+        assert_eq!(None, e.rfind(b'a'));
+    }
+
     type WriteResult = Result<(tempdir::TempDir, PathBuf, usize)>;
 
     fn write_tmp(name: &'static str, msg: &'static str) -> WriteResult {
@@ -866,4 +2407,1924 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn advise_cold_keeps_data() -> Result<()> {
+        let page = page_size();
+        let mut map = MapMut::new(2 * page)?;
+        map.fill(7);
+        map.advise(Advise::Cold)?;
+        assert!(map.iter().all(|&b| b == 7));
+        map.advise(Advise::PageOut)?;
+        assert!(map.iter().all(|&b| b == 7));
+        Ok(())
+    }
+
+    #[test]
+    fn try_from_ptr_rejects_invalid() {
+        use sealed::FromPtr;
+
+        let gran = crate::allocation_size();
+        for addr in [0, 1, gran - 1, usize::MAX, usize::MAX - 1] {
+            let ptr = addr as *mut u8;
+            let err = unsafe { MapMut::try_from_ptr(ptr, 1, Protect::ReadWrite) }.unwrap_err();
+            assert!(err.to_string().contains(Input::InvalidPointer.as_str()));
+            let res =
+                unsafe { Map::try_from_file_ptr(ptr, 1, 0, Protect::ReadOnly, MapFlags::NONE) };
+            assert!(res.is_err());
+        }
+    }
+
+    #[test]
+    fn stack_guard() -> Result<()> {
+        let page = page_size();
+        let mut stack = Stack::new(page + 1)?;
+        assert_eq!(stack.size(), 2 * page);
+        assert_eq!(stack.guard_size(), page);
+        assert_eq!(stack.bottom() as usize % page, 0);
+        assert_eq!(stack.top() as usize - stack.bottom() as usize, 2 * page);
+        stack.as_mut_slice().fill(0xa5);
+        assert!(stack.as_slice().iter().all(|&b| b == 0xa5));
+        Ok(())
+    }
+
+    #[test]
+    fn typed_map() -> Result<()> {
+        use std::convert::TryFrom;
+
+        let map = MapMut::with_options().len(12).alloc()?;
+        let (err, map) = TypedMap::<u64>::try_from(map).unwrap_err();
+        assert!(err.to_string().contains(Input::Unaligned.as_str()));
+
+        let mut ints = TypedMap::<u32>::try_from(map).map_err(|(e, _)| e)?;
+        assert_eq!(ints.len(), 3);
+        ints[2] = 0x01020304;
+        let map = ints.into_inner();
+        assert_eq!(map[8..12], 0x01020304u32.to_ne_bytes());
+
+        let map = MapMut::with_options().offset(1).len(8).alloc()?;
+        assert!(TypedMap::<u64>::try_from(map).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn growable_bounds() -> Result<()> {
+        let page = page_size();
+        let mut map = MapMut::growable(0, 2 * page)?;
+        assert!(map.is_empty());
+        map.grow(page + 1)?;
+        map[page] = 1;
+        map.grow(1)?;
+        assert_eq!(map.len(), page + 1);
+        assert!(map.grow(Size::alloc().round(2 * page) + 1).is_err());
+        assert!(MapMut::growable(2, 1).is_err());
+        assert!(MapMut::new(1)?.grow(2).is_err());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn mmap_alloc() {
+        use std::alloc::{GlobalAlloc, Layout};
+
+        let alloc = MmapAlloc::new();
+        for &(size, align) in &[
+            (1, 1),
+            (24, 8),
+            (100, 64),
+            (2048, 16),
+            (5000, 8),
+            (10, 4096),
+        ] {
+            let layout = Layout::from_size_align(size, align).unwrap();
+            unsafe {
+                let a = alloc.alloc(layout);
+                let b = alloc.alloc(layout);
+                assert!(!a.is_null() && !b.is_null());
+                assert_ne!(a, b);
+                assert_eq!(a as usize % align, 0);
+                ptr::write_bytes(a, 0xa5, size);
+                ptr::write_bytes(b, 0x5a, size);
+                assert_eq!(*a.add(size - 1), 0xa5);
+                alloc.dealloc(a, layout);
+                if size.max(align) <= 2048 {
+                    // Freed small blocks are reused first.
+                    assert_eq!(alloc.alloc(layout), a);
+                    alloc.dealloc(a, layout);
+                }
+                alloc.dealloc(b, layout);
+            }
+        }
+    }
+
+    #[test]
+    fn close_unmaps_once() -> Result<()> {
+        let page = page_size();
+        for _ in 0..16 {
+            let map = MapMut::new(page)?;
+            map.close()?;
+            // A second unmap of the closed range could remove this mapping if
+            // it reuses the same address.
+            let mut next = MapMut::new(page)?;
+            next[page - 1] = 1;
+            assert_eq!(next[page - 1], 1);
+        }
+
+        let map = MapMut::growable(page, 4 * page)?;
+        map.close()?;
+        let (_tmp, path, _) = write_default("close_unmaps_once")?;
+        let (map, _) = Map::with_options().open(&path)?;
+        map.close()?;
+        MapMut::new(0)?.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn file_span_tiny_lengths() -> Result<()> {
+        use crate::map::file_span;
+
+        let unit = crate::allocation_size();
+        assert_eq!(file_span(0, 1), (0, 1));
+        assert_eq!(file_span(unit - 1, 1), (0, unit));
+        assert_eq!(file_span(unit - 1, 2), (0, unit + 1));
+        assert_eq!(Size::alloc().round(file_span(unit - 1, 2).1), 2 * unit);
+        assert_eq!(file_span(unit, 1), (unit, 1));
+        assert_eq!(file_span(unit + 1, 0), (unit, 0));
+        assert_eq!(file_span(unit - 1, 0), (0, 0));
+
+        let tmp = tempdir::TempDir::new("vmap")?;
+        let path: PathBuf = tmp.path().join("file_span_tiny_lengths");
+        let data: Vec<u8> = (0..3 * unit).map(|i| (i % 251) as u8).collect();
+        fs::write(&path, &data)?;
+
+        for (off, len) in [(unit - 1, 2), (unit - 1, 1), (unit, 1), (2 * unit - 1, 2)] {
+            let (map, _) = Map::with_options().offset(off).len(len).open(&path)?;
+            assert_eq!(&map[..], &data[off..off + len]);
+        }
+        let (map, _) = Map::with_options().offset(unit + 1).len(0).open(&path)?;
+        assert!(map.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn map_clone_shared() -> Result<()> {
+        let (_tmp, path, len) = write_default("map_clone_shared")?;
+        let (map, file) = Map::with_options().write().open(&path)?;
+        assert!(!map.is_shared());
+
+        let workers: Vec<_> = (0..4)
+            .map(|i| {
+                let map = map.clone();
+                std::thread::spawn(move || (map.as_ptr() as usize, map[i]))
+            })
+            .collect();
+        for (i, worker) in workers.into_iter().enumerate() {
+            let (ptr, byte) = worker.join().unwrap();
+            assert_eq!(ptr, map.as_ptr() as usize);
+            assert_eq!(byte, map[i]);
+        }
+
+        let mut copy = map.clone();
+        assert!(map.is_shared());
+        let err = copy.remap(&file, 2, 5).unwrap_err();
+        assert!(err.to_string().contains(Input::Shared.as_str()));
+        let (err, copy) = copy.into_map_mut().unwrap_err();
+        assert!(err.to_string().contains(Input::Shared.as_str()));
+
+        map.close()?;
+        assert!(!copy.is_shared());
+        assert_eq!(copy.len(), len);
+        assert_eq!(Ok("A cross-platform"), from_utf8(&copy[..16]));
+        let mut map = copy.into_map_mut()?;
+        map[..1].copy_from_slice(b"a");
+        Ok(())
+    }
+
+    #[test]
+    fn options_preload() -> Result<()> {
+        let (_tmp, path, len) = write_default("options_preload")?;
+        let (map, _) = Map::with_options()
+            .advise(Advise::WillNeed)
+            .populate(true)
+            .locked(true)
+            .open(&path)?;
+        assert_eq!(map.len(), len);
+        assert_eq!(Ok("A cross-platform"), from_utf8(&map[..16]));
+
+        let map = MapMut::with_options()
+            .len(2 * page_size())
+            .advise(Advise::Sequential)
+            .populate(true)
+            .alloc()?;
+        assert!(map.iter().all(|&b| b == 0));
+        Ok(())
+    }
+
+    #[test]
+    fn copy_within_overlap() -> Result<()> {
+        let mut map = MapMut::with_options().len(10).alloc()?;
+        map.copy_from_slice(b"0123456789");
+        map.copy_within(2..8, 0)?;
+        assert_eq!(b"2345676789", &map[..]);
+        map.copy_within(0..6, 4)?;
+        assert_eq!(b"2345234567", &map[..]);
+        map.copy_within(10..10, 10)?;
+
+        #[allow(clippy::reversed_empty_ranges)]
+        for (src, dst) in [(5..11, 0), (0..4, 7), (0..1, usize::MAX), (6..4, 0)] {
+            assert!(map.copy_within(src, dst).is_err());
+        }
+        assert_eq!(b"2345234567", &map[..]);
+        Ok(())
+    }
+
+    #[test]
+    fn flush_anon_unsupported() -> Result<()> {
+        let (_tmp, path, _) = write_default("flush_anon_unsupported")?;
+        let (map, file) = MapMut::with_options().open(&path)?;
+        assert!(map.is_file_backed());
+        map.flush_range(&file, 0, 1, Flush::Async)?;
+
+        let anon = MapMut::new(page_size())?;
+        assert!(!anon.is_file_backed());
+        for res in [
+            anon.flush(&file, Flush::Sync),
+            anon.flush_range(&file, 0, 1, Flush::Async),
+            anon.sync_range_async(&file, 0, 1),
+        ] {
+            assert!(res
+                .unwrap_err()
+                .to_string()
+                .contains(Input::Unsupported.as_str()));
+        }
+        assert!(!MapMut::growable(1, page_size())?.is_file_backed());
+        assert!(Map::with_options().open(&path)?.0.is_file_backed());
+        Ok(())
+    }
+
+    #[test]
+    fn digest_large_file() -> Result<()> {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::Hasher;
+
+        let tmp = tempdir::TempDir::new("vmap")?;
+        let path: PathBuf = tmp.path().join("digest_large_file");
+        let data: Vec<u8> = (0..(3 << 20) + 123).map(|i| (i % 253) as u8).collect();
+        fs::write(&path, &data)?;
+
+        let mut expect = DefaultHasher::new();
+        expect.write(&data[7..]);
+
+        let (map, _) = Map::with_options().offset(7).open(&path)?;
+        let mut hasher = DefaultHasher::new();
+        map.digest(&mut hasher)?;
+        assert_eq!(expect.finish(), hasher.finish());
+        // Released pages are read back from the file.
+        assert_eq!(&map[..], &data[7..]);
+
+        let mut anon = MapMut::new(2 << 20)?;
+        anon[1 << 20] = 1;
+        anon.digest(&mut DefaultHasher::new())?;
+        assert_eq!(anon[1 << 20], 1);
+        Ok(())
+    }
+
+    #[test]
+    fn at_address_exact() -> Result<()> {
+        let page = crate::allocation_size();
+        let map = MapMut::new(2 * page)?;
+        let addr = map.as_ptr() as *mut u8;
+        for at in [addr, unsafe { addr.add(page) }] {
+            let err = MapMut::at_address(at, page).unwrap_err();
+            assert!(err.to_string().contains(Input::AddressInUse.as_str()));
+        }
+        let err = MapMut::at_address(unsafe { addr.add(1) }, page).unwrap_err();
+        assert!(err.to_string().contains(Input::Unaligned.as_str()));
+        assert!(MapMut::at_address(addr, 0).is_err());
+        assert_eq!(map.len(), 2 * page);
+
+        // Other tests may claim the freed address first, so retry a few times.
+        for _ in 0..8 {
+            let addr = MapMut::new(page)?.as_ptr() as *mut u8;
+            match MapMut::at_address(addr, page) {
+                Ok(mut map) => {
+                    assert_eq!(map.as_ptr(), addr);
+                    map[page - 1] = 1;
+                    return Ok(());
+                }
+                Err(e) => assert!(e.to_string().contains(Input::AddressInUse.as_str())),
+            }
+        }
+        panic!("freed address was never available");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn into_pages_split() -> Result<()> {
+        let page = crate::allocation_size();
+        let mut map = MapMut::with_options().len(3 * page + 10).alloc()?;
+        for i in 0..4 {
+            map[i * page] = i as u8 + 1;
+        }
+        let base = map.as_ptr();
+
+        let mut pages = map.into_pages().unwrap();
+        assert_eq!(pages.size_hint(), (4, Some(4)));
+        let first = pages.next().unwrap();
+        let mut second = pages.next().unwrap();
+        drop(pages);
+        assert_eq!((first.as_ptr(), first.len(), first[0]), (base, page, 1));
+        drop(first);
+        second[page - 1] = 9;
+        assert_eq!(second[0], 2);
+
+        let tail = MapMut::with_options().len(3 * page + 10).alloc()?;
+        let lens: Vec<usize> = tail.into_pages().unwrap().map(|p| p.len()).collect();
+        assert_eq!(lens, [page, page, page, 10]);
+
+        let (_tmp, path, _) = write_default("into_pages_split")?;
+        let (map, _) = MapMut::with_options().open(&path)?;
+        assert!(map.into_pages().is_err());
+        assert!(MapMut::growable(page, 4 * page)?.into_pages().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn flush_evict_shared_only() -> Result<()> {
+        let tmp = tempdir::TempDir::new("vmap")?;
+        let path: PathBuf = tmp.path().join("flush_evict_shared_only");
+        let page = page_size();
+        let (mut map, file) = MapMut::create(&path, 4 * page)?;
+        map[..page].fill(7);
+        map[page..2 * page].fill(8);
+        map.flush_evict(&file, 0..2 * page, Flush::Sync)?;
+        assert_eq!((map[0], map[page], map[2 * page]), (7, 8, 0));
+        assert_eq!(fs::read(&path)?[page], 8);
+        #[allow(clippy::reversed_empty_ranges)]
+        let bad = 2..1;
+        assert!(map.flush_evict(&file, bad, Flush::Sync).is_err());
+        assert!(map
+            .flush_evict(&file, 0..4 * page + 1, Flush::Sync)
+            .is_err());
+
+        let (mut copy, file) = MapMut::with_options().copy().open(&path)?;
+        copy[0] = 1;
+        assert!(copy.flush_evict(&file, 0..page, Flush::Sync).is_err());
+        assert_eq!(copy[0], 1);
+        assert!(MapMut::new(page)?
+            .flush_evict(&file, 0..1, Flush::Sync)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn protect_validated() -> Result<()> {
+        let calls = || map::PROTECT_CALLS.with(|n| n.get());
+
+        // A shared map cannot be made copy-on-write in place.
+        let mut shared = MapMut::new(100)?;
+        let err = shared.set_protect(Protect::ReadCopy).unwrap_err();
+        assert!(err.to_string().contains(Input::Unsupported.as_str()));
+        assert_eq!(shared.protection(), Protect::ReadWrite);
+
+        // Writes to a private map stay private when made read-write again.
+        let (_tmp, path, _) = write_default("protect_validated")?;
+        let (map, _) = Map::with_options().copy().open(&path)?;
+        let before = calls();
+        let mut map = map.into_map_mut()?;
+        assert_eq!(calls(), before);
+        assert_eq!(map.protection(), Protect::ReadCopy);
+        map.set_protect(Protect::ReadOnly)?;
+        map.set_protect(Protect::ReadWrite)?;
+        assert_eq!(map.protection(), Protect::ReadCopy);
+        map[..1].copy_from_slice(b"a");
+        assert_eq!(&fs::read(&path)?[..1], b"A");
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn file_deferred_validate() -> Result<()> {
+        let tmp = tempdir::TempDir::new("vmap")?;
+        let path: PathBuf = tmp.path().join("file_deferred_validate");
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        let page = page_size();
+        let mut map = MapMut::file_deferred(file.try_clone()?, page + 3, page)?;
+        let err = map.validate().unwrap_err();
+        assert!(err.to_string().contains(Input::InvalidRange.as_str()));
+
+        file.set_len(2 * page as u64 + 2)?;
+        assert!(map.validate().is_err());
+        file.set_len(2 * page as u64 + 3)?;
+        map.validate()?;
+        map[page - 1] = 5;
+        map.flush(&file, Flush::Sync)?;
+        assert_eq!(fs::read(&path)?[2 * page + 2], 5);
+
+        assert!(MapMut::new(1)?.validate().is_err());
+        assert!(MapMut::file_deferred(file, 0, 0)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn new_aligned_base() -> Result<()> {
+        let unit = crate::allocation_size();
+        for align in [64, unit / 2, unit, 4 * unit, 1 << 21] {
+            let mut map = MapMut::new_aligned(unit + 1, align)?;
+            assert_eq!(map.as_ptr() as usize % align, 0);
+            assert_eq!(map.len(), 2 * unit);
+            map[2 * unit - 1] = 1;
+        }
+        for align in [0, unit / 2 + 1, 3 * unit] {
+            let err = MapMut::new_aligned(unit, align).unwrap_err();
+            assert!(err.to_string().contains(Input::Unaligned.as_str()));
+        }
+        assert!(MapMut::new_aligned(0, 4 * unit)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn capacity_page_rounded() -> Result<()> {
+        let page = page_size();
+        let (_tmp, path, len) = write_default("capacity_page_rounded")?;
+        let (map, _) = Map::with_options().open(&path)?;
+        assert_eq!((map.len(), map.capacity()), (len, page));
+
+        let (map, _) = Map::with_options().offset(10).len(2).open(&path)?;
+        assert_eq!((map.len(), map.capacity()), (2, page - 10));
+
+        let map = MapMut::new(200)?;
+        assert_eq!(map.len(), map.capacity());
+        assert_eq!(MapMut::new(0)?.capacity(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn anon_read_only() -> Result<()> {
+        let map = Map::anon(3 * page_size())?;
+        assert_eq!(map.protection(), Protect::ReadOnly);
+        assert!(!map.is_file_backed());
+        assert!(map.iter().all(|&b| b == 0));
+
+        let mut map = map.into_map_mut()?;
+        map[0] = 1;
+        let map = Map::with_options().len(10).alloc()?;
+        assert_eq!(map.len(), 10);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn residency_short_circuit() -> Result<()> {
+        let page = page_size();
+        let mut map = MapMut::with_options().copy().len(200 * page).alloc()?;
+        assert!(!map.any_resident(0..200 * page)?);
+        assert!(map.all_resident(0..0)?);
+        assert!(!map.any_resident(5..5)?);
+
+        map[150 * page] = 1;
+        assert!(map.any_resident(0..200 * page)?);
+        assert!(!map.any_resident(0..150 * page)?);
+        assert!(map.all_resident(150 * page + 1..151 * page)?);
+        assert!(!map.all_resident(150 * page..152 * page)?);
+        for i in 0..200 {
+            map[i * page] = 1;
+        }
+        assert!(map.all_resident(0..200 * page)?);
+        assert!(map.all_resident(0..200 * page + 1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn try_from_file() -> Result<()> {
+        let (_tmp, path, len) = write_default("try_from_file")?;
+        let map = Map::try_from(fs::File::open(&path)?)?;
+        assert_eq!(map.len(), len);
+        assert!(map.file().is_some());
+
+        let (_, file) = MapMut::try_from(fs::File::open(&path)?).unwrap_err();
+        let mut map = MapMut::try_from(fs::OpenOptions::new().read(true).write(true).open(&path)?)?;
+        map[..1].copy_from_slice(b"a");
+        map.flush(map.file().unwrap(), Flush::Sync)?;
+        assert_eq!(&fs::read(&path)?[..2], b"a ");
+        drop(file);
+
+        let (_tmp, empty, _) = write_tmp("try_from_file_empty", "")?;
+        assert!(Map::try_from(fs::File::open(&empty)?)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "os")]
+    fn protect_ranges_batch() -> Result<()> {
+        let page = crate::allocation_size();
+        let mut map = MapMut::new(4 * page)?;
+        let base = map.as_mut_ptr();
+        let at = |i: usize| unsafe { base.add(i * page) };
+        unsafe {
+            os::protect_ranges(&[
+                (at(0), page, Protect::ReadOnly),
+                (at(1), page, Protect::ReadOnly),
+                (at(2), 0, Protect::ReadExec),
+                (at(3), page, Protect::ReadOnly),
+            ])?;
+            assert!(
+                os::protect_ranges(&[(base.wrapping_add(1), page, Protect::ReadOnly)]).is_err()
+            );
+            os::protect_ranges(&[
+                (at(0), 2 * page, Protect::ReadWrite),
+                (at(3), page, Protect::ReadWrite),
+            ])?;
+            os::protect_ranges(&[])?;
+        }
+        map[..].fill(1);
+        assert!(map.iter().all(|&b| b == 1));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "os")]
+    fn map_failed_is_error() -> Result<()> {
+        let (_tmp, path, len) = write_default("map_failed")?;
+        let file = fs::File::open(&path)?;
+
+        // A shared writable map of a read-only file fails with a real error
+        // rather than yielding the failure sentinel as a pointer.
+        let err = os::map_file(&file, 0, len, Protect::ReadWrite).unwrap_err();
+        assert_eq!(err.operation(), Operation::MapFile);
+        assert!(err.raw_os_error().is_some());
+        assert_eq!(err.length(), Some(len));
+
+        let err = os::map_anon(
+            usize::MAX & !(crate::allocation_size() - 1),
+            Protect::ReadWrite,
+        )
+        .unwrap_err();
+        assert_eq!(err.operation(), Operation::MapAnonymous);
+        assert!(err.raw_os_error().is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn atomic_array_shared() -> Result<()> {
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        let (_tmp, path, _) = write_tmp("atomic_array", "")?;
+        let page = crate::allocation_size();
+        let (mut a, _) = MapMut::with_options().resize(page).len(page).open(&path)?;
+        let (mut b, _) = MapMut::with_options().len(page).open(&path)?;
+
+        let xs = a.as_atomic_array::<AtomicU32>(8, 4)?;
+        let ys = b.as_atomic_array::<AtomicU32>(8, 4)?;
+        xs[2].store(7, Ordering::Release);
+        assert_eq!(ys[2].fetch_add(1, Ordering::AcqRel), 7);
+        assert_eq!(xs[2].load(Ordering::Acquire), 8);
+        assert_eq!(u32::from_ne_bytes(a[16..20].try_into().unwrap()), 8);
+
+        let err = a.as_atomic_array::<AtomicU32>(2, 1).unwrap_err();
+        assert!(err.to_string().contains(Input::Unaligned.as_str()));
+        let err = a.as_atomic_array::<AtomicU32>(0, usize::MAX).unwrap_err();
+        assert!(err.to_string().contains(Input::InvalidRange.as_str()));
+        assert!(a.as_atomic_array::<AtomicU32>(page, 0)?.is_empty());
+
+        assert_eq!(b.as_atomic::<AtomicU32>(16)?.load(Ordering::Acquire), 8);
+        let err = a.as_atomic::<AtomicU32>(page).unwrap_err();
+        assert!(err.to_string().contains(Input::InvalidRange.as_str()));
+        Ok(())
+    }
+
+    #[test]
+    fn map_flush_unsupported() -> Result<()> {
+        let (_tmp, path, _) = write_default("map_flush")?;
+        let (map, file) = MapMut::with_options().write().open(&path)?;
+        let map = map.into_map().map_err(|(e, _)| e)?;
+        let err = map.flush(&file, Flush::Sync).unwrap_err();
+        assert_eq!(err.operation(), Operation::Flush);
+        assert!(err.to_string().contains(Input::Unsupported.as_str()));
+        Ok(())
+    }
+
+    #[test]
+    fn cow_map_overlay() -> Result<()> {
+        let (_tmp, path, _) = write_tmp("cow_map", "")?;
+        let page = crate::allocation_size();
+        let len = 2 * page + 10;
+        fs::write(&path, vec![b'a'; len])?;
+        let (map, _) = Map::with_options().open(&path)?;
+        let mut cow = CowMap::new(map);
+        assert_eq!(cow.page_count(), 3);
+        assert_eq!(cow.page(2).len(), 10);
+
+        cow.write_page(1)?.fill(b'b');
+        cow.write_page(2)?[9] = b'c';
+        assert!(cow.write_page(3).is_err());
+        assert!(!cow.is_page_copied(0));
+        assert_eq!(cow.copied_pages(), 2);
+        assert!(cow.base().iter().all(|&b| b == b'a'));
+        assert_eq!(fs::read(&path)?, vec![b'a'; len]);
+
+        let mut buf = [0; 4];
+        assert_eq!(cow.read_at(page - 2, &mut buf), 4);
+        assert_eq!(&buf, b"aabb");
+        assert_eq!(cow.read_at(len - 2, &mut buf), 2);
+        assert_eq!(&buf[..2], b"ac");
+        assert_eq!(cow.read_at(len + 1, &mut buf), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn low_addr_alloc() -> Result<()> {
+        let res = MapMut::with_options().low_addr(true).len(1).alloc();
+        if cfg!(any(
+            windows,
+            all(target_os = "linux", target_arch = "x86_64")
+        )) {
+            let mut map = res?;
+            assert!(map.as_ptr() as usize + map.len() <= 1 << 31);
+            map[0] = 1;
+        } else {
+            let err = res.unwrap_err();
+            assert!(err.to_string().contains(Input::Unsupported.as_str()));
+        }
+
+        let err = Map::with_options()
+            .low_addr(true)
+            .open("README.md")
+            .unwrap_err();
+        assert_eq!(err.operation(), Operation::MapFile);
+        Ok(())
+    }
+
+    #[test]
+    fn constructor_failure_unmaps() -> Result<()> {
+        use crate::map::{FAIL_FROM_PTR, REJECTED_PTR};
+
+        let (_tmp, path, _) = write_default("constructor_failure")?;
+        let len = crate::allocation_size();
+        for step in 0..3 {
+            FAIL_FROM_PTR.with(|f| f.set(true));
+            let err = match step {
+                0 => MapMut::with_options().len(len).alloc().unwrap_err(),
+                1 => Map::with_options().open(&path).unwrap_err(),
+                _ => MapMut::new_aligned(len, 4 * len).unwrap_err(),
+            };
+            assert!(err.to_string().contains(Input::InvalidPointer.as_str()));
+
+            // The rejected region was unmapped, so it can be mapped again at
+            // exactly the same address.
+            let pg = REJECTED_PTR.with(|p| p.get()) as *mut u8;
+            let map = MapMut::at_address(pg, len)?;
+            assert_eq!(map.as_ptr(), pg);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn map_windows() -> Result<()> {
+        let (_tmp, path, len) = write_default("map_windows")?;
+        let (map, _) = Map::with_options().open(&path)?;
+        assert_eq!(map.windows(4).count(), len - 3);
+        assert!(map.windows(len).eq(std::iter::once(&map[..])));
+        assert_eq!(map.windows(len + 1).next(), None);
+
+        let empty = Map::with_options().len(0).alloc()?;
+        assert_eq!(empty.windows(1).count(), 0);
+        Ok(())
+    }
+
+    #[cfg(feature = "hexdump")]
+    #[test]
+    fn hexdump_lines() -> Result<()> {
+        let mut map = MapMut::with_options().len(20).alloc()?;
+        map[..].copy_from_slice(b"0123456789abcdef\x00\x01 ~");
+        assert_eq!(
+            map.hexdump().to_string(),
+            "00000000  30 31 32 33 34 35 36 37  38 39 61 62 63 64 65 66  |0123456789abcdef|\n\
+             00000010  00 01 20 7e                                       |.. ~|\n"
+        );
+        assert_eq!(
+            map.hexdump_range(17..18)?.to_string(),
+            "00000011  01                                                |.|\n"
+        );
+        assert_eq!(map.hexdump_range(20..20)?.to_string(), "");
+        assert!(map.hexdump_range(19..21).is_err());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "os")]
+    fn process_advise_self() -> Result<()> {
+        let pid = std::process::id() as i32;
+        os::process_advise(pid, &[], Advise::PageOut)?;
+        let err = os::process_advise(pid, &[(1, 1)], Advise::WillNotNeed).unwrap_err();
+        assert!(err.to_string().contains(Input::Unsupported.as_str()));
+
+        let mut map = MapMut::new(2 * crate::allocation_size())?;
+        map[..4].copy_from_slice(b"test");
+        let ranges = [(map.as_ptr() as usize, map.len())];
+        match os::process_advise(pid, &ranges, Advise::PageOut) {
+            Ok(()) => {}
+            // Older kernels and restricted containers do not allow it.
+            Err(e) if cfg!(target_os = "linux") => assert!(e.raw_os_error().is_some()),
+            Err(e) => assert!(e.to_string().contains(Input::Unsupported.as_str())),
+        }
+        assert_eq!(&map[..4], b"test");
+        Ok(())
+    }
+
+    #[test]
+    fn persist_to_file() -> Result<()> {
+        let (_tmp, path, _) = write_tmp("persist_to", "longer than the map")?;
+        let mut map = MapMut::with_options().len(4).alloc()?;
+        map.copy_from_slice(b"test");
+
+        let (err, map) = map.persist_to(&fs::File::open(&path)?).unwrap_err();
+        assert_eq!(err.operation(), Operation::MapFile);
+        assert_eq!(&map[..], b"test");
+
+        let file = fs::OpenOptions::new().read(true).write(true).open(&path)?;
+        let map = map.persist_to(&file).map_err(|(e, _)| e)?;
+        assert!(map.is_file_backed());
+        map.flush(&file, Flush::Sync)?;
+        assert_eq!(fs::read(&path)?, b"test");
+
+        let empty = MapMut::new(0)?.persist_to(&file).map_err(|(e, _)| e)?;
+        assert!(empty.is_empty());
+        assert_eq!(fs::metadata(&path)?.len(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn lock_file_range_records() -> Result<()> {
+        let (_tmp, path, _) = write_default("lock_file_range")?;
+        let (map, file) = MapMut::with_options().offset(4).open(&path)?;
+        {
+            let _a = map.lock_file_range(&file, 0..8, true)?;
+            let _b = map.lock_file_range(&file, 8..16, false)?;
+            #[cfg(target_os = "linux")]
+            {
+                // Ranges are shown with an inclusive end, in file offsets.
+                let locks = fs::read_to_string("/proc/locks")?;
+                let pid = format!(" {} ", std::process::id());
+                assert!(locks
+                    .lines()
+                    .any(|l| l.contains(&pid) && l.ends_with(" 4 11")));
+                assert!(locks
+                    .lines()
+                    .any(|l| l.contains(&pid) && l.ends_with(" 12 19")));
+            }
+        }
+        #[cfg(target_os = "linux")]
+        {
+            let locks = fs::read_to_string("/proc/locks")?;
+            let pid = format!(" {} ", std::process::id());
+            assert!(!locks
+                .lines()
+                .any(|l| l.contains(&pid) && l.ends_with(" 4 11")));
+        }
+
+        let err = map.lock_file_range(&file, 4..4, true).unwrap_err();
+        assert!(err.to_string().contains(Input::InvalidRange.as_str()));
+        assert!(map.lock_file_range(&file, 0..map.len() + 1, true).is_err());
+        let anon = MapMut::new(1)?;
+        let err = anon.lock_file_range(&file, 0..1, true).unwrap_err();
+        assert!(err.to_string().contains(Input::Unsupported.as_str()));
+        Ok(())
+    }
+
+    #[test]
+    fn fault_stats_delta() -> Result<()> {
+        let page = crate::page_size();
+        let mut map = MapMut::new(64 * page)?;
+        let before = fault_stats()?;
+        for i in (0..map.len()).step_by(page) {
+            map[i] = 1;
+        }
+        let after = fault_stats()?;
+        // Other threads also fault and huge pages may cover several of the
+        // touched pages, so only that some faults were counted is checked.
+        assert!(after.since(&before).minor > 0);
+        assert_eq!(before.since(&after), FaultStats::default());
+        Ok(())
+    }
+
+    #[test]
+    fn c_str_bounded() -> Result<()> {
+        let mut map = MapMut::with_options().len(8).alloc()?;
+        map.copy_from_slice(b"ab\0cdefg");
+        assert_eq!(map.c_str(0)?.to_bytes(), b"ab");
+        assert_eq!(map.c_str(2)?.to_bytes(), b"");
+        let err = map.c_str(3).unwrap_err();
+        assert!(err.to_string().contains(Input::InvalidRange.as_str()));
+        assert!(map.c_str(8).is_err());
+        assert!(map.c_str(usize::MAX).is_err());
+        assert_eq!((&map[..3]).c_str(1)?.to_bytes(), b"b");
+        Ok(())
+    }
+
+    #[test]
+    fn with_protection_converters() -> Result<()> {
+        let mut map = MapMut::with_options().copy().len(1).alloc()?;
+        map[0] = 7;
+        let (err, map) = map.with_protection(Protect::ReadOnly).unwrap_err();
+        assert!(err.to_string().contains(Input::Unsupported.as_str()));
+        let mut map = map.with_protection(Protect::ReadCopy).map_err(|(e, _)| e)?;
+        assert_eq!(map.protection(), Protect::ReadCopy);
+        map[0] += 1;
+
+        if cfg!(unix) {
+            let code = map.make_exec().map_err(|(e, _)| e)?;
+            assert_eq!(code.protection(), Protect::ReadExec);
+            let clone = code.clone();
+            let (err, code) = code.with_protection(Protect::ReadOnly).unwrap_err();
+            assert!(err.to_string().contains(Input::Shared.as_str()));
+            drop(clone);
+            let code = code
+                .with_protection(Protect::ReadOnly)
+                .map_err(|(e, _)| e)?;
+            assert_eq!(code.protection(), Protect::ReadOnly);
+            assert_eq!(code[0], 8);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn map_group_slots() -> Result<()> {
+        let group = MapGroup::new(13, 3)?;
+        assert_eq!(group.slot_size(), 16);
+        let mut slots: Vec<_> = std::iter::from_fn(|| group.alloc()).collect();
+        assert_eq!(slots.len(), 3);
+        assert_eq!(group.used(), 3);
+        for (i, slot) in slots.iter_mut().enumerate() {
+            assert_eq!(slot.len(), 16);
+            slot.fill(i as u8);
+        }
+        let base = group.as_ptr() as usize;
+        assert_eq!(slots[2].as_ptr() as usize - base, 32);
+        assert!(slots
+            .iter()
+            .all(|s| s.iter().all(|&b| b as usize == s.index())));
+
+        let idx = slots.remove(1).index();
+        assert_eq!(group.used(), 2);
+        let slot = group.alloc().unwrap();
+        assert_eq!(slot.index(), idx);
+        assert_eq!(slot[0], 1);
+
+        std::thread::scope(|s| {
+            s.spawn(|| assert!(group.alloc().is_none()));
+        });
+        drop(slots);
+        assert_eq!(group.used(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn sync_all_grown() -> Result<()> {
+        let (_tmp, path, len) = write_default("sync_all")?;
+        let (mut map, file) = MapMut::with_options()
+            .resize(Extent::Exact(2 * len))
+            .open(&path)?;
+        map[len..len + 4].copy_from_slice(b"tail");
+        map.sync_all(&file)?;
+        assert_eq!(fs::metadata(&path)?.len() as usize, 2 * len);
+        assert_eq!(&fs::read(&path)?[len..len + 4], b"tail");
+
+        let err = MapMut::new(1)?.sync_all(&file).unwrap_err();
+        assert!(err.to_string().contains(Input::Unsupported.as_str()));
+        Ok(())
+    }
+
+    #[test]
+    fn tail_zeroed() -> Result<()> {
+        let map = MapMut::new(200)?;
+        assert!(map.is_tail_zeroed());
+        let map = MapMut::with_options().len(200).alloc()?;
+        assert!(map.is_tail_zeroed());
+        let tail = unsafe { std::slice::from_raw_parts(map.as_ptr(), map.capacity()) };
+        assert!(tail[200..].iter().all(|&b| b == 0));
+        assert!(map.into_map().unwrap().is_tail_zeroed());
+
+        let map = MapMut::growable(200, page_size() * 4)?;
+        assert!(map.is_tail_zeroed());
+
+        let (_tmp, path, _len) = write_default("tail")?;
+        let (map, _) = Map::with_options().len(10).open(&path)?;
+        assert!(!map.is_tail_zeroed());
+        Ok(())
+    }
+
+    #[test]
+    fn dirty_ranges_coalesce() -> Result<()> {
+        let page = page_size();
+        let mut map = MapMut::new(8 * page)?;
+        map.fill(1);
+        if !matches!(map.dirty_ranges(), Ok(r) if !r.is_empty()) || map.clear_dirty().is_err() {
+            // The kernel lacks soft-dirty tracking.
+            return Ok(());
+        }
+        map[page] = 2;
+        map[2 * page + 10] = 2;
+        map[5 * page] = 2;
+        map[8 * page - 1] = 2;
+        let ranges = map.dirty_ranges()?;
+        for r in [page..3 * page, 5 * page..6 * page, 7 * page..8 * page] {
+            assert!(ranges.iter().any(|d| d.start <= r.start && r.end <= d.end));
+        }
+        assert!(ranges.windows(2).all(|w| w[0].end < w[1].start));
+        assert!(ranges.iter().all(|d| d.end <= map.len()));
+
+        map.clear_dirty()?;
+        assert!(!map.dirty_ranges()?.iter().any(|d| d.contains(&page)));
+        Ok(())
+    }
+
+    #[test]
+    fn known_file_len() -> Result<()> {
+        let (_tmp, path, len) = write_default("known")?;
+        let f = fs::File::open(&path)?;
+        let mut opts = Map::with_options();
+        opts.file_len(len as u64).len(10);
+        assert_eq!(10, opts.offset(5).map(&f)?.len());
+        assert!(opts.offset(len - 5).map(&f).is_err());
+
+        // The known length is used in place of the actual one.
+        opts.file_len(8);
+        assert!(opts.offset(5).map_if(&f)?.is_none());
+        let map = opts.offset(0).len(Extent::End).map(&f)?;
+        assert_eq!(8, map.len());
+        Ok(())
+    }
+
+    #[test]
+    fn into_map_read_only() -> Result<()> {
+        let mut map = MapMut::new(page_size())?;
+        map[0] = 1;
+        let map = map.into_map()?;
+        assert_eq!(map.protection(), Protect::ReadOnly);
+        #[cfg(target_os = "linux")]
+        {
+            let addr = format!("{:x}-", map.as_ptr() as usize);
+            let maps = fs::read_to_string("/proc/self/maps")?;
+            let line = maps.lines().find(|l| l.starts_with(&addr)).unwrap();
+            assert!(line.split(' ').nth(1).unwrap().starts_with("r--"));
+        }
+
+        let mut map = map.into_map_mut()?;
+        assert_eq!(map.protection(), Protect::ReadWrite);
+        map[0] = 2;
+        assert_eq!(map[0], 2);
+        Ok(())
+    }
+
+    #[test]
+    fn map_bounds_accessors() -> Result<()> {
+        let page = page_size();
+        let (_tmp, path, _) = write_default("bounds")?;
+        let (map, _) = Map::with_options().offset(5).len(20).open(&path)?;
+        assert_eq!(20, map.len());
+        assert!(!map.is_empty());
+        assert_eq!(5, map.offset());
+        let (pg, len) = map.aligned_bounds();
+        assert_eq!(0, pg as usize % page);
+        assert_eq!(pg, map.as_ptr().wrapping_sub(5));
+        assert_eq!(page, len);
+
+        let map = MapMut::new(page + 1)?;
+        assert_eq!(0, map.offset());
+        let (pg, len) = map.aligned_bounds();
+        assert_eq!(pg, map.as_ptr());
+        assert_eq!(Size::page().round(map.len()), len);
+
+        let map = MapMut::new(0)?;
+        assert!(map.is_empty());
+        assert_eq!(0, map.aligned_bounds().1);
+        Ok(())
+    }
+
+    #[test]
+    fn advise_will_not_need() -> Result<()> {
+        let mut map = MapMut::new(page_size())?;
+        map.fill(7);
+        map.advise(Advise::WillNeed)?;
+        map.advise(Advise::WillNotNeed)?;
+        assert!(map.iter().all(|&b| b == 7));
+
+        // Linux discards the pages of a private map rather than keeping them.
+        let mut map = MapMut::with_options().copy().len(page_size()).alloc()?;
+        map.fill(7);
+        map.advise(Advise::WillNotNeed)?;
+        if cfg!(target_os = "linux") {
+            assert!(map.iter().all(|&b| b == 0));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn advise_range_overflow() -> Result<()> {
+        let map = MapMut::new(page_size())?;
+        map.advise_range(0, map.len(), Advise::WillNeed)?;
+        let err = map
+            .advise_range(usize::MAX, 2, Advise::WillNeed)
+            .unwrap_err();
+        assert_eq!(Some(Input::InvalidRange), err.input_kind());
+        let err = map
+            .advise_range(1, usize::MAX, Advise::WillNeed)
+            .unwrap_err();
+        assert_eq!(Some(Input::InvalidRange), err.input_kind());
+        let map = map.into_map().map_err(|(e, _)| e)?;
+        let err = map
+            .advise_range(usize::MAX, 2, Advise::WillNeed)
+            .unwrap_err();
+        assert_eq!(Some(Input::InvalidRange), err.input_kind());
+        Ok(())
+    }
+
+    #[test]
+    fn resize_anon() -> Result<()> {
+        let unit = crate::allocation_size();
+        for mut map in [
+            MapMut::new(unit)?,
+            MapMut::with_options().copy().len(unit).alloc()?,
+        ] {
+            map.fill(3);
+            map.resize(8 * unit + 1)?;
+            assert_eq!(9 * unit, map.len());
+            assert!(map[..unit].iter().all(|&b| b == 3));
+            assert!(map[unit..].iter().all(|&b| b == 0));
+            map[9 * unit - 1] = 4;
+
+            map.resize(2 * unit)?;
+            assert_eq!(2 * unit, map.len());
+            assert!(map[..unit].iter().all(|&b| b == 3));
+
+            map.resize(0)?;
+            assert!(map.is_empty());
+            map.resize(1)?;
+            assert_eq!(unit, map.len());
+            assert_eq!(0, map[0]);
+        }
+
+        let (_tmp, path, _) = write_default("resize")?;
+        let (mut map, _) = MapMut::with_options().open(&path)?;
+        let err = map.resize(unit).unwrap_err();
+        assert!(err.to_string().contains(Input::Unsupported.as_str()));
+        Ok(())
+    }
+
+    #[test]
+    fn flush_range_bounds() -> Result<()> {
+        let (_tmp, path, len) = write_default("flush_range_bounds")?;
+        let file = fs::OpenOptions::new().read(true).write(true).open(&path)?;
+        let mut map = MapMut::with_options().offset(3).map(&file)?;
+        map[..4].copy_from_slice(b"TEST");
+        map.flush_range(&file, 0, 4, Flush::Sync)?;
+        map.flush_range(&file, map.len(), 0, Flush::Sync)?;
+        assert_eq!(&fs::read(&path)?[3..7], b"TEST");
+
+        for (off, n) in [(len - 3, 1), (1, usize::MAX), (usize::MAX, 2)] {
+            let err = map.flush_range(&file, off, n, Flush::Sync).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn lock_unlock_anon() -> Result<()> {
+        let mut map = MapMut::new(page_size())?;
+        map.lock()?;
+        map.fill(1);
+        map.unlock()?;
+
+        let map = map.into_map()?;
+        map.lock()?;
+        map.unlock()?;
+        Ok(())
+    }
+
+    #[test]
+    fn new_huge_no_fallback() -> Result<()> {
+        let huge = match Size::huge() {
+            Ok(huge) => huge.size(1),
+            Err(err) => {
+                assert!(err.to_string().contains(Input::Unsupported.as_str()));
+                return Ok(());
+            }
+        };
+        assert!(huge > page_size());
+        match MapMut::new_huge(1) {
+            Ok(mut map) => {
+                assert_eq!(map.len(), huge);
+                assert_eq!(map.as_ptr() as usize % huge, 0);
+                map[huge - 1] = 1;
+            }
+            // An empty huge page pool fails rather than using normal pages.
+            Err(err) => assert!(err.raw_os_error().is_some()),
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn into_split_halves() -> Result<()> {
+        let page = crate::allocation_size();
+        let map = MapMut::new(4 * page)?;
+        let map = match map.into_split(page + 1) {
+            Err((err, map)) => {
+                assert!(err.to_string().contains(Input::Unaligned.as_str()));
+                map
+            }
+            Ok(_) => panic!("split an unaligned map"),
+        };
+        let map = match map.into_split(5 * page) {
+            Err((err, map)) => {
+                assert!(err.to_string().contains(Input::InvalidRange.as_str()));
+                map
+            }
+            Ok(_) => panic!("split past the end"),
+        };
+
+        let (mut head, mut tail) = map.into_split(page)?;
+        assert_eq!((head.len(), tail.len()), (page, 3 * page));
+        assert_eq!(head.as_ptr().wrapping_add(page), tail.as_ptr());
+        head.fill(1);
+        tail.fill(2);
+        let t = std::thread::spawn(move || {
+            assert!(tail.iter().all(|&b| b == 2));
+            tail
+        });
+        let tail = t.join().unwrap();
+        assert!(head.iter().all(|&b| b == 1));
+        drop(tail);
+        assert!(head.iter().all(|&b| b == 1));
+        drop(head);
+
+        let (head, tail) = MapMut::new(page)?.into_split(page)?;
+        assert_eq!((head.len(), tail.len()), (page, 0));
+
+        let (_tmp, path, _) = write_default("into_split")?;
+        let (map, _) = MapMut::with_options().open(&path)?;
+        assert!(map.into_split(0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn map_send_sync() -> Result<()> {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Map>();
+        assert_send_sync::<MapMut>();
+        assert_send_sync::<MapSegment>();
+        assert_send_sync::<TypedMap<u64>>();
+
+        let mut map = MapMut::new(page_size())?;
+        map[0] = 1;
+        let map = std::thread::spawn(move || {
+            map[1] = 2;
+            map
+        })
+        .join()
+        .unwrap();
+        assert_eq!(&map[..2], &[1, 2]);
+
+        let map = std::sync::Arc::new(map.into_map()?);
+        let other = map.clone();
+        let sum = std::thread::spawn(move || other.iter().map(|&b| b as usize).sum::<usize>());
+        assert_eq!(sum.join().unwrap(), 3);
+        assert_eq!(map[1], 2);
+        Ok(())
+    }
+
+    #[test]
+    fn populate_resident() -> Result<()> {
+        let (_tmp, path, len) = write_default("populate_resident")?;
+        let (map, _) = Map::with_options().populate(true).open(&path)?;
+        assert_eq!(map.len(), len);
+        assert!(map.all_resident(0..len)?);
+
+        let map = MapMut::with_options()
+            .copy()
+            .len(4 * page_size())
+            .populate(true)
+            .alloc()?;
+        assert!(map.all_resident(0..map.len())?);
+        assert!(map.iter().all(|&b| b == 0));
+
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            let pg = os::map_anon_with(page_size(), Protect::ReadWrite, MapFlags::POPULATE)?;
+            unsafe { os::unmap(pg, page_size())? };
+        }
+        let err = os::map_anon_with(page_size(), Protect::ReadWrite, MapFlags::SYNC).unwrap_err();
+        assert!(err.to_string().contains(Input::Unsupported.as_str()));
+        Ok(())
+    }
+
+    #[test]
+    fn whole_file_lengths() -> Result<()> {
+        let (_tmp, path, len) = write_default("whole_file_lengths")?;
+        let f = fs::OpenOptions::new().read(true).write(true).open(&path)?;
+        assert_eq!(Map::whole_file(&f)?.len(), len);
+        assert_eq!(MapMut::whole_file(&f)?.len(), len);
+
+        f.set_len(0)?;
+        assert!(Map::whole_file(&f)?.is_empty());
+
+        #[cfg(target_pointer_width = "32")]
+        {
+            let err = Map::with_options().file_len(u64::MAX).map(&f).unwrap_err();
+            assert!(err.to_string().contains(Input::InvalidRange.as_str()));
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn shared_across_fork() -> Result<()> {
+        let mut map = MapMut::shared(page_size())?;
+        map[0] = 1;
+        let ptr = map.as_mut_ptr();
+        unsafe {
+            let pid = libc::fork();
+            assert!(pid >= 0, "fork failed");
+            if pid == 0 {
+                let code = if *ptr == 1 { 0 } else { 1 };
+                *ptr.add(1) = 2;
+                libc::_exit(code);
+            }
+            let mut status = 0;
+            assert_eq!(pid, libc::waitpid(pid, &mut status, 0));
+            assert!(libc::WIFEXITED(status));
+            assert_eq!(0, libc::WEXITSTATUS(status));
+        }
+        assert_eq!(2, map[1]);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn shared_handle_view() -> Result<()> {
+        let mut map = MapMut::shared(page_size())?;
+        let mut view = MapMut::from_shared_handle(map.shared_handle().unwrap(), map.len())?;
+        map[0] = 1;
+        view[1] = 2;
+        assert_eq!((1, 2), (view[0], map[1]));
+        assert!(MapMut::new(page_size())?.shared_handle().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn pages_cover_map() -> Result<()> {
+        let page = page_size();
+        let mut map = MapMut::new(3 * page)?;
+        for (i, b) in map.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        assert_eq!(3, map.pages().len());
+        assert!(map.pages().all(|pg| pg.len() == page));
+        assert_eq!(&map[..], &map.pages().collect::<Vec<_>>().concat()[..]);
+        assert_eq!(3, map.pages_mut().rev().count());
+
+        let (_tmp, path, len) = write_default("pages_cover_map")?;
+        let (map, _) = Map::with_options()
+            .offset(10)
+            .len(Extent::Max(page))
+            .open(&path)?;
+        assert_eq!(map.len(), std::cmp::min(page, len - 10));
+        let pages: Vec<&[u8]> = map.pages().collect();
+        assert_eq!(std::cmp::min(page - 10, map.len()), pages[0].len());
+        assert_eq!(&map[..], &pages.concat()[..]);
+        assert!(MapMut::new(0)?.pages().next().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn tail_past_eof_len() -> Result<()> {
+        let page = page_size();
+        let tmp = tempdir::TempDir::new("vmap")?;
+        let path = tmp.path().join("tail_past_eof_len");
+        let (map, file) = MapMut::create(&path, page + 100)?;
+        assert_eq!(page - 100, map.tail_past_eof(&file)?);
+
+        let map = Map::with_options().offset(page).len(50).map(&file)?;
+        assert_eq!(page - 100, map.tail_past_eof(&file)?);
+        let map = Map::with_options().len(50).map(&file)?;
+        assert_eq!(0, map.tail_past_eof(&file)?);
+
+        assert_eq!(0, MapMut::new(100)?.tail_past_eof(&file)?);
+        Ok(())
+    }
+
+    #[test]
+    fn resize_growable_in_place() -> Result<()> {
+        let page = crate::allocation_size();
+        let mut map = MapMut::growable(page, 8 * page)?;
+        map[..4].copy_from_slice(b"test");
+        let ptr = map.as_ptr();
+
+        map.resize(6 * page)?;
+        assert_eq!(6 * page, map.len());
+        assert_eq!(ptr, map.as_ptr());
+        map[6 * page - 1] = 1;
+
+        map.resize(page)?;
+        assert_eq!(page, map.len());
+        assert_eq!(b"test", &map[..4]);
+        map.resize(6 * page)?;
+        assert_eq!(0, map[6 * page - 1]);
+        assert_eq!(ptr, map.as_ptr());
+
+        let err = map.resize(9 * page).unwrap_err();
+        assert!(err.to_string().contains(Input::InvalidRange.as_str()));
+        assert_eq!(6 * page, map.len());
+        Ok(())
+    }
+
+    #[test]
+    fn advise_free() -> Result<()> {
+        let mut map = MapMut::with_options().copy().len(4 * page_size()).alloc()?;
+        map[0] = 1;
+        map.advise(Advise::Free)?;
+        map[0] = 2;
+        assert_eq!(2, map[0]);
+
+        let (map, _) = Map::with_options().open("README.md")?;
+        map.advise(Advise::Free)?;
+        assert_eq!(&fs::read("README.md")?[..], &map[..]);
+        Ok(())
+    }
+
+    #[test]
+    fn lock_range_bounds() -> Result<()> {
+        let map = MapMut::new(page_size())?;
+        for (off, len) in [(1, usize::MAX), (usize::MAX, 1), (map.len() + 1, 0)] {
+            let err = map.lock_range(off, len).unwrap_err();
+            assert!(err.to_string().contains(Input::InvalidRange.as_str()));
+            let err = map.unlock_range(off, len).unwrap_err();
+            assert!(err.to_string().contains(Input::InvalidRange.as_str()));
+        }
+        if map.lock_range(0, map.len()).is_ok() {
+            map.unlock_range(0, map.len())?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn as_type_views() -> Result<()> {
+        let mut map = MapMut::new(page_size())?;
+        *map.as_type_mut::<u32>(4)? = 7;
+        map.as_type_slice_mut::<u16>(8, 2)?.copy_from_slice(&[1, 2]);
+        assert_eq!(7, *map.as_type::<u32>(4)?);
+        assert_eq!(&[1, 2], map.as_type_slice::<u16>(8, 2)?);
+        assert_eq!(&[0, 7], map.as_type::<[u32; 2]>(0)?);
+        assert_eq!(0, map.as_type_slice::<u64>(map.len(), 0)?.len());
+
+        let err = map.as_type::<u32>(2).unwrap_err();
+        assert!(err.to_string().contains(Input::Unaligned.as_str()));
+        for (off, count) in [(map.len() - 2, 1), (0, usize::MAX), (usize::MAX, 1)] {
+            let err = map.as_type_slice::<u32>(off, count).unwrap_err();
+            assert!(err.to_string().contains(Input::InvalidRange.as_str()));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn options_huge_pages() -> Result<()> {
+        let err = Map::with_options()
+            .huge_pages(true)
+            .open("README.md")
+            .unwrap_err();
+        assert!(err.to_string().contains(Input::Unsupported.as_str()));
+        let err = MapMut::with_options()
+            .huge_pages(true)
+            .low_addr(true)
+            .alloc()
+            .unwrap_err();
+        assert!(err.to_string().contains(Input::Unsupported.as_str()));
+
+        if let Ok(mut map) = MapMut::with_options()
+            .huge_pages(true)
+            .len(Extent::Max(100))
+            .alloc()
+        {
+            assert_eq!(100, map.len());
+            assert_eq!(Size::huge()?.size(1), map.capacity());
+            map[99] = 1;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn flush_owned_file() -> Result<()> {
+        let (_tmp, path, len) = write_default("flush_owned_file")?;
+        let file = fs::OpenOptions::new().read(true).write(true).open(&path)?;
+        let mut map = MapMut::with_options().map_owned(file)?;
+        map[..1].copy_from_slice(b"a");
+        map.flush_owned_range(0, 1, Flush::Sync)?;
+        assert_eq!(b'a', fs::read(&path)?[0]);
+        map.flush_owned(Flush::Async)?;
+        let err = map.flush_owned_range(len, 1, Flush::Sync).unwrap_err();
+        assert!(err.to_string().contains(Input::InvalidRange.as_str()));
+
+        let (map, _) = MapMut::with_options().open(&path)?;
+        let err = map.flush_owned(Flush::Sync).unwrap_err();
+        assert!(err.to_string().contains(Input::Unsupported.as_str()));
+        Ok(())
+    }
+
+    #[test]
+    fn map_segments_share() -> Result<()> {
+        let (_tmp, path, len) = write_default("map_segments_share")?;
+        let (map, _) = Map::with_options().open(&path)?;
+        let seg = map.slice(2..16)?;
+        assert_eq!(&map[2..16], &seg[..]);
+        assert_eq!(2..16, seg.range());
+        assert!(map.slice(0..len + 1).is_err());
+
+        let (map, err) = match map.into_split(len + 1) {
+            Err((err, map)) => (map, err),
+            Ok(_) => panic!("split past the end"),
+        };
+        assert!(err.to_string().contains(Input::InvalidRange.as_str()));
+        let ptr = map.as_ptr();
+        let (head, tail) = map.into_split(16).map_err(|(e, _)| e)?;
+        assert_eq!(Ok("A cross-platform"), from_utf8(&head));
+        assert_eq!(unsafe { ptr.add(16) }, tail.as_ptr());
+        let (a, b) = tail.into_split(1).map_err(|(e, _)| e)?;
+        assert_eq!((1, len - 17), (a.len(), b.len()));
+        assert!(b.slice(0..b.len() + 1).is_err());
+
+        drop((head, a, seg));
+        let inner = b.clone().into_map();
+        drop(b);
+        assert_eq!(len, inner.len());
+        assert!(!inner.is_shared());
+        Ok(())
+    }
+
+    #[test]
+    fn shm_named() -> Result<()> {
+        let name = format!("vmap-test-{}", std::process::id());
+        let mut map = crate::shm::create(&name, 5000)?;
+        assert_eq!(5000, map.len());
+        assert!(!map.is_private());
+        map[4999] = 7;
+
+        let err = crate::shm::create(&name, 5000).unwrap_err();
+        assert_eq!(std::io::ErrorKind::AlreadyExists, err.kind());
+
+        let mut other = crate::shm::open(&name)?;
+        assert!(other.len() >= 5000);
+        assert_eq!(7, other[4999]);
+        other[0] = 1;
+        assert_eq!(1, map[0]);
+
+        crate::shm::remove(&name)?;
+        drop(other);
+        assert_eq!(7, map[4999]);
+        drop(map);
+
+        for bad in ["", "a/b", "a\\b"] {
+            let err = crate::shm::open(bad).unwrap_err();
+            assert!(err.to_string().contains(Input::InvalidName.as_str()));
+        }
+        let err = crate::shm::create(&name, 0).unwrap_err();
+        assert!(err.to_string().contains(Input::InvalidRange.as_str()));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(all(unix, any(target_arch = "x86_64", target_arch = "aarch64")))]
+    fn make_exec_runs_code() -> Result<()> {
+        // Returns 42 from an extern "C" fn() -> u32.
+        #[cfg(target_arch = "x86_64")]
+        const CODE: &[u8] = &[0xb8, 42, 0, 0, 0, 0xc3];
+        #[cfg(target_arch = "aarch64")]
+        const CODE: &[u8] = &[0x40, 0x05, 0x80, 0x52, 0xc0, 0x03, 0x5f, 0xd6];
+
+        let mut map = MapMut::with_options().copy().len(CODE.len()).alloc()?;
+        map[..CODE.len()].copy_from_slice(CODE);
+        let code = map.make_exec().map_err(|(e, _)| e)?;
+        let f: extern "C" fn() -> u32 = unsafe { mem::transmute(code.as_ptr()) };
+        assert_eq!(42, f());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn with_guards_fault() -> Result<()> {
+        let page = page_size();
+        let mut map = MapMut::with_guards(page + 1)?;
+        assert_eq!(2 * page, map.len());
+        map.fill(0xa5);
+        assert!(!map.is_file_backed());
+        assert!(map.resize(4 * page).is_err());
+
+        let ptr = map.as_ptr();
+        for off in [-1, 2 * page as isize] {
+            unsafe {
+                let pid = libc::fork();
+                assert!(pid >= 0, "fork failed");
+                if pid == 0 {
+                    ptr.offset(off).read_volatile();
+                    libc::_exit(0);
+                }
+                let mut status = 0;
+                assert_eq!(pid, libc::waitpid(pid, &mut status, 0));
+                assert!(libc::WIFSIGNALED(status), "access at {} did not fault", off);
+            }
+        }
+        assert!(map.iter().all(|&b| b == 0xa5));
+        assert_eq!(0, MapMut::with_guards(0)?.len());
+        Ok(())
+    }
+
+    #[test]
+    fn map_range_error_context() -> Result<()> {
+        let (_tmp, path, len) = write_default("range")?;
+        let file = fs::OpenOptions::new().read(true).write(true).open(&path)?;
+        let mut opts = MapMut::with_options();
+        opts.offset(10).len(Extent::Exact(len));
+
+        let err = opts.map(&file).unwrap_err();
+        assert_eq!(Some(Input::InvalidRange), err.input_kind());
+        assert_eq!(Some(10), err.offset());
+        assert_eq!(Some(len), err.length());
+        assert_eq!(Some(len as u64), err.file_len());
+        assert!(err.to_string().contains(&format!("file length {}", len)));
+        assert_eq!(None, Error::from(std::io::Error::other("x")).input_kind());
+
+        file.set_len(10 + len as u64)?;
+        assert_eq!(len, opts.map(&file)?.len());
+        Ok(())
+    }
+
+    #[test]
+    fn options_extend_file() -> Result<()> {
+        let (_tmp, path, _) = write_default("extend")?;
+        let page = page_size();
+        let (map, file) = MapMut::with_options()
+            .extend(true)
+            .offset(page)
+            .len(Extent::Min(10))
+            .open(&path)?;
+        assert_eq!(10, map.len());
+        assert_eq!((page + 10) as u64, file.metadata()?.len());
+
+        let (map, file) = MapMut::with_options().extend(true).len(5).open(&path)?;
+        assert_eq!(5, map.len());
+        assert_eq!((page + 10) as u64, file.metadata()?.len());
+
+        let err = MapMut::with_options()
+            .offset(2 * page)
+            .len(1)
+            .open(&path)
+            .unwrap_err();
+        assert_eq!(Some(Input::InvalidRange), err.input_kind());
+        Ok(())
+    }
+
+    #[test]
+    fn prefault_range_resident() -> Result<()> {
+        let page = page_size();
+        let (_tmp, path, _) = write_default("prefault_range")?;
+        let file = fs::OpenOptions::new().read(true).write(true).open(&path)?;
+        file.set_len(8 * page as u64)?;
+        let map = Map::with_options().map(&file)?;
+        map.prefault_range(2 * page + 1, page)?;
+        assert!(map.all_resident(2 * page..4 * page)?);
+        map.prefault_range(8 * page, 0)?;
+
+        let err = map.prefault_range(page, usize::MAX).unwrap_err();
+        assert_eq!(Some(Input::InvalidRange), err.input_kind());
+        assert!(map.prefault_range(8 * page + 1, 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(all(feature = "bytes", feature = "io"))]
+    fn bytes_from_map() -> Result<()> {
+        use bytes::{Buf, Bytes};
+
+        let (_tmp, path, len) = write_default("bytes")?;
+        let (map, _) = Map::with_options().open(&path)?;
+        let ptr = map.as_ptr();
+        let seg = map.slice(2..10)?;
+        let bytes = Bytes::from(map);
+        assert_eq!(ptr, bytes.as_ptr());
+        assert_eq!(len, bytes.len());
+        let seg = Bytes::from(seg);
+        drop(bytes);
+        assert_eq!(&fs::read(&path)?[2..10], &seg[..]);
+
+        let (map, _) = Map::with_options().open(&path)?;
+        let mut cur = crate::io::MapCursor::new(map);
+        assert_eq!(len, cur.remaining());
+        cur.advance(4);
+        assert_eq!(&fs::read(&path)?[4..], cur.chunk());
+        let rest = cur.copy_to_bytes(len - 4);
+        assert_eq!(0, cur.remaining());
+        assert_eq!(len - 4, rest.len());
+        Ok(())
+    }
+
+    #[test]
+    fn protect_range_regions() -> Result<()> {
+        let page = page_size();
+        let mut map = MapMut::new(4 * page)?;
+        let err = unsafe { map.protect_range(1, page, Protect::ReadOnly) }.unwrap_err();
+        assert_eq!(Some(Input::Unaligned), err.input_kind());
+        let err = unsafe { map.protect_range(page, 4 * page, Protect::ReadOnly) }.unwrap_err();
+        assert_eq!(Some(Input::InvalidRange), err.input_kind());
+
+        let mut region = MapRegion::new(map);
+        region.get_mut(page..2 * page)?.fill(7);
+        region.protect(page, 1, Protect::ReadOnly)?;
+        assert_eq!(Protect::ReadWrite, region.protection_at(page - 1));
+        assert_eq!(Protect::ReadOnly, region.protection_at(2 * page - 1));
+        assert_eq!(Protect::ReadWrite, region.protection_at(2 * page));
+        let err = region.get_mut(page - 1..page + 1).unwrap_err();
+        assert_eq!(Some(Input::Unsupported), err.input_kind());
+        assert_eq!(Some(Protect::ReadOnly), err.protect());
+        assert!(region[page..2 * page].iter().all(|&b| b == 7));
+        region.get_mut(0..page)?.fill(1);
+        region.get_mut(2 * page..4 * page)?.fill(2);
+
+        let mut map = region.into_inner().map_err(|(e, _)| e)?;
+        map[page..2 * page].fill(3);
+        assert!(map[page..2 * page].iter().all(|&b| b == 3));
+        Ok(())
+    }
+
+    #[test]
+    fn map_vec_persist() -> Result<()> {
+        let tmp = tempdir::TempDir::new("vmap")?;
+        let path = tmp.path().join("map_vec");
+        let mut values = MapVec::<u32>::open(&path)?;
+        assert!(values.is_empty());
+        for i in 0..10_000 {
+            values.push(i)?;
+        }
+        assert!(values.capacity() >= 10_000);
+        assert_eq!(Some(9_999), values.pop());
+        values[0] = 42;
+        values.set_flush_on_drop(Flush::Sync);
+        drop(values);
+        assert_eq!(9_999 * 4, fs::metadata(&path)?.len());
+
+        let mut values = MapVec::<u32>::open(&path)?;
+        assert_eq!(9_999, values.len());
+        assert_eq!(42, values[0]);
+        assert_eq!(9_998, values[9_998]);
+        values.truncate(2);
+        values.extend_from_slice(&[7, 8])?;
+        values.close()?;
+        assert_eq!(16, fs::metadata(&path)?.len());
+
+        fs::write(&path, [0; 6])?;
+        let err = MapVec::<u32>::open(&path).unwrap_err();
+        assert_eq!(Some(Input::Unaligned), err.input_kind());
+
+        let mut anon = MapVec::<u64>::new()?;
+        assert_eq!(0, anon.capacity());
+        anon.extend_from_slice(&[1, 2, 3])?;
+        anon.reserve(100_000)?;
+        assert_eq!(&[1, 2, 3], &anon[..]);
+        assert!(anon.reserve(usize::MAX).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn map_vec_policy() -> Result<()> {
+        let tmp = tempdir::TempDir::new("vmap")?;
+        let path = tmp.path().join("map_vec");
+        let mut values = MapVec::<u32>::open(&path)?.with_policy(GrowthPolicy::Fixed(256));
+        values.push(1)?;
+        assert_eq!(256, values.capacity());
+        assert_eq!(1024, fs::metadata(&path)?.len());
+        values.extend_from_slice(&[2; 300])?;
+        assert_eq!(512, values.capacity());
+        values.extend_from_slice(&[3; 1000])?;
+        assert_eq!(1301, values.capacity());
+        values.close()?;
+        assert_eq!(1301 * 4, fs::metadata(&path)?.len());
+
+        let page = page_size() / 8;
+        let mut anon = MapVec::<u64>::new()?.with_policy(GrowthPolicy::Custom(|cap| cap + 1));
+        anon.push(1)?;
+        assert_eq!(page, anon.capacity());
+        anon.extend_from_slice(&vec![2; page])?;
+        assert_eq!(2 * page, anon.capacity());
+        Ok(())
+    }
+
+    #[test]
+    fn track_writes_dirty() -> Result<()> {
+        let page = page_size();
+        let (_tmp, path, _) = write_default("track_writes")?;
+        let file = fs::OpenOptions::new().read(true).write(true).open(&path)?;
+        file.set_len(8 * page as u64)?;
+        let mut map = MapMut::with_options().map(&file)?;
+        map.track_writes()?;
+        assert!(map.is_tracking_writes());
+        assert_eq!(0, map[5 * page]);
+        map[2 * page + 7] = 1;
+        map[3 * page] = 1;
+        map[7 * page] = 1;
+        assert_eq!(
+            vec![2 * page..4 * page, 7 * page..8 * page],
+            map.dirty_ranges()?
+        );
+        for range in map.dirty_ranges()? {
+            map.flush_range(&file, range.start, range.len(), Flush::Sync)?;
+        }
+
+        map.clear_dirty()?;
+        assert!(map.dirty_pages()?.is_empty());
+        map[page] = 2;
+        assert_eq!(vec![1], map.dirty_pages()?);
+        map.untrack_writes()?;
+        assert!(!map.is_tracking_writes());
+        map[0] = 3;
+        drop(map);
+        assert_eq!(1, fs::read(&path)?[3 * page]);
+
+        let mut anon = MapMut::new(page)?;
+        anon.track_writes()?;
+        anon.resize(4 * page)?;
+        assert!(!anon.is_tracking_writes());
+        anon[3 * page] = 1;
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn map_fd_borrowed() -> Result<()> {
+        use std::os::fd::{AsFd, OwnedFd};
+
+        let (_tmp, path, len) = write_default("map_fd")?;
+        let fd: OwnedFd = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)?
+            .into();
+        let mut map = MapMut::with_options().map_fd(fd.as_fd())?;
+        assert_eq!(len, map.len());
+        map[..4].copy_from_slice(b"TEST");
+        drop(map);
+
+        // The descriptor is still open and usable after the map is dropped.
+        let map = Map::with_options().len(4).map_fd(&fd)?;
+        assert_eq!(b"TEST", &map[..]);
+        let file = fs::File::from(fd);
+        assert_eq!(len as u64, file.metadata()?.len());
+        Ok(())
+    }
+
+    #[test]
+    fn temp_in_unnamed() -> Result<()> {
+        let tmp = tempdir::TempDir::new("vmap")?;
+        let unit = crate::allocation_size();
+        let mut map = MapMut::temp_in(tmp.path(), unit + 1)?;
+        assert_eq!(2 * unit, map.len());
+        assert!(map.is_file_backed());
+        map[2 * unit - 1] = 1;
+        map.flush(map.file().unwrap(), Flush::Sync)?;
+        assert_eq!(0, fs::read_dir(tmp.path())?.count());
+        drop(map);
+
+        assert!(MapMut::temp_in(tmp.path(), 0)?.is_empty());
+        assert!(MapMut::temp_in(tmp.path().join("missing"), 1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn snapshot_stable() -> Result<()> {
+        let (_tmp, path, len) = write_default("snapshot_stable")?;
+        let (mut map, _) = MapMut::with_options()
+            .sharing(Sharing::Shared)
+            .offset(2)
+            .open(&path)?;
+        let snap = map.snapshot()?;
+        map[..5].clone_from_slice(b"CROSS");
+        assert_eq!(len - 2, snap.len());
+        assert_eq!(Ok("cross-platform"), from_utf8(&snap[..14]));
+        assert_eq!(
+            map.as_ptr() as usize % page_size(),
+            snap.as_ptr() as usize % page_size()
+        );
+        drop(map);
+        assert_eq!(Ok("cross-platform"), from_utf8(&snap[..14]));
+        assert!(MapMut::new(0)?.snapshot()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn reservation_commit() -> Result<()> {
+        let page = page_size();
+        let res = Reservation::new(64 * page)?;
+        assert_eq!(0, res.committed());
+
+        let mut a = res.commit(page, 3 * page)?;
+        a.fill(1);
+        assert_eq!(page, a.offset());
+        assert_eq!(unsafe { res.as_ptr().add(page) }, a.as_ptr());
+        let b = res.commit(4 * page, 1)?;
+        assert_eq!(4 * page, res.committed());
+
+        let inuse = [(0, 2 * page), (3 * page, 1), (4 * page, page)];
+        for (off, len) in inuse {
+            let err = res.commit(off, len).unwrap_err();
+            assert_eq!(Some(Input::AddressInUse), err.input_kind());
+        }
+        let err = res.commit(1, page).unwrap_err();
+        assert_eq!(Some(Input::Unaligned), err.input_kind());
+        let err = res.commit(63 * page, page + 1).unwrap_err();
+        assert_eq!(Some(Input::InvalidRange), err.input_kind());
+        assert!(res.commit(0, 0)?.is_empty());
+
+        b.decommit()?;
+        drop(a);
+        assert_eq!(0, res.committed());
+        let a = res.commit(0, 5 * page)?;
+        assert!(a.iter().all(|&b| b == 0));
+
+        assert!(Reservation::new(0)?.commit(0, 1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn sync_policy_periodic() -> Result<()> {
+        let (_tmp, path, _) = write_default("sync_policy_periodic")?;
+        let open = || fs::OpenOptions::new().read(true).write(true).open(&path);
+        let mut map = MapMut::with_options().map(&open()?)?;
+        assert_eq!(SyncPolicy::None, map.sync_policy());
+        assert!(!map.maybe_flush()?);
+        map.set_sync_policy(open()?, SyncPolicy::None);
+        assert!(map.file().is_none());
+
+        let policy = SyncPolicy::Periodic(Flush::Sync, Duration::from_secs(3600));
+        map.set_sync_policy(open()?, policy);
+        assert_eq!(policy, map.sync_policy());
+        assert!(!map.maybe_flush()?);
+
+        map.set_sync_policy(open()?, SyncPolicy::Periodic(Flush::Sync, Duration::ZERO));
+        map[..5].clone_from_slice(b"a CRO");
+        assert!(map.maybe_flush()?);
+        assert_eq!(b"a CRO", &fs::read(&path)?[..5]);
+
+        map.set_sync_policy(open()?, SyncPolicy::OnDrop(Flush::Async));
+        assert_eq!(SyncPolicy::OnDrop(Flush::Async), map.sync_policy());
+        assert!(!map.maybe_flush()?);
+        map.set_sync_policy(open()?, SyncPolicy::None);
+        assert_eq!(SyncPolicy::None, map.sync_policy());
+        assert!(map.file().is_some());
+        map.set_sync_policy(open()?, SyncPolicy::OnDrop(Flush::Async));
+        map[..1].clone_from_slice(b"A");
+        map.close()?;
+        assert_eq!(b"A CRO", &fs::read(&path)?[..5]);
+        Ok(())
+    }
+
+    #[test]
+    fn file_range_limits() -> Result<()> {
+        let (_tmp, path, len) = write_default("file_range_limits")?;
+        let file = fs::OpenOptions::new().read(true).write(true).open(&path)?;
+        let ranges = [
+            (usize::MAX, 2),
+            (1, usize::MAX),
+            (0, isize::MAX as usize + 1),
+        ];
+        for (off, len) in ranges {
+            let err = MapMut::file_deferred(file.try_clone()?, off, len).unwrap_err();
+            assert_eq!(Some(Input::InvalidRange), err.input_kind());
+            let err = MapMut::with_options().map(&file)?.remap(&file, off, len);
+            assert_eq!(Some(Input::InvalidRange), err.unwrap_err().input_kind());
+        }
+
+        #[cfg(target_pointer_width = "64")]
+        {
+            let off = 1usize << 63;
+            let err = MapMut::file_deferred(file.try_clone()?, off, 0).unwrap_err();
+            assert_eq!(Some(Input::InvalidRange), err.input_kind());
+            let err = Map::with_options()
+                .file_len(u64::MAX)
+                .offset(off)
+                .len(1)
+                .map(&file)
+                .unwrap_err();
+            assert_eq!(Some(Input::InvalidRange), err.input_kind());
+        }
+
+        let map = Map::with_options().offset(len).map(&file)?;
+        assert!(map.is_empty());
+        assert!(Map::empty().is_empty());
+        assert_eq!(b"", &MapMut::empty()[..]);
+        Ok(())
+    }
 }
@@ -1,19 +1,46 @@
 use std::convert::TryFrom;
 use std::fs::{File, OpenOptions};
-use std::ops::{Deref, DerefMut};
+use std::hash::Hasher;
+use std::iter::FusedIterator;
+use std::ops::{Deref, DerefMut, Range};
 use std::path::Path;
-use std::slice;
-use std::{cmp, fmt, io, marker};
+use std::ptr;
+use std::slice::{self, SliceIndex};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{cmp, fmt, io, marker, mem, thread};
 
-use crate::os::{advise, flush, lock, map_anon, map_file, protect, unlock, unmap};
-use crate::sealed::FromPtr;
+#[cfg(unix)]
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, RawFd};
+#[cfg(windows)]
+use std::os::windows::io::{AsHandle, AsRawHandle, BorrowedHandle, OwnedHandle, RawHandle};
+
+use crate::cpu;
+#[cfg(windows)]
+use crate::os::map_section;
+use crate::os::{
+    advise, bind_numa, clear_soft_dirty, commit_anon, decommit_anon, fadvise, find_residency,
+    flush, flush_icache, guard, is_truncated, lock, lock_file, lock_file_range, map_anon,
+    map_anon_aligned, map_anon_at, map_anon_huge, map_anon_low, map_anon_shared, map_anon_with,
+    map_file_with, protect, register_mapping, release_anon, remap_anon, remap_file, reserve_anon,
+    snapshot_pages, soft_dirty_pages, temp_file, unlock, unlock_file, unlock_file_range, unmap,
+    unregister_mapping, WriteWatch,
+};
+use crate::sealed::{Atomic, FromPtr};
 use crate::{
-    Advise, ConvertResult, Error, Extent, Flush, Input, Operation, Protect, Result, Size, Span,
-    SpanMut,
+    track_map, track_unmap, Advise, ConvertResult, Error, Extent, FAdvise, Flush, Input, MapFlags,
+    MapSegment, Operation, Protect, Result, Sharing, Size, Span, SpanMut, SyncPolicy,
 };
 
 /// Allocation of one or more read-only sequential pages.
 ///
+/// Cloning a `Map` is cheap: clones share the same pages rather than
+/// mapping them again, and the pages are unmapped when the last clone is
+/// dropped. As a `Map` is `Send` and `Sync`, clones may be handed to other
+/// threads to read a mapping without copying it. Operations that replace the
+/// mapping, such as [`.remap()`] or [`.into_map_mut()`], require the map to
+/// be the only clone and yield an [`Input::Shared`] error otherwise.
+///
 /// # Examples
 ///
 /// ```
@@ -33,7 +60,12 @@ use crate::{
 /// # Ok(())
 /// # }
 /// ```
-pub struct Map(MapMut);
+///
+/// [`.remap()`]: #method.remap
+/// [`.into_map_mut()`]: #method.into_map_mut
+/// [`Input::Shared`]: enum.Input.html#variant.Shared
+#[derive(Clone)]
+pub struct Map(Arc<MapMut>);
 
 impl Map {
     /// Returns a new [`Options`] object to create a read-only `Map`.
@@ -68,10 +100,86 @@ impl Map {
         Options::new()
     }
 
+    /// Creates an empty read-only map.
+    ///
+    /// No system mapping is made. The map dereferences to an empty slice
+    /// whose pointer is non-null and aligned, as for every empty map, so it
+    /// is safe to use anywhere a map of any length is expected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::Map;
+    ///
+    /// let map = Map::empty();
+    /// assert!(map.is_empty());
+    /// assert_eq!(b"", &map[..]);
+    /// ```
+    pub fn empty() -> Self {
+        unsafe { Self::from_ptr(empty_ptr(), 0, Protect::ReadOnly) }
+    }
+
+    /// Maps the whole of an open file as read-only.
+    ///
+    /// The length is read from the file's metadata, and the map spans
+    /// `[0, len)`. An empty file yields an empty map without creating an OS
+    /// mapping. A file longer than `usize::MAX`, which may happen on 32-bit
+    /// targets, returns an [`Input::InvalidRange`] error rather than mapping
+    /// a truncated length. This is the same as `Map::with_options().map(f)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::Map;
+    /// use std::fs::File;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let f = File::open("src/lib.rs")?;
+    /// let map = Map::whole_file(&f)?;
+    /// assert_eq!(map.len() as u64, f.metadata()?.len());
+    /// assert!(map.starts_with(b"//!"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    pub fn whole_file(f: &File) -> Result<Self> {
+        Self::with_options().map(f)
+    }
+
+    /// Creates a read-only anonymous map of at least `hint` bytes.
+    ///
+    /// The pages are zero and cannot be written, which suits a known-zero
+    /// scratch region or guard data. As with [`MapMut::new()`], the length
+    /// is rounded up to the allocation size.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Map, Protect};
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let map = Map::anon(200)?;
+    /// assert_eq!(map.len(), vmap::allocation_size());
+    /// assert_eq!(map.protection(), Protect::ReadOnly);
+    /// assert!(map.iter().all(|&b| b == 0));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`MapMut::new()`]: struct.MapMut.html#method.new
+    pub fn anon(hint: usize) -> Result<Self> {
+        Self::with_options().len(Extent::Min(hint)).alloc()
+    }
+
     /// Transfer ownership of the map into a mutable map.
     ///
     /// This will change the protection of the mapping. If the original file
-    /// was not opened with write permissions, this will error.
+    /// was not opened with write permissions, this will error. For a shared
+    /// mapping, modifications are written back to the file. Use
+    /// [`.into_map_copy()`] for modifications that are private to the map.
+    /// A map that is already private, such as one created with
+    /// [`Options::copy()`], stays copy-on-write.
     ///
     /// # Examples
     ///
@@ -98,15 +206,121 @@ impl Map {
     /// # Ok(())
     /// # }
     /// ```
+    ///
+    /// [`.into_map_copy()`]: #method.into_map_copy
+    /// [`Options::copy()`]: struct.Options.html#method.copy
     pub fn into_map_mut(self) -> ConvertResult<MapMut, Self> {
-        let (ptr, len) = unsafe { Size::page().bounds(self.0.ptr, self.0.len) };
-        match unsafe { protect(ptr, len, Protect::ReadWrite) } {
-            Ok(()) => Ok(self.0),
+        let map = match Arc::try_unwrap(self.0) {
+            Ok(map) => map,
+            Err(map) => return Err((Error::input(Operation::Protect, Input::Shared), Self(map))),
+        };
+        map.with_protection(Protect::ReadWrite)
+            .map_err(|(err, map)| (err, Self(Arc::new(map))))
+    }
+
+    /// Changes the protection of the read-only map.
+    ///
+    /// Any protection may be set, as the map never writes through the pages.
+    /// This allows switching between [`Protect::ReadOnly`] and
+    /// [`Protect::ReadExec`] without a bespoke method for each transition.
+    /// See [`MapMut::with_protection()`] for how a protection is validated
+    /// against the sharing of the mapping.
+    ///
+    /// The protection applies to the pages shared by every clone, so this
+    /// yields an [`Input::Shared`] error if other clones of the map remain.
+    /// The original map is returned with any error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Map, Protect};
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let (map, file) = Map::with_options().open("README.md")?;
+    /// # if cfg!(unix) {
+    /// let map = map.with_protection(Protect::ReadExec)?;
+    /// assert_eq!(Protect::ReadExec, map.protection());
+    /// let map = map.with_protection(Protect::ReadOnly)?;
+    /// assert_eq!(Protect::ReadOnly, map.protection());
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Protect::ReadOnly`]: enum.Protect.html#variant.ReadOnly
+    /// [`Protect::ReadExec`]: enum.Protect.html#variant.ReadExec
+    /// [`MapMut::with_protection()`]: struct.MapMut.html#method.with_protection
+    /// [`Input::Shared`]: enum.Input.html#variant.Shared
+    pub fn with_protection(mut self, prot: Protect) -> ConvertResult<Map, Self> {
+        let map = match Arc::get_mut(&mut self.0) {
+            Some(map) => map,
+            None => return Err((Error::input(Operation::Protect, Input::Shared), self)),
+        };
+        match map.set_protect(prot) {
+            Ok(()) => Ok(self),
+            Err(err) => Err((err, self)),
+        }
+    }
+
+    /// Transfer ownership of the map into a private copy-on-write map.
+    ///
+    /// Unlike [`.into_map_mut()`], which changes the protection of the
+    /// existing mapping, this remaps the same range of `file` as a private
+    /// mapping (`MAP_PRIVATE` on Unix, `FILE_MAP_COPY` on Windows), so
+    /// modifications are never written back to the file. The file only
+    /// needs to be opened for reading. The contents of the new map are read
+    /// from the file, and the original mapping is released once no other
+    /// clone of it remains.
+    ///
+    /// `file` must be the file that was mapped. An anonymous map yields an
+    /// [`Input::Unsupported`] error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::Map;
+    /// use std::path::PathBuf;
+    /// use std::str::from_utf8;
+    /// # use std::fs;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// # fs::write(&path, b"this is a test")?;
+    /// let (map, file) = Map::with_options().open(&path)?;
+    ///
+    /// let mut map = map.into_map_copy(&file)?;
+    /// map[..4].clone_from_slice(b"that");
+    /// assert_eq!(Ok("that is a test"), from_utf8(&map[..]));
+    /// assert_eq!(b"this is a test", &fs::read(&path)?[..]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.into_map_mut()`]: #method.into_map_mut
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    pub fn into_map_copy(mut self, file: &File) -> ConvertResult<MapMut, Self> {
+        match self.0.remap_copy(file) {
+            Ok(Some(mut map)) => {
+                if let Some(inner) = Arc::get_mut(&mut self.0) {
+                    map.file = inner.file.take();
+                }
+                Ok(map)
+            }
+            Ok(None) => Ok(Arc::try_unwrap(self.0).unwrap_or_else(|map| unsafe {
+                let off = map.file_offset().unwrap_or(0);
+                MapMut::from_file_ptr(empty_ptr(), 0, off, map.prot, map.flags)
+            })),
             Err(err) => Err((err, self)),
         }
     }
 
-    /// Updates the advise for the entire mapped region..
+    /// Updates the advise for the entire mapped region.
+    ///
+    /// See [`MapMut::advise()`] for the hints supported by each platform.
+    ///
+    /// [`MapMut::advise()`]: struct.MapMut.html#method.advise
     pub fn advise(&self, adv: Advise) -> Result<()> {
         self.0.advise(adv)
     }
@@ -116,274 +330,4500 @@ impl Map {
         self.0.advise_range(off, len, adv)
     }
 
+    /// Updates the page cache advise for a range of the mapped file.
+    ///
+    /// See [`MapMut::fadvise()`] for details.
+    ///
+    /// [`MapMut::fadvise()`]: struct.MapMut.html#method.fadvise
+    pub fn fadvise(&self, file: &File, off: usize, len: usize, adv: FAdvise) -> Result<()> {
+        self.0.fadvise(file, off, len, adv)
+    }
+
     /// Lock all mapped physical pages into memory.
+    ///
+    /// See [`MapMut::lock()`] for details.
+    ///
+    /// [`MapMut::lock()`]: struct.MapMut.html#method.lock
     pub fn lock(&self) -> Result<()> {
         self.0.lock()
     }
 
     /// Lock a range of physical pages into memory.
+    ///
+    /// Every page containing a byte of `off..off + len` is locked. An
+    /// [`Input::InvalidRange`] error is returned if the range is not within
+    /// the map, including when `off + len` overflows.
+    ///
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
     pub fn lock_range(&self, off: usize, len: usize) -> Result<()> {
         self.0.lock_range(off, len)
     }
 
+    /// Faults in every page of the map and locks them into memory.
+    ///
+    /// See [`MapMut::lock_prefault()`] for details.
+    ///
+    /// [`MapMut::lock_prefault()`]: struct.MapMut.html#method.lock_prefault
+    pub fn lock_prefault(&self) -> Result<()> {
+        self.0.lock_prefault()
+    }
+
     /// Unlock all mapped physical pages into memory.
     pub fn unlock(&self) -> Result<()> {
         self.0.unlock()
     }
 
     /// Unlock a range of physical pages into memory.
+    ///
+    /// The range is checked as in [`.lock_range()`].
+    ///
+    /// [`.lock_range()`]: #method.lock_range
     pub fn unlock_range(&self, off: usize, len: usize) -> Result<()> {
         self.0.unlock_range(off, len)
     }
-}
 
-impl FromPtr for Map {
-    unsafe fn from_ptr(ptr: *mut u8, len: usize) -> Self {
-        Self(MapMut::from_ptr(ptr, len))
+    /// Gets the current protection of the pages of the map.
+    ///
+    /// See [`MapMut::protection()`] for details.
+    ///
+    /// [`MapMut::protection()`]: struct.MapMut.html#method.protection
+    #[inline]
+    pub fn protection(&self) -> Protect {
+        self.0.protection()
     }
-}
 
-impl Span for Map {
+    /// Unmaps the map now and returns any error from doing so.
+    ///
+    /// When other clones of the map remain, this only releases this clone
+    /// and the pages stay mapped until the last clone is closed or dropped.
+    ///
+    /// See [`MapMut::close()`] for details.
+    ///
+    /// [`MapMut::close()`]: struct.MapMut.html#method.close
+    pub fn close(self) -> Result<()> {
+        match Arc::try_unwrap(self.0) {
+            Ok(map) => map.close(),
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// Tests if other clones share the pages of this map.
+    pub fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.0) > 1
+    }
+
+    /// Tests if modifications to the map are private to the process.
+    ///
+    /// See [`MapMut::is_private()`] for details.
+    ///
+    /// [`MapMut::is_private()`]: struct.MapMut.html#method.is_private
     #[inline]
-    fn len(&self) -> usize {
+    pub fn is_private(&self) -> bool {
+        self.0.is_private()
+    }
+
+    /// Gets the number of bytes mapped from the start of the map.
+    ///
+    /// See [`MapMut::capacity()`] for details.
+    ///
+    /// [`MapMut::capacity()`]: struct.MapMut.html#method.capacity
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+
+    /// Gets the length of the map in bytes.
+    ///
+    /// See [`MapMut::len()`] for details.
+    ///
+    /// [`MapMut::len()`]: struct.MapMut.html#method.len
+    #[inline]
+    pub fn len(&self) -> usize {
         self.0.len()
     }
 
+    /// Tests if the map has a length of zero.
     #[inline]
-    fn as_ptr(&self) -> *const u8 {
-        self.0.as_ptr()
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
     }
-}
 
-impl Deref for Map {
-    type Target = [u8];
+    /// Gets the offset of the first byte within its page.
+    ///
+    /// See [`MapMut::offset()`] for details.
+    ///
+    /// [`MapMut::offset()`]: struct.MapMut.html#method.offset
+    #[inline]
+    pub fn offset(&self) -> usize {
+        self.0.offset()
+    }
 
+    /// Gets the page-aligned address and length of the pages of the map.
+    ///
+    /// See [`MapMut::aligned_bounds()`] for details.
+    ///
+    /// [`MapMut::aligned_bounds()`]: struct.MapMut.html#method.aligned_bounds
     #[inline]
-    fn deref(&self) -> &[u8] {
-        unsafe { slice::from_raw_parts(self.0.ptr, self.0.len) }
+    pub fn aligned_bounds(&self) -> (*const u8, usize) {
+        self.0.aligned_bounds()
     }
-}
 
-impl AsRef<[u8]> for Map
-where
-    <Map as Deref>::Target: AsRef<[u8]>,
-{
+    /// Iterates over the bytes of the map one page at a time.
+    ///
+    /// See [`MapMut::pages()`] for details.
+    ///
+    /// [`MapMut::pages()`]: struct.MapMut.html#method.pages
     #[inline]
-    fn as_ref(&self) -> &[u8] {
-        self.deref()
+    pub fn pages(&self) -> Pages<'_> {
+        self.0.pages()
     }
-}
 
-impl TryFrom<MapMut> for Map {
-    type Error = (Error, MapMut);
+    /// Tests if the bytes between the length and capacity start out zero.
+    ///
+    /// See [`MapMut::is_tail_zeroed()`] for details.
+    ///
+    /// [`MapMut::is_tail_zeroed()`]: struct.MapMut.html#method.is_tail_zeroed
+    #[inline]
+    pub fn is_tail_zeroed(&self) -> bool {
+        self.0.is_tail_zeroed()
+    }
 
-    fn try_from(map: MapMut) -> ConvertResult<Self, MapMut> {
-        map.into_map()
+    /// Gets the number of bytes at the end of the capacity that lie past the
+    /// end of the file.
+    ///
+    /// See [`MapMut::tail_past_eof()`] for details.
+    ///
+    /// [`MapMut::tail_past_eof()`]: struct.MapMut.html#method.tail_past_eof
+    pub fn tail_past_eof(&self, file: &File) -> Result<usize> {
+        self.0.tail_past_eof(file)
     }
-}
 
-impl fmt::Debug for Map {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        fmt.debug_struct("Map")
-            .field("ptr", &self.0.ptr)
-            .field("len", &self.0.len)
-            .finish()
+    /// Tests if the map was created from a file rather than anonymously.
+    ///
+    /// See [`MapMut::is_file_backed()`] for details.
+    ///
+    /// [`MapMut::is_file_backed()`]: struct.MapMut.html#method.is_file_backed
+    #[inline]
+    pub fn is_file_backed(&self) -> bool {
+        self.0.is_file_backed()
+    }
+
+    /// Rejects a flush of the read-only map.
+    ///
+    /// A read-only map cannot hold modifications, so there is never anything
+    /// to write back. Rather than silently succeeding, this always returns an
+    /// [`Input::Unsupported`] error so an accidental flush of a read-only map
+    /// is caught. The method exists so code that holds either map type can
+    /// call it without checking which it has. Use [`MapMut::flush()`] to write
+    /// back a writable map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Flush, Map};
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let (map, file) = Map::with_options().open("README.md")?;
+    /// assert!(map.flush(&file, Flush::Sync).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    /// [`MapMut::flush()`]: struct.MapMut.html#method.flush
+    pub fn flush(&self, _file: &File, _mode: Flush) -> Result<()> {
+        Err(Error::input(Operation::Flush, Input::Unsupported))
+    }
+
+    /// Changes the range of the file covered by the map.
+    ///
+    /// This yields an [`Input::Shared`] error if other clones of the map
+    /// remain. See [`MapMut::remap()`] for details.
+    ///
+    /// [`Input::Shared`]: enum.Input.html#variant.Shared
+    /// [`MapMut::remap()`]: struct.MapMut.html#method.remap
+    pub fn remap(&mut self, file: &File, off: usize, len: usize) -> Result<()> {
+        match Arc::get_mut(&mut self.0) {
+            Some(map) => map.remap(file, off, len),
+            None => Err(Error::input(Operation::MapFile, Input::Shared)),
+        }
+    }
+
+    /// Gets the file retained by the map, if any.
+    ///
+    /// See [`Options::map_owned()`] for details.
+    ///
+    /// [`Options::map_owned()`]: struct.Options.html#method.map_owned
+    pub fn file(&self) -> Option<&File> {
+        self.0.file()
+    }
+
+    /// Borrows the file descriptor retained by the map, if any.
+    ///
+    /// See [`MapMut::as_fd()`] for details.
+    ///
+    /// [`MapMut::as_fd()`]: struct.MapMut.html#method.as_fd
+    #[cfg(unix)]
+    pub fn as_fd(&self) -> Option<BorrowedFd<'_>> {
+        self.0.as_fd()
+    }
+
+    /// Gets the raw file descriptor retained by the map, if any.
+    #[cfg(unix)]
+    pub fn as_raw_fd(&self) -> Option<RawFd> {
+        self.0.as_raw_fd()
+    }
+
+    /// Borrows the file handle retained by the map, if any.
+    ///
+    /// See [`MapMut::as_handle()`] for details.
+    ///
+    /// [`MapMut::as_handle()`]: struct.MapMut.html#method.as_handle
+    #[cfg(windows)]
+    pub fn as_handle(&self) -> Option<BorrowedHandle<'_>> {
+        self.0.as_handle()
+    }
+
+    /// Gets the raw file handle retained by the map, if any.
+    #[cfg(windows)]
+    pub fn as_raw_handle(&self) -> Option<RawHandle> {
+        self.0.as_raw_handle()
+    }
+
+    /// Checks that the retained file still covers the whole map.
+    ///
+    /// See [`MapMut::validate()`] for details.
+    ///
+    /// [`MapMut::validate()`]: struct.MapMut.html#method.validate
+    pub fn validate(&self) -> Result<()> {
+        self.0.validate()
+    }
+
+    /// Tests if a truncation of the underlying file has been detected.
+    ///
+    /// See [`MapMut::is_truncated()`] for details.
+    ///
+    /// [`MapMut::is_truncated()`]: struct.MapMut.html#method.is_truncated
+    pub fn is_truncated(&self) -> bool {
+        self.0.is_truncated()
+    }
+
+    /// Returns a sub-slice of the map after checking that none of it was
+    /// lost to a truncation of the underlying file.
+    ///
+    /// See [`MapMut::try_read()`] for details.
+    ///
+    /// [`MapMut::try_read()`]: struct.MapMut.html#method.try_read
+    pub fn try_read(&self, off: usize, len: usize) -> Result<&[u8]> {
+        self.0.try_read(off, len)
+    }
+
+    /// Faults in every page of the map by reading from it.
+    ///
+    /// See [`MapMut::prefault()`] for details.
+    ///
+    /// [`MapMut::prefault()`]: struct.MapMut.html#method.prefault
+    pub fn prefault(&self) {
+        self.0.prefault()
+    }
+
+    /// Faults in the pages of a range of the map by reading from it.
+    ///
+    /// See [`MapMut::prefault_range()`] for details.
+    ///
+    /// [`MapMut::prefault_range()`]: struct.MapMut.html#method.prefault_range
+    pub fn prefault_range(&self, off: usize, len: usize) -> Result<()> {
+        self.0.prefault_range(off, len)
+    }
+
+    /// Faults in every page of the map using multiple threads.
+    ///
+    /// See [`MapMut::prefault_parallel()`] for details.
+    ///
+    /// [`MapMut::prefault_parallel()`]: struct.MapMut.html#method.prefault_parallel
+    pub fn prefault_parallel(&self, threads: usize) -> Result<()> {
+        self.0.prefault_parallel(threads)
+    }
+
+    /// Tests if every page of a range is resident in memory.
+    ///
+    /// See [`MapMut::all_resident()`] for details.
+    ///
+    /// [`MapMut::all_resident()`]: struct.MapMut.html#method.all_resident
+    pub fn all_resident(&self, range: Range<usize>) -> Result<bool> {
+        self.0.all_resident(range)
+    }
+
+    /// Tests if any page of a range is resident in memory.
+    ///
+    /// See [`MapMut::any_resident()`] for details.
+    ///
+    /// [`MapMut::any_resident()`]: struct.MapMut.html#method.any_resident
+    pub fn any_resident(&self, range: Range<usize>) -> Result<bool> {
+        self.0.any_resident(range)
+    }
+
+    /// Feeds every byte of the map to `hasher` in one sequential pass.
+    ///
+    /// See [`MapMut::digest()`] for details.
+    ///
+    /// [`MapMut::digest()`]: struct.MapMut.html#method.digest
+    pub fn digest<H: Hasher>(&self, hasher: &mut H) -> Result<()> {
+        self.0.digest(hasher)
+    }
+
+    /// Acquire an advisory lock on the entire file backing the map.
+    ///
+    /// See [`MapMut::lock_file()`] for details.
+    ///
+    /// [`MapMut::lock_file()`]: struct.MapMut.html#method.lock_file
+    pub fn lock_file<'a>(&self, file: &'a File, exclusive: bool) -> Result<FileLock<'a>> {
+        self.0.lock_file(file, exclusive)
+    }
+
+    /// Acquire an advisory lock on a byte range of the file backing the map.
+    ///
+    /// See [`MapMut::lock_file_range()`] for details.
+    ///
+    /// [`MapMut::lock_file_range()`]: struct.MapMut.html#method.lock_file_range
+    pub fn lock_file_range<'a>(
+        &self,
+        file: &'a File,
+        range: Range<usize>,
+        exclusive: bool,
+    ) -> Result<FileRangeLock<'a>> {
+        self.0.lock_file_range(file, range, exclusive)
+    }
+
+    /// Bind the mapped physical pages to a NUMA node.
+    ///
+    /// See [`MapMut::bind_numa()`] for details.
+    ///
+    /// [`MapMut::bind_numa()`]: struct.MapMut.html#method.bind_numa
+    pub fn bind_numa(&self, node: u32) -> Result<()> {
+        self.0.bind_numa(node)
+    }
+
+    /// Returns a reference to a byte or sub-slice of the map, or `None` if
+    /// the index is out of bounds.
+    ///
+    /// This mirrors `slice::get` and is intended for indexes derived from
+    /// untrusted data where a panic would be inappropriate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::Map;
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// # std::fs::write(&path, b"this is a test")?;
+    /// let (map, file) = Map::with_options().open(&path)?;
+    /// assert_eq!(Some(&b't'), map.get(0));
+    /// assert_eq!(Some(&b"test"[..]), map.get(10..14));
+    /// assert_eq!(None, map.get(14));
+    /// assert_eq!(None, map.get(10..15));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn get<I: SliceIndex<[u8]>>(&self, index: I) -> Option<&I::Output> {
+        self.0.get(index)
+    }
+
+    /// Returns a sub-slice of `len` bytes starting at `off`, or `None` if the
+    /// range lies outside of the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::Map;
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// # std::fs::write(&path, b"this is a test")?;
+    /// let (map, file) = Map::with_options().open(&path)?;
+    /// assert_eq!(Some(&b"test"[..]), map.get_range(10, 4));
+    /// assert_eq!(None, map.get_range(10, 5));
+    /// assert_eq!(None, map.get_range(usize::MAX, 2));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn get_range(&self, off: usize, len: usize) -> Option<&[u8]> {
+        self.0.get_range(off, len)
+    }
+
+    /// Returns an iterator over every overlapping window of `size` bytes.
+    ///
+    /// This mirrors `slice::windows` without copying, and the windows borrow
+    /// the map so it cannot be unmapped while they are in use. Nothing is
+    /// yielded when `size` is greater than the length of the map.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::Map;
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// # std::fs::write(&path, b"this is a test")?;
+    /// let (map, file) = Map::with_options().open(&path)?;
+    /// assert_eq!(Some(10), map.windows(4).position(|w| w == b"test"));
+    /// assert_eq!(11, map.windows(4).count());
+    /// assert_eq!(0, map.windows(15).count());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn windows(&self, size: usize) -> slice::Windows<'_, u8> {
+        self.0.windows(size)
+    }
+
+    /// Gets an owned segment of a range of the map.
+    ///
+    /// The segment shares the pages of the map, which stay mapped until the
+    /// map and every segment of it are dropped. The range must lie within
+    /// the map, otherwise an [`Input::InvalidRange`] error is returned. See
+    /// [`MapSegment`] for details.
+    ///
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    /// [`MapSegment`]: struct.MapSegment.html
+    pub fn slice(&self, range: Range<usize>) -> Result<MapSegment> {
+        MapSegment::new(self.clone(), range)
+    }
+
+    /// Splits the map into two owned segments at byte `mid`.
+    ///
+    /// The first segment holds the bytes `0..mid` and the second the bytes
+    /// from `mid` to the end. Both share the pages of the map, which are
+    /// unmapped once the last of them is dropped. A `mid` past the end of the
+    /// map returns an [`Input::InvalidRange`] error along with the original
+    /// map.
+    ///
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    pub fn into_split(self, mid: usize) -> ConvertResult<(MapSegment, MapSegment), Self> {
+        MapSegment::from(self)
+            .into_split(mid)
+            .map_err(|(err, seg)| (err, seg.into_map()))
+    }
+}
+
+impl FromPtr for Map {
+    unsafe fn from_ptr(ptr: *mut u8, len: usize, prot: Protect) -> Self {
+        Self(Arc::new(MapMut::from_ptr(ptr, len, prot)))
+    }
+
+    unsafe fn from_file_ptr(
+        ptr: *mut u8,
+        len: usize,
+        off: u64,
+        prot: Protect,
+        flags: MapFlags,
+    ) -> Self {
+        Self(Arc::new(MapMut::from_file_ptr(ptr, len, off, prot, flags)))
+    }
+
+    fn set_file(&mut self, file: File) {
+        // A map is only given its file while it is being constructed, before
+        // it could have been cloned.
+        if let Some(map) = Arc::get_mut(&mut self.0) {
+            map.set_file(file)
+        }
+    }
+
+    fn set_huge(&mut self, size: usize) {
+        if let Some(map) = Arc::get_mut(&mut self.0) {
+            map.set_huge(size)
+        }
+    }
+}
+
+impl Span for Map {
+    #[inline]
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    fn as_ptr(&self) -> *const u8 {
+        self.0.as_ptr()
+    }
+}
+
+impl Deref for Map {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.0.ptr, self.0.len) }
+    }
+}
+
+impl AsRef<[u8]> for Map
+where
+    <Map as Deref>::Target: AsRef<[u8]>,
+{
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.deref()
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl From<Map> for bytes::Bytes {
+    /// Converts the map into `Bytes` without copying.
+    ///
+    /// The `Bytes` and every clone or slice of it hold the map, so the pages
+    /// are unmapped once the last of them is dropped. This allows the
+    /// contents of a mapped file to be handed to APIs built on [`bytes`],
+    /// such as for sending it over a network connection.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::Map;
+    /// use bytes::Bytes;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let (map, _) = Map::with_options().open("README.md")?;
+    /// let len = map.len();
+    /// let bytes = Bytes::from(map);
+    /// assert_eq!(len, bytes.len());
+    /// let head = bytes.slice(..4);
+    /// drop(bytes);
+    /// assert_eq!(&std::fs::read("README.md")?[..4], &head[..]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`bytes`]: https://docs.rs/bytes
+    fn from(map: Map) -> Self {
+        Self::from_owner(map)
+    }
+}
+
+impl TryFrom<MapMut> for Map {
+    type Error = (Error, MapMut);
+
+    fn try_from(map: MapMut) -> ConvertResult<Self, MapMut> {
+        map.into_map()
+    }
+}
+
+/// Maps the whole file read-only, retaining the file in the map.
+///
+/// An empty file yields an empty map. On failure, the file is returned with
+/// the error.
+///
+/// # Examples
+///
+/// ```
+/// use vmap::Map;
+/// use std::convert::TryInto;
+/// use std::fs::File;
+///
+/// # fn main() -> vmap::Result<()> {
+/// let map: Map = File::open("README.md")?.try_into()?;
+/// assert!(map.file().is_some());
+/// assert!(!map.is_empty());
+/// # Ok(())
+/// # }
+/// ```
+impl TryFrom<File> for Map {
+    type Error = (Error, File);
+
+    fn try_from(file: File) -> ConvertResult<Self, File> {
+        map_whole(Self::with_options(), file)
+    }
+}
+
+impl fmt::Debug for Map {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Map")
+            .field("ptr", &self.0.ptr)
+            .field("len", &self.0.len)
+            .finish()
+    }
+}
+
+/// Allocation of one or more read-write sequential pages.
+#[derive(Debug)]
+pub struct MapMut {
+    ptr: *mut u8,
+    len: usize,
+    // Memory backing the pages, including the file offset of the first byte.
+    backing: Backing,
+    // Current protection of the pages, used to skip redundant changes.
+    prot: Protect,
+    // Flags used to create a file mapping, reused when remapping. This also
+    // includes `PRIVATE` for any copy-on-write map.
+    flags: MapFlags,
+    // File handle retained by `Options::map_owned`.
+    file: Option<File>,
+    // Flush issued with the retained file when dropped, if any.
+    flush_on_drop: Option<Flush>,
+    // State that few maps use, boxed to keep the map small enough to return
+    // alongside an error in a `ConvertResult`.
+    extra: Option<Box<Extra>>,
+}
+
+#[derive(Debug, Default)]
+struct Extra {
+    // Write tracking started by `track_writes`, if any.
+    watch: Option<WriteWatch>,
+    // Interval of a periodic sync policy and the time of the last flush.
+    sync_every: Option<(Duration, Instant)>,
+    // Inheritable section handle of a map created by `shared`.
+    #[cfg(windows)]
+    section: Option<OwnedHandle>,
+}
+
+// The map owns its pages exclusively, much like a `Vec` owns its buffer, so
+// it may be moved to or shared with other threads. Neither the mapping nor
+// the retained file has any affinity to the thread that created it: pages
+// may be accessed, protected, and unmapped from any thread, and shared
+// access only hands out `&[u8]`. A `Map` is an `Arc` of a map, so it is
+// `Send` and `Sync` through these as well.
+unsafe impl Send for MapMut {}
+unsafe impl Sync for MapMut {}
+
+/// Iterator over the bytes of a map one page at a time.
+///
+/// This is created by the `.pages()` method of [`Map`] and [`MapMut`].
+///
+/// [`Map`]: struct.Map.html
+/// [`MapMut`]: struct.MapMut.html
+#[derive(Clone, Debug)]
+pub struct Pages<'a> {
+    head: Option<&'a [u8]>,
+    rest: slice::Chunks<'a, u8>,
+}
+
+impl<'a> Iterator for Pages<'a> {
+    type Item = &'a [u8];
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a [u8]> {
+        self.head.take().or_else(|| self.rest.next())
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.rest.len() + self.head.is_some() as usize;
+        (n, Some(n))
+    }
+}
+
+impl DoubleEndedIterator for Pages<'_> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.rest.next_back().or_else(|| self.head.take())
+    }
+}
+
+impl ExactSizeIterator for Pages<'_> {}
+
+impl FusedIterator for Pages<'_> {}
+
+/// Iterator over the mutable bytes of a map one page at a time.
+///
+/// This is created by [`MapMut::pages_mut()`].
+///
+/// [`MapMut::pages_mut()`]: struct.MapMut.html#method.pages_mut
+#[derive(Debug)]
+pub struct PagesMut<'a> {
+    head: Option<&'a mut [u8]>,
+    rest: slice::ChunksMut<'a, u8>,
+}
+
+impl<'a> Iterator for PagesMut<'a> {
+    type Item = &'a mut [u8];
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a mut [u8]> {
+        self.head.take().or_else(|| self.rest.next())
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.rest.len() + self.head.is_some() as usize;
+        (n, Some(n))
+    }
+}
+
+impl DoubleEndedIterator for PagesMut<'_> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.rest.next_back().or_else(|| self.head.take())
+    }
+}
+
+impl ExactSizeIterator for PagesMut<'_> {}
+
+impl FusedIterator for PagesMut<'_> {}
+
+// Splits off the bytes before the first page boundary of a slice, if it does
+// not start on one.
+fn split_page_head(bytes: &[u8]) -> (Option<&[u8]>, &[u8]) {
+    let lead = Size::page().offset(bytes.as_ptr() as usize);
+    if lead == 0 || bytes.is_empty() {
+        (None, bytes)
+    } else {
+        let (head, rest) = bytes.split_at(cmp::min(bytes.len(), Size::page().size(1) - lead));
+        (Some(head), rest)
+    }
+}
+
+// Iterator splitting an anonymous map into one map per allocation unit.
+struct IntoPages {
+    ptr: *mut u8,
+    len: usize,
+    off: usize,
+    prot: Protect,
+    flags: MapFlags,
+}
+
+impl Iterator for IntoPages {
+    type Item = MapMut;
+
+    fn next(&mut self) -> Option<MapMut> {
+        if self.off >= self.len {
+            return None;
+        }
+        let len = cmp::min(Size::alloc().size(1), self.len - self.off);
+        let mut map = unsafe { MapMut::from_ptr(self.ptr.add(self.off), len, self.prot) };
+        map.flags = self.flags;
+        self.off += len;
+        Some(map)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = Size::alloc().count(self.len - self.off) as usize;
+        (n, Some(n))
+    }
+}
+
+impl ExactSizeIterator for IntoPages {}
+
+impl Drop for IntoPages {
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Backing {
+    // Anonymous memory.
+    Anon,
+    // A file mapped from the offset of the first byte.
+    File(u64),
+    // Anonymous memory committed from a reservation of this many bytes.
+    Reserved(usize),
+    // Anonymous memory backed by huge pages of this size.
+    Huge(usize),
+    // Anonymous memory preceded by an inaccessible guard of this many bytes
+    // and followed by another that extends to the end of the allocation.
+    Guarded(usize),
+}
+
+impl MapMut {
+    /// Returns a new `Options` object to create a writable `MapMut`.
+    ///
+    /// When used to [`.open()`] a path or [`.map()`] a file, the default
+    /// [`Options`] object is assumed to cover the entire file.
+    ///
+    /// File mappings are shared by default (`MAP_SHARED` on Unix). Stores to
+    /// the map modify the page cache backing the file, so they are visible to
+    /// other handles of the file immediately and are persisted by
+    /// [`.flush()`]. Use [`.sharing()`] to state this explicitly or to select
+    /// a private mapping instead.
+    ///
+    /// See the [`Options`] type for details on options for modifying the file
+    /// size, specifying offset positions, and selecting specific lengths.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{MapMut, Flush};
+    /// use std::path::PathBuf;
+    /// use std::str::from_utf8;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// # std::fs::write(&path, "A cross-platform library for fast and safe memory-mapped IO in Rust")?;
+    /// let (mut map, file) = MapMut::with_options()
+    ///     .offset(29)
+    ///     .len(30)
+    ///     .open(&path)?;
+    /// assert_eq!(Ok("fast and safe memory-mapped IO"), from_utf8(&map));
+    /// map[..4].clone_from_slice(b"nice");
+    ///
+    /// map.flush_range(&file, 0, 4, Flush::Sync);
+    ///
+    /// assert_eq!(Ok("nice and safe memory-mapped IO"), from_utf8(&map));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.flush()`]: #method.flush
+    /// [`.sharing()`]: struct.Options.html#method.sharing
+    pub fn with_options() -> Options<Self> {
+        let mut opts = Options::new();
+        opts.write();
+        opts
+    }
+
+    /// Maps the whole of an open file as read-write.
+    ///
+    /// The file must be opened for writing. See [`Map::whole_file()`] for
+    /// details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Flush, MapMut};
+    /// use std::fs::OpenOptions;
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// # std::fs::write(&path, b"this is a test")?;
+    /// let f = OpenOptions::new().read(true).write(true).open(&path)?;
+    /// let mut map = MapMut::whole_file(&f)?;
+    /// map[..4].clone_from_slice(b"that");
+    /// map.flush(&f, Flush::Sync)?;
+    /// assert_eq!(b"that is a test", &std::fs::read(&path)?[..]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Map::whole_file()`]: struct.Map.html#method.whole_file
+    pub fn whole_file(f: &File) -> Result<Self> {
+        Self::with_options().map(f)
+    }
+
+    /// Create a new anonymous mapping at least as large as the hint.
+    ///
+    /// The allocation is shared, so on Unix a child process created with
+    /// `fork` writes to the same physical memory as the parent. Use the
+    /// [`.copy()`] option for an allocation that is private to each process.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{MapMut, Protect};
+    /// use std::str::from_utf8;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let mut map = MapMut::new(200)?;
+    /// map[..4].clone_from_slice(b"test");
+    /// assert_eq!(Ok("test"), from_utf8(&map[..4]));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.copy()`]: struct.Options.html#method.copy
+    pub fn new(hint: usize) -> Result<Self> {
+        Self::with_options().len(Extent::Min(hint)).alloc()
+    }
+
+    /// Creates an empty writable map.
+    ///
+    /// This is the same as `MapMut::new(0)`, but cannot fail. No system
+    /// mapping is made, and the map dereferences to an empty slice whose
+    /// pointer is non-null and aligned.
+    pub fn empty() -> Self {
+        unsafe { Self::from_ptr(empty_ptr(), 0, Protect::ReadWrite) }
+    }
+
+    /// Creates a shared anonymous map at least as large as the hint.
+    ///
+    /// On Unix, the pages are mapped with `MAP_SHARED`, so a scratch buffer
+    /// written by the parent before a `fork` is visible to the child, and
+    /// writes made by either process afterwards are visible to the other.
+    /// The mapping has no name, so this is only useful between processes
+    /// related by `fork`. Unrelated processes must share a file mapping
+    /// instead. This is the same as [`MapMut::new()`], as anonymous maps are
+    /// always mapped with `MAP_SHARED` on Unix.
+    ///
+    /// On Windows there is no `fork`, so the pages are mapped from a section
+    /// of the paging file with an inheritable handle. A child process created
+    /// with handle inheritance can map the same pages with
+    /// [`MapMut::from_shared_handle()`], given the value of
+    /// [`.shared_handle()`] by some other means, such as its command line.
+    ///
+    /// The length is rounded up to the allocation size as in
+    /// [`MapMut::new()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let mut map = MapMut::shared(100)?;
+    /// assert!(!map.is_private());
+    /// map[..4].copy_from_slice(b"test");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`MapMut::new()`]: #method.new
+    /// [`MapMut::from_shared_handle()`]: #method.from_shared_handle
+    /// [`.shared_handle()`]: #method.shared_handle
+    pub fn shared(hint: usize) -> Result<Self> {
+        let len = Size::alloc().round(hint);
+        if len == 0 {
+            return Self::new(0);
+        }
+        #[cfg(unix)]
+        let ptr = map_anon_shared(len)?;
+        #[cfg(windows)]
+        let (ptr, section) = map_anon_shared(len)?;
+        #[allow(unused_mut)]
+        let mut map =
+            unsafe { or_unmap(MapMut::try_from_ptr(ptr, len, Protect::ReadWrite), ptr, len)? };
+        #[cfg(windows)]
+        {
+            map.extra_mut().section = Some(section);
+        }
+        track_map(len);
+        Ok(map)
+    }
+
+    /// Maps the pages of a shared map created in another process.
+    ///
+    /// The handle must be the section handle of a map created with
+    /// [`MapMut::shared()`], typically inherited by a child process, and
+    /// `len` is the number of bytes to map from the start of it. Writes
+    /// through either map are visible to the other. The handle is only
+    /// borrowed and may be closed once this returns.
+    ///
+    /// [`MapMut::shared()`]: #method.shared
+    #[cfg(windows)]
+    pub fn from_shared_handle<H: AsHandle>(handle: H, len: usize) -> Result<Self> {
+        let ptr = map_section(handle.as_handle(), len)?;
+        let map =
+            unsafe { or_unmap(MapMut::try_from_ptr(ptr, len, Protect::ReadWrite), ptr, len)? };
+        track_map(len);
+        Ok(map)
+    }
+
+    /// Borrows the inheritable section handle of a shared map, if any.
+    ///
+    /// This is only set for a map created with [`MapMut::shared()`]. The
+    /// handle is inherited by child processes created with handle
+    /// inheritance, and stays open until the map is dropped.
+    ///
+    /// [`MapMut::shared()`]: #method.shared
+    #[cfg(windows)]
+    pub fn shared_handle(&self) -> Option<BorrowedHandle<'_>> {
+        self.extra.as_ref()?.section.as_ref().map(|h| h.as_handle())
+    }
+
+    /// Creates an anonymous map between two inaccessible guard pages.
+    ///
+    /// The map holds at least `hint` bytes, rounded up to the page size. The
+    /// page before the first byte and the pages from the end of the map to
+    /// the end of the allocation have all access removed, so a read or write
+    /// that overruns the map in either direction faults immediately rather
+    /// than silently reaching adjacent memory. This suits the stacks of
+    /// sandboxed interpreters and arenas that must not spill into each other.
+    /// The guards are unmapped along with the map.
+    ///
+    /// The length of a guarded map is fixed, so it cannot be resized or
+    /// split, and a `hint` of zero creates an empty map without guards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let page = vmap::page_size();
+    /// let mut map = MapMut::with_guards(100)?;
+    /// assert_eq!(page, map.len());
+    /// map[page - 1] = 1; // map[page] would fault
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_guards(hint: usize) -> Result<Self> {
+        let page = Size::page();
+        let (lead, len) = (page.size(1), page.round(hint));
+        if len == 0 {
+            return Self::new(0);
+        }
+        let total = Size::alloc().round(2 * lead + len);
+        let pg = map_anon(total, Protect::ReadWrite)?;
+        unsafe {
+            let res = guard(pg, lead)
+                .and_then(|_| guard(pg.add(lead + len), total - lead - len))
+                .and_then(|_| MapMut::try_from_ptr(pg.add(lead), len, Protect::ReadWrite));
+            let mut map = or_unmap(res, pg, total)?;
+            map.backing = Backing::Guarded(lead);
+            track_map(total);
+            Ok(map)
+        }
+    }
+
+    /// Creates an anonymous map whose address is a multiple of `align`.
+    ///
+    /// The base of any map is aligned to the [`allocation_size()`]. This
+    /// provides a stronger alignment, such as 2MB to allow transparent huge
+    /// pages, or for SIMD and DMA buffers with strict requirements. As with
+    /// [`MapMut::new()`], the length is rounded up to the allocation size.
+    ///
+    /// `align` must be a power of two, otherwise an [`Input::Unaligned`]
+    /// error is returned. An alignment no larger than the allocation size is
+    /// already met by every map, such as 64 bytes for a cache line. On Unix,
+    /// the extra address space used to find an aligned range is unmapped
+    /// before returning, so only the map itself remains.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let map = MapMut::new_aligned(4096, 2 << 20)?;
+    /// assert_eq!(map.as_ptr() as usize % (2 << 20), 0);
+    /// assert!(map.len() >= 4096);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`allocation_size()`]: fn.allocation_size.html
+    /// [`MapMut::new()`]: #method.new
+    /// [`Input::Unaligned`]: enum.Input.html#variant.Unaligned
+    pub fn new_aligned(len: usize, align: usize) -> Result<Self> {
+        if !align.is_power_of_two() {
+            return Err(Error::input(Operation::MapAnonymous, Input::Unaligned));
+        }
+        let len = Size::alloc().round(len);
+        if len == 0 || align <= Size::alloc().size(1) {
+            return Self::new(len);
+        }
+        let ptr = map_anon_aligned(len, align, Protect::ReadWrite)?;
+        let map =
+            unsafe { or_unmap(MapMut::try_from_ptr(ptr, len, Protect::ReadWrite), ptr, len)? };
+        track_map(len);
+        Ok(map)
+    }
+
+    /// Creates an anonymous map backed by huge pages.
+    ///
+    /// The `hint` is rounded up to the huge page size reported by
+    /// [`Size::huge()`] rather than the allocation size, and the map is
+    /// aligned to a huge page. Backing a large working set with huge pages
+    /// takes far fewer TLB entries, which can speed up random access.
+    ///
+    /// The pages are taken from a pool the system reserves for huge pages,
+    /// configured on Linux with `vm.nr_hugepages`. If the pool does not have
+    /// enough free pages, or the platform does not support huge pages, the
+    /// system error, or an [`Input::Unsupported`] error, is returned rather
+    /// than falling back to normal pages. On Linux this uses `MAP_HUGETLB`,
+    /// and on Windows a large page section, which requires the
+    /// `SeLockMemoryPrivilege` privilege. Large pages on Windows are never
+    /// paged out. Other platforms return an [`Input::Unsupported`] error.
+    ///
+    /// For transparent huge pages, which need no reserved pool but are not
+    /// guaranteed, use [`MapMut::new_aligned()`] with [`Advise::HugePage`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{MapMut, Size};
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// if let Ok(mut map) = MapMut::new_huge(100) {
+    ///     assert_eq!(map.len(), Size::huge()?.size(1));
+    ///     map[..4].copy_from_slice(b"test");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Size::huge()`]: struct.Size.html#method.huge
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    /// [`MapMut::new_aligned()`]: #method.new_aligned
+    /// [`Advise::HugePage`]: enum.Advise.html#variant.HugePage
+    pub fn new_huge(hint: usize) -> Result<Self> {
+        Self::with_options()
+            .huge_pages(true)
+            .len(Extent::Min(hint))
+            .alloc()
+    }
+
+    /// Creates an anonymous map of `len` bytes placed at exactly `addr`.
+    ///
+    /// Unlike an address hint, which the system is free to ignore, this
+    /// either places the map at `addr` or fails. An existing mapping is
+    /// never replaced, and an [`Input::AddressInUse`] error is returned if
+    /// any of the range is already in use. The address must be aligned to
+    /// the [`allocation_size()`], or an [`Input::Unaligned`] error is
+    /// returned, and the length must not be zero.
+    ///
+    /// How the placement is enforced depends on the platform:
+    ///
+    /// - On Linux, the mapping uses `MAP_FIXED_NOREPLACE`. Kernels before
+    ///   4.17 ignore this flag, in which case the behavior is the same as
+    ///   on other Unix platforms.
+    /// - On other Unix platforms, `addr` is passed to `mmap` as a hint, and
+    ///   a mapping the kernel placed elsewhere is unmapped before returning
+    ///   the error.
+    /// - On Windows, the view is mapped with `MapViewOfFileEx` at `addr`,
+    ///   which fails if the address is not free.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let map = MapMut::new(100)?;
+    /// let addr = map.as_ptr() as *mut u8;
+    /// assert!(MapMut::at_address(addr, 100).is_err());
+    ///
+    /// map.close()?;
+    /// let mut map = MapMut::at_address(addr, 100)?;
+    /// assert_eq!(map.as_ptr(), addr);
+    /// map[..4].copy_from_slice(b"test");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Input::AddressInUse`]: enum.Input.html#variant.AddressInUse
+    /// [`Input::Unaligned`]: enum.Input.html#variant.Unaligned
+    /// [`allocation_size()`]: fn.allocation_size.html
+    pub fn at_address(addr: *mut u8, len: usize) -> Result<Self> {
+        if len == 0 {
+            return Err(Error::input(Operation::MapAnonymous, Input::InvalidRange));
+        }
+        if Size::alloc().offset(addr as usize) != 0 {
+            return Err(Error::input(Operation::MapAnonymous, Input::Unaligned));
+        }
+        let ptr = map_anon_at(addr, len, Protect::ReadWrite)?;
+        let map =
+            unsafe { or_unmap(MapMut::try_from_ptr(ptr, len, Protect::ReadWrite), ptr, len)? };
+        track_map(Size::alloc().round(len));
+        Ok(map)
+    }
+
+    /// Creates an anonymous map that can grow in place without moving.
+    ///
+    /// The address space for `max` bytes is reserved up front without
+    /// committing any memory, and the first `initial` bytes are committed
+    /// for reading and writing. The map may then be extended up to `max`
+    /// bytes with [`.grow()`], and its address never changes. This differs
+    /// from remapping, which may move the map to find room to grow. The
+    /// [`.len()`] of the map is the number of committed bytes.
+    ///
+    /// The map is private to the process. The reservation counts against
+    /// the address space of the process but not against its memory until
+    /// the pages are committed.
+    ///
+    /// An [`Input::InvalidRange`] error is returned if `initial` is larger
+    /// than `max`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let mut map = MapMut::growable(100, 1 << 30)?;
+    /// assert_eq!(map.len(), 100);
+    /// map[..4].copy_from_slice(b"test");
+    /// let ptr = map.as_ptr();
+    ///
+    /// map.grow(1 << 20)?;
+    /// assert_eq!(map.len(), 1 << 20);
+    /// assert_eq!(map.as_ptr(), ptr);
+    /// assert_eq!(b"test", &map[..4]);
+    /// map[(1 << 20) - 1] = 1;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.grow()`]: #method.grow
+    /// [`.len()`]: #method.len
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    pub fn growable(initial: usize, max: usize) -> Result<Self> {
+        if initial > max {
+            return Err(Error::input(Operation::Reserve, Input::InvalidRange));
+        }
+        let reserved = Size::alloc().round(max);
+        if reserved == 0 {
+            return Self::new(0);
+        }
+        let ptr = reserve_anon(reserved)?;
+        let mut map = match unsafe { MapMut::try_from_ptr(ptr, 0, Protect::ReadWrite) } {
+            Ok(map) => map,
+            Err(e) => {
+                unsafe { release_anon(ptr, reserved).unwrap_or_default() };
+                return Err(e);
+            }
+        };
+        map.flags = MapFlags::PRIVATE;
+        map.backing = Backing::Reserved(reserved);
+        track_map(reserved);
+        map.grow(initial)?;
+        Ok(map)
+    }
+
+    /// Commits more of the reservation of a growable map.
+    ///
+    /// The length of the map becomes `new_len`, and the map is not moved.
+    /// Newly committed bytes are zero. This does nothing if `new_len` is not
+    /// larger than the current length. An [`Input::InvalidRange`] error is
+    /// returned if `new_len` exceeds the reservation, and an
+    /// [`Input::Unsupported`] error is returned for a map not created with
+    /// [`MapMut::growable()`].
+    ///
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    /// [`MapMut::growable()`]: #method.growable
+    pub fn grow(&mut self, new_len: usize) -> Result<()> {
+        let reserved = match self.backing {
+            Backing::Reserved(n) => n,
+            _ => return Err(Error::input(Operation::Commit, Input::Unsupported)),
+        };
+        if new_len > reserved {
+            return Err(Error::input(Operation::Commit, Input::InvalidRange));
+        }
+        if new_len <= self.len {
+            return Ok(());
+        }
+        self.untrack_writes()?;
+        let page = Size::page();
+        let (old, new) = (page.round(self.len), page.round(new_len));
+        if new > old {
+            unsafe { commit_anon(self.ptr.add(old), new - old)? };
+        }
+        self.len = new_len;
+        Ok(())
+    }
+
+    /// Changes the length of an anonymous map.
+    ///
+    /// As with [`MapMut::new()`], `hint` is rounded up to the allocation
+    /// size, and the bytes up to the smaller of the old and new lengths are
+    /// preserved. Any new bytes are zero. Shrinking the map unmaps the pages
+    /// past the new length in place. Growing may move the map to a new
+    /// address, so any pointer obtained from it must not be used afterwards:
+    ///
+    /// - On Linux, a private map, such as one from [`Options::copy()`], is
+    ///   grown with `mremap`. This extends the map in place when the address
+    ///   space after it is free, and otherwise moves its pages to a new
+    ///   address without copying them.
+    /// - On other platforms, and for a shared map, a new allocation is made
+    ///   and all bytes are copied into it before the old one is unmapped.
+    ///   This always moves the map, and uses the memory of both maps until
+    ///   the copy is complete. On Windows this is also the case when
+    ///   shrinking.
+    ///
+    /// A map from [`MapMut::growable()`] never moves. It grows by
+    /// committing more of its reservation as in [`.grow()`], and shrinks by
+    /// decommitting the pages past the new length, which keeps them
+    /// reserved. It cannot grow past its reservation, and an
+    /// [`Input::InvalidRange`] error is returned instead.
+    ///
+    /// Any [`.advise()`] or [`.lock()`] applied to the map covers only the
+    /// pages it had before growing. A file map, or a map from
+    /// [`MapMut::new_huge()`], returns an [`Input::Unsupported`] error; use
+    /// [`.remap()`] for a file map instead. Should the new allocation fail,
+    /// the map is left unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let page = vmap::allocation_size();
+    /// let mut map = MapMut::new(page)?;
+    /// map[..5].copy_from_slice(b"hello");
+    ///
+    /// map.resize(4 * page)?;
+    /// assert_eq!(4 * page, map.len());
+    /// assert_eq!(b"hello", &map[..5]);
+    /// assert_eq!(0, map[page]);
+    ///
+    /// map.resize(3)?;
+    /// assert_eq!(page, map.len());
+    /// assert_eq!(b"hello", &map[..5]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`MapMut::new()`]: #method.new
+    /// [`Options::copy()`]: struct.Options.html#method.copy
+    /// [`.advise()`]: #method.advise
+    /// [`.lock()`]: #method.lock
+    /// [`MapMut::growable()`]: #method.growable
+    /// [`MapMut::new_huge()`]: #method.new_huge
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    /// [`.remap()`]: #method.remap
+    /// [`.grow()`]: #method.grow
+    pub fn resize(&mut self, hint: usize) -> Result<()> {
+        self.untrack_writes()?;
+        let size = Size::alloc();
+        match self.backing {
+            Backing::Anon => {}
+            Backing::Reserved(reserved) => {
+                let len = size.round(hint);
+                if hint > reserved {
+                    return Err(Error::input(Operation::Commit, Input::InvalidRange));
+                } else if len >= self.len {
+                    return self.grow(len);
+                }
+                let page = Size::page();
+                let (new, old) = (page.round(len), page.round(self.len));
+                if old > new {
+                    unsafe { decommit_anon(self.ptr.add(new), old - new)? };
+                }
+                self.len = len;
+                return Ok(());
+            }
+            _ => return Err(Error::input(Operation::MapAnonymous, Input::Unsupported)),
+        }
+        if self.len == 0 {
+            let len = size.round(hint);
+            if len > 0 {
+                self.ptr = map_anon(len, self.prot)?;
+                self.len = len;
+                track_map(len);
+            }
+            return Ok(());
+        }
+        if hint == 0 {
+            return self.release();
+        }
+        let (pg, oldlen) = unsafe { size.bounds(self.ptr, self.len) };
+        let lead = self.ptr as usize - pg as usize;
+        let len = size.round(lead + hint);
+        let new = unsafe { remap_anon(pg, oldlen, len, self.prot)? };
+        track_unmap(oldlen);
+        track_map(len);
+        self.ptr = unsafe { new.add(lead) };
+        self.len = len - lead;
+        Ok(())
+    }
+
+    /// Creates or opens a file, sets its length, and maps all of it.
+    ///
+    /// The file is opened for reading and writing and is created if it does
+    /// not exist. Its length is then set to exactly `len` bytes, extending
+    /// it with zeros or truncating it as needed, and the whole file is
+    /// mapped writable. The file is returned as well so it may be used to
+    /// [`.flush()`] changes.
+    ///
+    /// This is equivalent to:
+    ///
+    /// ```
+    /// # use vmap::MapMut;
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// # let path = tmp.path().join("example");
+    /// # let len = 100;
+    /// MapMut::with_options().create(true).resize(len).open(&path)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Flush, MapMut};
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// let (mut map, file) = MapMut::create(&path, 100)?;
+    /// assert_eq!(100, map.len());
+    /// assert_eq!(100, file.metadata()?.len());
+    /// map[..4].copy_from_slice(b"test");
+    /// map.flush(&file, Flush::Sync)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.flush()`]: #method.flush
+    pub fn create<P: AsRef<Path>>(path: P, len: usize) -> Result<(Self, File)> {
+        Self::with_options().create(true).resize(len).open(path)
+    }
+
+    /// Creates a scratch map backed by an unnamed temporary file.
+    ///
+    /// The file is created in [`std::env::temp_dir()`], sized to at least
+    /// `hint` bytes rounded up to the allocation size as in
+    /// [`MapMut::new()`], and mapped shared for reading and writing. Unlike
+    /// anonymous memory, the pages of a file can be written out to disk and
+    /// dropped under memory pressure, even on a system without swap, which
+    /// suits large scratch buffers for sorting or image processing.
+    ///
+    /// The file has no name, or is removed as soon as it is created, so no
+    /// other process can open it and it is deleted once the map is dropped.
+    /// It is retained by the map and available from [`.file()`]. The
+    /// temporary directory is often a memory file system such as `tmpfs`,
+    /// so use [`MapMut::temp_in()`] to place the file on a disk instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let mut map = MapMut::temp(1 << 20)?;
+    /// assert!(map.is_file_backed());
+    /// assert!(map.len() >= 1 << 20);
+    /// map[..4].copy_from_slice(b"test");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`std::env::temp_dir()`]: https://doc.rust-lang.org/std/env/fn.temp_dir.html
+    /// [`MapMut::new()`]: #method.new
+    /// [`.file()`]: #method.file
+    /// [`MapMut::temp_in()`]: #method.temp_in
+    pub fn temp(hint: usize) -> Result<Self> {
+        Self::temp_in(std::env::temp_dir(), hint)
+    }
+
+    /// Creates a scratch map backed by an unnamed temporary file in `dir`.
+    ///
+    /// See [`MapMut::temp()`] for details.
+    ///
+    /// [`MapMut::temp()`]: #method.temp
+    pub fn temp_in<P: AsRef<Path>>(dir: P, hint: usize) -> Result<Self> {
+        let file = temp_file(dir.as_ref())?;
+        Self::with_options()
+            .resize(Size::alloc().round(hint))
+            .map_owned(file)
+    }
+
+    /// Creates a read-write map of a file range that may not exist yet.
+    ///
+    /// Unlike [`Options::map()`], the length of the file is not checked, so
+    /// the range may extend past the end of a file that is expected to grow
+    /// to cover it, such as one being written by another process. The file
+    /// is retained by the map, and [`.validate()`] checks whether the file
+    /// has since grown to cover the range. Accessing a page that lies
+    /// entirely beyond the end of the file raises `SIGBUS`, which can be
+    /// recovered with [`install_sigbus_handler()`].
+    ///
+    /// Windows cannot map a view beyond the end of a file, so there a range
+    /// that is not already covered yields an [`Input::Unsupported`] error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    /// use std::fs::OpenOptions;
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// let file = OpenOptions::new().read(true).write(true).create(true).open(&path)?;
+    /// # if cfg!(unix) {
+    /// let map = MapMut::file_deferred(file.try_clone()?, 0, 4096)?;
+    /// assert!(map.validate().is_err());
+    ///
+    /// file.set_len(4096)?;
+    /// map.validate()?;
+    /// assert_eq!(map[4095], 0);
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Options::map()`]: struct.Options.html#method.map
+    /// [`.validate()`]: #method.validate
+    /// [`install_sigbus_handler()`]: fn.install_sigbus_handler.html
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    pub fn file_deferred(file: File, off: usize, len: usize) -> Result<Self> {
+        let end = file_end(off, len)?;
+        if cfg!(windows) {
+            let flen = file.metadata().map_err(map_file_err)?.len();
+            if end > flen {
+                return Err(Error::input(Operation::MapFile, Input::Unsupported));
+            }
+        }
+        let (prot, flags) = (Protect::ReadWrite, MapFlags::NONE);
+        let mut map = if len == 0 {
+            unsafe { MapMut::try_from_file_ptr(empty_ptr(), 0, off as u64, prot, flags)? }
+        } else {
+            let (mapoff, maplen) = file_span(off, len);
+            let ptr = map_file_with(&file, mapoff, maplen, prot, flags)?;
+            let map = unsafe {
+                let pg = ptr.wrapping_add(off - mapoff);
+                let map = MapMut::try_from_file_ptr(pg, len, off as u64, prot, flags);
+                or_unmap(map, ptr, maplen)?
+            };
+            register_mapping(ptr, maplen);
+            track_map(Size::alloc().round(maplen));
+            map
+        };
+        map.set_file(file);
+        Ok(map)
+    }
+
+    /// Checks that the retained file still covers the whole map.
+    ///
+    /// This compares the current length of the file retained by the map,
+    /// as with [`MapMut::file_deferred()`] or [`Options::map_owned()`],
+    /// against the mapped range. An [`Input::InvalidRange`] error is
+    /// returned if the file ends before the last byte of the map, and an
+    /// [`Input::Unsupported`] error if the map does not retain its file.
+    ///
+    /// [`MapMut::file_deferred()`]: #method.file_deferred
+    /// [`Options::map_owned()`]: struct.Options.html#method.map_owned
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    pub fn validate(&self) -> Result<()> {
+        let (file, off) = match (&self.file, self.file_offset()) {
+            (Some(file), Some(off)) => (file, off),
+            _ => return Err(Error::input(Operation::MapFile, Input::Unsupported)),
+        };
+        let flen = file.metadata().map_err(map_file_err)?.len();
+        if off.saturating_add(self.len as u64) > flen {
+            Err(Error::input(Operation::MapFile, Input::InvalidRange).with_length(self.len))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Creates a read-only snapshot of the current contents of the map.
+    ///
+    /// The snapshot is a separate anonymous map that keeps the contents at
+    /// the time of the call, however the original is written afterward. The
+    /// start of the snapshot has the same offset within its page as the map.
+    ///
+    /// On macOS and iOS the pages are remapped copy-on-write, so taking the
+    /// snapshot copies nothing, and a page is only copied when the original
+    /// is next written there. Linux and Windows have no way to share the
+    /// pages of an existing mapping copy-on-write, so there the contents are
+    /// copied in full.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let mut map = MapMut::new(200)?;
+    /// map[..4].copy_from_slice(b"test");
+    /// let snap = map.snapshot()?;
+    /// map[..4].copy_from_slice(b"next");
+    /// assert_eq!(b"test", &snap[..4]);
+    /// assert_eq!(map.len(), snap.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn snapshot(&self) -> Result<Map> {
+        if self.len == 0 {
+            return Ok(Map::empty());
+        }
+        unsafe {
+            let (pg, len) = Size::page().bounds(self.ptr, self.len);
+            let dst = snapshot_pages(pg, len)?;
+            track_map(Size::alloc().round(len));
+            let ptr = dst.add(self.ptr as usize - pg as usize);
+            Ok(Map::from_ptr(ptr, self.len, Protect::ReadOnly))
+        }
+    }
+
+    /// Transfer ownership of the map into a read-only map.
+    ///
+    /// This changes the protection of the mapping to [`Protect::ReadOnly`],
+    /// so the pages can no longer be written through any pointer into the
+    /// map. Use [`Map::into_map_mut()`] to make it writable again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    /// use std::fs::OpenOptions;
+    /// use std::path::PathBuf;
+    /// use std::str::from_utf8;
+    /// # use std::fs;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// # fs::write(&path, b"this is a test")?;
+    /// let (mut map, file) = MapMut::with_options().len(14).open(&path)?;
+    /// assert_eq!(Ok("this is a test"), from_utf8(&map[..]));
+    ///
+    /// map[..4].clone_from_slice(b"that");
+    ///
+    /// let map = map.into_map()?;
+    /// assert_eq!(Ok("that is a test"), from_utf8(&map[..]));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Protect::ReadOnly`]: enum.Protect.html#variant.ReadOnly
+    /// [`Map::into_map_mut()`]: struct.Map.html#method.into_map_mut
+    pub fn into_map(self) -> ConvertResult<Map, Self> {
+        self.into_map_with(Protect::ReadOnly)
+    }
+
+    /// Changes the protection of the map while keeping it writable.
+    ///
+    /// This only accepts the writable protections [`Protect::ReadWrite`] and
+    /// [`Protect::ReadCopy`], as any other would fault on a write through the
+    /// mutable map. Other protections yield an [`Input::Unsupported`] error;
+    /// use [`Map::with_protection()`] or [`.make_exec()`] to give up write
+    /// access. As with the other conversions, the sharing of the mapping is
+    /// kept, so [`Protect::ReadCopy`] is rejected for a shared map and
+    /// [`Protect::ReadWrite`] becomes copy-on-write for a private one. The
+    /// original map is returned with any error.
+    ///
+    /// [`Protect::ReadWrite`]: enum.Protect.html#variant.ReadWrite
+    /// [`Protect::ReadCopy`]: enum.Protect.html#variant.ReadCopy
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    /// [`Map::with_protection()`]: struct.Map.html#method.with_protection
+    /// [`.make_exec()`]: #method.make_exec
+    pub fn with_protection(mut self, prot: Protect) -> ConvertResult<MapMut, Self> {
+        if !matches!(prot, Protect::ReadWrite | Protect::ReadCopy) {
+            let err = Error::input(Operation::Protect, Input::Unsupported).with_protect(prot);
+            return Err((err, self));
+        }
+        match self.set_protect(prot) {
+            Ok(()) => Ok(self),
+            Err(err) => Err((err, self)),
+        }
+    }
+
+    /// Transfer ownership of the map into an executable read-only map.
+    ///
+    /// This is the final step of generating code at runtime: the code is
+    /// written into a [`Protect::ReadWrite`] map, which is then switched to
+    /// [`Protect::ReadExec`] before it is run. The pages are never writable
+    /// and executable at the same time. The instruction cache is flushed for
+    /// the range before the switch, as required on ARM, so the code may be
+    /// called as soon as this returns. This may fail where the mapping was
+    /// not created to allow execution, such as an anonymous map on Windows,
+    /// and the original map is returned with the error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{MapMut, Protect};
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let mut map = MapMut::with_options().copy().len(4096).alloc()?;
+    /// map[0] = 0xc3; // ret
+    /// # if cfg!(unix) {
+    /// let code = map.make_exec()?;
+    /// assert_eq!(Protect::ReadExec, code.protection());
+    /// assert_eq!(0xc3, code[0]);
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Protect::ReadWrite`]: enum.Protect.html#variant.ReadWrite
+    /// [`Protect::ReadExec`]: enum.Protect.html#variant.ReadExec
+    pub fn make_exec(self) -> ConvertResult<Map, Self> {
+        self.into_map_with(Protect::ReadExec)
+    }
+
+    // Converts into a read-only map through `Map::with_protection`, so every
+    // conversion validates and applies the protection in one place.
+    fn into_map_with(self, prot: Protect) -> ConvertResult<Map, Self> {
+        Map(Arc::new(self))
+            .with_protection(prot)
+            .map_err(|(err, map)| match Arc::try_unwrap(map.0) {
+                Ok(map) => (err, map),
+                // The map was only just wrapped, so it has no other clones.
+                Err(_) => unreachable!(),
+            })
+    }
+
+    /// Transfer ownership of the map into a private copy-on-write map.
+    ///
+    /// This remaps the same range of `file` as a private mapping
+    /// (`MAP_PRIVATE` on Unix, `FILE_MAP_COPY` on Windows), so further
+    /// modifications are no longer written back to the file. The contents of
+    /// a shared map are the contents of the file's page cache, so every
+    /// modification made before the call remains visible in the new map.
+    /// Those earlier modifications are still written back to the file by
+    /// the system, even without a flush, and remain on disk.
+    ///
+    /// `file` must be the file that was mapped. An anonymous map yields an
+    /// [`Input::Unsupported`] error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Flush, MapMut};
+    /// use std::path::PathBuf;
+    /// # use std::fs;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// # fs::write(&path, b"this is a test")?;
+    /// let (mut map, file) = MapMut::with_options().open(&path)?;
+    /// map[..4].clone_from_slice(b"that");
+    /// map.flush(&file, Flush::Sync)?;
+    ///
+    /// let mut map = map.into_private(&file)?;
+    /// map[10..].clone_from_slice(b"game");
+    /// assert_eq!(b"that is a game", &map[..]);
+    /// assert_eq!(b"that is a test", &fs::read(&path)?[..]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    pub fn into_private(mut self, file: &File) -> ConvertResult<MapMut, Self> {
+        match self.remap_copy(file) {
+            Ok(Some(mut map)) => {
+                map.file = self.file.take();
+                Ok(map)
+            }
+            Ok(None) => Ok(self),
+            Err(err) => Err((err, self)),
+        }
+    }
+
+    /// Transfer the contents of the map into a new shared mapping of `file`.
+    ///
+    /// The file is resized to the length of the map, the whole file is
+    /// mapped for reading and writing, and the contents are copied into it.
+    /// This turns data built up in an anonymous map into a file-backed map
+    /// in one call. The bytes are always copied, as anonymous memory cannot
+    /// be moved into a file's page cache or reflinked. The copy is not
+    /// flushed, so call [`.flush()`] on the new map to make it durable.
+    ///
+    /// `file` must be opened for reading and writing. If the file cannot be
+    /// resized or mapped, the error is returned along with the original map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Flush, MapMut};
+    /// use std::fs::OpenOptions;
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// let mut map = MapMut::with_options().len(14).alloc()?;
+    /// map.copy_from_slice(b"this is a test");
+    ///
+    /// let file = OpenOptions::new().read(true).write(true).create(true).open(&path)?;
+    /// let map = map.persist_to(&file)?;
+    /// map.flush(&file, Flush::Sync)?;
+    /// assert!(map.is_file_backed());
+    /// assert_eq!(b"this is a test", &std::fs::read(&path)?[..]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.flush()`]: #method.flush
+    pub fn persist_to(self, file: &File) -> ConvertResult<MapMut, Self> {
+        let res = file
+            .set_len(self.len as u64)
+            .map_err(map_file_err)
+            .and_then(|_| {
+                MapMut::with_options()
+                    .len(Extent::Exact(self.len))
+                    .map(file)
+            });
+        match res {
+            Ok(mut map) => {
+                map.copy_from_slice(&self);
+                Ok(map)
+            }
+            Err(err) => Err((err, self)),
+        }
+    }
+
+    /// Transfer ownership of the map into one map per page.
+    ///
+    /// The pages are yielded in order, each as a map that unmaps only its
+    /// own page when dropped, so they may be handed to different owners and
+    /// released independently. Every map is one [`allocation_size()`] in
+    /// length, except that the last covers the remaining bytes. Pages that
+    /// are not taken from the iterator are unmapped when it is dropped.
+    ///
+    /// This requires an anonymous map that starts on an allocation boundary
+    /// and was not created by [`MapMut::growable()`] or
+    /// [`MapMut::new_huge()`]. Part of a mapping can
+    /// only be unmapped on Unix, so this yields an [`Input::Unsupported`]
+    /// error for any other map and on Windows.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let page = vmap::allocation_size();
+    /// let mut map = MapMut::new(3 * page)?;
+    /// map[page] = 1;
+    /// # if cfg!(unix) {
+    /// let pages: Vec<MapMut> = map.into_pages()?.collect();
+    /// assert_eq!(pages.len(), 3);
+    /// assert_eq!(pages[1][0], 1);
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`allocation_size()`]: fn.allocation_size.html
+    /// [`MapMut::growable()`]: #method.growable
+    /// [`MapMut::new_huge()`]: #method.new_huge
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    pub fn into_pages(mut self) -> ConvertResult<impl Iterator<Item = MapMut>, Self> {
+        if cfg!(windows)
+            || self.backing != Backing::Anon
+            || Size::alloc().offset(self.ptr as usize) != 0
+        {
+            return Err((Error::input(Operation::Unmap, Input::Unsupported), self));
+        }
+        if let Err(err) = self.untrack_writes() {
+            return Err((err, self));
+        }
+        let pages = IntoPages {
+            ptr: self.ptr,
+            len: self.len,
+            off: 0,
+            prot: self.prot,
+            flags: self.flags,
+        };
+        // Each page is now owned by the iterator, so empty the map to keep
+        // it from unmapping them as well.
+        self.ptr = empty_ptr();
+        self.len = 0;
+        Ok(pages)
+    }
+
+    /// Transfer ownership of the map into two maps split at byte `mid`.
+    ///
+    /// The first map holds the bytes `0..mid` and the second the bytes from
+    /// `mid` to the end. Each unmaps only its own pages when dropped, so the
+    /// halves may be moved to different threads and released in any order.
+    /// The address at `mid` must lie on an allocation boundary, so for a map
+    /// from [`MapMut::new()`], `mid` must be a multiple of the
+    /// [`allocation_size()`]. Otherwise an [`Input::Unaligned`] error is
+    /// returned, unless `mid` is zero or the length of the map, which leaves
+    /// one of the maps empty. A `mid` past the end of the map returns an
+    /// [`Input::InvalidRange`] error.
+    ///
+    /// As with [`.into_pages()`], this requires an anonymous map that was not
+    /// created by [`MapMut::growable()`] or [`MapMut::new_huge()`]. Windows
+    /// cannot unmap part of a view, so this yields an [`Input::Unsupported`]
+    /// error for any other map and on Windows. The original map is returned
+    /// with any error. To split the bytes as slices instead, use
+    /// `<[u8]>::split_at` through the dereferenced map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # if cfg!(unix) {
+    /// let page = vmap::allocation_size();
+    /// let map = MapMut::new(3 * page)?;
+    /// let (mut head, mut tail) = map.into_split(page)?;
+    /// assert_eq!((head.len(), tail.len()), (page, 2 * page));
+    ///
+    /// let t = std::thread::spawn(move || tail.fill(2));
+    /// head.fill(1);
+    /// t.join().unwrap();
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`MapMut::new()`]: #method.new
+    /// [`allocation_size()`]: fn.allocation_size.html
+    /// [`Input::Unaligned`]: enum.Input.html#variant.Unaligned
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    /// [`.into_pages()`]: #method.into_pages
+    /// [`MapMut::growable()`]: #method.growable
+    /// [`MapMut::new_huge()`]: #method.new_huge
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    pub fn into_split(mut self, mid: usize) -> ConvertResult<(MapMut, MapMut), Self> {
+        if cfg!(windows) || self.backing != Backing::Anon {
+            return Err((Error::input(Operation::Unmap, Input::Unsupported), self));
+        }
+        if mid > self.len {
+            return Err((Error::input(Operation::Unmap, Input::InvalidRange), self));
+        }
+        if mid > 0 && mid < self.len && Size::alloc().offset(self.ptr as usize + mid) != 0 {
+            return Err((Error::input(Operation::Unmap, Input::Unaligned), self));
+        }
+        if let Err(err) = self.untrack_writes() {
+            return Err((err, self));
+        }
+        let (prot, flags) = (self.prot, self.flags);
+        let part = |ptr: *mut u8, len: usize| {
+            let ptr = if len == 0 { empty_ptr() } else { ptr };
+            let mut map = unsafe { MapMut::from_ptr(ptr, len, prot) };
+            map.flags = flags;
+            map
+        };
+        let head = part(self.ptr, mid);
+        let tail = part(self.ptr.wrapping_add(mid), self.len - mid);
+        if !head.is_empty() && !tail.is_empty() {
+            // Both maps are released separately, but the bytes are unchanged.
+            track_map(0);
+        }
+        // The pages are now owned by the two maps, so empty the map to keep
+        // it from unmapping them as well.
+        self.ptr = empty_ptr();
+        self.len = 0;
+        Ok((head, tail))
+    }
+
+    /// Starts recording the pages written to the map.
+    ///
+    /// Every page of the map is made read-only, and a fault handler for the
+    /// process catches the first write to each page, records it, and makes
+    /// the page writable again so the write completes. Until tracking ends,
+    /// [`.dirty_pages()`] and [`.dirty_ranges()`] report the pages recorded
+    /// for this map, and [`.clear_dirty()`] forgets them and protects the
+    /// pages again. This works on every platform and tracks only this map,
+    /// unlike the soft-dirty bits used otherwise. Flushing just the dirty
+    /// ranges after each checkpoint avoids writing back an entire large map.
+    ///
+    /// Each page costs one fault per checkpoint. The handler is installed
+    /// for `SIGSEGV` and `SIGBUS` on Unix, passing any other fault to the
+    /// previous handler, and as a vectored exception handler on Windows.
+    /// At most 64 maps may be tracked at once, with an
+    /// [`Input::Unsupported`] error for any more, and the map must be
+    /// writable. Tracking ends with [`.untrack_writes()`], or when the map is
+    /// resized, remapped, split, or its protection is changed.
+    ///
+    /// Only writes made by the process fault. A system call that writes into
+    /// a read-only page fails instead, such as `read` returning `EFAULT`,
+    /// and nothing is recorded. Call [`.mark_written()`] on the range before
+    /// passing it to such a call.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let page = vmap::page_size();
+    /// let mut map = MapMut::new(4 * page)?;
+    /// map.track_writes()?;
+    /// map[page] = 1;
+    /// map[3 * page + 10] = 1;
+    /// assert_eq!(vec![1, 3], map.dirty_pages()?);
+    ///
+    /// map.clear_dirty()?;
+    /// assert!(map.dirty_pages()?.is_empty());
+    /// map.untrack_writes()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.dirty_pages()`]: #method.dirty_pages
+    /// [`.dirty_ranges()`]: #method.dirty_ranges
+    /// [`.clear_dirty()`]: #method.clear_dirty
+    /// [`.untrack_writes()`]: #method.untrack_writes
+    /// [`.mark_written()`]: #method.mark_written
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    pub fn track_writes(&mut self) -> Result<()> {
+        if self.watch().is_some() {
+            return Ok(());
+        }
+        if !matches!(self.prot, Protect::ReadWrite | Protect::ReadCopy) {
+            return Err(
+                Error::input(Operation::TrackWrites, Input::Unsupported).with_protect(self.prot)
+            );
+        }
+        let watch = unsafe {
+            let (pg, len) = Size::page().bounds(self.ptr, self.len);
+            WriteWatch::start(pg, len, self.prot)?
+        };
+        self.extra_mut().watch = Some(watch);
+        Ok(())
+    }
+
+    /// Records a range as written before it is written by the system.
+    ///
+    /// While writes are tracked with [`.track_writes()`], the pages holding
+    /// the range are recorded as dirty and made writable, so that a system
+    /// call such as `read` can then write into the range. This does nothing
+    /// if writes are not tracked. An [`Input::InvalidRange`] error is
+    /// returned if the range is not within the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Read;
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let page = vmap::page_size();
+    /// let mut map = MapMut::new(4 * page)?;
+    /// map.track_writes()?;
+    /// map.mark_written(page, 4)?;
+    /// std::fs::File::open("README.md")?.read_exact(&mut map[page..page + 4])?;
+    /// assert_eq!(vec![1], map.dirty_pages()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.track_writes()`]: #method.track_writes
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    pub fn mark_written(&self, off: usize, len: usize) -> Result<()> {
+        if off > self.len || len > self.len - off {
+            return Err(Error::input(Operation::TrackWrites, Input::InvalidRange));
+        }
+        match self.watch() {
+            Some(watch) => watch.mark(Size::page().offset(self.ptr as usize) + off, len),
+            None => Ok(()),
+        }
+    }
+
+    /// Stops recording the pages written to the map.
+    ///
+    /// The pages are made writable again, and [`.dirty_pages()`] returns to
+    /// the soft-dirty bits. This does nothing if writes are not tracked.
+    ///
+    /// [`.dirty_pages()`]: #method.dirty_pages
+    pub fn untrack_writes(&mut self) -> Result<()> {
+        match self.extra.as_mut().and_then(|extra| extra.watch.take()) {
+            Some(watch) => watch.stop(),
+            None => Ok(()),
+        }
+    }
+
+    /// Tests if the pages written to the map are being recorded.
+    #[inline]
+    pub fn is_tracking_writes(&self) -> bool {
+        self.watch().is_some()
+    }
+
+    /// Clears the dirty pages reported by [`.dirty_pages()`].
+    ///
+    /// When writes are tracked with [`.track_writes()`], this forgets the
+    /// pages recorded for this map and makes them read-only again.
+    /// Otherwise, this clears the soft-dirty bits using
+    /// `/proc/self/clear_refs`, which clears the bits of every page in the
+    /// process rather than only the pages of this map. Any other user of
+    /// soft-dirty tracking in the process is reset as well. This is only
+    /// supported on Linux kernels built with `CONFIG_MEM_SOFT_DIRTY`, and
+    /// other platforms yield an [`Input::Unsupported`] error.
+    ///
+    /// [`.dirty_pages()`]: #method.dirty_pages
+    /// [`.track_writes()`]: #method.track_writes
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    pub fn clear_dirty(&self) -> Result<()> {
+        match self.watch() {
+            Some(watch) => watch.clear(),
+            None => clear_soft_dirty(),
+        }
+    }
+
+    /// Gets the indices of the pages written since the last [`.clear_dirty()`].
+    ///
+    /// Page `0` is the page containing the first byte of the map. When
+    /// writes are tracked with [`.track_writes()`], these are the pages
+    /// recorded for this map on any platform. Otherwise, this reads the
+    /// soft-dirty bits from `/proc/self/pagemap` and is only supported on
+    /// Linux. Hardened kernels may restrict access to `pagemap`, in which
+    /// case an error is returned. The kernel may also report pages as dirty
+    /// that were not written, such as after the pages were moved, but never
+    /// misses a written page.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let page = vmap::page_size();
+    /// let mut map = MapMut::new(4 * page)?;
+    /// map.fill(1);
+    /// map.clear_dirty()?;
+    /// map[2 * page] = 2;
+    /// assert!(map.dirty_pages()?.contains(&2));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.clear_dirty()`]: #method.clear_dirty
+    /// [`.track_writes()`]: #method.track_writes
+    pub fn dirty_pages(&self) -> Result<Vec<usize>> {
+        match self.watch() {
+            Some(watch) => Ok(watch.dirty_pages()),
+            None => soft_dirty_pages(self.ptr, self.len),
+        }
+    }
+
+    /// Gets the byte ranges written since the last [`.clear_dirty()`].
+    ///
+    /// This reads the same soft-dirty bits as [`.dirty_pages()`] and joins
+    /// consecutive dirty pages into a single range. The ranges are sorted,
+    /// relative to the start of the map, and clipped to its length, so each
+    /// one can be copied out of the map directly. This makes it possible to
+    /// ship only the changed extents of the map to a replica, and then call
+    /// [`.clear_dirty()`] to start tracking from the new baseline. As with
+    /// [`.dirty_pages()`], this is only supported on Linux unless writes are
+    /// tracked, and other platforms yield an [`Input::Unsupported`] error.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let page = vmap::page_size();
+    /// let mut map = MapMut::new(4 * page)?;
+    /// map.fill(1);
+    /// map.clear_dirty()?;
+    /// map[page] = 2;
+    /// map[2 * page] = 2;
+    /// assert_eq!(vec![page..3 * page], map.dirty_ranges()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.clear_dirty()`]: #method.clear_dirty
+    /// [`.dirty_pages()`]: #method.dirty_pages
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    pub fn dirty_ranges(&self) -> Result<Vec<Range<usize>>> {
+        let page = Size::page().size(1);
+        let lead = self.ptr as usize % page;
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        for idx in self.dirty_pages()? {
+            let start = (idx * page).saturating_sub(lead);
+            let end = self.len.min((idx + 1) * page - lead);
+            match ranges.last_mut() {
+                Some(last) if last.end == start => last.end = end,
+                _ => ranges.push(start..end),
+            }
+        }
+        Ok(ranges)
+    }
+
+    /// Gets the current protection of the pages of the map.
+    ///
+    /// This is the protection the map was created with, as updated by any
+    /// later conversion. It is tracked by the map, so no system call is
+    /// made. Changes made directly through [`os::protect()`] are not seen.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{MapMut, Protect};
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let map = MapMut::new(1)?;
+    /// assert_eq!(map.protection(), Protect::ReadWrite);
+    ///
+    /// let map = MapMut::with_options().copy().alloc()?;
+    /// assert_eq!(map.protection(), Protect::ReadCopy);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`os::protect()`]: os/fn.protect.html
+    #[inline]
+    pub fn protection(&self) -> Protect {
+        self.prot
+    }
+
+    /// Changes the protection of a range of pages within the map.
+    ///
+    /// The range starts at byte `off`, whose address must be at a page
+    /// boundary, otherwise an [`Input::Unaligned`] error is returned. The
+    /// length is rounded up to a whole number of pages, and the range must be
+    /// within the map, otherwise an [`Input::InvalidRange`] error is returned.
+    /// The protection is validated against the sharing of the mapping as in
+    /// [`.with_protection()`], and the instruction cache is flushed for a
+    /// range made [`Protect::ReadExec`] as in [`.make_exec()`].
+    ///
+    /// The rest of the map keeps its protection, and [`.protection()`]
+    /// continues to report the protection of the whole map. A
+    /// [`MapRegion`] tracks the protection of each page and checks writes
+    /// against it, without any unsafe code.
+    ///
+    /// # Safety
+    ///
+    /// A range that is not writable faults on any write to it, including
+    /// writes through the safe `&mut [u8]` of this map. The caller must not
+    /// write to the range until it is made writable again, and must restore
+    /// the protection of the map with this method before converting the map
+    /// or changing its protection as a whole.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{MapMut, Protect};
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let page = vmap::page_size();
+    /// let mut map = MapMut::new(2 * page)?;
+    /// map[0] = 1;
+    /// unsafe { map.protect_range(0, page, Protect::ReadOnly)? };
+    /// map[page] = 2; // map[0] = 2 would fault
+    /// unsafe { map.protect_range(0, page, Protect::ReadWrite)? };
+    /// map[0] = 2;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Input::Unaligned`]: enum.Input.html#variant.Unaligned
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    /// [`.with_protection()`]: #method.with_protection
+    /// [`Protect::ReadExec`]: enum.Protect.html#variant.ReadExec
+    /// [`.make_exec()`]: #method.make_exec
+    /// [`.protection()`]: #method.protection
+    /// [`MapRegion`]: struct.MapRegion.html
+    pub unsafe fn protect_range(&mut self, off: usize, len: usize, prot: Protect) -> Result<()> {
+        if off > self.len || len > self.len - off {
+            return Err(Error::input(Operation::Protect, Input::InvalidRange));
+        }
+        let page = Size::page();
+        if page.offset(self.ptr as usize + off) != 0 {
+            return Err(Error::input(Operation::Protect, Input::Unaligned));
+        }
+        let prot = self.check_protect(prot)?;
+        if len == 0 {
+            return Ok(());
+        }
+        self.untrack_writes()?;
+        let (ptr, len) = (self.ptr.add(off), page.round(len));
+        if prot == Protect::ReadExec {
+            flush_icache(ptr, len)?;
+        }
+        protect(ptr, len, prot)
+    }
+
+    /// Tests if modifications to the map are private to the process.
+    ///
+    /// This is true for a map created with [`Options::copy()`] or with
+    /// [`Sharing::Private`], whose writes are copy-on-write and never reach
+    /// the underlying file or other processes. The sharing mode is recorded
+    /// when the map is created and is kept across later conversions, such as
+    /// [`.into_map()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// assert!(!MapMut::new(1)?.is_private());
+    /// assert!(MapMut::with_options().copy().len(1).alloc()?.is_private());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Options::copy()`]: struct.Options.html#method.copy
+    /// [`Sharing::Private`]: enum.Sharing.html#variant.Private
+    /// [`.into_map()`]: #method.into_map
+    #[inline]
+    pub fn is_private(&self) -> bool {
+        self.flags.contains(MapFlags::PRIVATE)
+    }
+
+    /// Gets the number of bytes mapped from the start of the map.
+    ///
+    /// The [`.len()`] of a map is its logical length: the length of the file
+    /// range that was requested, or for an anonymous map the length it was
+    /// created with, which [`MapMut::new()`] rounds up to the allocation
+    /// size. The system maps whole pages, so the capacity extends the length
+    /// to the end of the last page, which is a huge page for a map backed by
+    /// huge pages. Those extra bytes may be accessed through the pointer, but
+    /// for a file map they lie beyond the requested range and, past the end of
+    /// the file, are zero and never written back.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// # let path = tmp.path().join("example");
+    /// let (map, file) = MapMut::create(&path, 200)?;
+    /// assert_eq!(map.len(), 200);
+    /// assert_eq!(map.capacity(), vmap::page_size());
+    ///
+    /// let map = MapMut::with_options().len(200).alloc()?;
+    /// assert_eq!(map.len(), 200);
+    /// assert_eq!(map.capacity(), vmap::page_size());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.len()`]: #method.len
+    /// [`MapMut::new()`]: #method.new
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        let size = match self.backing {
+            // The huge page size is a power of two, as recorded on creation.
+            Backing::Huge(huge) => unsafe { Size::with_size(huge) },
+            _ => Size::page(),
+        };
+        let (pg, len) = unsafe { size.bounds(self.ptr, self.len) };
+        len - (self.ptr as usize - pg as usize)
+    }
+
+    /// Gets the length of the map in bytes.
+    ///
+    /// This is the same as the length of the slice the map dereferences to.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let (map, _) = MapMut::with_options().offset(10).len(20).open("README.md")?;
+    /// assert_eq!(20, map.len());
+    /// assert!(!map.is_empty());
+    /// assert!(MapMut::new(0)?.is_empty());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Tests if the map has a length of zero.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Gets the offset of the first byte within its page.
+    ///
+    /// A map of a file range starts at the requested offset, while the system
+    /// maps from the page boundary below it. This is the number of bytes of
+    /// that leading page that precede the map, which is always zero for an
+    /// anonymous map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let (map, _) = MapMut::with_options().offset(10).len(20).open("README.md")?;
+    /// assert_eq!(10, map.offset());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn offset(&self) -> usize {
+        Size::page().offset(self.ptr as usize)
+    }
+
+    /// Gets the page-aligned address and length of the pages of the map.
+    ///
+    /// This is the range computed by [`Size::bounds()`] for the page size,
+    /// covering every page that contains a byte of the map. It can be passed
+    /// to the functions in [`os`], such as to change the protection of the
+    /// pages. An empty map yields a length of zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let (map, _) = MapMut::with_options().offset(10).len(20).open("README.md")?;
+    /// let (pg, len) = map.aligned_bounds();
+    /// assert_eq!(pg, map.as_ptr().wrapping_sub(10));
+    /// assert_eq!(len, vmap::page_size());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Size::bounds()`]: struct.Size.html#method.bounds
+    /// [`os`]: os/index.html
+    #[inline]
+    pub fn aligned_bounds(&self) -> (*const u8, usize) {
+        let (pg, len) = unsafe { Size::page().bounds(self.ptr, self.len) };
+        (pg as *const u8, len)
+    }
+
+    /// Iterates over the bytes of the map one page at a time.
+    ///
+    /// Each slice holds the bytes of the map within one page, so every slice
+    /// is [`page_size()`] long and starts on a page boundary, except that
+    /// the first is shorter when the map starts within a page, such as a
+    /// file map at an unaligned offset, and the last is shorter when the map
+    /// ends within a page. The slices are in order and together cover the
+    /// whole map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let page = vmap::page_size();
+    /// let mut map = MapMut::new(3 * page)?;
+    /// map[page] = 1;
+    /// let dirty: Vec<usize> = map
+    ///     .pages()
+    ///     .enumerate()
+    ///     .filter(|(_, pg)| pg.iter().any(|&b| b != 0))
+    ///     .map(|(i, _)| i)
+    ///     .collect();
+    /// assert_eq!(dirty, [1]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`page_size()`]: fn.page_size.html
+    #[inline]
+    pub fn pages(&self) -> Pages<'_> {
+        let (head, rest) = split_page_head(self.deref());
+        Pages {
+            head,
+            rest: rest.chunks(Size::page().size(1)),
+        }
+    }
+
+    /// Iterates over the mutable bytes of the map one page at a time.
+    ///
+    /// The slices are split as in [`.pages()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let mut map = MapMut::new(2 * vmap::page_size())?;
+    /// for (i, pg) in map.pages_mut().enumerate() {
+    ///     pg[0] = i as u8 + 1;
+    /// }
+    /// assert_eq!(map[vmap::page_size()], 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.pages()`]: #method.pages
+    #[inline]
+    pub fn pages_mut(&mut self) -> PagesMut<'_> {
+        let page = Size::page().size(1);
+        let lead = self.offset();
+        let bytes = self.deref_mut();
+        let (head, rest) = if lead == 0 || bytes.is_empty() {
+            (None, bytes)
+        } else {
+            let n = cmp::min(bytes.len(), page - lead);
+            let (head, rest) = bytes.split_at_mut(n);
+            (Some(head), rest)
+        };
+        PagesMut {
+            head,
+            rest: rest.chunks_mut(page),
+        }
+    }
+
+    /// Tests if the bytes between [`.len()`] and [`.capacity()`] start out zero.
+    ///
+    /// The system zero-fills every anonymous page, so the tail of a map from
+    /// [`MapMut::new()`], [`.alloc()`], [`MapMut::growable()`] or any other
+    /// anonymous constructor is zero until it is written through the pointer.
+    /// The tail of a file map instead holds whatever the file has after the
+    /// requested range. Only the part of the last page beyond the end of the
+    /// file reads as zero, and writes to it are never stored. Pages wholly past
+    /// the end of the file are outside the capacity and raise `SIGBUS` when
+    /// accessed. This returns `false` for any file-backed map, as the tail
+    /// cannot be relied upon to be zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let map = MapMut::with_options().len(200).alloc()?;
+    /// assert!(map.is_tail_zeroed());
+    ///
+    /// let (map, _) = MapMut::with_options().len(200).open("README.md")?;
+    /// assert!(!map.is_tail_zeroed());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.len()`]: #method.len
+    /// [`.capacity()`]: #method.capacity
+    /// [`MapMut::new()`]: #method.new
+    /// [`.alloc()`]: struct.Options.html#method.alloc
+    /// [`MapMut::growable()`]: #method.growable
+    #[inline]
+    pub fn is_tail_zeroed(&self) -> bool {
+        !self.is_file_backed()
+    }
+
+    /// Gets the number of bytes at the end of the capacity that lie past the
+    /// end of the file.
+    ///
+    /// A file map may end anywhere within the file, but the system maps
+    /// whole pages, so when the file length is not a multiple of the page
+    /// size the last page of the map spills past the end of the file. Those
+    /// bytes between the end of the file and the [`.capacity()`] read as zero,
+    /// and writes to them never reach the file, even when flushed. This
+    /// reads the current length of `file`, which must be the file the map was
+    /// created from, and returns zero when every byte of the capacity is
+    /// backed by the file. Anonymous maps are not backed by any file and
+    /// always return zero.
+    ///
+    /// The bytes of the map itself, up to [`.len()`], always lie within the
+    /// file when it is mapped, so this only reports on the tail.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// # let path = tmp.path().join("example");
+    /// let (map, file) = MapMut::create(&path, 200)?;
+    /// assert_eq!(map.tail_past_eof(&file)?, vmap::page_size() - 200);
+    ///
+    /// file.set_len(vmap::page_size() as u64)?;
+    /// assert_eq!(map.tail_past_eof(&file)?, 0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.capacity()`]: #method.capacity
+    /// [`.len()`]: #method.len
+    pub fn tail_past_eof(&self, file: &File) -> Result<usize> {
+        let off = match self.file_offset() {
+            Some(off) => off,
+            None => return Ok(0),
+        };
+        let flen = file.metadata().map_err(map_file_err)?.len();
+        let end = cmp::max(flen, off + self.len as u64);
+        Ok((off + self.capacity() as u64).saturating_sub(end) as usize)
+    }
+
+    /// Tests if the map was created from a file rather than anonymously.
+    ///
+    /// Only a file-backed map has modifications to write back, so the flush
+    /// methods return an [`Input::Unsupported`] error for any other map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Flush, MapMut};
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let (map, file) = MapMut::with_options().open("README.md")?;
+    /// assert!(map.is_file_backed());
+    ///
+    /// let anon = MapMut::new(100)?;
+    /// assert!(!anon.is_file_backed());
+    /// assert!(anon.flush(&file, Flush::Sync).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    #[inline]
+    pub fn is_file_backed(&self) -> bool {
+        matches!(self.backing, Backing::File(_))
+    }
+
+    /// Changes the range of the file covered by the map.
+    ///
+    /// The map is updated to cover `len` bytes of `file` starting at `off`,
+    /// keeping the same protection and sharing. This is intended for a
+    /// window that slides over a large file. On Unix, the new range reuses
+    /// the address of the previous mapping whenever it fits within the
+    /// previous pages, and on Linux the previous mapping is grown in place
+    /// when the adjacent address space is free. Otherwise the map is moved
+    /// to a new address. Any unflushed changes to a private map are lost.
+    ///
+    /// The range must lie within the file, and `file` must be the file that
+    /// was mapped. If an error is returned, the map is unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::Map;
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// # std::fs::write(&path, "A cross-platform library for fast and safe memory-mapped IO in Rust")?;
+    /// let (mut map, file) = Map::with_options().len(16).open(&path)?;
+    /// assert_eq!(b"A cross-platform", &map[..]);
+    ///
+    /// map.remap(&file, 29, 30)?;
+    /// assert_eq!(b"fast and safe memory-mapped IO", &map[..]);
+    /// assert!(map.remap(&file, 60, 30).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn remap(&mut self, file: &File, off: usize, len: usize) -> Result<()> {
+        if self.file_offset().is_none() {
+            return Err(Error::input(Operation::MapFile, Input::Unsupported));
+        }
+        let end = file_end(off, len)?;
+        self.untrack_writes()?;
+        let flen = file.metadata().map_err(map_file_err)?.len();
+        if end > flen {
+            return Err(Error::input(Operation::MapFile, Input::InvalidRange));
+        }
+
+        let (mapoff, maplen) = file_span(off, len);
+        let (oldpg, oldlen) = unsafe { Size::alloc().bounds(self.ptr, self.len) };
+        let pg = match (self.len, len) {
+            (0, 0) => empty_ptr(),
+            (0, _) => map_file_with(file, mapoff, maplen, self.prot, self.flags)?,
+            (_, 0) => unsafe {
+                unmap(oldpg, oldlen).unwrap_or_default();
+                empty_ptr()
+            },
+            _ => unsafe { remap_file(oldpg, oldlen, file, mapoff, maplen, self.prot, self.flags)? },
+        };
+        if self.len > 0 {
+            unregister_mapping(oldpg);
+            track_unmap(oldlen);
+        }
+        if len > 0 {
+            register_mapping(pg, maplen);
+            track_map(Size::alloc().round(maplen));
+        }
+
+        self.ptr = unsafe { pg.add(off - mapoff) };
+        self.len = len;
+        self.backing = Backing::File(off as u64);
+        Ok(())
+    }
+
+    // Maps the same file range as a new private mapping. Empty maps are
+    // never mapped, so `None` is returned to keep using the original.
+    fn remap_copy(&self, file: &File) -> Result<Option<MapMut>> {
+        if self.len == 0 {
+            return Ok(None);
+        }
+        let off = match self.file_offset() {
+            Some(off) => off as usize,
+            None => return Err(Error::input(Operation::MapFile, Input::Unsupported)),
+        };
+        let (mapoff, maplen) = file_span(off, self.len);
+        let ptr = map_file_with(file, mapoff, maplen, Protect::ReadCopy, MapFlags::NONE)?;
+        let map = unsafe {
+            let (pg, off, prot) = (
+                ptr.wrapping_add(off - mapoff),
+                off as u64,
+                Protect::ReadCopy,
+            );
+            let map = MapMut::try_from_file_ptr(pg, self.len, off, prot, MapFlags::NONE);
+            or_unmap(map, ptr, maplen)?
+        };
+        register_mapping(ptr, maplen);
+        track_map(Size::alloc().round(maplen));
+        Ok(Some(map))
+    }
+
+    // Unmaps the pages and leaves the map empty, so that a later call, such
+    // as from `Drop` after `close`, does nothing. The map is emptied before
+    // unmapping so a failed unmap is never retried on a range that may since
+    // belong to another allocation.
+    fn release(&mut self) -> Result<()> {
+        let (ptr, len, backing) = (self.ptr, self.len, self.backing);
+        // The pages are unmapped, so there is no protection to restore.
+        if let Some(ref mut extra) = self.extra {
+            extra.watch = None;
+        }
+        self.ptr = empty_ptr();
+        self.len = 0;
+        self.backing = Backing::Anon;
+        if ptr.is_null() {
+            return Ok(());
+        }
+        unsafe {
+            if let Backing::Reserved(reserved) = backing {
+                track_unmap(reserved);
+                return release_anon(ptr, reserved);
+            }
+            if let Backing::Guarded(lead) = backing {
+                let pg = ptr.sub(lead);
+                let len = Size::alloc().round(2 * lead + Size::page().round(len));
+                track_unmap(len);
+                return unmap(pg, len);
+            }
+            if let Backing::Huge(huge) = backing {
+                // Huge pages can only be unmapped as whole huge pages.
+                let (pg, len) = Size::with_size(huge).bounds(ptr, len);
+                track_unmap(len);
+                return unmap(pg, len);
+            }
+            if len == 0 {
+                return Ok(());
+            }
+            let mut res = Ok(());
+            if let (Some(mode), Some(file)) = (self.flush_on_drop, &self.file) {
+                let (pg, len) = Size::page().bounds(ptr, len);
+                res = flush(pg, file, len, mode);
+            }
+            let (pg, len) = Size::alloc().bounds(ptr, len);
+            unregister_mapping(pg);
+            track_unmap(len);
+            res.and(unmap(pg, len))
+        }
+    }
+
+    // Gets the protection to request from the system in place of `prot`. The
+    // sharing of a mapping is fixed when it is created: writes to a private
+    // map must stay private, so read-write is requested as copy-on-write,
+    // which Windows requires for a copy view. A shared map cannot become
+    // copy-on-write without being remapped.
+    fn check_protect(&self, prot: Protect) -> Result<Protect> {
+        match (prot, self.is_private()) {
+            (Protect::ReadWrite, true) => Ok(Protect::ReadCopy),
+            (Protect::ReadCopy, false) => {
+                Err(Error::input(Operation::Protect, Input::Unsupported).with_protect(prot))
+            }
+            _ => Ok(prot),
+        }
+    }
+
+    // Gets the write tracking started by `track_writes`, if any.
+    #[inline]
+    fn watch(&self) -> Option<&WriteWatch> {
+        self.extra.as_ref()?.watch.as_ref()
+    }
+
+    // Gets the interval of a periodic sync policy and the last flush time.
+    #[inline]
+    fn sync_every(&self) -> Option<(Duration, Instant)> {
+        self.extra.as_ref()?.sync_every
+    }
+
+    // Gets the boxed state of the map, allocating it on first use.
+    fn extra_mut(&mut self) -> &mut Extra {
+        self.extra.get_or_insert_with(Default::default)
+    }
+
+    // Gets the file offset of the first byte, or `None` when not a file map.
+    #[inline]
+    fn file_offset(&self) -> Option<u64> {
+        match self.backing {
+            Backing::File(off) => Some(off),
+            _ => None,
+        }
+    }
+
+    // Changes the protection of the pages unless they already have it. The
+    // request is validated first, so a transition the mapping cannot make
+    // is reported as unsupported rather than as an obscure system error.
+    pub(crate) fn set_protect(&mut self, prot: Protect) -> Result<()> {
+        let prot = self.check_protect(prot)?;
+        self.untrack_writes()?;
+        if self.len == 0 || self.prot == prot {
+            return Ok(());
+        }
+        unsafe {
+            let (ptr, len) = Size::page().bounds(self.ptr, self.len);
+            if prot == Protect::ReadExec {
+                // Code written through the map must reach the instruction
+                // cache before it can be run.
+                flush_icache(ptr, len)?;
+            }
+            protect(ptr, len, prot)?;
+        }
+        #[cfg(test)]
+        PROTECT_CALLS.with(|n| n.set(n.get() + 1));
+        self.prot = prot;
+        Ok(())
+    }
+
+    /// Writes modifications back to the filesystem.
+    ///
+    /// Flushes will happen automatically, but this will invoke a flush and
+    /// return any errors with doing so.
+    ///
+    /// The map records its exact length rather than the page-rounded length,
+    /// so only the pages containing mapped bytes are flushed. For a file that
+    /// ends partway through a page, this includes the final partial page but
+    /// never a whole page beyond the end of the file.
+    ///
+    /// An anonymous map has no file to write to, so this returns an
+    /// [`Input::Unsupported`] error rather than flushing.
+    ///
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    pub fn flush(&self, file: &File, mode: Flush) -> Result<()> {
+        if !self.is_file_backed() {
+            return Err(Error::input(Operation::Flush, Input::Unsupported));
+        }
+        unsafe {
+            let (ptr, len) = Size::page().bounds(self.ptr, self.len);
+            flush(ptr, file, len, mode)
+        }
+    }
+
+    /// Writes modifications and the file metadata durably to storage.
+    ///
+    /// This is a [`.flush()`] with [`Flush::Sync`] followed by
+    /// `File::sync_all`. The flush alone waits until the dirty pages of the
+    /// map have been written to the file, using `msync(MS_SYNC)` on Unix and
+    /// `FlushViewOfFile` with `FlushFileBuffers` on Windows. On Unix it makes
+    /// no promise about the file's metadata, so after the file has grown the
+    /// new length may be lost in a crash, and some systems only hand the
+    /// pages to the drive without flushing its cache. The `sync_all` call
+    /// adds an `fsync` of the file, which makes its data and metadata,
+    /// including the length, durable. On macOS this uses `F_FULLFSYNC`, so
+    /// the drive's cache is flushed as well.
+    ///
+    /// Neither covers the directory entry of a newly created or renamed
+    /// file. For that to survive a crash on Unix, also sync the parent
+    /// directory once, by opening it and calling `File::sync_all` on it.
+    ///
+    /// An anonymous map returns an [`Input::Unsupported`] error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    /// use std::fs::File;
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// let (mut map, file) = MapMut::with_options().create(true).resize(100).open(&path)?;
+    /// map[..4].clone_from_slice(b"test");
+    /// map.sync_all(&file)?;
+    /// # #[cfg(unix)]
+    /// File::open(path.parent().unwrap())?.sync_all()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.flush()`]: #method.flush
+    /// [`Flush::Sync`]: enum.Flush.html#variant.Sync
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    pub fn sync_all(&self, file: &File) -> Result<()> {
+        self.flush(file, Flush::Sync)?;
+        file.sync_all().map_err(|e| Error::io(Operation::Flush, e))
+    }
+
+    /// Writes modifications back to the filesystem for a sub-range of the map.
+    ///
+    /// Flushes will happen automatically, but this will invoke a flush and
+    /// return any errors with doing so. The byte range `off..off + len` is
+    /// relative to the start of the map and is widened to the pages that
+    /// contain it, so only those pages are written rather than the whole
+    /// map. A range that does not lie within the map returns an
+    /// [`Input::InvalidRange`] error, whose kind is
+    /// [`io::ErrorKind::InvalidInput`]. As with [`.flush()`], this returns an
+    /// [`Input::Unsupported`] error for an anonymous map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Flush, MapMut};
+    /// use std::io::ErrorKind;
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// let (mut map, file) = MapMut::with_options().create(true).resize(10000).open(&path)?;
+    /// map[5000..5004].clone_from_slice(b"test");
+    /// map.flush_range(&file, 5000, 4, Flush::Sync)?;
+    ///
+    /// let err = map.flush_range(&file, 9999, 2, Flush::Sync).unwrap_err();
+    /// assert_eq!(ErrorKind::InvalidInput, err.kind());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.flush()`]: #method.flush
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    /// [`io::ErrorKind::InvalidInput`]: https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.InvalidInput
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    pub fn flush_range(&self, file: &File, off: usize, len: usize, mode: Flush) -> Result<()> {
+        if !self.is_file_backed() {
+            Err(Error::input(Operation::Flush, Input::Unsupported))
+        } else if off > self.len || len > self.len - off {
+            Err(Error::input(Operation::Flush, Input::InvalidRange))
+        } else {
+            unsafe {
+                let (ptr, len) = Size::page().bounds(self.ptr.add(off), len);
+                flush(ptr, file, len, mode)
+            }
+        }
+    }
+
+    /// Writes modifications back to the file retained by the map.
+    ///
+    /// This is a [`.flush()`] of a map created with
+    /// [`Options::map_owned()`], so the file need not be passed again. A map
+    /// that does not retain its file returns an [`Input::Unsupported`] error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Flush, MapMut};
+    /// use std::fs::OpenOptions;
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// # std::fs::write(&path, b"this is a test")?;
+    /// let f = OpenOptions::new().read(true).write(true).open(&path)?;
+    /// let mut map = MapMut::with_options().map_owned(f)?;
+    /// map[..4].clone_from_slice(b"that");
+    /// map.flush_owned_range(0, 4, Flush::Async)?;
+    /// map.flush_owned(Flush::Sync)?;
+    /// assert_eq!(b"that is a test", &std::fs::read(&path)?[..]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.flush()`]: #method.flush
+    /// [`Options::map_owned()`]: struct.Options.html#method.map_owned
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    pub fn flush_owned(&self, mode: Flush) -> Result<()> {
+        match self.file {
+            Some(ref file) => self.flush(file, mode),
+            None => Err(Error::input(Operation::Flush, Input::Unsupported)),
+        }
+    }
+
+    /// Writes modifications back to the file retained by the map for a
+    /// sub-range of the map.
+    ///
+    /// This is a [`.flush_range()`] with the file retained by
+    /// [`Options::map_owned()`]. See [`.flush_owned()`] for details.
+    ///
+    /// [`.flush_range()`]: #method.flush_range
+    /// [`Options::map_owned()`]: struct.Options.html#method.map_owned
+    /// [`.flush_owned()`]: #method.flush_owned
+    pub fn flush_owned_range(&self, off: usize, len: usize, mode: Flush) -> Result<()> {
+        match self.file {
+            Some(ref file) => self.flush_range(file, off, len, mode),
+            None => Err(Error::input(Operation::Flush, Input::Unsupported)),
+        }
+    }
+
+    /// Writes a sub-range of the map back to the file and evicts its pages.
+    ///
+    /// The range is flushed as with [`.flush_range()`], and once that
+    /// succeeds the pages are released with [`Advise::WillNotNeed`]. This
+    /// keeps the resident memory flat while writing a large file in order:
+    /// each completed chunk is flushed and forgotten, and is read back from
+    /// the file if accessed again. Use [`Flush::Sync`] to wait for the data
+    /// to be written before the pages are released.
+    ///
+    /// Releasing the pages is only safe once their contents are in the file,
+    /// so this requires a shared file-backed map. A private or anonymous map
+    /// yields an [`Input::Unsupported`] error, as its modified pages would be
+    /// discarded. On Windows, the pages are flushed but not evicted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Flush, MapMut};
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// let chunk = 4 * vmap::page_size();
+    /// let (mut map, file) = MapMut::create(&path, 4 * chunk)?;
+    /// for i in 0..4 {
+    ///     let range = i * chunk..(i + 1) * chunk;
+    ///     map[range.clone()].fill(i as u8);
+    ///     map.flush_evict(&file, range, Flush::Sync)?;
+    /// }
+    /// assert_eq!(map[3 * chunk], 3);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.flush_range()`]: #method.flush_range
+    /// [`Advise::WillNotNeed`]: enum.Advise.html#variant.WillNotNeed
+    /// [`Flush::Sync`]: enum.Flush.html#variant.Sync
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    pub fn flush_evict(&self, file: &File, range: Range<usize>, mode: Flush) -> Result<()> {
+        if !self.is_file_backed() || self.is_private() {
+            return Err(Error::input(Operation::Flush, Input::Unsupported));
+        }
+        if range.start > range.end {
+            return Err(Error::input(Operation::Flush, Input::InvalidRange));
+        }
+        let len = range.end - range.start;
+        self.flush_range(file, range.start, len, mode)?;
+        self.advise_range(range.start, len, Advise::WillNotNeed)
+    }
+
+    /// Writes modifications in a sub-range back to the filesystem without
+    /// blocking the async executor.
+    ///
+    /// The flush runs on the blocking thread pool of the current [`tokio`]
+    /// runtime, and the returned future resolves once it completes. This
+    /// must be called from within a runtime. The file is shared with the
+    /// blocking task through an [`Arc`] so that it remains open until the
+    /// flush is done.
+    ///
+    /// The future borrows the map, so the map cannot be unmapped while the
+    /// flush is awaited. Dropping the future early ends that borrow, but the
+    /// flush keeps running on the blocking pool and its result is lost. If
+    /// the map is then unmapped, the flush may fail partway through, and if
+    /// the address range is mapped again in the meantime, the new mapping
+    /// is flushed in its place. Neither can corrupt memory, as the system
+    /// checks the range, but await the future to completion before
+    /// unmapping the map wherever the flush must take effect.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Flush, MapMut};
+    /// use std::fs::OpenOptions;
+    /// use std::sync::Arc;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// # let path = tmp.path().join("example");
+    /// let (mut map, file) = MapMut::with_options()
+    ///     .create(true)
+    ///     .resize(4096)
+    ///     .open(&path)?;
+    /// let file = Arc::new(file);
+    /// map[..5].copy_from_slice(b"hello");
+    ///
+    /// let rt = tokio::runtime::Builder::new_current_thread().build()?;
+    /// rt.block_on(map.flush_async(file.clone(), 0, 5, Flush::Sync))?;
+    /// assert!(rt.block_on(map.flush_async(file, usize::MAX, 2, Flush::Sync)).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`tokio`]: https://docs.rs/tokio
+    /// [`Arc`]: https://doc.rust-lang.org/std/sync/struct.Arc.html
+    #[cfg(feature = "tokio")]
+    pub fn flush_async(
+        &self,
+        file: std::sync::Arc<File>,
+        off: usize,
+        len: usize,
+        mode: Flush,
+    ) -> impl std::future::Future<Output = Result<()>> + '_ {
+        let range = if !self.is_file_backed() {
+            Err(Error::input(Operation::Flush, Input::Unsupported))
+        } else if off > self.len || len > self.len - off {
+            Err(Error::input(Operation::Flush, Input::InvalidRange))
+        } else {
+            let (ptr, len) = unsafe { Size::page().bounds(self.ptr.add(off), len) };
+            // The address is sent as an integer since raw pointers are not
+            // `Send`. The borrow of `self` keeps the map alive only for as
+            // long as the future is polled.
+            Ok((ptr as usize, len))
+        };
+        async move {
+            let (ptr, len) = range?;
+            tokio::task::spawn_blocking(move || unsafe { flush(ptr as *mut u8, &file, len, mode) })
+                .await
+                .map_err(|e| Error::io(Operation::Flush, e.into()))?
+        }
+    }
+
+    /// Writes a sub-range back to the filesystem after calling a hook on it.
+    ///
+    /// The `pre` hook is called with the bytes of the range immediately
+    /// before the range is flushed as with [`.flush_range()`]. The hook may
+    /// modify the range, such as to stamp a checksum into a trailer, and its
+    /// changes are included in the flush.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Flush, MapMut};
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// let (mut map, file) = MapMut::with_options().create(true).resize(16).open(&path)?;
+    /// map[..4].clone_from_slice(b"test");
+    /// map.flush_with(&file, 0, 5, Flush::Sync, |rec| {
+    ///     // Stamp a simple checksum into the last byte of the record.
+    ///     rec[4] = rec[..4].iter().fold(0, |a, b| a ^ b);
+    /// })?;
+    /// assert!(map.flush_with(&file, usize::MAX, 2, Flush::Sync, |_| ()).is_err());
+    /// assert_eq!(b"test\x16", &std::fs::read(&path)?[..5]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.flush_range()`]: #method.flush_range
+    pub fn flush_with<F>(
+        &mut self,
+        file: &File,
+        off: usize,
+        len: usize,
+        mode: Flush,
+        pre: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(&mut [u8]),
+    {
+        if off > self.len || len > self.len - off {
+            return Err(Error::input(Operation::Flush, Input::InvalidRange));
+        }
+        pre(&mut self[off..off + len]);
+        self.flush_range(file, off, len, mode)
+    }
+
+    /// Starts writeback of a sub-range of the map without waiting for it.
+    ///
+    /// On Linux, this uses `sync_file_range` with `SYNC_FILE_RANGE_WRITE` to
+    /// initiate writeback of the dirty pages in the corresponding range of
+    /// the file. This allows writeback to be scheduled separately from a
+    /// durability barrier, such as a later [`.flush_range()`] using
+    /// [`Flush::Sync`]. This does not guarantee durability on its own, nor
+    /// does it write back any file metadata.
+    ///
+    /// On other platforms, this is the same as [`.flush_range()`] using
+    /// [`Flush::Async`], which returns an error for an anonymous map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// let (mut map, file) = MapMut::with_options().create(true).resize(8192).open(&path)?;
+    /// map[4096..4100].clone_from_slice(b"test");
+    /// map.sync_range_async(&file, 4096, 4)?;
+    /// map.sync_range_async(&file, 8192, 0)?;
+    /// assert!(map.sync_range_async(&file, usize::MAX, 2).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.flush_range()`]: #method.flush_range
+    /// [`Flush::Sync`]: enum.Flush.html#variant.Sync
+    /// [`Flush::Async`]: enum.Flush.html#variant.Async
+    pub fn sync_range_async(&self, file: &File, off: usize, len: usize) -> Result<()> {
+        if off > self.len || len > self.len - off {
+            return Err(Error::input(Operation::Flush, Input::InvalidRange));
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(base) = self.file_offset() {
+            // A zero length asks `sync_file_range` for the rest of the file.
+            if len == 0 {
+                return Ok(());
+            }
+            return crate::os::sync_file_range(file, base + off as u64, len);
+        }
+        self.flush_range(file, off, len, Flush::Async)
+    }
+
+    /// Makes stores to a sub-range of the map durable on persistent memory.
+    ///
+    /// For a map created with [`.sync()`] on a DAX file system, this writes
+    /// back the CPU cache lines covering the range using `clwb`, `clflushopt`,
+    /// or `clflush` (whichever the processor supports), followed by an
+    /// `sfence`. This avoids the system call and page granularity of a
+    /// [`.flush_range()`].
+    ///
+    /// Only a map created with [`.sync()`] is durable once its cache lines
+    /// are written back. Any other map, and any map on a processor without
+    /// cache write-back instructions, is instead flushed with a synchronous
+    /// [`.flush_range()`] using the `file`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let (mut map, file) = MapMut::with_options()
+    ///     .sync(true)
+    ///     .open("/mnt/pmem/example")?;
+    /// map[100..104].clone_from_slice(b"test");
+    /// map.persist(&file, 100, 4)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.sync()`]: struct.Options.html#method.sync
+    /// [`.flush_range()`]: #method.flush_range
+    pub fn persist(&self, file: &File, off: usize, len: usize) -> Result<()> {
+        if off > self.len || len > self.len - off {
+            Err(Error::input(Operation::Flush, Input::InvalidRange))
+        } else if self.flags.contains(MapFlags::SYNC)
+            && unsafe { cpu::writeback(self.ptr.add(off), len) }
+        {
+            Ok(())
+        } else {
+            self.flush_range(file, off, len, Flush::Sync)
+        }
+    }
+
+    /// Writes bytes into the map using non-temporal (streaming) stores.
+    ///
+    /// Streaming stores bypass the CPU cache, which avoids evicting useful
+    /// data when writing a large buffer that won't be read again soon. On
+    /// x86-64 this uses `movntdq` for the aligned body of the range followed
+    /// by an `sfence`. On other architectures this is a normal copy.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let mut map = MapMut::new(4096)?;
+    /// let data = vec![0xa5u8; 1000];
+    /// map.write_nontemporal(3, &data)?;
+    /// assert_eq!(&data[..], &map[3..1003]);
+    /// assert!(map.write_nontemporal(map.len() - 4, b"overflow").is_err());
+    /// assert!(map.write_nontemporal(usize::MAX, b"overflow").is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn write_nontemporal(&mut self, off: usize, data: &[u8]) -> Result<()> {
+        if off > self.len || data.len() > self.len - off {
+            Err(Error::input(Operation::Write, Input::InvalidRange))
+        } else {
+            unsafe { cpu::copy_nontemporal(self.ptr.add(off), data.as_ptr(), data.len()) };
+            Ok(())
+        }
+    }
+
+    /// Copies several buffers consecutively into the map.
+    ///
+    /// The buffers are written one after another starting at `off`, and the
+    /// total number of bytes written is returned. If the buffers would not
+    /// all fit, an error is returned and nothing is written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let mut map = MapMut::new(4096)?;
+    /// let len = map.write_vectored(10, &[&[4, 0], b"test"])?;
+    /// assert_eq!(len, 6);
+    /// assert_eq!(b"\x04\x00test", &map[10..16]);
+    /// assert!(map.write_vectored(map.len() - 5, &[b"too", b"long"]).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn write_vectored(&mut self, off: usize, bufs: &[&[u8]]) -> Result<usize> {
+        let total = bufs
+            .iter()
+            .try_fold(0usize, |n, buf| n.checked_add(buf.len()))
+            .filter(|&n| off.checked_add(n).is_some_and(|end| end <= self.len))
+            .ok_or_else(|| Error::input(Operation::Write, Input::InvalidRange))?;
+        let mut pos = off;
+        for buf in bufs {
+            self[pos..pos + buf.len()].copy_from_slice(buf);
+            pos += buf.len();
+        }
+        Ok(total)
+    }
+
+    /// Updates the advise for the entire mapped region.
+    ///
+    /// Hints are only advisory, and a hint that the platform does not
+    /// support returns `Ok(())` without effect, so code using any [`Advise`]
+    /// variant remains portable. The hints take effect as follows:
+    ///
+    /// | Hint          | Linux / Android   | macOS / BSD       | Windows                 |
+    /// |---------------|-------------------|-------------------|-------------------------|
+    /// | `Normal`      | `MADV_NORMAL`     | `MADV_NORMAL`     | no-op                   |
+    /// | `Sequential`  | `MADV_SEQUENTIAL` | `MADV_SEQUENTIAL` | no-op                   |
+    /// | `Random`      | `MADV_RANDOM`     | `MADV_RANDOM`     | no-op                   |
+    /// | `WillNeed`    | `MADV_WILLNEED`   | `MADV_WILLNEED`   | `PrefetchVirtualMemory` |
+    /// | `WillNotNeed` | `MADV_DONTNEED`   | `MADV_DONTNEED`   | no-op                   |
+    /// | `Cold`        | `MADV_COLD`*      | no-op             | no-op                   |
+    /// | `PageOut`     | `MADV_PAGEOUT`*   | no-op             | no-op                   |
+    /// | `HugePage`    | `MADV_HUGEPAGE`*  | no-op             | no-op                   |
+    /// | `Free`        | `MADV_FREE`*      | `MADV_FREE`       | no-op                   |
+    ///
+    /// \* A no-op on kernels that do not recognize the hint. `Free` is also a
+    /// no-op for any map that is not private and anonymous.
+    ///
+    /// `WillNotNeed` lets the system drop the pages immediately. The pages of
+    /// a shared map keep their contents, but on Linux the pages of a private
+    /// map, such as one created with [`Options::copy()`], are discarded. Any
+    /// writes are lost, and the pages read back as the original file contents,
+    /// or as zero for an anonymous map. Use `Cold` or `PageOut` to release
+    /// memory without losing data.
+    ///
+    /// Errors are still returned for a hint the platform supports but
+    /// rejects, such as when the range is not mapped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Advise, MapMut};
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let map = MapMut::new(4 * vmap::page_size())?;
+    /// map.advise(Advise::HugePage)?;
+    /// map.advise(Advise::Sequential)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Advise`]: enum.Advise.html
+    /// [`Options::copy()`]: struct.Options.html#method.copy
+    pub fn advise(&self, adv: Advise) -> Result<()> {
+        unsafe {
+            let (ptr, len) = Size::page().bounds(self.ptr, self.len);
+            advise(ptr, len, adv)
+        }
+    }
+
+    /// Updates the advise for a specific range of the mapped region.
+    ///
+    /// See [`.advise()`] for the hints supported by each platform.
+    ///
+    /// [`.advise()`]: #method.advise
+    pub fn advise_range(&self, off: usize, len: usize, adv: Advise) -> Result<()> {
+        if off > self.len || len > self.len - off {
+            Err(Error::input(Operation::Advise, Input::InvalidRange))
+        } else {
+            unsafe {
+                let (ptr, len) = Size::page().bounds(self.ptr.add(off), len);
+                advise(ptr, len, adv)
+            }
+        }
+    }
+
+    /// Updates the page cache advise for a range of the mapped file.
+    ///
+    /// The range is relative to the start of the map, and `file` must be the
+    /// file that was mapped. This uses `posix_fadvise`, which applies to the
+    /// file's pages in the page cache rather than to this mapping. For
+    /// example, [`FAdvise::DontNeed`] may be used to drop a range from the
+    /// page cache once it is no longer needed. To advise a file range before
+    /// it is mapped, use [`os::fadvise()`] directly.
+    ///
+    /// This is a no-op on platforms without `posix_fadvise`, including macOS
+    /// and Windows. An anonymous map yields an [`Input::Unsupported`] error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{FAdvise, Map};
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// # std::fs::write(&path, vec![1u8; 8192])?;
+    /// let (map, file) = Map::with_options().open(&path)?;
+    /// map.fadvise(&file, 0, map.len(), FAdvise::Sequential)?;
+    /// assert_eq!(map[4096], 1);
+    /// map.fadvise(&file, 0, map.len(), FAdvise::DontNeed)?;
+    /// assert!(map.fadvise(&file, usize::MAX, 2, FAdvise::DontNeed).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`FAdvise::DontNeed`]: enum.FAdvise.html#variant.DontNeed
+    /// [`os::fadvise()`]: os/fn.fadvise.html
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    pub fn fadvise(&self, file: &File, off: usize, len: usize, adv: FAdvise) -> Result<()> {
+        if off > self.len || len > self.len - off {
+            return Err(Error::input(Operation::AdviseFile, Input::InvalidRange));
+        }
+        if len == 0 {
+            // A zero length means the rest of the file to `posix_fadvise`.
+            return Ok(());
+        }
+        match self.file_offset() {
+            Some(base) => fadvise(file, base + off as u64, len, adv),
+            None => Err(Error::input(Operation::AdviseFile, Input::Unsupported)),
+        }
+    }
+
+    /// Lock all mapped physical pages into memory.
+    ///
+    /// The pages are kept resident until [`.unlock()`] is called or the map
+    /// is dropped, so they are never written to swap. This suits secrets such
+    /// as key material. Locking the pages of a file map keeps them in memory,
+    /// but does not stop their contents from being written to the file.
+    ///
+    /// The amount of memory a process may lock is limited. On Unix, `mlock`
+    /// fails once `RLIMIT_MEMLOCK` would be exceeded. On Windows, `VirtualLock`
+    /// is limited by the minimum working set size of the process, and fails
+    /// with `ERROR_WORKING_SET_QUOTA` when it is too small. Either way the
+    /// system error is returned, and is available from
+    /// [`Error::raw_os_error()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let mut key = MapMut::new(vmap::page_size())?;
+    /// key.lock()?;
+    /// key[..4].copy_from_slice(b"\x01\x02\x03\x04");
+    /// // ... use the key ...
+    /// key.fill(0);
+    /// key.unlock()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.unlock()`]: #method.unlock
+    /// [`Error::raw_os_error()`]: struct.Error.html#method.raw_os_error
+    pub fn lock(&self) -> Result<()> {
+        unsafe {
+            let (ptr, len) = Size::page().bounds(self.ptr, self.len);
+            lock(ptr, len)
+        }
+    }
+
+    /// Lock a range of physical pages into memory.
+    ///
+    /// Every page containing a byte of `off..off + len` is locked. An
+    /// [`Input::InvalidRange`] error is returned if the range is not within
+    /// the map, including when `off + len` overflows.
+    ///
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    pub fn lock_range(&self, off: usize, len: usize) -> Result<()> {
+        if off > self.len || len > self.len - off {
+            Err(Error::input(Operation::Lock, Input::InvalidRange))
+        } else {
+            unsafe {
+                let (ptr, len) = Size::page().bounds(self.ptr.add(off), len);
+                lock(ptr, len)
+            }
+        }
+    }
+
+    /// Faults in every page of the map and locks them into memory.
+    ///
+    /// This is intended to be called before a latency-sensitive section so
+    /// that no page faults occur within it. Every page is first faulted in
+    /// as with [`.prefault()`], and the whole map is then locked as with
+    /// [`.lock()`] so the pages cannot be swapped out afterward. On Linux,
+    /// locking a writable private map also resolves its copy-on-write
+    /// faults, so the first write to each page does not fault either.
+    ///
+    /// Locked memory counts against the `RLIMIT_MEMLOCK` resource limit on
+    /// Unix, which is often only a few megabytes for unprivileged processes.
+    /// Exceeding it yields an error, in which case the pages remain faulted
+    /// in but are not locked. On Windows the limit is the minimum working
+    /// set size of the process. Call [`.unlock()`] to release the pages.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let map = MapMut::new(4 * vmap::page_size())?;
+    /// if map.lock_prefault().is_ok() {
+    ///     // ... critical section ...
+    ///     map.unlock()?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.prefault()`]: #method.prefault
+    /// [`.lock()`]: #method.lock
+    /// [`.unlock()`]: #method.unlock
+    pub fn lock_prefault(&self) -> Result<()> {
+        self.prefault();
+        self.lock()
+    }
+
+    /// Unlock all mapped physical pages into memory.
+    pub fn unlock(&self) -> Result<()> {
+        unsafe {
+            let (ptr, len) = Size::page().bounds(self.ptr, self.len);
+            unlock(ptr, len)
+        }
+    }
+
+    /// Unlock a range of physical pages into memory.
+    ///
+    /// The range is checked as in [`.lock_range()`].
+    ///
+    /// [`.lock_range()`]: #method.lock_range
+    pub fn unlock_range(&self, off: usize, len: usize) -> Result<()> {
+        if off > self.len || len > self.len - off {
+            Err(Error::input(Operation::Unlock, Input::InvalidRange))
+        } else {
+            unsafe {
+                let (ptr, len) = Size::page().bounds(self.ptr.add(off), len);
+                unlock(ptr, len)
+            }
+        }
+    }
+
+    /// Bind the mapped physical pages to a NUMA node.
+    ///
+    /// This uses `mbind(MPOL_BIND)` on Linux and Android, and is a no-op on
+    /// other platforms. The policy is applied when a page is first faulted
+    /// in, so pages that have already been touched are not moved. Bind the
+    /// map before writing to it (or otherwise populating it) to ensure the
+    /// pages are allocated on the desired node. An [`Input::InvalidRange`]
+    /// error is returned if `node` is above the highest possible node.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let mut map = MapMut::new(200)?;
+    /// map.bind_numa(0)?;
+    /// # #[cfg(any(target_os = "linux", target_os = "android"))]
+    /// assert!(map.bind_numa(u32::MAX).is_err());
+    /// map[..4].clone_from_slice(b"test");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    pub fn bind_numa(&self, node: u32) -> Result<()> {
+        unsafe {
+            let (ptr, len) = Size::page().bounds(self.ptr, self.len);
+            bind_numa(ptr, len, node)
+        }
+    }
+
+    /// Gets the file retained by the map, if any.
+    ///
+    /// This is set for a map created with [`Options::map_owned()`] or after
+    /// calling [`.set_flush_on_drop()`].
+    ///
+    /// [`Options::map_owned()`]: struct.Options.html#method.map_owned
+    /// [`.set_flush_on_drop()`]: #method.set_flush_on_drop
+    pub fn file(&self) -> Option<&File> {
+        self.file.as_ref()
+    }
+
+    /// Flushes the map to the file whenever it is dropped.
+    ///
+    /// Modifications to a shared file mapping are written back by the system
+    /// eventually, but are not otherwise flushed when the map is dropped.
+    /// With this set, dropping the map first flushes the entire map with the
+    /// given mode, as with [`.flush()`], and then unmaps it. The map takes
+    /// ownership of `file`, replacing any file it already retains, which may
+    /// then be borrowed with [`.file()`].
+    ///
+    /// As with unmapping, any error from the flush in `Drop` is ignored. Call
+    /// [`.flush()`] directly where the result must be known.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Flush, MapMut};
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// let (mut map, file) = MapMut::create(&path, 100)?;
+    /// map.set_flush_on_drop(file, Flush::Sync);
+    /// map[..4].copy_from_slice(b"test");
+    /// drop(map);
+    /// assert_eq!(b"test", &std::fs::read(&path)?[..4]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.flush()`]: #method.flush
+    /// [`.file()`]: #method.file
+    pub fn set_flush_on_drop(&mut self, file: File, mode: Flush) {
+        self.set_sync_policy(file, SyncPolicy::OnDrop(mode));
+    }
+
+    /// Sets the policy for flushing the map to `file`.
+    ///
+    /// The map takes ownership of `file`, replacing any file it already
+    /// retains, as with [`.set_flush_on_drop()`]. The policy then decides
+    /// when the map is flushed without a call to [`.flush()`]:
+    ///
+    /// - [`SyncPolicy::None`] never flushes, and the system writes the pages
+    ///   back eventually. As nothing is flushed, `file` is dropped rather
+    ///   than retained, and any file the map already retains is kept.
+    /// - [`SyncPolicy::OnDrop`] flushes the entire map when it is dropped or
+    ///   [`.close()`]d, exactly like [`.set_flush_on_drop()`].
+    /// - [`SyncPolicy::Periodic`] also flushes when dropped, and in addition
+    ///   [`.maybe_flush()`] flushes once the interval has passed since the
+    ///   last flush. Call it from a write loop to bound how much is lost on
+    ///   a crash without flushing on every write. The interval starts when
+    ///   the policy is set.
+    ///
+    /// As with unmapping, any error from the flush in `Drop` is ignored, so
+    /// use [`.close()`] where the result must be known.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Flush, MapMut, SyncPolicy};
+    /// use std::path::PathBuf;
+    /// use std::time::Duration;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// let (mut map, file) = MapMut::create(&path, 100)?;
+    /// let every = Duration::from_secs(1);
+    /// map.set_sync_policy(file, SyncPolicy::Periodic(Flush::Async, every));
+    /// for i in 0..4 {
+    ///     map[i] = b'a' + i as u8;
+    ///     map.maybe_flush()?;
+    /// }
+    /// map.close()?;
+    /// assert_eq!(b"abcd", &std::fs::read(&path)?[..4]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.set_flush_on_drop()`]: #method.set_flush_on_drop
+    /// [`.flush()`]: #method.flush
+    /// [`.close()`]: #method.close
+    /// [`.maybe_flush()`]: #method.maybe_flush
+    /// [`SyncPolicy::None`]: enum.SyncPolicy.html#variant.None
+    /// [`SyncPolicy::OnDrop`]: enum.SyncPolicy.html#variant.OnDrop
+    /// [`SyncPolicy::Periodic`]: enum.SyncPolicy.html#variant.Periodic
+    pub fn set_sync_policy(&mut self, file: File, policy: SyncPolicy) {
+        if policy != SyncPolicy::None {
+            self.file = Some(file);
+        }
+        let every;
+        (self.flush_on_drop, every) = match policy {
+            SyncPolicy::None => (None, None),
+            SyncPolicy::OnDrop(mode) => (Some(mode), None),
+            SyncPolicy::Periodic(mode, every) => (Some(mode), Some((every, Instant::now()))),
+        };
+        if every.is_some() || self.extra.is_some() {
+            self.extra_mut().sync_every = every;
+        }
+    }
+
+    /// Gets the policy for flushing the map to its retained file.
+    pub fn sync_policy(&self) -> SyncPolicy {
+        match (self.flush_on_drop, self.sync_every()) {
+            (Some(mode), Some((every, _))) => SyncPolicy::Periodic(mode, every),
+            (Some(mode), None) => SyncPolicy::OnDrop(mode),
+            (None, _) => SyncPolicy::None,
+        }
+    }
+
+    /// Flushes the map if the interval of a periodic sync policy has passed.
+    ///
+    /// Returns `true` if the map was flushed. This does nothing unless the
+    /// policy set with [`.set_sync_policy()`] is [`SyncPolicy::Periodic`].
+    /// The interval restarts only after a successful flush, so a failed
+    /// flush is retried on the next call.
+    ///
+    /// [`.set_sync_policy()`]: #method.set_sync_policy
+    /// [`SyncPolicy::Periodic`]: enum.SyncPolicy.html#variant.Periodic
+    pub fn maybe_flush(&mut self) -> Result<bool> {
+        let (mode, every, last) = match (self.flush_on_drop, self.sync_every()) {
+            (Some(mode), Some((every, last))) => (mode, every, last),
+            _ => return Ok(false),
+        };
+        if last.elapsed() < every || self.len == 0 {
+            return Ok(false);
+        }
+        self.flush_owned(mode)?;
+        self.extra_mut().sync_every = Some((every, Instant::now()));
+        Ok(true)
+    }
+
+    /// Unmaps the map now and returns any error from doing so.
+    ///
+    /// Dropping a map also unmaps it but ignores any error. This performs
+    /// the same steps, including the flush requested by
+    /// [`.set_flush_on_drop()`], and reports the first error encountered.
+    /// The map is unmapped exactly once: it is marked empty before the
+    /// system call, so neither a failure nor the following drop attempts to
+    /// unmap the range again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let mut map = MapMut::new(100)?;
+    /// map[..4].copy_from_slice(b"test");
+    /// map.close()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.set_flush_on_drop()`]: #method.set_flush_on_drop
+    pub fn close(mut self) -> Result<()> {
+        self.release()
+    }
+
+    /// Borrows the file descriptor retained by the map, if any.
+    ///
+    /// This allows file-level calls, such as `fcntl` or `posix_fadvise`, to
+    /// be made on the file backing a map created with
+    /// [`Options::map_owned()`]. Other maps, including anonymous maps,
+    /// return `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::Map;
+    /// use std::fs::File;
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// # std::fs::write(&path, b"this is a test")?;
+    /// let map = Map::with_options().map_owned(File::open(&path)?)?;
+    /// # #[cfg(unix)]
+    /// assert!(map.as_fd().is_some());
+    /// # #[cfg(unix)]
+    /// assert!(vmap::MapMut::new(1)?.as_fd().is_none());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Options::map_owned()`]: struct.Options.html#method.map_owned
+    #[cfg(unix)]
+    pub fn as_fd(&self) -> Option<BorrowedFd<'_>> {
+        self.file.as_ref().map(|f| f.as_fd())
+    }
+
+    /// Gets the raw file descriptor retained by the map, if any.
+    ///
+    /// See [`.as_fd()`] for details.
+    ///
+    /// [`.as_fd()`]: #method.as_fd
+    #[cfg(unix)]
+    pub fn as_raw_fd(&self) -> Option<RawFd> {
+        self.file.as_ref().map(|f| f.as_raw_fd())
+    }
+
+    /// Borrows the file handle retained by the map, if any.
+    ///
+    /// This allows file-level calls to be made on the file backing a map
+    /// created with [`Options::map_owned()`]. Other maps, including
+    /// anonymous maps, return `None`.
+    ///
+    /// [`Options::map_owned()`]: struct.Options.html#method.map_owned
+    #[cfg(windows)]
+    pub fn as_handle(&self) -> Option<BorrowedHandle<'_>> {
+        self.file.as_ref().map(|f| f.as_handle())
+    }
+
+    /// Gets the raw file handle retained by the map, if any.
+    ///
+    /// See [`.as_handle()`] for details.
+    ///
+    /// [`.as_handle()`]: #method.as_handle
+    #[cfg(windows)]
+    pub fn as_raw_handle(&self) -> Option<RawHandle> {
+        self.file.as_ref().map(|f| f.as_raw_handle())
+    }
+
+    /// Tests if a truncation of the underlying file has been detected.
+    ///
+    /// This is only detected for file mappings created after calling
+    /// [`install_sigbus_handler()`], and only once a page beyond the end of
+    /// the truncated file has been accessed. When `true`, some of the pages
+    /// in the map have been replaced with zero-filled anonymous memory.
+    ///
+    /// [`install_sigbus_handler()`]: fn.install_sigbus_handler.html
+    pub fn is_truncated(&self) -> bool {
+        is_truncated(self.ptr)
+    }
+
+    /// Returns a sub-slice of the map after checking that none of it was
+    /// lost to a truncation of the underlying file.
+    ///
+    /// Each page of the range is accessed first, so any fault from an
+    /// underlying file that has been truncated is raised before the check
+    /// with [`.is_truncated()`]. This requires [`install_sigbus_handler()`]
+    /// to have been called before the map was created. Otherwise, accessing
+    /// a truncated page will still terminate the process.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::Map;
+    /// use std::fs::OpenOptions;
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// # std::fs::write(&path, vec![b'x'; 3 * vmap::page_size()])?;
+    /// vmap::install_sigbus_handler()?;
+    /// let (map, file) = Map::with_options().open(&path)?;
+    /// assert_eq!(b"xxxx", map.try_read(0, 4)?);
+    /// assert!(map.try_read(usize::MAX, 2).is_err());
+    ///
+    /// # if cfg!(unix) {
+    /// // Another process truncates the file...
+    /// OpenOptions::new().write(true).open(&path)?.set_len(0)?;
+    ///
+    /// assert!(map.try_read(2 * vmap::page_size(), 4).is_err());
+    /// assert!(map.is_truncated());
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.is_truncated()`]: #method.is_truncated
+    /// [`install_sigbus_handler()`]: fn.install_sigbus_handler.html
+    pub fn try_read(&self, off: usize, len: usize) -> Result<&[u8]> {
+        if off > self.len || len > self.len - off {
+            return Err(Error::input(Operation::Read, Input::InvalidRange));
+        }
+        unsafe { touch(self.ptr as usize + off, len) };
+        if self.is_truncated() {
+            Err(Error::input(Operation::Read, Input::Truncated))
+        } else {
+            Ok(&self[off..off + len])
+        }
+    }
+
+    /// Faults in every page of the map by reading from it.
+    ///
+    /// This reads one byte from each page so that later accesses do not
+    /// incur a page fault. For a file mapping this reads the file into the
+    /// page cache. Pages of a writable map are only faulted in for reading,
+    /// so the first write to a private page may still fault.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let map = MapMut::new(16 * vmap::page_size())?;
+    /// map.prefault();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn prefault(&self) {
+        unsafe { touch(self.ptr as usize, self.len) }
+    }
+
+    /// Faults in the pages of a range of the map by reading from it.
+    ///
+    /// Every page containing a byte of `off..off + len` is first advised
+    /// with [`Advise::WillNeed`], which starts reading all of them in at once
+    /// (`MADV_WILLNEED` on Unix, `PrefetchVirtualMemory` on Windows), and is
+    /// then read as with [`.prefault()`]. This suits warming only the part of
+    /// a large map that a first scan will touch. An [`Input::InvalidRange`]
+    /// error is returned if the range is not within the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::Map;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let (map, _) = Map::with_options().open("README.md")?;
+    /// map.prefault_range(0, 100)?;
+    /// assert!(map.all_resident(0..100)?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Advise::WillNeed`]: enum.Advise.html#variant.WillNeed
+    /// [`.prefault()`]: #method.prefault
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    pub fn prefault_range(&self, off: usize, len: usize) -> Result<()> {
+        if off > self.len || len > self.len - off {
+            return Err(Error::input(Operation::Advise, Input::InvalidRange));
+        }
+        if len == 0 {
+            return Ok(());
+        }
+        unsafe {
+            let (pg, pglen) = Size::page().bounds(self.ptr.add(off), len);
+            advise(pg, pglen, Advise::WillNeed)?;
+            touch(self.ptr as usize + off, len);
+        }
+        Ok(())
+    }
+
+    /// Faults in every page of the map using multiple threads.
+    ///
+    /// The pages are split into contiguous chunks, one for each thread, and
+    /// each chunk is read as with [`.prefault()`]. The scoped threads are
+    /// joined before returning. For a large cold mapping, this overlaps the
+    /// cost of the page faults and any reads from storage.
+    ///
+    /// The number of threads is limited to the available parallelism and to
+    /// the number of pages, and a value of `0` or `1` faults in the pages on
+    /// the current thread. An error is returned if a thread fails to spawn.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let map = MapMut::new(64 * vmap::page_size())?;
+    /// map.prefault_parallel(4)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.prefault()`]: #method.prefault
+    pub fn prefault_parallel(&self, threads: usize) -> Result<()> {
+        let (pg, len) = unsafe { Size::page().bounds(self.ptr, self.len) };
+        let page = Size::page().size(1);
+        let pages = len / page;
+        let max = thread::available_parallelism().map_or(1, |n| n.get());
+        let threads = cmp::min(cmp::min(threads, max), pages);
+        if threads <= 1 {
+            self.prefault();
+            return Ok(());
+        }
+
+        // Addresses are passed as integers because raw pointers are not `Send`.
+        // The scope joins every thread before the map can be dropped.
+        let chunk = page * pages.div_ceil(threads);
+        let start = pg as usize;
+        thread::scope(|s| {
+            for off in (0..len).step_by(chunk) {
+                let n = cmp::min(chunk, len - off);
+                thread::Builder::new()
+                    .spawn_scoped(s, move || unsafe { touch(start + off, n) })
+                    .map_err(|e| Error::io(Operation::Read, e))?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Tests if every page of a range is resident in memory.
+    ///
+    /// This uses `mincore` over only the pages of the range, without
+    /// allocating, and stops at the first page that is not resident. An
+    /// empty range is always resident. This may be used to decide whether
+    /// to prefetch a range before reading it, for instance with
+    /// [`Advise::WillNeed`]. The result is only a snapshot, as pages may be
+    /// reclaimed or read in at any time.
+    ///
+    /// An [`Input::InvalidRange`] error is returned if the range is not
+    /// within the map. This is not supported on Windows, which yields an
+    /// [`Input::Unsupported`] error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let page = vmap::page_size();
+    /// let mut map = MapMut::new(4 * page)?;
+    /// map[..page].fill(1);
+    /// # if cfg!(unix) {
+    /// assert!(map.all_resident(0..page)?);
+    /// assert!(map.any_resident(0..4 * page)?);
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Advise::WillNeed`]: enum.Advise.html#variant.WillNeed
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    pub fn all_resident(&self, range: Range<usize>) -> Result<bool> {
+        self.find_residency(range, false).map(|i| i.is_none())
+    }
+
+    /// Tests if any page of a range is resident in memory.
+    ///
+    /// This stops at the first resident page, and an empty range is never
+    /// resident. See [`.all_resident()`] for details.
+    ///
+    /// [`.all_resident()`]: #method.all_resident
+    pub fn any_resident(&self, range: Range<usize>) -> Result<bool> {
+        self.find_residency(range, true).map(|i| i.is_some())
+    }
+
+    fn find_residency(&self, range: Range<usize>, resident: bool) -> Result<Option<usize>> {
+        if range.start > range.end || range.end > self.len {
+            return Err(Error::input(Operation::Residency, Input::InvalidRange));
+        }
+        if range.start == range.end {
+            return Ok(None);
+        }
+        unsafe { find_residency(self.ptr.add(range.start), range.end - range.start, resident) }
+    }
+
+    /// Feeds every byte of the map to `hasher` in one sequential pass.
+    ///
+    /// The bytes are written in page-sized chunks, in order, using
+    /// [`Hasher::write`]. Any hasher that is not sensitive to how its input
+    /// is split therefore produces the same result as writing the whole map
+    /// at once. Note that this differs from hashing the map as a `[u8]`,
+    /// which also writes the length.
+    ///
+    /// The map is first advised with [`Advise::Sequential`] so the pages are
+    /// read ahead, and the advice remains afterward. For a shared file map,
+    /// the pages already hashed are released with [`Advise::WillNotNeed`]
+    /// once each megabyte is read, so a scan of a large file does not keep
+    /// it resident in the process. Their contents remain in the file and are
+    /// read again on the next access. Pages of an anonymous or private map
+    /// could be discarded by this advice, so they are never released.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::Map;
+    /// use std::collections::hash_map::DefaultHasher;
+    /// use std::hash::Hasher;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let (map, file) = Map::with_options().open("README.md")?;
+    /// let mut hasher = DefaultHasher::new();
+    /// map.digest(&mut hasher)?;
+    ///
+    /// let mut whole = DefaultHasher::new();
+    /// whole.write(&map);
+    /// assert_eq!(whole.finish(), hasher.finish());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Hasher::write`]: https://doc.rust-lang.org/std/hash/trait.Hasher.html#tymethod.write
+    /// [`Advise::Sequential`]: enum.Advise.html#variant.Sequential
+    /// [`Advise::WillNotNeed`]: enum.Advise.html#variant.WillNotNeed
+    pub fn digest<H: Hasher>(&self, hasher: &mut H) -> Result<()> {
+        const WINDOW: usize = 1 << 20;
+
+        self.advise(Advise::Sequential)?;
+        let release = self.is_file_backed() && !self.is_private();
+        let (start, page) = (self.ptr as usize, Size::page().size(1));
+        let (mut off, mut released) = (0, 0);
+        while off < self.len {
+            // Chunks end on page boundaries, even when the map does not
+            // start on one.
+            let end = cmp::min(self.len, Size::page().truncate(start + off + page) - start);
+            hasher.write(&self[off..end]);
+            off = end;
+            if release && (off - released >= WINDOW || off == self.len) {
+                self.advise_range(released, off - released, Advise::WillNotNeed)?;
+                released = off;
+            }
+        }
+        Ok(())
     }
-}
-
-/// Allocation of one or more read-write sequential pages.
-#[derive(Debug)]
-pub struct MapMut {
-    ptr: *mut u8,
-    len: usize,
-}
 
-impl MapMut {
-    /// Returns a new `Options` object to create a writable `MapMut`.
+    /// Acquire an advisory lock on the entire file backing the map.
     ///
-    /// When used to [`.open()`] a path or [`.map()`] a file, the default
-    /// [`Options`] object is assumed to cover the entire file.
+    /// This uses `flock` on Unix and `LockFileEx` on Windows, blocking until
+    /// the lock is available. An exclusive lock excludes all other locks,
+    /// while any number of shared locks may be held at once. The lock is
+    /// released when the returned [`FileLock`] is dropped.
     ///
-    /// See the [`Options`] type for details on options for modifying the file
-    /// size, specifying offset positions, and selecting specific lengths.
+    /// The lock is advisory, so it only coordinates processes that also
+    /// lock the file before accessing it. It does not prevent any access to
+    /// the mapped memory, and it always covers the whole file rather than
+    /// the mapped range.
     ///
     /// # Examples
     ///
     /// ```
-    /// use vmap::{MapMut, Flush};
+    /// use vmap::{Flush, MapMut};
     /// use std::path::PathBuf;
-    /// use std::str::from_utf8;
     ///
     /// # fn main() -> vmap::Result<()> {
     /// # let tmp = tempdir::TempDir::new("vmap")?;
     /// let path: PathBuf = /* path to file */
     /// # tmp.path().join("example");
-    /// # std::fs::write(&path, "A cross-platform library for fast and safe memory-mapped IO in Rust")?;
-    /// let (mut map, file) = MapMut::with_options()
-    ///     .offset(29)
-    ///     .len(30)
-    ///     .open(&path)?;
-    /// assert_eq!(Ok("fast and safe memory-mapped IO"), from_utf8(&map));
-    /// map[..4].clone_from_slice(b"nice");
-    ///
-    /// map.flush_range(&file, 0, 4, Flush::Sync);
-    ///
-    /// assert_eq!(Ok("nice and safe memory-mapped IO"), from_utf8(&map));
+    /// let (mut map, file) = MapMut::with_options().create(true).resize(100).open(&path)?;
+    /// {
+    ///     let _lock = map.lock_file(&file, true)?;
+    ///     map[..4].clone_from_slice(b"test");
+    ///     map.flush(&file, Flush::Sync)?;
+    /// }
     /// # Ok(())
     /// # }
     /// ```
-    pub fn with_options() -> Options<Self> {
-        let mut opts = Options::new();
-        opts.write();
-        opts
+    ///
+    /// [`FileLock`]: struct.FileLock.html
+    pub fn lock_file<'a>(&self, file: &'a File, exclusive: bool) -> Result<FileLock<'a>> {
+        lock_file(file, exclusive)?;
+        Ok(FileLock { file })
     }
 
-    /// Create a new anonymous mapping at least as large as the hint.
+    /// Acquire an advisory lock on a byte range of the file backing the map.
+    ///
+    /// The `range` is relative to the start of the map, and the matching
+    /// bytes of the file are locked. This uses POSIX record locks
+    /// (`fcntl(F_SETLKW)`) on Unix and `LockFileEx` on Windows, blocking
+    /// until the lock is available. An exclusive lock excludes all other
+    /// locks on any of the bytes, so concurrent writers can coordinate on
+    /// disjoint regions. The range is unlocked when the returned
+    /// [`FileRangeLock`] is dropped.
+    ///
+    /// An empty range or one that lies outside of the map yields an
+    /// [`Input::InvalidRange`] error, and an anonymous map yields an
+    /// [`Input::Unsupported`] error.
+    ///
+    /// On Unix, record locks belong to the process rather than the file
+    /// handle. They never conflict with other locks held by the same
+    /// process, and closing *any* file handle for the file, even one opened
+    /// separately, releases all of the process's locks on it. On Windows the
+    /// locks belong to the handle, and locking the same bytes twice through
+    /// the same handle blocks. As with [`.lock_file()`], access to the
+    /// mapped memory is never prevented.
     ///
     /// # Examples
     ///
     /// ```
-    /// use vmap::{MapMut, Protect};
-    /// use std::str::from_utf8;
+    /// use vmap::MapMut;
+    /// use std::path::PathBuf;
     ///
     /// # fn main() -> vmap::Result<()> {
-    /// let mut map = MapMut::new(200)?;
-    /// map[..4].clone_from_slice(b"test");
-    /// assert_eq!(Ok("test"), from_utf8(&map[..4]));
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// let (mut map, file) = MapMut::with_options().create(true).resize(100).open(&path)?;
+    /// {
+    ///     let _lock = map.lock_file_range(&file, 10..20, true)?;
+    ///     map[10..14].clone_from_slice(b"test");
+    /// }
+    /// assert!(map.lock_file_range(&file, 90..110, true).is_err());
     /// # Ok(())
     /// # }
     /// ```
-    pub fn new(hint: usize) -> Result<Self> {
-        Self::with_options().len(Extent::Min(hint)).alloc()
+    ///
+    /// [`FileRangeLock`]: struct.FileRangeLock.html
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    /// [`.lock_file()`]: #method.lock_file
+    pub fn lock_file_range<'a>(
+        &self,
+        file: &'a File,
+        range: Range<usize>,
+        exclusive: bool,
+    ) -> Result<FileRangeLock<'a>> {
+        let base = self
+            .file_offset()
+            .ok_or_else(|| Error::input(Operation::LockFile, Input::Unsupported))?;
+        if range.start >= range.end || range.end > self.len {
+            return Err(Error::input(Operation::LockFile, Input::InvalidRange));
+        }
+        let (off, len) = (base + range.start as u64, range.len() as u64);
+        lock_file_range(file, off, len, exclusive)?;
+        Ok(FileRangeLock { file, off, len })
     }
 
-    /// Transfer ownership of the map into a mutable map.
+    /// Returns a reference to a byte or sub-slice of the map, or `None` if
+    /// the index is out of bounds.
     ///
-    /// This will change the protection of the mapping. If the original file
-    /// was not opened with write permissions, this will error.
+    /// This mirrors `slice::get` and is intended for indexes derived from
+    /// untrusted data where a panic would be inappropriate.
+    #[inline]
+    pub fn get<I: SliceIndex<[u8]>>(&self, index: I) -> Option<&I::Output> {
+        self.deref().get(index)
+    }
+
+    /// Returns a mutable reference to a byte or sub-slice of the map, or
+    /// `None` if the index is out of bounds.
     ///
     /// # Examples
     ///
     /// ```
     /// use vmap::MapMut;
-    /// use std::fs::OpenOptions;
-    /// use std::path::PathBuf;
-    /// use std::str::from_utf8;
-    /// # use std::fs;
     ///
     /// # fn main() -> vmap::Result<()> {
-    /// # let tmp = tempdir::TempDir::new("vmap")?;
-    /// let path: PathBuf = /* path to file */
-    /// # tmp.path().join("example");
-    /// # fs::write(&path, b"this is a test")?;
-    /// let (mut map, file) = MapMut::with_options().len(14).open(&path)?;
-    /// assert_eq!(Ok("this is a test"), from_utf8(&map[..]));
+    /// let mut map = MapMut::with_options().len(4).alloc()?;
+    /// if let Some(b) = map.get_mut(1) {
+    ///     *b = b'x';
+    /// }
+    /// assert!(map.get_mut(4).is_none());
+    /// assert_eq!(b"\0x\0\0", &map[..]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn get_mut<I: SliceIndex<[u8]>>(&mut self, index: I) -> Option<&mut I::Output> {
+        self.deref_mut().get_mut(index)
+    }
+
+    /// Returns a sub-slice of `len` bytes starting at `off`, or `None` if the
+    /// range lies outside of the map.
+    #[inline]
+    pub fn get_range(&self, off: usize, len: usize) -> Option<&[u8]> {
+        self.get(off..off.checked_add(len)?)
+    }
+
+    /// Returns an iterator over every overlapping window of `size` bytes.
     ///
-    /// map[..4].clone_from_slice(b"that");
+    /// See [`Map::windows()`] for details.
     ///
-    /// let map = map.into_map()?;
-    /// assert_eq!(Ok("that is a test"), from_utf8(&map[..]));
+    /// [`Map::windows()`]: struct.Map.html#method.windows
+    #[inline]
+    pub fn windows(&self, size: usize) -> slice::Windows<'_, u8> {
+        self.deref().windows(size)
+    }
+
+    /// Returns a mutable sub-slice of `len` bytes starting at `off`, or `None`
+    /// if the range lies outside of the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let mut map = MapMut::with_options().len(8).alloc()?;
+    /// if let Some(s) = map.get_range_mut(2, 4) {
+    ///     s.copy_from_slice(b"test");
+    /// }
+    /// assert!(map.get_range_mut(6, 4).is_none());
+    /// assert_eq!(b"\0\0test\0\0", &map[..]);
     /// # Ok(())
     /// # }
     /// ```
-    pub fn into_map(self) -> ConvertResult<Map, Self> {
-        let (ptr, len) = unsafe { Size::page().bounds(self.ptr, self.len) };
-        match unsafe { protect(ptr, len, Protect::ReadWrite) } {
-            Ok(()) => Ok(Map(self)),
-            Err(err) => Err((err, self)),
-        }
+    #[inline]
+    pub fn get_range_mut(&mut self, off: usize, len: usize) -> Option<&mut [u8]> {
+        self.get_mut(off..off.checked_add(len)?)
     }
 
-    /// Writes modifications back to the filesystem.
+    /// Copies the bytes in `src` to the position starting at `dst`.
     ///
-    /// Flushes will happen automatically, but this will invoke a flush and
-    /// return any errors with doing so.
-    pub fn flush(&self, file: &File, mode: Flush) -> Result<()> {
-        unsafe {
-            let (ptr, len) = Size::page().bounds(self.ptr, self.len);
-            flush(ptr, file, len, mode)
+    /// The ranges may overlap, so this is suitable for shifting records in
+    /// place within a mapped file. Unlike the slice method of the same name,
+    /// an invalid range returns an [`Input::InvalidRange`] error rather than
+    /// panicking, and the map is left unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let mut map = MapMut::with_options().len(8).alloc()?;
+    /// map.copy_from_slice(b"abcdefgh");
+    /// map.copy_within(0..6, 2)?;
+    /// assert_eq!(b"ababcdef", &map[..]);
+    /// assert!(map.copy_within(4..8, 6).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    pub fn copy_within(&mut self, src: Range<usize>, dst: usize) -> Result<()> {
+        let len = src.end.wrapping_sub(src.start);
+        if src.start > src.end || src.end > self.len || dst > self.len - len {
+            return Err(Error::input(Operation::Write, Input::InvalidRange));
         }
+        unsafe { ptr::copy(self.ptr.add(src.start), self.ptr.add(dst), len) };
+        Ok(())
     }
 
-    /// Writes modifications back to the filesystem for a sub-range of the map.
+    /// Views `count` atomic values starting at byte `offset` of the map.
+    ///
+    /// The atomics operate on the mapped memory directly, so for a shared
+    /// mapping of a file or memory fd they coordinate with every other
+    /// process mapping the same pages. This is the building block for
+    /// cross-process structures such as a job queue kept in a shared ring.
+    ///
+    /// An [`Input::InvalidRange`] error is returned when the values extend
+    /// past the end of the map, and an [`Input::Unaligned`] error when
+    /// `offset` is not aligned for `T`.
+    ///
+    /// Each process must agree on the layout of the region, including the
+    /// offset, count, and width of the values, as nothing is recorded in the
+    /// map itself. Memory orderings apply across processes just as they do
+    /// across threads, so pair `Release` stores with `Acquire` loads when
+    /// publishing other data in the map. Atomics on a private or
+    /// copy-on-write map are only visible within this process, and storing
+    /// through a map that is not writable will fault.
+    ///
+    /// The map stays mutably borrowed while the atomics are in use, as a
+    /// store through them would otherwise change bytes behind a `&[u8]`
+    /// borrowed from the map. This cannot stop another process from changing
+    /// the pages of a shared map, so read such a region only through atomics
+    /// or volatile reads.
     ///
-    /// Flushes will happen automatically, but this will invoke a flush and
-    /// return any errors with doing so.
-    pub fn flush_range(&self, file: &File, off: usize, len: usize, mode: Flush) -> Result<()> {
-        if off + len > self.len {
-            Err(Error::input(Operation::Flush, Input::InvalidRange))
-        } else {
-            unsafe {
-                let (ptr, len) = Size::page().bounds(self.ptr.add(off), len);
-                flush(ptr, file, len, mode)
-            }
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    /// use std::sync::atomic::{AtomicU64, Ordering};
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let mut map = MapMut::new(4096)?;
+    /// let slots = map.as_atomic_array::<AtomicU64>(64, 4)?;
+    /// slots[1].fetch_add(3, Ordering::AcqRel);
+    /// assert_eq!(slots[1].load(Ordering::Acquire), 3);
+    /// assert!(map.as_atomic_array::<AtomicU64>(4, 1).is_err());
+    /// assert!(map.as_atomic_array::<AtomicU64>(4088, 2).is_err());
+    /// assert_eq!(3, map[72]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    /// [`Input::Unaligned`]: enum.Input.html#variant.Unaligned
+    pub fn as_atomic_array<T: Atomic>(&mut self, offset: usize, count: usize) -> Result<&[T]> {
+        let end = count
+            .checked_mul(mem::size_of::<T>())
+            .and_then(|len| len.checked_add(offset));
+        match end {
+            Some(end) if end <= self.len => {}
+            _ => return Err(Error::input(Operation::Cast, Input::InvalidRange)),
         }
-    }
-
-    /// Updates the advise for the entire mapped region..
-    pub fn advise(&self, adv: Advise) -> Result<()> {
-        unsafe {
-            let (ptr, len) = Size::page().bounds(self.ptr, self.len);
-            advise(ptr, len, adv)
+        let ptr = unsafe { self.ptr.add(offset) };
+        if !(ptr as usize).is_multiple_of(mem::align_of::<T>()) {
+            return Err(Error::input(Operation::Cast, Input::Unaligned));
         }
+        // Atomics have the same layout as their integers and any bit pattern
+        // is valid. The mutable borrow of the map keeps any slice of it from
+        // being read while the atomics may change the bytes.
+        Ok(unsafe { slice::from_raw_parts(ptr as *const T, count) })
     }
 
-    /// Updates the advise for a specific range of the mapped region.
-    pub fn advise_range(&self, off: usize, len: usize, adv: Advise) -> Result<()> {
-        if off + len > self.len {
-            Err(Error::input(Operation::Advise, Input::InvalidRange))
-        } else {
-            unsafe {
-                let (ptr, len) = Size::page().bounds(self.ptr.add(off), len);
-                advise(ptr, len, adv)
-            }
-        }
+    /// Views a single atomic value at byte `offset` of the map.
+    ///
+    /// This is the same as [`.as_atomic_array()`] with a count of one, and
+    /// returns the same errors, and the map is likewise borrowed mutably
+    /// while the value is in use. Unlike a plain read through the slice of
+    /// the map, every access to the value is made to memory, so the compiler
+    /// never assumes it is left unchanged by another process.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    /// use std::sync::atomic::{AtomicU32, Ordering};
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let mut map = MapMut::shared(4096)?;
+    /// let flag = map.as_atomic::<AtomicU32>(16)?;
+    /// flag.store(1, Ordering::Release);
+    /// assert_eq!(1, flag.load(Ordering::Acquire));
+    /// assert!(map.as_atomic::<AtomicU32>(18).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.as_atomic_array()`]: #method.as_atomic_array
+    #[inline]
+    pub fn as_atomic<T: Atomic>(&mut self, offset: usize) -> Result<&T> {
+        Ok(&self.as_atomic_array(offset, 1)?[0])
     }
+}
 
-    /// Lock all mapped physical pages into memory.
-    pub fn lock(&self) -> Result<()> {
-        unsafe {
-            let (ptr, len) = Size::page().bounds(self.ptr, self.len);
-            lock(ptr, len)
-        }
+// Records a copy-on-write protection as private, so the sharing mode is
+// kept when the protection is later changed.
+#[inline]
+fn private_flags(prot: Protect, flags: MapFlags) -> MapFlags {
+    if prot == Protect::ReadCopy {
+        flags | MapFlags::PRIVATE
+    } else {
+        flags
     }
+}
 
-    /// Lock a range of physical pages into memory.
-    pub fn lock_range(&self, off: usize, len: usize) -> Result<()> {
-        if off + len > self.len {
-            Err(Error::input(Operation::Lock, Input::InvalidRange))
-        } else {
-            unsafe {
-                let (ptr, len) = Size::page().bounds(self.ptr.add(off), len);
-                lock(ptr, len)
-            }
+impl FromPtr for MapMut {
+    unsafe fn from_ptr(ptr: *mut u8, len: usize, prot: Protect) -> Self {
+        Self {
+            ptr,
+            len,
+            backing: Backing::Anon,
+            prot,
+            flags: private_flags(prot, MapFlags::NONE),
+            file: None,
+            flush_on_drop: None,
+            extra: None,
         }
     }
 
-    /// Unlock all mapped physical pages into memory.
-    pub fn unlock(&self) -> Result<()> {
-        unsafe {
-            let (ptr, len) = Size::page().bounds(self.ptr, self.len);
-            unlock(ptr, len)
+    unsafe fn from_file_ptr(
+        ptr: *mut u8,
+        len: usize,
+        off: u64,
+        prot: Protect,
+        flags: MapFlags,
+    ) -> Self {
+        Self {
+            ptr,
+            len,
+            backing: Backing::File(off),
+            prot,
+            flags: private_flags(prot, flags),
+            file: None,
+            flush_on_drop: None,
+            extra: None,
         }
     }
 
-    /// Unlock a range of physical pages into memory.
-    pub fn unlock_range(&self, off: usize, len: usize) -> Result<()> {
-        if off + len > self.len {
-            Err(Error::input(Operation::Unlock, Input::InvalidRange))
-        } else {
-            unsafe {
-                let (ptr, len) = Size::page().bounds(self.ptr.add(off), len);
-                unlock(ptr, len)
-            }
-        }
+    fn set_file(&mut self, file: File) {
+        self.file = Some(file);
     }
-}
 
-impl FromPtr for MapMut {
-    unsafe fn from_ptr(ptr: *mut u8, len: usize) -> Self {
-        Self { ptr, len }
+    fn set_huge(&mut self, size: usize) {
+        self.backing = Backing::Huge(size);
     }
 }
 
@@ -408,12 +4848,7 @@ impl SpanMut for MapMut {
 
 impl Drop for MapMut {
     fn drop(&mut self) {
-        unsafe {
-            if self.len > 0 {
-                let (ptr, len) = Size::alloc().bounds(self.ptr, self.len);
-                unmap(ptr, len).unwrap_or_default();
-            }
-        }
+        self.release().unwrap_or_default();
     }
 }
 
@@ -461,6 +4896,65 @@ impl TryFrom<Map> for MapMut {
     }
 }
 
+/// Maps the whole file read-write, retaining the file in the map.
+///
+/// The file must be open for writing. An empty file yields an empty map.
+/// On failure, the file is returned with the error.
+impl TryFrom<File> for MapMut {
+    type Error = (Error, File);
+
+    fn try_from(file: File) -> ConvertResult<Self, File> {
+        map_whole(Self::with_options(), file)
+    }
+}
+
+fn map_whole<T: FromPtr>(opts: Options<T>, file: File) -> ConvertResult<T, File> {
+    match opts.map(&file) {
+        Ok(mut map) => {
+            map.set_file(file);
+            Ok(map)
+        }
+        Err(err) => Err((err, file)),
+    }
+}
+
+/// An advisory lock on a file that is released when dropped.
+///
+/// This is created by [`Map::lock_file()`] or [`MapMut::lock_file()`].
+///
+/// [`Map::lock_file()`]: struct.Map.html#method.lock_file
+/// [`MapMut::lock_file()`]: struct.MapMut.html#method.lock_file
+#[derive(Debug)]
+pub struct FileLock<'a> {
+    file: &'a File,
+}
+
+impl Drop for FileLock<'_> {
+    fn drop(&mut self) {
+        unlock_file(self.file).unwrap_or_default();
+    }
+}
+
+/// An advisory lock on a byte range of a file that is released when dropped.
+///
+/// This is created by [`Map::lock_file_range()`] or
+/// [`MapMut::lock_file_range()`].
+///
+/// [`Map::lock_file_range()`]: struct.Map.html#method.lock_file_range
+/// [`MapMut::lock_file_range()`]: struct.MapMut.html#method.lock_file_range
+#[derive(Debug)]
+pub struct FileRangeLock<'a> {
+    file: &'a File,
+    off: u64,
+    len: u64,
+}
+
+impl Drop for FileRangeLock<'_> {
+    fn drop(&mut self) {
+        unlock_file_range(self.file, self.off, self.len).unwrap_or_default();
+    }
+}
+
 /// Options and flags which can be used to configure how a map is allocated.
 ///
 /// This builder exposes the ability to configure how a [`Map`] or a [`MapMut`]
@@ -502,6 +4996,15 @@ pub struct Options<T: FromPtr> {
     offset: usize,
     protect: Protect,
     truncate: bool,
+    align: Option<Size>,
+    flags: MapFlags,
+    advise: Option<Advise>,
+    populate: bool,
+    locked: bool,
+    low_addr: bool,
+    huge: bool,
+    extend: bool,
+    file_len: Option<u64>,
     _marker: marker::PhantomData<fn() -> T>,
 }
 
@@ -524,6 +5027,15 @@ impl<T: FromPtr> Options<T> {
             offset: 0,
             protect: Protect::ReadOnly,
             truncate: false,
+            align: None,
+            flags: MapFlags::NONE,
+            advise: None,
+            populate: false,
+            locked: false,
+            low_addr: false,
+            huge: false,
+            extend: false,
+            file_len: None,
             _marker: marker::PhantomData,
         }
     }
@@ -576,19 +5088,80 @@ impl<T: FromPtr> Options<T> {
     /// let (mut map2, _) = MapMut::with_options().copy().open("README.md")?;
     /// let first = map1[0];
     ///
-    /// map1[0] = b'X';
+    /// map1[0] = b'X';
+    ///
+    /// assert_eq!(first, map2[0]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`MapMut::with_options()`]: struct.MapMut.html#method.with_options
+    /// [`Map`]: struct.Map.html
+    /// [`Map::into_map_mut()`]: struct.Map.html#method.into_map_mut
+    pub fn copy(&mut self) -> &mut Self {
+        self.open_options.write(false);
+        self.protect = Protect::ReadCopy;
+        self
+    }
+
+    /// Sets the visibility of modifications to a file mapping.
+    ///
+    /// By default, a [`Map`] or a writable [`MapMut`] is [`Sharing::Shared`]
+    /// so that changes are written back to the file. With [`Sharing::Private`]
+    /// the file is opened with the same access, but any modifications are
+    /// copied on write and are never written back. [`Sharing::Copy`] is the
+    /// same as calling [`.copy()`].
+    ///
+    /// Selecting [`Sharing::Shared`] after [`.copy()`] restores write access
+    /// to the file, as with [`.write()`].
+    ///
+    /// This has no effect on anonymous mappings.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Map, MapMut, Sharing};
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// # std::fs::write(&path, b"this is a test")?;
+    /// let (mut private, _) = MapMut::with_options().sharing(Sharing::Private).open(&path)?;
+    /// let (mut shared, _) = MapMut::with_options().sharing(Sharing::Shared).open(&path)?;
+    ///
+    /// private[..4].clone_from_slice(b"that");
+    /// assert_eq!(b"this", &shared[..4]);
     ///
-    /// assert_eq!(first, map2[0]);
+    /// shared[10..].clone_from_slice(b"game");
+    ///
+    /// let (map, _) = Map::with_options().open(&path)?;
+    /// assert_eq!(b"this is a game", &map[..]);
     /// # Ok(())
     /// # }
     /// ```
     ///
-    /// [`MapMut::with_options()`]: struct.MapMut.html#method.with_options
     /// [`Map`]: struct.Map.html
-    /// [`Map::into_map_mut()`]: struct.Map.html#method.into_map_mut
-    pub fn copy(&mut self) -> &mut Self {
-        self.open_options.write(false);
-        self.protect = Protect::ReadCopy;
+    /// [`MapMut`]: struct.MapMut.html
+    /// [`Sharing::Shared`]: enum.Sharing.html#variant.Shared
+    /// [`Sharing::Private`]: enum.Sharing.html#variant.Private
+    /// [`Sharing::Copy`]: enum.Sharing.html#variant.Copy
+    /// [`.copy()`]: #method.copy
+    /// [`.write()`]: #method.write
+    pub fn sharing(&mut self, sharing: Sharing) -> &mut Self {
+        match sharing {
+            Sharing::Shared => {
+                if self.protect == Protect::ReadCopy {
+                    self.write();
+                }
+                self.flags.set(MapFlags::PRIVATE, false);
+            }
+            Sharing::Private => self.flags.set(MapFlags::PRIVATE, true),
+            Sharing::Copy => {
+                self.copy();
+            }
+        }
         self
     }
 
@@ -718,6 +5291,16 @@ impl<T: FromPtr> Options<T> {
     /// from the beginning of the resource. This must be within the range of
     /// the file.
     ///
+    /// The offset need not be aligned. The underlying mapping starts at the
+    /// offset rounded down to the [allocation size] and covers only the units
+    /// containing the mapped bytes, so a range of `len` bytes at `offset`
+    /// maps `Size::alloc().round(offset % allocation_size() + len)` bytes. A
+    /// range that crosses a unit boundary, such as 2 bytes starting at the
+    /// last byte of a unit, maps both units. An empty range creates no
+    /// mapping at all.
+    ///
+    /// [allocation size]: fn.allocation_size.html
+    ///
     /// # Examples
     ///
     /// ```
@@ -742,6 +5325,258 @@ impl<T: FromPtr> Options<T> {
         self
     }
 
+    /// Sets a required alignment for the offset and length of a file mapping.
+    ///
+    /// When mapping a file that is also accessed using direct I/O (e.g.
+    /// `O_DIRECT`), the regions must be aligned to the device block size
+    /// rather than the page size. When set, [`.open()`], [`.open_if()`],
+    /// [`.map()`], and [`.map_if()`] will return an error if either the
+    /// offset or the resolved length is not a multiple of the size. A
+    /// suitable size may be obtained with [`Size::block()`].
+    ///
+    /// This has no effect on anonymous mappings.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Map, Size};
+    /// use std::path::PathBuf;
+    /// use std::fs;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// fs::write(&path, vec![0u8; 8192])?;
+    ///
+    /// let block = unsafe { Size::with_size(512) };
+    /// assert!(Map::with_options().align(block).offset(512).len(1024).open(&path).is_ok());
+    /// assert!(Map::with_options().align(block).offset(100).len(1024).open(&path).is_err());
+    /// assert!(Map::with_options().align(block).offset(512).len(1000).open(&path).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.open()`]: #method.open
+    /// [`.open_if()`]: #method.open_if
+    /// [`.map()`]: #method.map
+    /// [`.map_if()`]: #method.map_if
+    /// [`Size::block()`]: struct.Size.html#method.block
+    pub fn align(&mut self, size: Size) -> &mut Self {
+        self.align = Some(size);
+        self
+    }
+
+    /// Sets the option to use synchronous page faults for persistent memory.
+    ///
+    /// On a DAX-mounted file system backed by persistent memory, this maps
+    /// the file with `MAP_SYNC | MAP_SHARED_VALIDATE`. The kernel guarantees
+    /// that the file metadata needed to reach a written page is durable
+    /// before the page becomes writable, so any store that has been flushed
+    /// from the CPU cache is persistent without calling [`.flush()`].
+    ///
+    /// This is only supported on Linux for a [`.write()`] mapping. If the
+    /// file system does not support `MAP_SYNC` the mapping will fail, and on
+    /// other platforms an [`Input::Unsupported`] error is returned. This has
+    /// no effect on anonymous mappings.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let (mut map, file) = MapMut::with_options()
+    ///     .sync(true)
+    ///     .open("/mnt/pmem/example")?;
+    /// map[..4].clone_from_slice(b"test");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.flush()`]: struct.MapMut.html#method.flush
+    /// [`.write()`]: #method.write
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    pub fn sync(&mut self, sync: bool) -> &mut Self {
+        self.flags.set(MapFlags::SYNC, sync);
+        self
+    }
+
+    /// Sets an access pattern hint to apply to the new mapping.
+    ///
+    /// The hint is applied before the pages are touched or locked, so a
+    /// hint such as [`Advise::WillNeed`] or [`Advise::Sequential`] also
+    /// speeds up [`.populate()`] where it touches each page. Together with [`.locked()`], this loads a
+    /// mapping and pins it in memory in one call:
+    ///
+    /// ```
+    /// use vmap::{Advise, Map};
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let (map, file) = Map::with_options()
+    ///     .advise(Advise::Sequential)
+    ///     .populate(true)
+    ///     .locked(true)
+    ///     .open("README.md")?;
+    /// assert!(map.iter().any(|&b| b == b'v'));
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// If the hint fails, the mapping is released and the error reports the
+    /// advise operation.
+    ///
+    /// [`Advise::WillNeed`]: enum.Advise.html#variant.WillNeed
+    /// [`Advise::Sequential`]: enum.Advise.html#variant.Sequential
+    /// [`.populate()`]: #method.populate
+    /// [`.locked()`]: #method.locked
+    pub fn advise(&mut self, adv: Advise) -> &mut Self {
+        self.advise = Some(adv);
+        self
+    }
+
+    /// Sets the option to fault in every page once mapped.
+    ///
+    /// On Linux and Android the mapping is created with
+    /// [`MapFlags::POPULATE`], so the kernel fills in the page tables before
+    /// the constructor returns. A writable anonymous map is faulted in for
+    /// writing, so its first writes do not fault either. Elsewhere, and for
+    /// private writable file maps where `MAP_POPULATE` would copy every page,
+    /// this reads a byte from each page after any [`.advise()`] hint is
+    /// applied. Either way, the first access to the map does not wait for
+    /// I/O. See [`MapMut::prefault()`] for details.
+    ///
+    /// [`MapFlags::POPULATE`]: struct.MapFlags.html#associatedconstant.POPULATE
+    /// [`.advise()`]: #method.advise
+    /// [`MapMut::prefault()`]: struct.MapMut.html#method.prefault
+    pub fn populate(&mut self, populate: bool) -> &mut Self {
+        self.populate = populate;
+        self
+    }
+
+    /// Sets the option to lock the pages into memory once mapped.
+    ///
+    /// The pages are locked last, after any [`.advise()`] hint and
+    /// [`.populate()`]. Locking is subject to the resource limit described
+    /// in [`MapMut::lock_prefault()`]. If it fails, the mapping is released
+    /// and the error reports the lock operation.
+    ///
+    /// [`.advise()`]: #method.advise
+    /// [`.populate()`]: #method.populate
+    /// [`MapMut::lock_prefault()`]: struct.MapMut.html#method.lock_prefault
+    pub fn locked(&mut self, locked: bool) -> &mut Self {
+        self.locked = locked;
+        self
+    }
+
+    /// Sets the option to allocate within the low 2GiB of the address space.
+    ///
+    /// This suits code that must reach the allocation with 32-bit relative
+    /// or absolute addresses, such as some JIT and FFI uses. On Linux for
+    /// x86-64 this uses `MAP_32BIT`, and on Windows the free address ranges
+    /// below 2GiB are searched for one that fits. If no low range is free the
+    /// allocation fails.
+    ///
+    /// This only applies to anonymous allocations from [`.alloc()`]. Mapping
+    /// a file with this option, or allocating on any other platform, returns
+    /// an [`Input::Unsupported`] error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # if cfg!(any(windows, all(target_os = "linux", target_arch = "x86_64"))) {
+    /// let map = MapMut::with_options().low_addr(true).len(4096).alloc()?;
+    /// assert!((map.as_ptr() as usize) + map.len() <= 1 << 31);
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.alloc()`]: #method.alloc
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    pub fn low_addr(&mut self, low_addr: bool) -> &mut Self {
+        self.low_addr = low_addr;
+        self
+    }
+
+    /// Sets the option to allocate from the huge pages of the system.
+    ///
+    /// The length of the allocation is rounded up to a multiple of
+    /// [`Size::huge()`], and the pages come from the pool of huge pages
+    /// reserved by the system. See [`MapMut::new_huge()`] for the platforms
+    /// supported and how the pool is configured. No fallback to normal pages
+    /// is made, so the allocation fails when the pool is exhausted.
+    ///
+    /// This only applies to anonymous allocations from [`.alloc()`]. Mapping
+    /// a file with this option, or combining it with [`.low_addr()`],
+    /// returns an [`Input::Unsupported`] error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{MapMut, Size};
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// if let Ok(map) = MapMut::with_options().huge_pages(true).len(100).alloc() {
+    ///     assert_eq!(map.len(), 100);
+    ///     assert_eq!(map.capacity(), Size::huge()?.size(1));
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Size::huge()`]: struct.Size.html#method.huge
+    /// [`MapMut::new_huge()`]: struct.MapMut.html#method.new_huge
+    /// [`.alloc()`]: #method.alloc
+    /// [`.low_addr()`]: #method.low_addr
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    pub fn huge_pages(&mut self, huge: bool) -> &mut Self {
+        self.huge = huge;
+        self
+    }
+
+    /// Sets the known length of the file to be mapped.
+    ///
+    /// Mapping a file normally queries its length to check the requested
+    /// range. When the length is already known, such as when mapping many
+    /// ranges of the same file, this skips the `fstat` call and checks the
+    /// range against `len` instead. Any [`.resize()`] or [`.truncate()`] is
+    /// still applied and replaces the known length.
+    ///
+    /// The length is trusted as given. If the file is actually shorter, the
+    /// range past its end is mapped anyway, and accessing a page that lies
+    /// entirely past the end raises `SIGBUS` on Unix platforms.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::Map;
+    /// use std::fs::File;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let f = File::open("README.md")?;
+    /// let flen = f.metadata()?.len();
+    /// let mut opts = Map::with_options();
+    /// opts.file_len(flen).len(16);
+    /// for off in [0, 16, 32] {
+    ///     let map = opts.offset(off).map(&f)?;
+    ///     assert_eq!(16, map.len());
+    /// }
+    /// assert!(opts.offset(flen as usize).map(&f).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.resize()`]: #method.resize
+    /// [`.truncate()`]: #method.truncate
+    pub fn file_len(&mut self, len: u64) -> &mut Self {
+        self.file_len = Some(len);
+        self
+    }
+
     /// Sets the byte length extent of the mapping.
     ///
     /// For file-based mappings, this length must be available in the
@@ -895,7 +5730,7 @@ impl<T: FromPtr> Options<T> {
     ///
     /// In order for the file to be resized, [`.write()`] access must be used.
     ///
-    /// This has no affect on anonymous mappings.
+    /// This has no effect on anonymous mappings.
     ///
     /// # Resize with `Extent::End`
     ///
@@ -1032,6 +5867,51 @@ impl<T: FromPtr> Options<T> {
         self
     }
 
+    /// Sets the option to extend the file to cover the requested range.
+    ///
+    /// When the [`.offset()`] plus an [`Extent::Exact`] or [`Extent::Min`]
+    /// length lies past the end of the file, the file is extended with zeros
+    /// to end exactly where the range does, rather than failing to map. A
+    /// file that already covers the range is left as is, so it is never
+    /// shortened. This suits files that grow as they are appended to, such
+    /// as a log, where each new range is mapped past the current end. The
+    /// extension is applied after any [`.resize()`].
+    ///
+    /// In order for the file to be extended, [`.write()`] access must be used.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::MapMut;
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// std::fs::write(&path, b"head")?;
+    ///
+    /// let (mut map, file) = MapMut::with_options()
+    ///     .extend(true)
+    ///     .offset(4096)
+    ///     .len(100)
+    ///     .open(&path)?;
+    /// map[..4].copy_from_slice(b"tail");
+    /// assert_eq!(4196, file.metadata()?.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.offset()`]: #method.offset
+    /// [`.resize()`]: #method.resize
+    /// [`.write()`]: #method.write
+    /// [`Extent::Exact`]: enum.Extent.html#variant.Exact
+    /// [`Extent::Min`]: enum.Extent.html#variant.Min
+    pub fn extend(&mut self, extend: bool) -> &mut Self {
+        self.extend = extend;
+        self
+    }
+
     /// Opens and maps a file using the current options specified by `self`.
     ///
     /// Unlike [`.open_if()`], when the requested offset or length lies outside of
@@ -1113,7 +5993,10 @@ impl<T: FromPtr> Options<T> {
     /// Maps an open `File` using the current options specified by `self`.
     ///
     /// Unlike [`.map_if()`], when the requested offset or length lies outside of
-    /// the underlying file, an error is returned.
+    /// the underlying file, an error is returned. The error is an
+    /// [`Input::InvalidRange`] that records the requested offset, the
+    /// requested length when one was given, and the length of the file, so
+    /// the file may be extended before trying again.
     ///
     /// # Examples
     ///
@@ -1140,9 +6023,107 @@ impl<T: FromPtr> Options<T> {
     /// ```
     ///
     /// [`.map_if()`]: #method.map_if
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
     pub fn map(&self, f: &File) -> Result<T> {
-        self.map_if(f)?
-            .ok_or_else(|| Error::input(Operation::MapFile, Input::InvalidRange))
+        if let Some(map) = self.map_if(f)? {
+            return Ok(map);
+        }
+        let mut err = Error::input(Operation::MapFile, Input::InvalidRange)
+            .with_offset(self.offset as u64)
+            .with_file_len(f.metadata().map_err(map_file_err)?.len());
+        if let Extent::Min(len) | Extent::Exact(len) = self.len {
+            err = err.with_length(len);
+        }
+        Err(err)
+    }
+
+    /// Maps an open `File` and retains it in the map.
+    ///
+    /// This is the same as [`.map()`], except that the map takes ownership
+    /// of the file. The handle stays open for as long as the map, including
+    /// after converting between [`Map`] and [`MapMut`], and is available from
+    /// the map's `.file()` method, such as to flush the map. This is not
+    /// required for the map to remain valid on any platform, as the system
+    /// keeps its own reference to the file while it is mapped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{Flush, MapMut};
+    /// use std::fs::OpenOptions;
+    /// use std::path::PathBuf;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// # let tmp = tempdir::TempDir::new("vmap")?;
+    /// let path: PathBuf = /* path to file */
+    /// # tmp.path().join("example");
+    /// # std::fs::write(&path, b"this is a test")?;
+    /// let f = OpenOptions::new().read(true).write(true).open(&path)?;
+    /// let mut map = MapMut::with_options().map_owned(f)?;
+    /// map[..4].clone_from_slice(b"that");
+    /// map.flush(map.file().unwrap(), Flush::Sync)?;
+    /// assert_eq!(b"that is a test", &std::fs::read(&path)?[..]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.map()`]: #method.map
+    /// [`Map`]: struct.Map.html
+    /// [`MapMut`]: struct.MapMut.html
+    pub fn map_owned(&self, f: File) -> Result<T> {
+        let mut map = self.map(&f)?;
+        map.set_file(f);
+        Ok(map)
+    }
+
+    /// Maps an open file descriptor that need not be a `File`.
+    ///
+    /// This is the same as [`.map()`], but accepts anything that can lend a
+    /// descriptor, such as an `OwnedFd` or `BorrowedFd` for a memfd, a
+    /// shared memory object, a device node, or a descriptor received over a
+    /// Unix socket. The descriptor is only borrowed and is never closed by
+    /// the map. The range is checked against the size reported by `fstat`,
+    /// which is zero for many devices, so use [`.file_len()`] to give the
+    /// size of such a device instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::Map;
+    /// use std::os::fd::OwnedFd;
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let fd: OwnedFd = std::fs::File::open("README.md")?.into();
+    /// let map = Map::with_options().len(4).map_fd(&fd)?;
+    /// assert_eq!(4, map.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`.map()`]: #method.map
+    /// [`.file_len()`]: #method.file_len
+    #[cfg(unix)]
+    pub fn map_fd<F: AsFd>(&self, fd: F) -> Result<T> {
+        use std::os::unix::io::FromRawFd;
+        // The descriptor is borrowed, so the `File` must never be dropped.
+        let f = mem::ManuallyDrop::new(unsafe { File::from_raw_fd(fd.as_fd().as_raw_fd()) });
+        self.map(&f)
+    }
+
+    /// Maps an open file handle that need not be a `File`.
+    ///
+    /// This is the same as [`.map()`], but accepts anything that can lend a
+    /// handle, such as an `OwnedHandle` or `BorrowedHandle` received from
+    /// another process. The handle is only borrowed and is never closed by
+    /// the map.
+    ///
+    /// [`.map()`]: #method.map
+    #[cfg(windows)]
+    pub fn map_handle<H: AsHandle>(&self, handle: H) -> Result<T> {
+        use std::os::windows::io::FromRawHandle;
+        // The handle is borrowed, so the `File` must never be dropped.
+        let f = unsafe { File::from_raw_handle(handle.as_handle().as_raw_handle()) };
+        self.map(&mem::ManuallyDrop::new(f))
     }
 
     /// Maps an open `File` with the options specified by `self` if the provided
@@ -1151,6 +6132,14 @@ impl<T: FromPtr> Options<T> {
     /// Unlike [`.map()`], when the requested offset or length lies outside of
     /// the underlying file, `Ok(None)` will be returned rather than an error.
     ///
+    /// A zero-length range, such as the end of the file, yields an empty map
+    /// without creating an OS mapping.
+    ///
+    /// A range that ends within the file is always accepted, even when the
+    /// last page of the mapping extends past the end of the file. The bytes
+    /// of that page past the end of the file are zero-filled and never
+    /// written back, which [`MapMut::tail_past_eof()`] reports.
+    ///
     /// # Examples
     ///
     /// ```
@@ -1179,9 +6168,24 @@ impl<T: FromPtr> Options<T> {
     /// ```
     ///
     /// [`.map()`]: #method.map
+    /// [`MapMut::tail_past_eof()`]: struct.MapMut.html#method.tail_past_eof
     pub fn map_if(&self, f: &File) -> Result<Option<T>> {
+        if self.low_addr || self.huge {
+            return Err(Error::input(Operation::MapFile, Input::Unsupported));
+        }
         let off = self.offset;
-        let mut flen = f.metadata().map_err(map_file_err)?.len() as usize;
+        if let Some(sz) = self.align {
+            if sz.offset(off) != 0 {
+                return Err(Error::input(Operation::MapFile, Input::Unaligned));
+            }
+        }
+
+        let flen = match self.file_len {
+            Some(len) => len,
+            None => f.metadata().map_err(map_file_err)?.len(),
+        };
+        let mut flen = usize::try_from(flen)
+            .map_err(|_| Error::input(Operation::MapFile, Input::InvalidRange))?;
 
         let resize = |sz: usize| f.set_len(sz as u64).map(|_| sz).map_err(map_file_err);
 
@@ -1196,6 +6200,15 @@ impl<T: FromPtr> Options<T> {
             _ => flen,
         };
 
+        if let (true, &Extent::Min(l) | &Extent::Exact(l)) = (self.extend, &self.len) {
+            let end = off
+                .checked_add(l)
+                .ok_or_else(|| Error::input(Operation::MapFile, Input::InvalidRange))?;
+            if end > flen {
+                flen = resize(end)?;
+            }
+        }
+
         if flen < off {
             return Ok(None);
         }
@@ -1208,14 +6221,38 @@ impl<T: FromPtr> Options<T> {
             Extent::Exact(l) => l,
         };
 
-        let mapoff = Size::alloc().truncate(off);
-        let maplen = len + (off - mapoff);
-        let ptr = map_file(f, mapoff, maplen, self.protect)?;
-        unsafe { Ok(Some(T::from_ptr(ptr.add(off - mapoff), len))) }
+        if let Some(sz) = self.align {
+            if sz.offset(len) != 0 {
+                return Err(Error::input(Operation::MapFile, Input::Unaligned));
+            }
+        }
+
+        file_end(off, len)?;
+        if len == 0 {
+            let ptr = empty_ptr();
+            return unsafe {
+                T::try_from_file_ptr(ptr, 0, off as u64, self.protect, self.flags).map(Some)
+            };
+        }
+
+        let (mapoff, maplen) = file_span(off, len);
+        let populate = self.populate_flags(true);
+        let ptr = map_file_with(f, mapoff, maplen, self.protect, self.flags | populate)?;
+        let map = unsafe {
+            let pg = ptr.wrapping_add(off - mapoff);
+            let map = T::try_from_file_ptr(pg, len, off as u64, self.protect, self.flags);
+            or_unmap(map, ptr, maplen)?
+        };
+        register_mapping(ptr, maplen);
+        track_map(Size::alloc().round(maplen));
+        unsafe { self.prepare(ptr.wrapping_add(off - mapoff), len, !populate.is_empty())? };
+        Ok(Some(map))
     }
 
     /// Creates an anonymous allocation using the options specified by `self`.
     ///
+    /// A zero length yields an empty map without creating an OS mapping.
+    ///
     /// # Examples
     ///
     /// ```
@@ -1228,15 +6265,79 @@ impl<T: FromPtr> Options<T> {
     /// # }
     /// ```
     pub fn alloc(&self) -> Result<T> {
+        if self.huge && self.low_addr {
+            return Err(Error::input(Operation::MapAnonymous, Input::Unsupported));
+        }
+        let size = if self.huge {
+            Size::huge()?
+        } else {
+            Size::alloc()
+        };
         let off = Size::page().offset(self.offset);
         let len = match self.len {
-            Extent::End => Size::alloc().round(off + 1) - off,
-            Extent::Min(l) => Size::alloc().round(off + l) - off,
+            Extent::End => size.round(off + 1) - off,
+            Extent::Min(l) => size.round(off + l) - off,
             Extent::Max(l) | Extent::Exact(l) => l,
         };
 
-        let ptr = map_anon(off + len, self.protect)?;
-        unsafe { Ok(T::from_ptr(ptr.add(off), len)) }
+        if len == 0 {
+            return unsafe { T::try_from_ptr(empty_ptr(), 0, self.protect) };
+        }
+
+        let populate = self.populate_flags(false);
+        let (ptr, maplen) = if self.huge {
+            let maplen = size.round(off + len);
+            (map_anon_huge(maplen, self.protect)?, maplen)
+        } else if self.low_addr {
+            (map_anon_low(off + len, self.protect)?, off + len)
+        } else {
+            (map_anon_with(off + len, self.protect, populate)?, off + len)
+        };
+        let map = unsafe {
+            let map = T::try_from_ptr(ptr.wrapping_add(off), len, self.protect);
+            let mut map = or_unmap(map, ptr, maplen)?;
+            if self.huge {
+                map.set_huge(size.size(1));
+            }
+            map
+        };
+        track_map(Size::alloc().round(maplen));
+        let populated = !self.low_addr && !self.huge && !populate.is_empty();
+        unsafe { self.prepare(ptr.wrapping_add(off), len, populated)? };
+        Ok(map)
+    }
+
+    // Gets the flags that populate a new mapping in the system call. This is
+    // empty when the pages are to be touched instead, which includes private
+    // writable file maps, as `MAP_POPULATE` would copy every page of those.
+    fn populate_flags(&self, file: bool) -> MapFlags {
+        let private = self.protect == Protect::ReadCopy
+            || (self.protect == Protect::ReadWrite && self.flags.contains(MapFlags::PRIVATE));
+        if self.populate
+            && cfg!(any(target_os = "linux", target_os = "android"))
+            && !(file && private)
+        {
+            MapFlags::POPULATE
+        } else {
+            MapFlags::NONE
+        }
+    }
+
+    // Applies the advise, populate, and locked options to a new mapping, in
+    // that order. The pages are only touched if they were not `populated` by
+    // the system call. On error, dropping the constructed map releases it.
+    unsafe fn prepare(&self, ptr: *mut u8, len: usize, populated: bool) -> Result<()> {
+        let (pg, pglen) = Size::page().bounds(ptr, len);
+        if let Some(adv) = self.advise {
+            advise(pg, pglen, adv)?;
+        }
+        if self.populate && !populated {
+            touch(ptr as usize, len);
+        }
+        if self.locked {
+            lock(pg, pglen)?;
+        }
+        Ok(())
     }
 }
 
@@ -1246,6 +6347,75 @@ impl<T: FromPtr> Default for Options<T> {
     }
 }
 
+#[cfg(test)]
+thread_local! {
+    // Number of protection changes made by `set_protect` on this thread.
+    pub(crate) static PROTECT_CALLS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+
+    // When set, the next pointer check on this thread fails, and the address
+    // it rejected is recorded.
+    pub(crate) static FAIL_FROM_PTR: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+    pub(crate) static REJECTED_PTR: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+// Unmaps a region that was just mapped when constructing a map over it
+// fails, so an error after the system call never leaks the mapping.
+#[inline]
+unsafe fn or_unmap<T>(res: Result<T>, pg: *mut u8, len: usize) -> Result<T> {
+    if res.is_err() {
+        unmap(pg, len).unwrap_or_default();
+    }
+    res
+}
+
+// Reads one byte from each page in a range.
+unsafe fn touch(ptr: usize, len: usize) {
+    if len == 0 {
+        return;
+    }
+    let (pg, len) = Size::page().bounds(ptr as *mut u8, len);
+    for i in (0..len).step_by(Size::page().size(1)) {
+        ptr::read_volatile(pg.add(i));
+    }
+}
+
+// Empty maps are never mapped, so they use a dangling pointer that is aligned
+// to the allocation size. Page bounds of this pointer are always empty.
+fn empty_ptr() -> *mut u8 {
+    Size::alloc().size(1) as *mut u8
+}
+
+// Gets the offset and length of the file mapping that holds the bytes
+// `off..off + len`. The offset is aligned down to the allocation size and
+// the length is extended by the same amount, so the mapping covers the
+// `Size::alloc().round(off % alloc + len)` bytes from the aligned offset:
+// the fewest whole units containing the range. An empty range needs no
+// mapping and yields a zero length, regardless of the offset.
+pub(crate) fn file_span(off: usize, len: usize) -> (usize, usize) {
+    let mapoff = Size::alloc().truncate(off);
+    if len == 0 {
+        (mapoff, 0)
+    } else {
+        (mapoff, len + (off - mapoff))
+    }
+}
+
+// Gets the file offset after the bytes `off..off + len`. The end must fit
+// in the signed offset of the system calls, so a range that would overflow
+// or end past `i64::MAX` is rejected before it can reach the system as a
+// wrapped or negative offset. As with any slice, the length may not exceed
+// `isize::MAX`.
+fn file_end(off: usize, len: usize) -> Result<u64> {
+    (off as u64)
+        .checked_add(len as u64)
+        .filter(|&end| end <= i64::MAX as u64 && len <= isize::MAX as usize)
+        .ok_or_else(|| {
+            Error::input(Operation::MapFile, Input::InvalidRange)
+                .with_offset(off as u64)
+                .with_length(len)
+        })
+}
+
 fn map_file_err(e: io::Error) -> Error {
     Error::io(Operation::MapFile, e)
 }
@@ -9,3 +9,115 @@ pub use self::unix::*;
 mod windows;
 #[cfg(windows)]
 pub use self::windows::*;
+
+mod watch;
+pub(crate) use self::watch::WriteWatch;
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{Error, Input, Operation, Result};
+
+#[cfg(any(feature = "os", not(any(target_os = "macos", target_os = "ios"))))]
+use crate::Protect;
+
+/// Changes the protection of many page ranges with as few calls as possible.
+///
+/// The ranges are applied in order, and each run of ranges that share a
+/// protection and follow one another in memory, so that one ends where the
+/// next begins, is changed with a single call to [`protect()`]. Passing the
+/// ranges in address order therefore uses the fewest calls. Empty ranges are
+/// skipped. Neither Unix nor Windows has a batched form of `mprotect` or
+/// `VirtualProtect`, so each run is still one system call.
+///
+/// If a call fails, its error is returned and the ranges before it keep
+/// their new protection.
+///
+/// # Safety
+///
+/// This does not know or care if the ranges are valid. That is, a pointer
+/// may be null, not at a proper page boundary, point to a size different
+/// from its length, or worse yet, point to a properly mapped pointer from
+/// some other allocation system.
+///
+/// Generally don't use this unless you are entirely sure you are
+/// doing so correctly.
+///
+/// [`protect()`]: fn.protect.html
+#[cfg(feature = "os")]
+pub unsafe fn protect_ranges(ranges: &[(*mut u8, usize, Protect)]) -> Result<()> {
+    let mut ranges = ranges.iter().copied().filter(|&(_, len, _)| len > 0);
+    let (mut pg, mut len, mut prot) = match ranges.next() {
+        Some(range) => range,
+        None => return Ok(()),
+    };
+    for (next, next_len, next_prot) in ranges {
+        if next_prot == prot && pg.wrapping_add(len) == next {
+            len += next_len;
+        } else {
+            protect(pg, len, prot)?;
+            (pg, len, prot) = (next, next_len, next_prot);
+        }
+    }
+    protect(pg, len, prot)
+}
+
+/// Copies a page range into a new read-only anonymous allocation.
+///
+/// The allocation is `len` bytes rounded up to the allocation size, with the
+/// range copied to its start. On macOS and iOS the pages are instead
+/// remapped copy-on-write, so nothing is copied until a page of either range
+/// is written. Neither Linux nor Windows can share the pages of an existing
+/// mapping copy-on-write, so elsewhere every byte is copied.
+///
+/// # Safety
+///
+/// `pg` must be at a page boundary and `len` bytes from it must be mapped
+/// and readable.
+#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+pub unsafe fn snapshot_pages(pg: *const u8, len: usize) -> Result<*mut u8> {
+    let alloc = crate::Size::alloc().round(len);
+    let dst = map_anon(alloc, Protect::ReadCopy)?;
+    std::ptr::copy_nonoverlapping(pg, dst, len);
+    if let Err(err) = protect(dst, alloc, Protect::ReadOnly) {
+        unmap(dst, alloc).unwrap_or_default();
+        return Err(err);
+    }
+    Ok(dst)
+}
+
+// Accepts an object name that is valid on every platform. Unix requires a
+// single leading `/`, which is added by the platform layer, and Windows
+// reserves `\` for namespace prefixes.
+fn check_shm_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.contains(['/', '\\', '\0']) {
+        Err(Error::input(Operation::SharedMemory, Input::InvalidName))
+    } else {
+        Ok(())
+    }
+}
+
+// Creates a new file in `dir` with a unique name, for a temporary file that
+// is removed as soon as it is opened. The options must include `create_new`.
+fn create_temp(dir: &Path, opts: &OpenOptions) -> Result<(File, PathBuf)> {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    for _ in 0..16 {
+        let n = NEXT.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!(".vmap-{}-{}-{}", std::process::id(), n, nanos));
+        match opts.open(&path) {
+            Ok(f) => return Ok((f, path)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(Error::io(Operation::TempFile, e)),
+        }
+    }
+    Err(Error::io(
+        Operation::TempFile,
+        io::ErrorKind::AlreadyExists.into(),
+    ))
+}
@@ -0,0 +1,77 @@
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use libc::{
+    c_int, c_void, sigaction, sigemptyset, siginfo_t, SA_ONSTACK, SA_SIGINFO, SIGBUS, SIGSEGV,
+    SIG_DFL, SIG_IGN,
+};
+
+use crate::os::watch::write_fault;
+use crate::{Error, Operation, Result};
+
+// A write to a read-only page raises `SIGSEGV` on Linux and the BSDs, but
+// `SIGBUS` on macOS and iOS, so both are handled.
+const SIGNALS: [c_int; 2] = [SIGSEGV, SIGBUS];
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+static INSTALL: Mutex<()> = Mutex::new(());
+
+struct Previous(UnsafeCell<[MaybeUninit<sigaction>; 2]>);
+
+// The previous actions are written once while holding `INSTALL`, before
+// `INSTALLED` is set, and are only read afterward.
+unsafe impl Sync for Previous {}
+
+static PREVIOUS: Previous = Previous(UnsafeCell::new([MaybeUninit::uninit(); 2]));
+
+/// Installs the handler that records write faults in tracked maps.
+pub(crate) fn install_write_handler() -> Result<()> {
+    let _guard = INSTALL.lock().unwrap_or_else(|e| e.into_inner());
+    if INSTALLED.load(Ordering::Acquire) {
+        return Ok(());
+    }
+
+    unsafe {
+        let mut act = MaybeUninit::<sigaction>::zeroed().assume_init();
+        act.sa_sigaction = handle as Handler as usize;
+        // Run on the alternate stack where one is set up, as the Rust runtime
+        // does for its own handler, so a stack overflow is still reported.
+        act.sa_flags = SA_SIGINFO | SA_ONSTACK;
+        sigemptyset(&mut act.sa_mask);
+        let prev = &mut *PREVIOUS.0.get();
+        for (sig, prev) in SIGNALS.iter().zip(prev.iter_mut()) {
+            if sigaction(*sig, &act, prev.as_mut_ptr()) < 0 {
+                return Err(Error::last_os_error(Operation::TrackWrites));
+            }
+        }
+    }
+    INSTALLED.store(true, Ordering::Release);
+    Ok(())
+}
+
+type Handler = extern "C" fn(c_int, *mut siginfo_t, *mut c_void);
+
+extern "C" fn handle(sig: c_int, info: *mut siginfo_t, ctx: *mut c_void) {
+    unsafe {
+        if write_fault((*info).si_addr() as usize) {
+            return;
+        }
+
+        let idx = if sig == SIGSEGV { 0 } else { 1 };
+        let prev = &*(*PREVIOUS.0.get())[idx].as_ptr();
+        if prev.sa_sigaction == SIG_DFL || prev.sa_sigaction == SIG_IGN {
+            // Restore the previous disposition and return, so the faulting
+            // instruction is retried and handled as if never installed.
+            sigaction(sig, prev, ptr::null_mut());
+        } else if prev.sa_flags & SA_SIGINFO != 0 {
+            let f: Handler = std::mem::transmute(prev.sa_sigaction);
+            f(sig, info, ctx);
+        } else {
+            let f: extern "C" fn(c_int) = std::mem::transmute(prev.sa_sigaction);
+            f(sig);
+        }
+    }
+}
@@ -1,39 +1,85 @@
-use crate::{Advise, Flush, Protect};
+use crate::{Advise, FAdvise, Flush, MapFlags, Protect};
 
-use std::fs::File;
+use std::fs::{File, OpenOptions};
 use std::os::unix::io::AsRawFd;
-use std::ptr;
+use std::path::Path;
+use std::{cmp, ptr};
 
 use libc::{
-    c_void, madvise, mlock, mmap, mprotect, msync, munlock, munmap, off_t, sysconf, MADV_DONTNEED,
-    MADV_NORMAL, MADV_RANDOM, MADV_SEQUENTIAL, MADV_WILLNEED, MAP_ANON, MAP_FAILED, MAP_PRIVATE,
-    MAP_SHARED, MS_ASYNC, MS_SYNC, PROT_EXEC, PROT_READ, PROT_WRITE, _SC_PAGESIZE,
+    c_void, madvise, mlock, mmap, mprotect, msync, munlock, munmap, off_t, sysconf, _SC_PAGESIZE,
+    MADV_DONTNEED, MADV_NORMAL, MADV_RANDOM, MADV_SEQUENTIAL, MADV_WILLNEED, MAP_ANON, MAP_FAILED,
+    MAP_PRIVATE, MAP_SHARED, MS_ASYNC, MS_SYNC, PROT_EXEC, PROT_NONE, PROT_READ, PROT_WRITE,
 };
 
-use crate::{Error, Operation, Result};
+use crate::{Error, Input, Operation, Result};
 
 use self::Operation::*;
 
 // For macOS and iOS we use the mach vm system for rings. The posix module
 // does work correctly on these targets, but it necessitates an otherwise
 // uneeded file descriptor.
-#[cfg(all(feature = "io", any(target_os = "macos", target_os = "ios")))]
+#[cfg(all(
+    any(feature = "io", feature = "os"),
+    any(target_os = "macos", target_os = "ios")
+))]
 mod mach;
-#[cfg(all(feature = "io", any(target_os = "macos", target_os = "ios")))]
+#[cfg(all(
+    any(feature = "io", feature = "os"),
+    any(target_os = "macos", target_os = "ios")
+))]
 pub use self::mach::{map_ring, unmap_ring};
 
 // For non-mach targets load the POSIX version of the ring mapping functions.
-#[cfg(all(feature = "io", not(any(target_os = "macos", target_os = "ios"))))]
+#[cfg(all(
+    any(feature = "io", feature = "os"),
+    not(any(target_os = "macos", target_os = "ios"))
+))]
 mod posix;
-#[cfg(all(feature = "io", not(any(target_os = "macos", target_os = "ios"))))]
+#[cfg(all(
+    any(feature = "io", feature = "os"),
+    not(any(target_os = "macos", target_os = "ios"))
+))]
 pub use self::posix::{map_ring, unmap_ring};
 
+mod fault;
+pub(crate) use self::fault::install_write_handler;
+
+mod sigbus;
+pub use self::sigbus::*;
+
 /// Requests the page size and allocation granularity from the system.
 pub fn system_info() -> (u32, u32) {
     let size = unsafe { sysconf(_SC_PAGESIZE) as u32 };
     (size, size)
 }
 
+/// Requests the block size required for direct I/O on a file.
+///
+/// For block devices on Linux and Android this is the logical sector size
+/// of the device. Otherwise, the block size of the containing file system
+/// is used.
+pub fn block_size(file: &File) -> Result<usize> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        let meta = file.metadata().map_err(|e| Error::io(BlockSize, e))?;
+        if meta.file_type().is_block_device() {
+            let mut size: libc::c_int = 0;
+            if unsafe { libc::ioctl(file.as_raw_fd(), libc::BLKSSZGET as _, &mut size) } < 0 {
+                return Err(Error::last_os_error(BlockSize));
+            }
+            return Ok(size as usize);
+        }
+    }
+
+    let mut st = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    if unsafe { libc::fstatvfs(file.as_raw_fd(), st.as_mut_ptr()) } < 0 {
+        Err(Error::last_os_error(BlockSize))
+    } else {
+        Ok(unsafe { st.assume_init() }.f_bsize as usize)
+    }
+}
+
 fn result(op: Operation, pg: *mut c_void) -> Result<*mut u8> {
     if pg == MAP_FAILED {
         Err(Error::last_os_error(op))
@@ -43,18 +89,105 @@ fn result(op: Operation, pg: *mut c_void) -> Result<*mut u8> {
 }
 
 /// Memory maps a given range of a file.
+#[cfg(feature = "os")]
 pub fn map_file(file: &File, off: usize, len: usize, prot: Protect) -> Result<*mut u8> {
-    let (prot, flags) = match prot {
+    map_file_with(file, off, len, prot, MapFlags::NONE)
+}
+
+/// Memory maps a given range of a file with additional [`MapFlags`].
+///
+/// [`MapFlags`]: ../struct.MapFlags.html
+pub fn map_file_with(
+    file: &File,
+    off: usize,
+    len: usize,
+    prot: Protect,
+    opts: MapFlags,
+) -> Result<*mut u8> {
+    map_file_flags(file, off, len, prot, opts, ptr::null_mut()).map_err(|e| {
+        e.with_offset(off as u64)
+            .with_length(len)
+            .with_protect(prot)
+    })
+}
+
+/// Memory maps a new range of a file in place of a previous mapping.
+///
+/// When the new range fits within the pages of the previous mapping, the
+/// new range replaces those pages at the same address, and any remaining
+/// pages are unmapped. On Linux, the previous mapping is first grown in
+/// place when possible. Otherwise, the previous mapping is unmapped and the
+/// new range is mapped at a new address. If the new range cannot be mapped,
+/// the previous mapping is unchanged.
+///
+/// # Safety
+///
+/// This does not know or care if `pg` or `oldlen` are valid. That is,
+/// it may be null, not at a proper page boundary, point to a size
+/// different from `oldlen`, or worse yet, point to a properly mapped
+/// pointer from some other allocation system.
+///
+/// Generally don't use this unless you are entirely sure you are
+/// doing so correctly.
+pub unsafe fn remap_file(
+    pg: *mut u8,
+    oldlen: usize,
+    file: &File,
+    off: usize,
+    len: usize,
+    prot: Protect,
+    opts: MapFlags,
+) -> Result<*mut u8> {
+    let new = map_file_with(file, off, len, prot, opts)?;
+    let need = crate::Size::alloc().round(len);
+    #[allow(unused_mut)]
+    let mut oldlen = oldlen;
+
+    #[cfg(target_os = "linux")]
+    if need > oldlen && libc::mremap(pg as *mut c_void, oldlen, need, 0) != MAP_FAILED {
+        oldlen = need;
+    }
+
+    if need <= oldlen && map_file_flags(file, off, len, prot, opts, pg).is_ok() {
+        munmap(new as *mut c_void, len);
+        if oldlen > need {
+            munmap(pg.add(need) as *mut c_void, oldlen - need);
+        }
+        return Ok(pg);
+    }
+    munmap(pg as *mut c_void, oldlen);
+    Ok(new)
+}
+
+fn map_file_flags(
+    file: &File,
+    off: usize,
+    len: usize,
+    prot: Protect,
+    opts: MapFlags,
+    at: *mut u8,
+) -> Result<*mut u8> {
+    let (prot, mut flags) = match prot {
         Protect::ReadOnly => (PROT_READ, MAP_SHARED),
         Protect::ReadWrite => (PROT_READ | PROT_WRITE, MAP_SHARED),
         Protect::ReadCopy => (PROT_READ | PROT_WRITE, MAP_PRIVATE),
         Protect::ReadExec => (PROT_READ | PROT_EXEC, MAP_PRIVATE),
     };
+    if opts.contains(MapFlags::PRIVATE) {
+        flags = (flags & !MAP_SHARED) | MAP_PRIVATE;
+    }
+    if opts.contains(MapFlags::SYNC) {
+        flags = sync_flags(flags)?;
+    }
+    flags |= populate_flags(MapFile, opts)?;
+    if !at.is_null() {
+        flags |= libc::MAP_FIXED;
+    }
     unsafe {
         result(
             MapFile,
             mmap(
-                ptr::null_mut(),
+                at as *mut c_void,
                 len,
                 prot,
                 flags,
@@ -65,15 +198,579 @@ pub fn map_file(file: &File, off: usize, len: usize, prot: Protect) -> Result<*m
     }
 }
 
-/// Creates an anonymous allocation.
-pub fn map_anon(len: usize, prot: Protect) -> Result<*mut u8> {
-    let (prot, flags) = match prot {
-        Protect::ReadOnly => (PROT_READ, MAP_SHARED),
+// MAP_SYNC is only accepted along with MAP_SHARED_VALIDATE, which causes
+// the kernel to reject the mapping when the file system does not support it.
+#[cfg(all(
+    target_os = "linux",
+    not(any(target_arch = "mips", target_arch = "mips64"))
+))]
+fn sync_flags(flags: libc::c_int) -> Result<libc::c_int> {
+    if flags & MAP_SHARED == 0 {
+        Err(Error::input(MapFile, Input::Unsupported))
+    } else {
+        Ok((flags & !MAP_SHARED) | libc::MAP_SHARED_VALIDATE | libc::MAP_SYNC)
+    }
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    not(any(target_arch = "mips", target_arch = "mips64"))
+)))]
+fn sync_flags(_flags: libc::c_int) -> Result<libc::c_int> {
+    Err(Error::input(MapFile, Input::Unsupported))
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn populate_flags(_op: Operation, opts: MapFlags) -> Result<libc::c_int> {
+    if opts.contains(MapFlags::POPULATE) {
+        Ok(libc::MAP_POPULATE)
+    } else {
+        Ok(0)
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn populate_flags(op: Operation, opts: MapFlags) -> Result<libc::c_int> {
+    if opts.contains(MapFlags::POPULATE) {
+        Err(Error::input(op, Input::Unsupported))
+    } else {
+        Ok(0)
+    }
+}
+
+fn anon_flags(prot: Protect) -> (libc::c_int, libc::c_int) {
+    match prot {
+        Protect::ReadOnly => (PROT_READ, MAP_ANON | MAP_SHARED),
         Protect::ReadWrite => (PROT_READ | PROT_WRITE, MAP_ANON | MAP_SHARED),
         Protect::ReadCopy => (PROT_READ | PROT_WRITE, MAP_ANON | MAP_PRIVATE),
         Protect::ReadExec => (PROT_READ | PROT_EXEC, MAP_ANON | MAP_PRIVATE),
+    }
+}
+
+/// Creates an anonymous allocation.
+pub fn map_anon(len: usize, prot: Protect) -> Result<*mut u8> {
+    map_anon_with(len, prot, MapFlags::NONE)
+}
+
+/// Creates a read-write anonymous allocation shared with forked processes.
+///
+/// This uses `MAP_ANON | MAP_SHARED`, so a child process created with
+/// `fork` shares the same physical pages as the parent, and writes by
+/// either process are visible to the other. The allocation has no name, so
+/// it cannot be opened by unrelated processes. On Unix this is the same as
+/// [`map_anon()`] with [`Protect::ReadWrite`], which is already shared.
+///
+/// [`map_anon()`]: fn.map_anon.html
+/// [`Protect::ReadWrite`]: ../enum.Protect.html#variant.ReadWrite
+pub fn map_anon_shared(len: usize) -> Result<*mut u8> {
+    map_anon(len, Protect::ReadWrite)
+}
+
+/// Copies a page range into a new read-only anonymous allocation.
+///
+/// The pages are remapped copy-on-write with `vm_remap`, so no memory is
+/// copied until a page of either range is written. The allocation is `len`
+/// bytes rounded up to the page size.
+///
+/// # Safety
+///
+/// `pg` must be at a page boundary and `len` bytes from it must be mapped
+/// and readable.
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub unsafe fn snapshot_pages(pg: *const u8, len: usize) -> Result<*mut u8> {
+    use libc::{
+        kern_return_t, vm_address_t, vm_inherit_t, vm_map_t, vm_prot_t, vm_size_t, KERN_SUCCESS,
+        VM_INHERIT_COPY,
+    };
+
+    const VM_FLAGS_ANYWHERE: libc::c_int = 0x0001;
+
+    extern "C" {
+        fn mach_task_self() -> vm_map_t;
+
+        fn vm_remap(
+            target_task: vm_map_t,
+            target_address: *mut vm_address_t,
+            size: vm_size_t,
+            mask: vm_address_t,
+            flags: libc::c_int,
+            src_task: vm_map_t,
+            src_address: vm_address_t,
+            copy: libc::c_int,
+            cur_protection: *mut vm_prot_t,
+            max_protection: *mut vm_prot_t,
+            inheritance: vm_inherit_t,
+        ) -> kern_return_t;
+    }
+
+    let len = crate::Size::page().round(len);
+    let (mut addr, mut cur, mut max) = (0, 0, 0);
+    let task = mach_task_self();
+    let ret = vm_remap(
+        task,
+        &mut addr,
+        len,
+        0,
+        VM_FLAGS_ANYWHERE,
+        task,
+        pg as vm_address_t,
+        1,
+        &mut cur,
+        &mut max,
+        VM_INHERIT_COPY as vm_inherit_t,
+    );
+    if ret != KERN_SUCCESS {
+        return Err(Error::kernel(MapAnonymous, ret).with_length(len));
+    }
+    let dst = addr as *mut u8;
+    if let Err(err) = protect(dst, len, Protect::ReadOnly) {
+        unmap(dst, len).unwrap_or_default();
+        return Err(err);
+    }
+    Ok(dst)
+}
+
+/// Maps a named shared memory object as read-write.
+///
+/// With `create`, a new object is created with `shm_open` and sized to
+/// `len` bytes, and an error is returned if the name is already in use.
+/// Otherwise, the existing object is opened and `len` is ignored in favor of
+/// its current size. The name is given without the leading `/`, which is
+/// added here. Returns the pointer and the length of the mapping.
+///
+/// The object outlives the mapping and the process, and it must be removed
+/// with [`unlink_shm()`]. An [`Input::InvalidRange`] error is returned for a
+/// zero length, and on Android, which has no `shm_open`, an
+/// [`Input::Unsupported`] error is always returned.
+///
+/// [`unlink_shm()`]: fn.unlink_shm.html
+/// [`Input::InvalidRange`]: ../enum.Input.html#variant.InvalidRange
+/// [`Input::Unsupported`]: ../enum.Input.html#variant.Unsupported
+pub fn map_shm(name: &str, len: usize, create: bool) -> Result<(*mut u8, usize)> {
+    let path = shm_path(name)?;
+    let oflags = if create {
+        libc::O_RDWR | libc::O_CREAT | libc::O_EXCL | libc::O_CLOEXEC
+    } else {
+        libc::O_RDWR | libc::O_CLOEXEC
+    };
+    unsafe {
+        let fd = shm_open(path.as_ptr(), oflags)?;
+        let res = map_shm_fd(fd, len, create);
+        libc::close(fd);
+        if res.is_err() && create {
+            shm_unlink(path.as_ptr()).unwrap_or_default();
+        }
+        res
+    }
+}
+
+/// Removes the name of a shared memory object.
+///
+/// Existing mappings of the object remain valid, and its memory is released
+/// once the last of them is unmapped. On Android an [`Input::Unsupported`]
+/// error is returned.
+///
+/// [`Input::Unsupported`]: ../enum.Input.html#variant.Unsupported
+pub fn unlink_shm(name: &str) -> Result<()> {
+    let path = shm_path(name)?;
+    unsafe { shm_unlink(path.as_ptr()) }
+}
+
+/// Creates an unnamed temporary file in `dir` for reading and writing.
+///
+/// The file has no name, so it is removed once it is closed and no longer
+/// mapped. On Linux this uses `O_TMPFILE` where the file system supports it.
+/// Otherwise a file is created with a unique name and then removed at once.
+pub fn temp_file(dir: &Path) -> Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    match OpenOptions::new()
+        .read(true)
+        .write(true)
+        .mode(0o600)
+        .custom_flags(libc::O_TMPFILE)
+        .open(dir)
+    {
+        Ok(f) => return Ok(f),
+        // The kernel or file system does not support unnamed files.
+        Err(e)
+            if matches!(
+                e.raw_os_error(),
+                Some(libc::EOPNOTSUPP | libc::EISDIR | libc::EINVAL)
+            ) => {}
+        Err(e) => return Err(Error::io(TempFile, e)),
+    }
+
+    let mut opts = OpenOptions::new();
+    opts.read(true).write(true).create_new(true).mode(0o600);
+    let (file, path) = super::create_temp(dir, &opts)?;
+    std::fs::remove_file(path).map_err(|e| Error::io(TempFile, e))?;
+    Ok(file)
+}
+
+fn shm_path(name: &str) -> Result<std::ffi::CString> {
+    super::check_shm_name(name)?;
+    std::ffi::CString::new(format!("/{}", name))
+        .map_err(|_| Error::input(SharedMemory, Input::InvalidName))
+}
+
+unsafe fn map_shm_fd(fd: libc::c_int, len: usize, create: bool) -> Result<(*mut u8, usize)> {
+    let invalid = || Error::input(SharedMemory, Input::InvalidRange);
+    let len = if create {
+        let size = off_t::try_from(len).map_err(|_| invalid())?;
+        if len == 0 {
+            return Err(invalid());
+        }
+        if libc::ftruncate(fd, size) < 0 {
+            return Err(Error::last_os_error(SharedMemory));
+        }
+        len
+    } else {
+        let mut st = std::mem::MaybeUninit::<libc::stat>::uninit();
+        if libc::fstat(fd, st.as_mut_ptr()) < 0 {
+            return Err(Error::last_os_error(SharedMemory));
+        }
+        match usize::try_from(st.assume_init().st_size) {
+            Ok(len) if len > 0 => len,
+            _ => return Err(invalid()),
+        }
     };
-    unsafe { result(MapAnonymous, mmap(ptr::null_mut(), len, prot, flags, -1, 0)) }
+    let pg = mmap(
+        ptr::null_mut(),
+        len,
+        PROT_READ | PROT_WRITE,
+        MAP_SHARED,
+        fd,
+        0,
+    );
+    result(MapFile, pg)
+        .map(|pg| (pg, len))
+        .map_err(|e| e.with_length(len).with_protect(Protect::ReadWrite))
+}
+
+#[cfg(not(target_os = "android"))]
+unsafe fn shm_open(path: *const libc::c_char, oflags: libc::c_int) -> Result<libc::c_int> {
+    let fd = libc::shm_open(path, oflags, 0o600);
+    if fd < 0 {
+        Err(Error::last_os_error(SharedMemory))
+    } else {
+        Ok(fd)
+    }
+}
+
+#[cfg(not(target_os = "android"))]
+unsafe fn shm_unlink(path: *const libc::c_char) -> Result<()> {
+    if libc::shm_unlink(path) < 0 {
+        Err(Error::last_os_error(SharedMemory))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "android")]
+unsafe fn shm_open(_path: *const libc::c_char, _oflags: libc::c_int) -> Result<libc::c_int> {
+    Err(Error::input(SharedMemory, Input::Unsupported))
+}
+
+#[cfg(target_os = "android")]
+unsafe fn shm_unlink(_path: *const libc::c_char) -> Result<()> {
+    Err(Error::input(SharedMemory, Input::Unsupported))
+}
+
+/// Creates an anonymous allocation with additional flags.
+///
+/// Only [`MapFlags::POPULATE`] applies to an anonymous allocation, and any
+/// other flag returns an [`Input::Unsupported`] error.
+///
+/// [`MapFlags::POPULATE`]: ../struct.MapFlags.html#associatedconstant.POPULATE
+/// [`Input::Unsupported`]: ../enum.Input.html#variant.Unsupported
+pub fn map_anon_with(len: usize, prot: Protect, opts: MapFlags) -> Result<*mut u8> {
+    let err = |e: Error| e.with_length(len).with_protect(prot);
+    let mut rest = opts;
+    rest.set(MapFlags::POPULATE, false);
+    if !rest.is_empty() {
+        return Err(err(Error::input(MapAnonymous, Input::Unsupported)));
+    }
+    let (prot, flags) = anon_flags(prot);
+    let flags = flags | populate_flags(MapAnonymous, opts).map_err(err)?;
+    unsafe { result(MapAnonymous, mmap(ptr::null_mut(), len, prot, flags, -1, 0)).map_err(err) }
+}
+
+/// Changes the length of an anonymous allocation.
+///
+/// The bytes up to the smaller of the two lengths are preserved. Shrinking unmaps
+/// the trailing pages in place. On Linux, a private allocation is grown
+/// with `mremap`, which extends it in place when the following address
+/// space is free and moves it otherwise. Any other allocation is grown by
+/// mapping a new allocation, copying the bytes, and unmapping the previous
+/// one. If
+/// the new allocation cannot be made, the previous allocation is unchanged.
+/// Both lengths must be non-zero multiples of the allocation size.
+///
+/// # Safety
+///
+/// This does not know or care if `pg` or `oldlen` are valid. That is,
+/// it may be null, not at a proper page boundary, point to a size
+/// different from `oldlen`, or worse yet, point to a properly mapped
+/// pointer from some other allocation system.
+///
+/// Generally don't use this unless you are entirely sure you are
+/// doing so correctly.
+pub unsafe fn remap_anon(pg: *mut u8, oldlen: usize, len: usize, prot: Protect) -> Result<*mut u8> {
+    let err = |e: Error| e.with_length(len).with_protect(prot);
+    if len <= oldlen {
+        if len < oldlen {
+            munmap(pg.add(len) as *mut c_void, oldlen - len);
+        }
+        return Ok(pg);
+    }
+    let private = anon_flags(prot).1 & MAP_PRIVATE != 0;
+
+    #[cfg(target_os = "linux")]
+    if private {
+        let new = libc::mremap(pg as *mut c_void, oldlen, len, libc::MREMAP_MAYMOVE);
+        return result(MapAnonymous, new).map_err(err);
+    }
+
+    // Map the copy writable, and apply the protection once it is filled.
+    let copy = if private {
+        Protect::ReadCopy
+    } else {
+        Protect::ReadWrite
+    };
+    let new = map_anon(len, copy)?;
+    ptr::copy_nonoverlapping(pg, new, oldlen);
+    if copy != prot {
+        if let Err(e) = protect(new, len, prot) {
+            munmap(new as *mut c_void, len);
+            return Err(err(e));
+        }
+    }
+    munmap(pg as *mut c_void, oldlen);
+    Ok(new)
+}
+
+/// Creates an anonymous allocation backed by huge pages.
+///
+/// This uses `MAP_HUGETLB` and is only supported on Linux and Android, where
+/// `len` must be a multiple of [`huge_page_size()`]. The pages come from
+/// the pool of huge pages configured by the system, and when the pool is
+/// empty the system error is returned rather than falling back to normal
+/// pages. Elsewhere an [`Input::Unsupported`] error is returned.
+///
+/// [`huge_page_size()`]: fn.huge_page_size.html
+/// [`Input::Unsupported`]: ../enum.Input.html#variant.Unsupported
+pub fn map_anon_huge(len: usize, prot: Protect) -> Result<*mut u8> {
+    let err = |e: Error| e.with_length(len).with_protect(prot);
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let (prot, flags) = anon_flags(prot);
+        let flags = flags | libc::MAP_HUGETLB;
+        unsafe { result(MapAnonymous, mmap(ptr::null_mut(), len, prot, flags, -1, 0)).map_err(err) }
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    {
+        let _ = len;
+        Err(err(Error::input(MapAnonymous, Input::Unsupported)))
+    }
+}
+
+/// Requests the default huge page size of the system.
+///
+/// On Linux and Android this is the `Hugepagesize` reported in
+/// `/proc/meminfo`, and an [`Input::Unsupported`] error is returned when the
+/// kernel does not report one. Other platforms always return an
+/// [`Input::Unsupported`] error.
+///
+/// [`Input::Unsupported`]: ../enum.Input.html#variant.Unsupported
+pub fn huge_page_size() -> Result<usize> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let info =
+            std::fs::read_to_string("/proc/meminfo").map_err(|e| Error::io(HugePageSize, e))?;
+        info.lines()
+            .find_map(|line| {
+                let kb = line
+                    .strip_prefix("Hugepagesize:")?
+                    .trim()
+                    .strip_suffix("kB")?;
+                kb.trim().parse::<usize>().ok()
+            })
+            .filter(|&kb| kb > 0)
+            .map(|kb| kb << 10)
+            .ok_or_else(|| Error::input(HugePageSize, Input::Unsupported))
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    {
+        Err(Error::input(HugePageSize, Input::Unsupported))
+    }
+}
+
+/// Creates an anonymous allocation within the low 2GiB of the address space.
+///
+/// This uses `MAP_32BIT` and is only supported on Linux for x86-64. Elsewhere
+/// an [`Input::Unsupported`] error is returned. When no low address range is
+/// free, the system error is returned.
+///
+/// [`Input::Unsupported`]: ../enum.Input.html#variant.Unsupported
+pub fn map_anon_low(len: usize, prot: Protect) -> Result<*mut u8> {
+    let err = |e: Error| e.with_length(len).with_protect(prot);
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    {
+        let (prot, flags) = anon_flags(prot);
+        let flags = flags | libc::MAP_32BIT;
+        unsafe { result(MapAnonymous, mmap(ptr::null_mut(), len, prot, flags, -1, 0)).map_err(err) }
+    }
+    #[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+    {
+        Err(err(Error::input(MapAnonymous, Input::Unsupported)))
+    }
+}
+
+/// Creates an anonymous allocation at exactly the address `pg`.
+///
+/// An existing mapping is never replaced. On Linux this uses
+/// `MAP_FIXED_NOREPLACE`. Elsewhere, and on Linux kernels before 4.17 that
+/// ignore that flag, the address is passed as a hint and a mapping placed
+/// anywhere else is unmapped again. Either way, an [`Input::AddressInUse`]
+/// error is returned when the allocation cannot be placed at `pg`.
+///
+/// [`Input::AddressInUse`]: ../enum.Input.html#variant.AddressInUse
+pub fn map_anon_at(pg: *mut u8, len: usize, prot: Protect) -> Result<*mut u8> {
+    let err = |e: Error| e.with_length(len).with_protect(prot);
+    let (prot, flags) = anon_flags(prot);
+    #[cfg(target_os = "linux")]
+    let flags = flags | libc::MAP_FIXED_NOREPLACE;
+    let used = || Error::input(MapAnonymous, Input::AddressInUse);
+    unsafe {
+        match result(
+            MapAnonymous,
+            mmap(pg as *mut c_void, len, prot, flags, -1, 0),
+        ) {
+            Ok(ptr) if ptr == pg => Ok(ptr),
+            Ok(ptr) => {
+                munmap(ptr as *mut c_void, len);
+                Err(used())
+            }
+            Err(e) if e.raw_os_error() == Some(libc::EEXIST) => Err(used()),
+            Err(e) => Err(e),
+        }
+        .map_err(err)
+    }
+}
+
+/// Creates an anonymous allocation whose address is a multiple of `align`.
+///
+/// The allocation is made `align` bytes larger than requested, and the
+/// excess before and after the aligned range is unmapped again, so only
+/// `len` bytes remain mapped. `align` must be a power of two multiple of the
+/// page size.
+pub fn map_anon_aligned(len: usize, align: usize, prot: Protect) -> Result<*mut u8> {
+    let total = len
+        .checked_add(align)
+        .ok_or_else(|| Error::input(MapAnonymous, Input::InvalidRange).with_length(len))?;
+    let pg = map_anon(total, prot)?;
+    let head = pg.align_offset(align);
+    let tail = total - head - len;
+    unsafe {
+        if head > 0 {
+            munmap(pg as *mut c_void, head);
+        }
+        if tail > 0 {
+            munmap(pg.add(head + len) as *mut c_void, tail);
+        }
+        Ok(pg.add(head))
+    }
+}
+
+/// Reserves a private anonymous page range without access.
+///
+/// No memory is committed for the range until pages are made accessible with
+/// [`commit_anon()`]. The range is released with [`release_anon()`].
+///
+/// [`commit_anon()`]: fn.commit_anon.html
+/// [`release_anon()`]: fn.release_anon.html
+pub fn reserve_anon(len: usize) -> Result<*mut u8> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let flags = MAP_ANON | MAP_PRIVATE | libc::MAP_NORESERVE;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    let flags = MAP_ANON | MAP_PRIVATE;
+    unsafe {
+        result(Reserve, mmap(ptr::null_mut(), len, PROT_NONE, flags, -1, 0))
+            .map_err(|e| e.with_length(len))
+    }
+}
+
+/// Commits a page range from a reservation, making it readable and writable.
+///
+/// # Safety
+///
+/// This does not know or care if `pg` or `len` are valid. That is,
+/// it may be null, not at a proper page boundary, point to a size
+/// different from `len`, or worse yet, point to a properly mapped
+/// pointer from some other allocation system.
+///
+/// Generally don't use this unless you are entirely sure you are
+/// doing so correctly.
+pub unsafe fn commit_anon(pg: *mut u8, len: usize) -> Result<()> {
+    if mprotect(pg as *mut c_void, len, PROT_READ | PROT_WRITE) != 0 {
+        Err(Error::last_os_error(Commit).with_length(len))
+    } else {
+        Ok(())
+    }
+}
+
+/// Decommits a page range of a reservation, returning it to the reserved state.
+///
+/// The pages are discarded and the range becomes inaccessible until it is
+/// committed again with [`commit_anon()`], when it reads as zero. This
+/// replaces the range with a new inaccessible mapping, so the memory is
+/// given back to the system while the address space stays reserved.
+///
+/// [`commit_anon()`]: fn.commit_anon.html
+///
+/// # Safety
+///
+/// This does not know or care if `pg` or `len` are valid. That is,
+/// it may be null, not at a proper page boundary, point to a size
+/// different from `len`, or worse yet, point to a properly mapped
+/// pointer from some other allocation system.
+///
+/// Generally don't use this unless you are entirely sure you are
+/// doing so correctly.
+pub unsafe fn decommit_anon(pg: *mut u8, len: usize) -> Result<()> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let flags = MAP_ANON | MAP_PRIVATE | libc::MAP_FIXED | libc::MAP_NORESERVE;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    let flags = MAP_ANON | MAP_PRIVATE | libc::MAP_FIXED;
+    result(
+        Release,
+        mmap(pg as *mut c_void, len, PROT_NONE, flags, -1, 0),
+    )
+    .map(|_| ())
+    .map_err(|e| e.with_length(len))
+}
+
+/// Releases a page range reserved with [`reserve_anon()`].
+///
+/// [`reserve_anon()`]: fn.reserve_anon.html
+///
+/// # Safety
+///
+/// This does not know or care if `pg` or `len` are valid. That is,
+/// it may be null, not at a proper page boundary, point to a size
+/// different from `len`, or worse yet, point to a properly mapped
+/// pointer from some other allocation system.
+///
+/// Generally don't use this unless you are entirely sure you are
+/// doing so correctly.
+pub unsafe fn release_anon(pg: *mut u8, len: usize) -> Result<()> {
+    if munmap(pg as *mut c_void, len) < 0 {
+        Err(Error::last_os_error(Release).with_length(len))
+    } else {
+        Ok(())
+    }
 }
 
 /// Unmaps a page range from a previos mapping.
@@ -89,7 +786,7 @@ pub fn map_anon(len: usize, prot: Protect) -> Result<*mut u8> {
 /// doing so correctly.
 pub unsafe fn unmap(pg: *mut u8, len: usize) -> Result<()> {
     if munmap(pg as *mut c_void, len) < 0 {
-        Err(Error::last_os_error(Unmap))
+        Err(Error::last_os_error(Unmap).with_length(len))
     } else {
         Ok(())
     }
@@ -107,14 +804,80 @@ pub unsafe fn unmap(pg: *mut u8, len: usize) -> Result<()> {
 /// Generally don't use this unless you are entirely sure you are
 /// doing so correctly.
 pub unsafe fn protect(pg: *mut u8, len: usize, prot: Protect) -> Result<()> {
-    let prot = match prot {
+    let flags = match prot {
         Protect::ReadOnly => PROT_READ,
         Protect::ReadWrite => PROT_READ | PROT_WRITE,
         Protect::ReadCopy => PROT_READ | PROT_WRITE,
         Protect::ReadExec => PROT_READ | PROT_EXEC,
     };
-    if mprotect(pg as *mut c_void, len, prot) != 0 {
-        Err(Error::last_os_error(Protect))
+    if mprotect(pg as *mut c_void, len, flags) != 0 {
+        Err(Error::last_os_error(Protect)
+            .with_length(len)
+            .with_protect(prot))
+    } else {
+        Ok(())
+    }
+}
+
+/// Makes code written to a page range visible to instruction fetches.
+///
+/// After instructions are stored through a writable mapping, the
+/// instruction cache must be synchronized with the data cache before they
+/// are run on architectures without coherent caches, such as ARM. This uses
+/// `sys_icache_invalidate` on macOS and iOS and `__clear_cache` elsewhere,
+/// and does nothing on x86 and x86_64.
+///
+/// # Safety
+///
+/// This does not know or care if `pg` or `len` are valid. That is,
+/// it may be null, not at a proper page boundary, point to a size
+/// different from `len`, or worse yet, point to a properly mapped
+/// pointer from some other allocation system.
+///
+/// Generally don't use this unless you are entirely sure you are
+/// doing so correctly.
+pub unsafe fn flush_icache(pg: *mut u8, len: usize) -> Result<()> {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    let _ = (pg, len);
+    #[cfg(all(
+        not(any(target_arch = "x86", target_arch = "x86_64")),
+        any(target_os = "macos", target_os = "ios")
+    ))]
+    {
+        extern "C" {
+            fn sys_icache_invalidate(start: *mut c_void, len: usize);
+        }
+        sys_icache_invalidate(pg as *mut c_void, len);
+    }
+    #[cfg(not(any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        target_os = "macos",
+        target_os = "ios"
+    )))]
+    {
+        extern "C" {
+            fn __clear_cache(start: *mut libc::c_char, end: *mut libc::c_char);
+        }
+        __clear_cache(pg as *mut libc::c_char, pg.add(len) as *mut libc::c_char);
+    }
+    Ok(())
+}
+
+/// Removes all access to a page range so that any access to it faults.
+///
+/// # Safety
+///
+/// This does not know or care if `pg` or `len` are valid. That is,
+/// it may be null, not at a proper page boundary, point to a size
+/// different from `len`, or worse yet, point to a properly mapped
+/// pointer from some other allocation system.
+///
+/// Generally don't use this unless you are entirely sure you are
+/// doing so correctly.
+pub unsafe fn guard(pg: *mut u8, len: usize) -> Result<()> {
+    if mprotect(pg as *mut c_void, len, PROT_NONE) != 0 {
+        Err(Error::last_os_error(Protect).with_length(len))
     } else {
         Ok(())
     }
@@ -137,7 +900,30 @@ pub unsafe fn flush(pg: *mut u8, _file: &File, len: usize, mode: Flush) -> Resul
         Flush::Async => MS_ASYNC,
     };
     if msync(pg as *mut c_void, len, flags) < 0 {
-        Err(Error::last_os_error(Flush))
+        Err(Error::last_os_error(Flush).with_length(len))
+    } else {
+        Ok(())
+    }
+}
+
+/// Initiates writeback of the dirty pages of a file range without waiting.
+///
+/// This uses `sync_file_range` with `SYNC_FILE_RANGE_WRITE` and is only
+/// available on Linux.
+#[cfg(target_os = "linux")]
+pub fn sync_file_range(file: &File, off: u64, len: usize) -> Result<()> {
+    let rc = unsafe {
+        libc::sync_file_range(
+            file.as_raw_fd(),
+            off as _,
+            len as _,
+            libc::SYNC_FILE_RANGE_WRITE,
+        )
+    };
+    if rc < 0 {
+        Err(Error::last_os_error(Flush)
+            .with_offset(off)
+            .with_length(len))
     } else {
         Ok(())
     }
@@ -161,17 +947,213 @@ pub unsafe fn advise(pg: *mut u8, len: usize, adv: Advise) -> Result<()> {
         Advise::Random => MADV_RANDOM,
         Advise::WillNeed => MADV_WILLNEED,
         Advise::WillNotNeed => MADV_DONTNEED,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        Advise::Cold => libc::MADV_COLD,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        Advise::PageOut => libc::MADV_PAGEOUT,
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        Advise::HugePage => libc::MADV_HUGEPAGE,
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        Advise::Cold | Advise::PageOut | Advise::HugePage => return Ok(()),
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ))]
+        Advise::Free => libc::MADV_FREE,
+        #[cfg(not(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        )))]
+        Advise::Free => return Ok(()),
     };
 
     if madvise(pg as *mut c_void, len, adv) < 0 {
+        // Older kernels, or those built without transparent huge pages,
+        // reject these hints as unknown, so they are treated as a no-op. A
+        // free is also rejected for any map that is not private anonymous.
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if matches!(
+            adv,
+            libc::MADV_COLD | libc::MADV_PAGEOUT | libc::MADV_HUGEPAGE | libc::MADV_FREE
+        ) && std::io::Error::last_os_error().raw_os_error() == Some(libc::EINVAL)
+        {
+            return Ok(());
+        }
         Err(Error::last_os_error(Advise))
     } else {
         Ok(())
     }
 }
 
+/// Updates the page cache advise for a range of a file.
+///
+/// This uses `posix_fadvise` where available and otherwise does nothing. A
+/// `len` of zero applies the advise through the end of the file.
+pub fn fadvise(file: &File, off: u64, len: usize, adv: FAdvise) -> Result<()> {
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "dragonfly"
+    ))]
+    {
+        let adv = match adv {
+            FAdvise::Normal => libc::POSIX_FADV_NORMAL,
+            FAdvise::Sequential => libc::POSIX_FADV_SEQUENTIAL,
+            FAdvise::Random => libc::POSIX_FADV_RANDOM,
+            FAdvise::WillNeed => libc::POSIX_FADV_WILLNEED,
+            FAdvise::DontNeed => libc::POSIX_FADV_DONTNEED,
+            FAdvise::NoReuse => libc::POSIX_FADV_NOREUSE,
+        };
+        // The error code is returned directly rather than through errno.
+        let rc = unsafe { libc::posix_fadvise(file.as_raw_fd(), off as _, len as _, adv) };
+        if rc != 0 {
+            return Err(Error::io(AdviseFile, std::io::Error::from_raw_os_error(rc)));
+        }
+    }
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "dragonfly"
+    )))]
+    let _ = (file, off, len, adv);
+    Ok(())
+}
+
+/// Initiates readahead of a file range into the page cache.
+///
+/// This uses `readahead` on Linux, which starts reading the range into the
+/// page cache without waiting for it and without requiring a mapping. On
+/// other platforms this does nothing and returns `Ok`.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// # use std::path::PathBuf;
+///
+/// # fn main() -> vmap::Result<()> {
+/// # let tmp = tempdir::TempDir::new("vmap")?;
+/// # let path: PathBuf = tmp.path().join("example");
+/// # std::fs::write(&path, vec![1u8; 8192])?;
+/// let file = File::open(&path)?;
+/// vmap::os::readahead(&file, 0, 8192)?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "os")]
+pub fn readahead(file: &File, off: u64, len: usize) -> Result<()> {
+    #[cfg(target_os = "linux")]
+    if unsafe { libc::readahead(file.as_raw_fd(), off as _, len) } < 0 {
+        return Err(Error::last_os_error(Readahead));
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = (file, off, len);
+    Ok(())
+}
+
+/// Clears the soft-dirty bits of every page in the process.
+///
+/// This writes `4` to `/proc/self/clear_refs`, so it affects all mappings in
+/// the process, not just those created by this crate. It is only supported
+/// on Linux kernels built with `CONFIG_MEM_SOFT_DIRTY`.
+pub fn clear_soft_dirty() -> Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        std::fs::write("/proc/self/clear_refs", b"4").map_err(|e| Error::io(SoftDirty, e))
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        Err(Error::input(SoftDirty, Input::Unsupported))
+    }
+}
+
+/// Gets the indices of the pages in a range with the soft-dirty bit set.
+///
+/// This reads the entries for the range from `/proc/self/pagemap`, where bit
+/// 55 of each entry is set once the page is written after the last call to
+/// [`clear_soft_dirty()`]. The indices are relative to the page containing
+/// `pg`. This is only supported on Linux.
+///
+/// [`clear_soft_dirty()`]: fn.clear_soft_dirty.html
+pub fn soft_dirty_pages(pg: *const u8, len: usize) -> Result<Vec<usize>> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::FileExt;
+
+        const SOFT_DIRTY: u64 = 1 << 55;
+        let page = crate::page_size();
+        let first = pg as usize / page;
+        let count = (pg as usize + len).div_ceil(page) - first;
+        let mut buf = vec![0u8; count * 8];
+        let map = File::open("/proc/self/pagemap").map_err(|e| Error::io(SoftDirty, e))?;
+        map.read_exact_at(&mut buf, first as u64 * 8)
+            .map_err(|e| Error::io(SoftDirty, e))?;
+        Ok(buf
+            .chunks_exact(8)
+            .enumerate()
+            .filter(|(_, e)| u64::from_ne_bytes((*e).try_into().unwrap()) & SOFT_DIRTY != 0)
+            .map(|(i, _)| i)
+            .collect())
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (pg, len);
+        Err(Error::input(SoftDirty, Input::Unsupported))
+    }
+}
+
+/// Finds the first page in a range that is, or is not, resident in memory.
+///
+/// This uses `mincore` on a small fixed buffer at a time, so no memory is
+/// allocated, and stops at the first page whose residency matches
+/// `resident`. The index returned is relative to the page containing `pg`.
+///
+/// # Safety
+///
+/// This does not know or care if `pg` or `len` are valid. That is,
+/// it may be null, not at a proper page boundary, point to a size
+/// different from `len`, or worse yet, point to a properly mapped
+/// pointer from some other allocation system.
+///
+/// Generally don't use this unless you are entirely sure you are
+/// doing so correctly.
+pub unsafe fn find_residency(pg: *mut u8, len: usize, resident: bool) -> Result<Option<usize>> {
+    const CHUNK: usize = 64;
+    let page = crate::page_size();
+    let (pg, len) = crate::Size::page().bounds(pg, len);
+    let mut vec = [0u8; CHUNK];
+    for first in (0..len / page).step_by(CHUNK) {
+        let n = cmp::min(CHUNK, len / page - first);
+        let at = pg.add(first * page) as *mut c_void;
+        if libc::mincore(at, n * page, vec.as_mut_ptr() as _) != 0 {
+            return Err(Error::last_os_error(Residency).with_length(len));
+        }
+        if let Some(i) = vec[..n].iter().position(|v| (v & 1 != 0) == resident) {
+            return Ok(Some(first + i));
+        }
+    }
+    Ok(Option::None)
+}
+
 /// Locks physical pages into memory.
 ///
+/// This uses `mlock`, which fails with `ENOMEM` or `EPERM` once the process
+/// would exceed its `RLIMIT_MEMLOCK` limit without the privilege to raise it.
+///
 /// # Safety
 ///
 /// This does not know or care if `pg` or `len` are valid. That is,
@@ -207,3 +1189,259 @@ pub unsafe fn unlock(pg: *mut u8, len: usize) -> Result<()> {
         Ok(())
     }
 }
+
+/// Binds the physical pages of a range to a NUMA node.
+///
+/// The binding only applies to pages faulted in after this call, so it
+/// should be applied before the range is first touched. This is only
+/// supported on Linux and Android. Other platforms treat this as a no-op.
+/// An [`Input::InvalidRange`] error is returned if `node` is above the
+/// highest possible node of the system.
+///
+/// # Safety
+///
+/// This does not know or care if `pg` or `len` are valid. That is,
+/// it may be null, not at a proper page boundary, point to a size
+/// different from `len`, or worse yet, point to a properly mapped
+/// pointer from some other allocation system.
+///
+/// Generally don't use this unless you are entirely sure you are
+/// doing so correctly.
+///
+/// [`Input::InvalidRange`]: ../enum.Input.html#variant.InvalidRange
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub unsafe fn bind_numa(pg: *mut u8, len: usize, node: u32) -> Result<()> {
+    use std::os::raw::{c_int, c_ulong};
+
+    const MPOL_BIND: c_int = 2;
+    const BITS: usize = c_ulong::BITS as usize;
+
+    // The possible nodes are listed as ranges such as `0-3`, so the last
+    // number is the highest node. The kernel never allows more than 1024.
+    let max = std::fs::read_to_string("/sys/devices/system/node/possible")
+        .ok()
+        .and_then(|s| s.trim().rsplit(['-', ',']).next()?.parse::<u32>().ok())
+        .unwrap_or(1023);
+    if node > max {
+        return Err(Error::input(Bind, Input::InvalidRange));
+    }
+
+    let node = node as usize;
+    let mut mask = vec![0 as c_ulong; node / BITS + 1];
+    mask[node / BITS] |= 1 << (node % BITS);
+
+    // The kernel expects the maximum node to be one past the number of bits.
+    let rc = libc::syscall(
+        libc::SYS_mbind,
+        pg as *mut c_void,
+        len,
+        MPOL_BIND,
+        mask.as_ptr(),
+        mask.len() * BITS + 1,
+        0,
+    );
+    if rc < 0 {
+        Err(Error::last_os_error(Bind))
+    } else {
+        Ok(())
+    }
+}
+
+/// Binds the physical pages of a range to a NUMA node.
+///
+/// The binding only applies to pages faulted in after this call, so it
+/// should be applied before the range is first touched. This is only
+/// supported on Linux and Android. Other platforms treat this as a no-op.
+///
+/// # Safety
+///
+/// This does not know or care if `pg` or `len` are valid. That is,
+/// it may be null, not at a proper page boundary, point to a size
+/// different from `len`, or worse yet, point to a properly mapped
+/// pointer from some other allocation system.
+///
+/// Generally don't use this unless you are entirely sure you are
+/// doing so correctly.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub unsafe fn bind_numa(_pg: *mut u8, _len: usize, _node: u32) -> Result<()> {
+    Ok(())
+}
+
+/// Updates the advise for page ranges in the address space of another process.
+///
+/// Each range is an address and length within the process `pid`. This uses
+/// `process_madvise` on Linux 5.10 and later, which requires permission to
+/// trace the process and `CAP_SYS_NICE`. Only [`Advise::WillNeed`],
+/// [`Advise::Cold`], and [`Advise::PageOut`] are accepted, as they never
+/// discard data. Any other advise, or any other platform, returns an
+/// [`Input::Unsupported`] error.
+///
+/// The ranges are passed to the kernel in batches, so an error may be
+/// returned after the advise was applied to some of the ranges.
+///
+/// [`Advise::WillNeed`]: ../enum.Advise.html#variant.WillNeed
+/// [`Advise::Cold`]: ../enum.Advise.html#variant.Cold
+/// [`Advise::PageOut`]: ../enum.Advise.html#variant.PageOut
+/// [`Input::Unsupported`]: ../enum.Input.html#variant.Unsupported
+#[cfg(all(feature = "os", target_os = "linux"))]
+pub fn process_advise(pid: i32, ranges: &[(usize, usize)], adv: Advise) -> Result<()> {
+    const BATCH: usize = 1024;
+
+    let adv = match adv {
+        Advise::WillNeed => MADV_WILLNEED,
+        Advise::Cold => libc::MADV_COLD,
+        Advise::PageOut => libc::MADV_PAGEOUT,
+        _ => return Err(Error::input(Advise, Input::Unsupported)),
+    };
+    let mut iov: Vec<libc::iovec> = ranges
+        .iter()
+        .filter(|&&(_, len)| len > 0)
+        .map(|&(addr, len)| libc::iovec {
+            iov_base: addr as *mut c_void,
+            iov_len: len,
+        })
+        .collect();
+    if iov.is_empty() {
+        return Ok(());
+    }
+
+    let pidfd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) };
+    if pidfd < 0 {
+        return Err(Error::last_os_error(Advise));
+    }
+    let pidfd = pidfd as libc::c_int;
+    let mut i = 0;
+    let res = loop {
+        let n = cmp::min(iov.len() - i, BATCH);
+        let rc = unsafe {
+            libc::syscall(
+                libc::SYS_process_madvise,
+                pidfd,
+                iov[i..].as_ptr(),
+                n,
+                adv,
+                0,
+            )
+        };
+        if rc < 0 {
+            break Err(Error::last_os_error(Advise));
+        }
+        if rc == 0 {
+            // No progress was made on a non-empty range, so retrying would
+            // spin forever. Report it as the transient failure it likely is.
+            let err = std::io::Error::from_raw_os_error(libc::EAGAIN);
+            break Err(Error::io(Advise, err));
+        }
+        // A short count means a later range failed, so the remainder is
+        // retried to either continue or report the error.
+        let mut done = rc as usize;
+        while i < iov.len() && done >= iov[i].iov_len {
+            done -= iov[i].iov_len;
+            i += 1;
+        }
+        if i == iov.len() {
+            break Ok(());
+        }
+        iov[i].iov_base = (iov[i].iov_base as usize + done) as *mut c_void;
+        iov[i].iov_len -= done;
+    };
+    unsafe { libc::close(pidfd) };
+    res
+}
+
+/// Updates the advise for page ranges in the address space of another process.
+///
+/// This is only supported on Linux, and returns an [`Input::Unsupported`]
+/// error elsewhere.
+///
+/// [`Input::Unsupported`]: ../enum.Input.html#variant.Unsupported
+#[cfg(all(feature = "os", not(target_os = "linux")))]
+pub fn process_advise(_pid: i32, _ranges: &[(usize, usize)], _adv: Advise) -> Result<()> {
+    Err(Error::input(Advise, Input::Unsupported))
+}
+
+/// Gets the minor and major page fault counts of the process.
+///
+/// This uses `getrusage(RUSAGE_SELF)`, so the counts cover every thread of
+/// the process rather than any one mapping.
+pub fn page_faults() -> Result<(u64, u64)> {
+    let mut ru = std::mem::MaybeUninit::<libc::rusage>::uninit();
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, ru.as_mut_ptr()) } < 0 {
+        Err(Error::last_os_error(PageFaults))
+    } else {
+        let ru = unsafe { ru.assume_init() };
+        Ok((ru.ru_minflt as u64, ru.ru_majflt as u64))
+    }
+}
+
+/// Acquires an advisory lock on an entire file.
+///
+/// This uses `flock` and blocks until the lock is available. An exclusive
+/// lock excludes all other locks, while any number of shared locks may be
+/// held at once.
+pub fn lock_file(file: &File, exclusive: bool) -> Result<()> {
+    let op = if exclusive {
+        libc::LOCK_EX
+    } else {
+        libc::LOCK_SH
+    };
+    if unsafe { libc::flock(file.as_raw_fd(), op) } < 0 {
+        Err(Error::last_os_error(LockFile))
+    } else {
+        Ok(())
+    }
+}
+
+/// Releases an advisory lock acquired with [`lock_file`].
+///
+/// [`lock_file`]: fn.lock_file.html
+pub fn unlock_file(file: &File) -> Result<()> {
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_UN) } < 0 {
+        Err(Error::last_os_error(UnlockFile))
+    } else {
+        Ok(())
+    }
+}
+
+/// Acquires an advisory lock on a byte range of a file.
+///
+/// This uses a POSIX record lock with `fcntl(F_SETLKW)` and blocks until the
+/// lock is available. An exclusive lock excludes all other locks on any of
+/// the bytes, while any number of shared locks may overlap. `len` must not
+/// be zero, as that would lock through the end of the file.
+///
+/// Record locks belong to the process rather than the file descriptor, so
+/// they never conflict with other locks held by the same process, and
+/// closing *any* descriptor of the file releases all of the locks that the
+/// process holds on it.
+pub fn lock_file_range(file: &File, off: u64, len: u64, exclusive: bool) -> Result<()> {
+    let ty = if exclusive {
+        libc::F_WRLCK
+    } else {
+        libc::F_RDLCK
+    };
+    record_lock(LockFile, file, off, len, ty as _)
+}
+
+/// Releases an advisory lock acquired with [`lock_file_range`].
+///
+/// [`lock_file_range`]: fn.lock_file_range.html
+pub fn unlock_file_range(file: &File, off: u64, len: u64) -> Result<()> {
+    record_lock(UnlockFile, file, off, len, libc::F_UNLCK as _)
+}
+
+fn record_lock(op: Operation, file: &File, off: u64, len: u64, ty: libc::c_short) -> Result<()> {
+    if len == 0 || off > i64::MAX as u64 || len > i64::MAX as u64 - off {
+        return Err(Error::input(op, Input::InvalidRange));
+    }
+    let mut lk = unsafe { std::mem::zeroed::<libc::flock>() };
+    lk.l_type = ty;
+    lk.l_whence = libc::SEEK_SET as _;
+    lk.l_start = off as off_t;
+    lk.l_len = len as off_t;
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_SETLKW, &lk) } < 0 {
+        Err(Error::last_os_error(op))
+    } else {
+        Ok(())
+    }
+}
@@ -19,6 +19,36 @@ use self::Operation::*;
 /// The length is the size of the sequential range, and the offset of
 /// `len+1` refers to the same memory location at offset `0`. The circle
 /// continues to up through the offset of `2*len - 1`.
+///
+/// The returned pointer is the base of the full `2*len` range. A single
+/// shared memory object, from `memfd_create` or `shm_open`, is mapped
+/// into both halves with `MAP_FIXED`.
+/// The length must be a multiple of the [`allocation_size()`], and the range
+/// must be released with [`unmap_ring()`]. This is the primitive that
+/// [`Ring`] and [`InfiniteRing`] are built on, for use by other circular
+/// structures.
+///
+/// # Examples
+///
+/// ```
+/// use vmap::os::{map_ring, unmap_ring};
+///
+/// # fn main() -> vmap::Result<()> {
+/// let len = vmap::allocation_size();
+/// let pg = map_ring(len)?;
+/// unsafe {
+///     *pg.add(len - 1) = 1;
+///     assert_eq!(*pg.add(2 * len - 1), 1);
+///     unmap_ring(pg, len)?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`allocation_size()`]: ../fn.allocation_size.html
+/// [`unmap_ring()`]: fn.unmap_ring.html
+/// [`Ring`]: ../io/struct.Ring.html
+/// [`InfiniteRing`]: ../io/struct.InfiniteRing.html
 pub fn map_ring(len: usize) -> Result<*mut u8> {
     // Create a temporary file descriptor truncated to the ring size.
     let fd = tmp_open(len)?;
@@ -0,0 +1,181 @@
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use libc::{
+    c_int, c_void, mmap, sigaction, sigemptyset, siginfo_t, MAP_ANON, MAP_FAILED, MAP_FIXED,
+    MAP_PRIVATE, PROT_READ, PROT_WRITE, SA_SIGINFO, SIGBUS, SIG_DFL, SIG_IGN,
+};
+
+use crate::{Error, Operation, Result};
+
+// The handler cannot allocate or take locks, so the registered ranges are
+// kept in a fixed table of atomics. Mappings created once the table is full
+// are not protected.
+const SLOTS: usize = 256;
+
+struct Slot {
+    start: AtomicUsize,
+    len: AtomicUsize,
+    truncated: AtomicBool,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY: Slot = Slot {
+    start: AtomicUsize::new(0),
+    len: AtomicUsize::new(0),
+    truncated: AtomicBool::new(false),
+};
+
+static SLOT_TABLE: [Slot; SLOTS] = [EMPTY; SLOTS];
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+static INSTALL: Mutex<()> = Mutex::new(());
+static PAGE: AtomicUsize = AtomicUsize::new(0);
+
+struct Previous(UnsafeCell<MaybeUninit<sigaction>>);
+
+// The previous action is written once while holding `INSTALL`, before
+// `INSTALLED` is set, and is only read afterward.
+unsafe impl Sync for Previous {}
+
+static PREVIOUS: Previous = Previous(UnsafeCell::new(MaybeUninit::uninit()));
+
+/// Installs a `SIGBUS` handler to recover from faults in truncated files.
+///
+/// When a mapped file is truncated by another process, accessing the pages
+/// beyond the new end of the file raises `SIGBUS`, which normally terminates
+/// the process. Once this handler is installed, file mappings created
+/// afterward are registered with it. A fault within a registered mapping
+/// replaces the faulting page with a zero-filled anonymous page, allowing
+/// the access to complete, and marks the mapping as truncated. Faults
+/// outside of any registered mapping are passed to the previously installed
+/// handler.
+///
+/// This is opt-in as it replaces the process-wide signal disposition.
+/// Calling it more than once has no further effect.
+///
+/// # Limitations
+///
+/// - Data read from a replaced page is zero rather than the file contents,
+///   and writes to it are never persisted. Check [`Map::is_truncated()`] or
+///   use [`Map::try_read()`] to detect this.
+/// - Only 256 mappings may be registered at once. Mappings beyond this
+///   limit are not protected.
+/// - Replacing the page uses `mmap`, which POSIX does not list as
+///   async-signal-safe, although it is safe on Linux, macOS, and the BSDs.
+/// - Any other library that installs a `SIGBUS` handler afterward will take
+///   precedence over this handler.
+///
+/// [`Map::is_truncated()`]: ../struct.Map.html#method.is_truncated
+/// [`Map::try_read()`]: ../struct.Map.html#method.try_read
+pub fn install_sigbus_handler() -> Result<()> {
+    let _guard = INSTALL.lock().unwrap_or_else(|e| e.into_inner());
+    if INSTALLED.load(Ordering::Acquire) {
+        return Ok(());
+    }
+
+    PAGE.store(crate::page_size(), Ordering::Relaxed);
+    unsafe {
+        let mut act = MaybeUninit::<sigaction>::zeroed().assume_init();
+        act.sa_sigaction = handle as Handler as usize;
+        act.sa_flags = SA_SIGINFO;
+        sigemptyset(&mut act.sa_mask);
+        if sigaction(SIGBUS, &act, (*PREVIOUS.0.get()).as_mut_ptr()) < 0 {
+            return Err(Error::last_os_error(Operation::Signal));
+        }
+    }
+    INSTALLED.store(true, Ordering::Release);
+    Ok(())
+}
+
+/// Registers a file mapping range with the `SIGBUS` handler.
+pub(crate) fn register_mapping(pg: *mut u8, len: usize) {
+    if !INSTALLED.load(Ordering::Acquire) || len == 0 {
+        return;
+    }
+    let start = pg as usize;
+    for slot in SLOT_TABLE.iter() {
+        if slot
+            .start
+            .compare_exchange(0, start, Ordering::AcqRel, Ordering::Relaxed)
+            .is_ok()
+        {
+            slot.truncated.store(false, Ordering::Relaxed);
+            slot.len.store(len, Ordering::Release);
+            return;
+        }
+    }
+}
+
+/// Removes a file mapping range from the `SIGBUS` handler.
+pub(crate) fn unregister_mapping(pg: *mut u8) {
+    if !INSTALLED.load(Ordering::Acquire) {
+        return;
+    }
+    let start = pg as usize;
+    for slot in SLOT_TABLE.iter() {
+        if slot.start.load(Ordering::Acquire) == start {
+            slot.len.store(0, Ordering::Release);
+            slot.start.store(0, Ordering::Release);
+            return;
+        }
+    }
+}
+
+/// Tests if a `SIGBUS` was recovered within the mapping containing `ptr`.
+pub(crate) fn is_truncated(ptr: *const u8) -> bool {
+    find(ptr as usize).is_some_and(|slot| slot.truncated.load(Ordering::Acquire))
+}
+
+fn find(addr: usize) -> Option<&'static Slot> {
+    if !INSTALLED.load(Ordering::Acquire) {
+        return None;
+    }
+    SLOT_TABLE.iter().find(|slot| {
+        let start = slot.start.load(Ordering::Acquire);
+        let len = slot.len.load(Ordering::Acquire);
+        start != 0 && addr >= start && addr - start < len
+    })
+}
+
+type Handler = extern "C" fn(c_int, *mut siginfo_t, *mut c_void);
+
+extern "C" fn handle(sig: c_int, info: *mut siginfo_t, ctx: *mut c_void) {
+    unsafe {
+        let addr = (*info).si_addr() as usize;
+        // A write to a tracked page is reported as `SIGBUS` on macOS and iOS.
+        if crate::os::watch::write_fault(addr) {
+            return;
+        }
+        if let Some(slot) = find(addr) {
+            let page = PAGE.load(Ordering::Relaxed);
+            let pg = mmap(
+                (addr & !(page - 1)) as *mut c_void,
+                page,
+                PROT_READ | PROT_WRITE,
+                MAP_FIXED | MAP_PRIVATE | MAP_ANON,
+                -1,
+                0,
+            );
+            if pg != MAP_FAILED {
+                slot.truncated.store(true, Ordering::Release);
+                return;
+            }
+        }
+
+        let prev = &*(*PREVIOUS.0.get()).as_ptr();
+        if prev.sa_sigaction == SIG_DFL || prev.sa_sigaction == SIG_IGN {
+            // Restore the previous disposition and return, so the faulting
+            // instruction is retried and handled as if never installed.
+            sigaction(SIGBUS, prev, ptr::null_mut());
+        } else if prev.sa_flags & SA_SIGINFO != 0 {
+            let f: Handler = std::mem::transmute(prev.sa_sigaction);
+            f(sig, info, ctx);
+        } else {
+            let f: extern "C" fn(c_int) = std::mem::transmute(prev.sa_sigaction);
+            f(sig);
+        }
+    }
+}
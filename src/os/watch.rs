@@ -0,0 +1,182 @@
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+use std::{cmp, fmt};
+
+use super::{install_write_handler, protect};
+use crate::{Error, Input, Operation, Protect, Result};
+
+// The fault handler cannot allocate or take locks, so the tracked ranges are
+// kept in a fixed table of atomics, as with the `SIGBUS` handler.
+const SLOTS: usize = 64;
+
+const BITS: usize = usize::BITS as usize;
+
+struct Slot {
+    start: AtomicUsize,
+    len: AtomicUsize,
+    bits: AtomicPtr<AtomicUsize>,
+    copy: AtomicBool,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY: Slot = Slot {
+    start: AtomicUsize::new(0),
+    len: AtomicUsize::new(0),
+    bits: AtomicPtr::new(ptr::null_mut()),
+    copy: AtomicBool::new(false),
+};
+
+static SLOT_TABLE: [Slot; SLOTS] = [EMPTY; SLOTS];
+static PAGE: AtomicUsize = AtomicUsize::new(0);
+
+/// Records the pages of a range written since tracking began.
+///
+/// Every page of the range is made read-only, and the first write to each
+/// page faults. The fault handler sets the bit of the page and makes it
+/// writable again, so each page faults at most once until cleared.
+pub(crate) struct WriteWatch {
+    pg: *mut u8,
+    len: usize,
+    prot: Protect,
+    bits: Box<[AtomicUsize]>,
+}
+
+impl WriteWatch {
+    /// Starts tracking writes to a page-aligned range with protection `prot`.
+    ///
+    /// # Safety
+    ///
+    /// The range must be mapped with `prot`, which is either `ReadWrite` or
+    /// `ReadCopy`, for as long as the watch exists.
+    pub unsafe fn start(pg: *mut u8, len: usize, prot: Protect) -> Result<Self> {
+        install_write_handler()?;
+        let page = crate::page_size();
+        PAGE.store(page, Ordering::Relaxed);
+        let bits: Box<[AtomicUsize]> = (0..len.div_ceil(page).div_ceil(BITS))
+            .map(|_| AtomicUsize::new(0))
+            .collect();
+        let slot = SLOT_TABLE
+            .iter()
+            .find(|slot| {
+                slot.start
+                    .compare_exchange(0, pg as usize, Ordering::AcqRel, Ordering::Relaxed)
+                    .is_ok()
+            })
+            .ok_or_else(|| Error::input(Operation::TrackWrites, Input::Unsupported))?;
+        slot.copy
+            .store(prot == Protect::ReadCopy, Ordering::Relaxed);
+        slot.bits
+            .store(bits.as_ptr() as *mut AtomicUsize, Ordering::Relaxed);
+        slot.len.store(len, Ordering::Release);
+        let watch = Self {
+            pg,
+            len,
+            prot,
+            bits,
+        };
+        if len > 0 {
+            protect(pg, len, Protect::ReadOnly)?;
+        }
+        Ok(watch)
+    }
+
+    /// Gets the indices of the pages written since the last clear.
+    pub fn dirty_pages(&self) -> Vec<usize> {
+        let mut pages = Vec::new();
+        for (i, word) in self.bits.iter().enumerate() {
+            let mut word = word.load(Ordering::Acquire);
+            while word != 0 {
+                pages.push(i * BITS + word.trailing_zeros() as usize);
+                word &= word - 1;
+            }
+        }
+        pages
+    }
+
+    /// Records the pages holding `off..off + len` as written and makes them
+    /// writable, so a write that cannot fault, such as one by the system
+    /// into the range, succeeds and is not missed.
+    pub fn mark(&self, off: usize, len: usize) -> Result<()> {
+        if len == 0 {
+            return Ok(());
+        }
+        let page = crate::page_size();
+        let (start, end) = (off / page, (off + len).div_ceil(page));
+        for idx in start..end {
+            self.bits[idx / BITS].fetch_or(1 << (idx % BITS), Ordering::AcqRel);
+        }
+        let len = cmp::min(end * page, self.len) - start * page;
+        unsafe { protect(self.pg.add(start * page), len, self.prot) }
+    }
+
+    /// Forgets the written pages and makes the range read-only again.
+    pub fn clear(&self) -> Result<()> {
+        if self.len > 0 {
+            unsafe { protect(self.pg, self.len, Protect::ReadOnly)? };
+        }
+        for word in self.bits.iter() {
+            word.store(0, Ordering::Release);
+        }
+        Ok(())
+    }
+
+    /// Stops tracking and restores the protection of the range.
+    pub fn stop(self) -> Result<()> {
+        match self.len {
+            0 => Ok(()),
+            len => unsafe { protect(self.pg, len, self.prot) },
+        }
+    }
+}
+
+impl Drop for WriteWatch {
+    fn drop(&mut self) {
+        let start = self.pg as usize;
+        for slot in SLOT_TABLE.iter() {
+            if slot.start.load(Ordering::Acquire) == start {
+                slot.len.store(0, Ordering::Release);
+                slot.bits.store(ptr::null_mut(), Ordering::Release);
+                slot.start.store(0, Ordering::Release);
+                return;
+            }
+        }
+    }
+}
+
+impl fmt::Debug for WriteWatch {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("WriteWatch")
+            .field("ptr", &self.pg)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+/// Records a write fault at `addr`, returning `false` if it is not tracked.
+///
+/// This is called from the fault handler, so it only uses atomics and the
+/// protection call.
+pub(crate) fn write_fault(addr: usize) -> bool {
+    for slot in SLOT_TABLE.iter() {
+        let start = slot.start.load(Ordering::Acquire);
+        let len = slot.len.load(Ordering::Acquire);
+        if start == 0 || addr < start || addr - start >= len {
+            continue;
+        }
+        let bits = slot.bits.load(Ordering::Acquire);
+        if bits.is_null() {
+            return false;
+        }
+        let page = PAGE.load(Ordering::Relaxed);
+        let idx = (addr - start) / page;
+        let prot = match slot.copy.load(Ordering::Relaxed) {
+            true => Protect::ReadCopy,
+            false => Protect::ReadWrite,
+        };
+        unsafe {
+            (*bits.add(idx / BITS)).fetch_or(1 << (idx % BITS), Ordering::AcqRel);
+            return protect((start + idx * page) as *mut u8, page, prot).is_ok();
+        }
+    }
+    false
+}
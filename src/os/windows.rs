@@ -1,27 +1,45 @@
-use crate::{Advise, Flush, Protect};
+use crate::{Advise, FAdvise, Flush, MapFlags, Protect};
 use std::os::windows::raw::HANDLE;
 
-use std::fs::File;
+use std::ffi::OsStr;
+use std::fs::{File, OpenOptions};
 use std::os::raw::c_void;
-use std::os::windows::io::AsRawHandle;
-use std::{mem, ptr};
+use std::os::windows::ffi::OsStrExt;
+use std::os::windows::io::{AsRawHandle, BorrowedHandle, FromRawHandle, OwnedHandle};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+use std::{cmp, mem, ptr};
 
 use winapi::shared::basetsd::SIZE_T;
-use winapi::shared::minwindef::DWORD;
-use winapi::um::fileapi::FlushFileBuffers;
+use winapi::shared::minwindef::{DWORD, FALSE, TRUE};
+use winapi::shared::ntdef::LONG;
+use winapi::shared::winerror::{ERROR_ALREADY_EXISTS, ERROR_INVALID_ADDRESS};
+use winapi::um::errhandlingapi::AddVectoredExceptionHandler;
+use winapi::um::fileapi::{FlushFileBuffers, LockFileEx, UnlockFileEx, FILE_STORAGE_INFO};
 use winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
 use winapi::um::memoryapi::{
-    CreateFileMappingW, FlushViewOfFile, MapViewOfFileEx, UnmapViewOfFile, VirtualAlloc,
-    VirtualFree, VirtualLock, VirtualProtect, VirtualUnlock, FILE_MAP_COPY, FILE_MAP_EXECUTE,
-    FILE_MAP_READ, FILE_MAP_WRITE,
+    CreateFileMappingW, FlushViewOfFile, GetLargePageMinimum, MapViewOfFileEx, OpenFileMappingW,
+    PrefetchVirtualMemory, UnmapViewOfFile, VirtualAlloc, VirtualFree, VirtualLock, VirtualProtect,
+    VirtualQuery, VirtualUnlock, FILE_MAP_COPY, FILE_MAP_EXECUTE, FILE_MAP_LARGE_PAGES,
+    FILE_MAP_READ, FILE_MAP_WRITE, WIN32_MEMORY_RANGE_ENTRY,
 };
+use winapi::um::minwinbase::{
+    FileStorageInfo, EXCEPTION_ACCESS_VIOLATION, LOCKFILE_EXCLUSIVE_LOCK, OVERLAPPED,
+    SECURITY_ATTRIBUTES,
+};
+use winapi::um::processthreadsapi::{FlushInstructionCache, GetCurrentProcess};
+use winapi::um::psapi::{GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS};
 use winapi::um::sysinfoapi::{GetSystemInfo, LPSYSTEM_INFO, SYSTEM_INFO};
+use winapi::um::winbase::{GetFileInformationByHandleEx, FILE_FLAG_DELETE_ON_CLOSE};
 use winapi::um::winnt::{
-    MEM_RELEASE, MEM_RESERVE, PAGE_EXECUTE_READ, PAGE_NOACCESS, PAGE_READONLY, PAGE_READWRITE,
-    PAGE_WRITECOPY,
+    EXCEPTION_POINTERS, FILE_ATTRIBUTE_TEMPORARY, MEMORY_BASIC_INFORMATION, MEM_COMMIT,
+    MEM_DECOMMIT, MEM_FREE, MEM_RELEASE, MEM_RESERVE, PAGE_EXECUTE_READ, PAGE_NOACCESS,
+    PAGE_READONLY, PAGE_READWRITE, PAGE_WRITECOPY, SEC_COMMIT, SEC_LARGE_PAGES,
 };
+use winapi::vc::excpt::{EXCEPTION_CONTINUE_EXECUTION, EXCEPTION_CONTINUE_SEARCH};
 
-use crate::{Error, Operation, Result};
+use crate::{Error, Input, Operation, Result};
 
 use self::Operation::*;
 
@@ -49,14 +67,16 @@ impl MapHandle {
     pub unsafe fn view_ptr(
         &self,
         access: DWORD,
-        off: usize,
+        off: u64,
         len: usize,
         at: *mut c_void,
     ) -> *mut c_void {
+        // The offset is always split as 64 bits so that views beyond 4GB of
+        // a file are not truncated, regardless of the pointer width.
         MapViewOfFileEx(
             self.map,
             access as DWORD,
-            (off >> 16 >> 16) as DWORD,
+            (off >> 32) as DWORD,
             (off & 0xffffffff) as DWORD,
             len as SIZE_T,
             at,
@@ -67,7 +87,7 @@ impl MapHandle {
         &self,
         op: Operation,
         access: DWORD,
-        off: usize,
+        off: u64,
         len: usize,
         at: *mut c_void,
     ) -> Result<*mut u8> {
@@ -98,10 +118,70 @@ pub fn system_info() -> (u32, u32) {
     (info.dwPageSize, info.dwAllocationGranularity)
 }
 
+/// Requests the block size required for direct I/O on a file.
+///
+/// This is the logical sector size of the volume containing the file.
+pub fn block_size(file: &File) -> Result<usize> {
+    let mut info = mem::MaybeUninit::<FILE_STORAGE_INFO>::uninit();
+    let ok = unsafe {
+        GetFileInformationByHandleEx(
+            file.as_raw_handle(),
+            FileStorageInfo,
+            info.as_mut_ptr() as *mut c_void,
+            mem::size_of::<FILE_STORAGE_INFO>() as DWORD,
+        )
+    };
+    if ok == 0 {
+        Err(Error::last_os_error(BlockSize))
+    } else {
+        Ok(unsafe { info.assume_init() }.LogicalBytesPerSector as usize)
+    }
+}
+
 /// Memory maps a given range of a file.
+#[cfg(feature = "os")]
 pub fn map_file(file: &File, off: usize, len: usize, prot: Protect) -> Result<*mut u8> {
+    map_file_with(file, off, len, prot, MapFlags::NONE)
+}
+
+/// Memory maps a given range of a file with additional [`MapFlags`].
+///
+/// The [`MapFlags::SYNC`] and [`MapFlags::POPULATE`] flags are not
+/// supported on Windows.
+///
+/// [`MapFlags`]: ../struct.MapFlags.html
+/// [`MapFlags::SYNC`]: ../struct.MapFlags.html#associatedconstant.SYNC
+/// [`MapFlags::POPULATE`]: ../struct.MapFlags.html#associatedconstant.POPULATE
+pub fn map_file_with(
+    file: &File,
+    off: usize,
+    len: usize,
+    prot: Protect,
+    opts: MapFlags,
+) -> Result<*mut u8> {
+    map_file_flags(file, off, len, prot, opts).map_err(|e| {
+        e.with_offset(off as u64)
+            .with_length(len)
+            .with_protect(prot)
+    })
+}
+
+fn map_file_flags(
+    file: &File,
+    off: usize,
+    len: usize,
+    prot: Protect,
+    opts: MapFlags,
+) -> Result<*mut u8> {
+    if opts.contains(MapFlags::SYNC) || opts.contains(MapFlags::POPULATE) {
+        return Err(Error::input(MapFile, Input::Unsupported));
+    }
+
+    let private = opts.contains(MapFlags::PRIVATE);
     let (prot, access) = match prot {
+        Protect::ReadOnly if private => (PAGE_WRITECOPY, FILE_MAP_COPY),
         Protect::ReadOnly => (PAGE_READONLY, FILE_MAP_READ),
+        Protect::ReadWrite if private => (PAGE_WRITECOPY, FILE_MAP_COPY),
         Protect::ReadWrite => (PAGE_READWRITE, FILE_MAP_READ | FILE_MAP_WRITE),
         Protect::ReadCopy => (PAGE_WRITECOPY, FILE_MAP_COPY),
         Protect::ReadExec => (PAGE_EXECUTE_READ, FILE_MAP_READ | FILE_MAP_EXECUTE),
@@ -109,12 +189,278 @@ pub fn map_file(file: &File, off: usize, len: usize, prot: Protect) -> Result<*m
 
     unsafe {
         let map = MapHandle::new(MapFileHandle, file.as_raw_handle(), prot, 0)?;
-        map.view(MapFileView, access, off, len, ptr::null_mut())
+        map.view(MapFileView, access, off as u64, len, ptr::null_mut())
     }
 }
 
+/// Memory maps a new range of a file in place of a previous mapping.
+///
+/// On Windows, the new range is always mapped at a new address before the
+/// previous mapping is unmapped. If the new range cannot be mapped, the
+/// previous mapping is unchanged.
+///
+/// # Safety
+///
+/// This does not know or care if `pg` or `oldlen` are valid. That is,
+/// it may be null, not at a proper page boundary, point to a size
+/// different from `oldlen`, or worse yet, point to a properly mapped
+/// pointer from some other allocation system.
+///
+/// Generally don't use this unless you are entirely sure you are
+/// doing so correctly.
+pub unsafe fn remap_file(
+    pg: *mut u8,
+    oldlen: usize,
+    file: &File,
+    off: usize,
+    len: usize,
+    prot: Protect,
+    opts: MapFlags,
+) -> Result<*mut u8> {
+    let new = map_file_with(file, off, len, prot, opts)?;
+    unmap(pg, oldlen).unwrap_or_default();
+    Ok(new)
+}
+
 /// Creates an anonymous allocation.
 pub fn map_anon(len: usize, prot: Protect) -> Result<*mut u8> {
+    map_anon_view(ptr::null_mut(), len, prot)
+}
+
+/// Creates a read-write anonymous allocation backed by the paging file.
+///
+/// The view is mapped from a section created with `CreateFileMappingW`
+/// over `INVALID_HANDLE_VALUE`, and the section handle is returned along
+/// with the pointer. The handle is inheritable, so a child process created
+/// with handle inheritance receives it and can map the same pages with
+/// [`map_section()`]. The section has no name, so it cannot be opened by
+/// unrelated processes. It remains while the handle or any view of it in
+/// any process is open.
+///
+/// [`map_section()`]: fn.map_section.html
+pub fn map_anon_shared(len: usize) -> Result<(*mut u8, OwnedHandle)> {
+    let err = |e: Error| e.with_length(len).with_protect(Protect::ReadWrite);
+    let mut sa = SECURITY_ATTRIBUTES {
+        nLength: mem::size_of::<SECURITY_ATTRIBUTES>() as DWORD,
+        lpSecurityDescriptor: ptr::null_mut(),
+        bInheritHandle: TRUE,
+    };
+    unsafe {
+        let map = CreateFileMappingW(
+            INVALID_HANDLE_VALUE,
+            &mut sa,
+            PAGE_READWRITE,
+            (len >> 16 >> 16) as DWORD,
+            (len & 0xffffffff) as DWORD,
+            ptr::null(),
+        );
+        if map.is_null() {
+            return Err(err(Error::last_os_error(MapAnonymousHandle)));
+        }
+        let map = MapHandle { map };
+        let access = FILE_MAP_READ | FILE_MAP_WRITE;
+        let pg = map
+            .view(MapAnonymousView, access, 0, len, ptr::null_mut())
+            .map_err(err)?;
+        // The handle is now owned by the caller rather than closed on drop.
+        let map = mem::ManuallyDrop::new(map);
+        Ok((pg, OwnedHandle::from_raw_handle(map.map)))
+    }
+}
+
+/// Maps `len` bytes of a section as read-write.
+///
+/// This maps a view of a section created by [`map_anon_shared()`], such as
+/// one whose handle was inherited from the parent process, so that both
+/// views share the same pages. The handle is only borrowed and may be closed
+/// once the view is mapped. An [`Input::InvalidRange`] error is returned for
+/// a zero length.
+///
+/// [`map_anon_shared()`]: fn.map_anon_shared.html
+/// [`Input::InvalidRange`]: ../enum.Input.html#variant.InvalidRange
+pub fn map_section(handle: BorrowedHandle<'_>, len: usize) -> Result<*mut u8> {
+    if len == 0 {
+        return Err(Error::input(MapAnonymous, Input::InvalidRange));
+    }
+    let access = FILE_MAP_READ | FILE_MAP_WRITE;
+    let pg = unsafe { MapViewOfFileEx(handle.as_raw_handle(), access, 0, 0, len, ptr::null_mut()) };
+    if pg.is_null() {
+        Err(Error::last_os_error(MapAnonymousView)
+            .with_length(len)
+            .with_protect(Protect::ReadWrite))
+    } else {
+        Ok(pg as *mut u8)
+    }
+}
+
+/// Maps a named shared memory object as read-write.
+///
+/// With `create`, a new section backed by the paging file is created with
+/// `CreateFileMappingW` and sized to `len` bytes, and an error is returned if
+/// the name is already in use. Otherwise, the existing section is opened with
+/// `OpenFileMappingW` and `len` is ignored in favor of its size, rounded up
+/// to the page size. Returns the pointer and the length of the mapping.
+///
+/// The section handle is closed once the view is mapped, and the section and
+/// its name remain until the last view of it in any process is unmapped. An
+/// [`Input::InvalidRange`] error is returned for a zero length.
+///
+/// [`Input::InvalidRange`]: ../enum.Input.html#variant.InvalidRange
+pub fn map_shm(name: &str, len: usize, create: bool) -> Result<(*mut u8, usize)> {
+    super::check_shm_name(name)?;
+    let name: Vec<u16> = OsStr::new(name).encode_wide().chain(Some(0)).collect();
+    unsafe {
+        let map = if create {
+            if len == 0 {
+                return Err(Error::input(SharedMemory, Input::InvalidRange));
+            }
+            let map = CreateFileMappingW(
+                INVALID_HANDLE_VALUE,
+                ptr::null_mut(),
+                PAGE_READWRITE | SEC_COMMIT,
+                (len >> 16 >> 16) as DWORD,
+                (len & 0xffffffff) as DWORD,
+                name.as_ptr(),
+            );
+            let err = std::io::Error::last_os_error();
+            if !map.is_null() && err.raw_os_error() == Some(ERROR_ALREADY_EXISTS as i32) {
+                CloseHandle(map);
+                return Err(Error::io(SharedMemory, err));
+            }
+            map
+        } else {
+            OpenFileMappingW(FILE_MAP_READ | FILE_MAP_WRITE, FALSE, name.as_ptr())
+        };
+        if map.is_null() {
+            return Err(Error::last_os_error(SharedMemory));
+        }
+        let map = MapHandle { map };
+        let access = FILE_MAP_READ | FILE_MAP_WRITE;
+        let view_len = if create { len } else { 0 };
+        let pg = map.view(MapFile, access, 0, view_len, ptr::null_mut())?;
+        if create {
+            return Ok((pg, len));
+        }
+        let mut info = mem::MaybeUninit::<MEMORY_BASIC_INFORMATION>::uninit();
+        let size = mem::size_of::<MEMORY_BASIC_INFORMATION>();
+        if VirtualQuery(pg as *const c_void, info.as_mut_ptr(), size) == 0 {
+            let err = Error::last_os_error(SharedMemory);
+            UnmapViewOfFile(pg as *mut c_void);
+            return Err(err);
+        }
+        Ok((pg, info.assume_init().RegionSize))
+    }
+}
+
+/// Removes the name of a shared memory object.
+///
+/// A Windows section is removed along with its name once the last view is
+/// unmapped, so this only checks the name.
+pub fn unlink_shm(name: &str) -> Result<()> {
+    super::check_shm_name(name)
+}
+
+/// Creates an unnamed temporary file in `dir` for reading and writing.
+///
+/// The file is created with a unique name and is deleted once it is closed
+/// and no longer mapped. It is marked temporary, so the system avoids
+/// writing it to disk while there is enough memory to cache it.
+pub fn temp_file(dir: &Path) -> Result<File> {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    let mut opts = OpenOptions::new();
+    opts.read(true)
+        .write(true)
+        .create_new(true)
+        .attributes(FILE_ATTRIBUTE_TEMPORARY)
+        .custom_flags(FILE_FLAG_DELETE_ON_CLOSE);
+    super::create_temp(dir, &opts).map(|(file, _)| file)
+}
+
+/// Creates an anonymous allocation with additional flags.
+///
+/// No flags are supported on Windows, and any flag returns an
+/// [`Input::Unsupported`] error.
+///
+/// [`Input::Unsupported`]: ../enum.Input.html#variant.Unsupported
+pub fn map_anon_with(len: usize, prot: Protect, opts: MapFlags) -> Result<*mut u8> {
+    if !opts.is_empty() {
+        return Err(Error::input(MapAnonymous, Input::Unsupported)
+            .with_length(len)
+            .with_protect(prot));
+    }
+    map_anon(len, prot)
+}
+
+/// Creates an anonymous allocation at exactly the address `pg`.
+///
+/// An existing mapping is never replaced. The view is mapped with
+/// `MapViewOfFileEx` at `pg`, which must be aligned to the allocation
+/// granularity. An [`Input::AddressInUse`] error is returned when any of
+/// the range is already in use.
+///
+/// [`Input::AddressInUse`]: ../enum.Input.html#variant.AddressInUse
+pub fn map_anon_at(pg: *mut u8, len: usize, prot: Protect) -> Result<*mut u8> {
+    map_anon_view(pg, len, prot).map_err(|e| {
+        if e.raw_os_error() == Some(ERROR_INVALID_ADDRESS as i32) {
+            Error::input(MapAnonymous, Input::AddressInUse)
+                .with_length(len)
+                .with_protect(prot)
+        } else {
+            e
+        }
+    })
+}
+
+/// Changes the length of an anonymous allocation.
+///
+/// The bytes up to the smaller of the two lengths are preserved. A view cannot be
+/// resized, so a new allocation is mapped, the bytes copied, and the
+/// previous view unmapped, and the address always changes. If
+/// the new allocation cannot be made, the previous allocation is unchanged.
+/// Both lengths must be non-zero multiples of the allocation size.
+///
+/// # Safety
+///
+/// This does not know or care if `pg` or `oldlen` are valid. That is,
+/// it may be null, not at a proper page boundary, point to a size
+/// different from `oldlen`, or worse yet, point to a properly mapped
+/// pointer from some other allocation system.
+///
+/// Generally don't use this unless you are entirely sure you are
+/// doing so correctly.
+pub unsafe fn remap_anon(pg: *mut u8, oldlen: usize, len: usize, prot: Protect) -> Result<*mut u8> {
+    if len == oldlen {
+        return Ok(pg);
+    }
+    // Map the copy writable, and apply the protection once it is filled.
+    let copy = match prot {
+        Protect::ReadCopy | Protect::ReadExec => Protect::ReadCopy,
+        _ => Protect::ReadWrite,
+    };
+    let new = map_anon(len, copy)?;
+    ptr::copy_nonoverlapping(pg, new, cmp::min(oldlen, len));
+    if copy != prot {
+        if let Err(e) = protect(new, len, prot) {
+            UnmapViewOfFile(new as *mut c_void);
+            return Err(e.with_length(len));
+        }
+    }
+    UnmapViewOfFile(pg as *mut c_void);
+    Ok(new)
+}
+
+/// Creates an anonymous allocation backed by large pages.
+///
+/// The view is mapped from a section created with `SEC_LARGE_PAGES`, and
+/// `len` must be a multiple of [`huge_page_size()`]. This requires the
+/// `SeLockMemoryPrivilege` privilege to be enabled for the process, and
+/// fails with the system error otherwise rather than falling back to normal
+/// pages.
+///
+/// [`huge_page_size()`]: fn.huge_page_size.html
+pub fn map_anon_huge(len: usize, prot: Protect) -> Result<*mut u8> {
+    let err = |e: Error| e.with_length(len).with_protect(prot);
     let (prot, access) = match prot {
         Protect::ReadOnly => (PAGE_READONLY, FILE_MAP_READ),
         Protect::ReadWrite => (PAGE_READWRITE, FILE_MAP_READ | FILE_MAP_WRITE),
@@ -123,11 +469,109 @@ pub fn map_anon(len: usize, prot: Protect) -> Result<*mut u8> {
     };
 
     unsafe {
-        let map = MapHandle::new(MapAnonymousHandle, INVALID_HANDLE_VALUE, prot, len)?;
+        let prot = prot | SEC_COMMIT | SEC_LARGE_PAGES;
+        let map =
+            MapHandle::new(MapAnonymousHandle, INVALID_HANDLE_VALUE, prot, len).map_err(err)?;
+        let access = access | FILE_MAP_LARGE_PAGES;
         map.view(MapAnonymousView, access, 0, len, ptr::null_mut())
+            .map_err(err)
     }
 }
 
+/// Requests the minimum large page size of the system.
+///
+/// An [`Input::Unsupported`] error is returned when the processor does not
+/// support large pages.
+///
+/// [`Input::Unsupported`]: ../enum.Input.html#variant.Unsupported
+pub fn huge_page_size() -> Result<usize> {
+    match unsafe { GetLargePageMinimum() } {
+        0 => Err(Error::input(HugePageSize, Input::Unsupported)),
+        size => Ok(size),
+    }
+}
+
+/// Creates an anonymous allocation within the low 2GiB of the address space.
+///
+/// The free regions below 2GiB are searched with `VirtualQuery`, and the
+/// allocation is mapped with [`map_anon_at()`] at the first one that fits.
+/// If another thread takes the address in the meantime, the search resumes
+/// from the next allocation boundary. An [`Input::AddressInUse`] error is returned when no free
+/// region is large enough.
+///
+/// [`map_anon_at()`]: fn.map_anon_at.html
+/// [`Input::AddressInUse`]: ../enum.Input.html#variant.AddressInUse
+pub fn map_anon_low(len: usize, prot: Protect) -> Result<*mut u8> {
+    const LIMIT: usize = 1 << 31;
+    let gran = crate::allocation_size();
+    let mut addr = gran;
+    while addr < LIMIT {
+        let mut info = mem::MaybeUninit::<MEMORY_BASIC_INFORMATION>::uninit();
+        let size = mem::size_of::<MEMORY_BASIC_INFORMATION>();
+        if unsafe { VirtualQuery(addr as *const c_void, info.as_mut_ptr(), size) } == 0 {
+            break;
+        }
+        let info = unsafe { info.assume_init() };
+        let end = info.BaseAddress as usize + info.RegionSize;
+        let at = (info.BaseAddress as usize).div_ceil(gran) * gran;
+        if info.State == MEM_FREE && at < end && end - at >= len && at + len <= LIMIT {
+            match map_anon_at(at as *mut u8, len, prot) {
+                // Resume past the address that was taken, so the search
+                // always moves forward and ends at the limit.
+                Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+                    addr = at + gran;
+                    continue;
+                }
+                res => return res,
+            }
+        }
+        addr = end;
+    }
+    Err(Error::input(MapAnonymous, Input::AddressInUse)
+        .with_length(len)
+        .with_protect(prot))
+}
+
+/// Creates an anonymous allocation whose address is a multiple of `align`.
+///
+/// A view cannot be partially unmapped, so an address range `align` bytes
+/// larger than requested is reserved to find a free aligned address, and
+/// then released and mapped at that address. This is retried if another
+/// thread takes the address in the meantime. `align` must be a power of two
+/// multiple of the allocation granularity.
+pub fn map_anon_aligned(len: usize, align: usize, prot: Protect) -> Result<*mut u8> {
+    let total = len
+        .checked_add(align)
+        .ok_or_else(|| Error::input(MapAnonymous, Input::InvalidRange).with_length(len))?;
+    loop {
+        let pg = reserve_anon(total)?;
+        unsafe { release_anon(pg, total)? };
+        let at = pg.wrapping_add(pg.align_offset(align));
+        match map_anon_at(at, len, prot) {
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => continue,
+            res => return res,
+        }
+    }
+}
+
+fn map_anon_view(pg: *mut u8, len: usize, prot: Protect) -> Result<*mut u8> {
+    let err = |e: Error| e.with_length(len).with_protect(prot);
+    let (prot, access) = match prot {
+        Protect::ReadOnly => (PAGE_READONLY, FILE_MAP_READ),
+        Protect::ReadWrite => (PAGE_READWRITE, FILE_MAP_READ | FILE_MAP_WRITE),
+        Protect::ReadCopy => (PAGE_WRITECOPY, FILE_MAP_COPY),
+        Protect::ReadExec => (PAGE_EXECUTE_READ, FILE_MAP_READ | FILE_MAP_EXECUTE),
+    };
+
+    unsafe {
+        let map =
+            MapHandle::new(MapAnonymousHandle, INVALID_HANDLE_VALUE, prot, len).map_err(err)?;
+        map.view(MapAnonymousView, access, 0, len, pg as *mut c_void)
+            .map_err(err)
+    }
+}
+
+#[cfg(any(feature = "io", feature = "os"))]
 unsafe fn reserve(len: usize) -> Result<*mut c_void> {
     let pg = VirtualAlloc(ptr::null_mut(), len as SIZE_T, MEM_RESERVE, PAGE_NOACCESS);
     if pg.is_null() {
@@ -138,7 +582,7 @@ unsafe fn reserve(len: usize) -> Result<*mut c_void> {
     }
 }
 
-#[cfg(feature = "io")]
+#[cfg(any(feature = "io", feature = "os"))]
 unsafe fn map_ring_handle(map: &MapHandle, len: usize, pg: *mut c_void) -> Result<*mut u8> {
     let a = map.view(RingPrimary, FILE_MAP_READ | FILE_MAP_WRITE, 0, len, pg)?;
     let b = map.view(
@@ -152,7 +596,7 @@ unsafe fn map_ring_handle(map: &MapHandle, len: usize, pg: *mut c_void) -> Resul
         UnmapViewOfFile(a as *mut c_void);
         b
     } else {
-        Ok(a as *mut u8)
+        Ok(a)
     }
 }
 
@@ -161,7 +605,37 @@ unsafe fn map_ring_handle(map: &MapHandle, len: usize, pg: *mut c_void) -> Resul
 /// The length is the size of the sequential range, and the offset of
 /// `len+1` refers to the same memory location at offset `0`. The circle
 /// continues to up through the offset of `2*len - 1`.
-#[cfg(feature = "io")]
+///
+/// The returned pointer is the base of the full `2*len` range. A single
+/// pagefile-backed section from `CreateFileMapping` is mapped into both
+/// halves with `MapViewOfFileEx`.
+/// The length must be a multiple of the [`allocation_size()`], and the range
+/// must be released with [`unmap_ring()`]. This is the primitive that
+/// [`Ring`] and [`InfiniteRing`] are built on, for use by other circular
+/// structures.
+///
+/// # Examples
+///
+/// ```
+/// use vmap::os::{map_ring, unmap_ring};
+///
+/// # fn main() -> vmap::Result<()> {
+/// let len = vmap::allocation_size();
+/// let pg = map_ring(len)?;
+/// unsafe {
+///     *pg.add(len - 1) = 1;
+///     assert_eq!(*pg.add(2 * len - 1), 1);
+///     unmap_ring(pg, len)?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`allocation_size()`]: ../fn.allocation_size.html
+/// [`unmap_ring()`]: fn.unmap_ring.html
+/// [`Ring`]: ../io/struct.Ring.html
+/// [`InfiniteRing`]: ../io/struct.InfiniteRing.html
+#[cfg(any(feature = "io", feature = "os"))]
 pub fn map_ring(len: usize) -> Result<*mut u8> {
     let full = 2 * len;
     let map = unsafe { MapHandle::new(RingAllocate, INVALID_HANDLE_VALUE, PAGE_READWRITE, full)? };
@@ -189,13 +663,94 @@ pub fn map_ring(len: usize) -> Result<*mut u8> {
 /// Generally don't use this unless you are entirely sure you are
 /// doing so correctly.
 pub unsafe fn unmap(pg: *mut u8, _len: usize) -> Result<()> {
-    if UnmapViewOfFile(pg as *mut c_void) != 0 {
+    if UnmapViewOfFile(pg as *mut c_void) == 0 {
         Err(Error::last_os_error(Unmap))
     } else {
         Ok(())
     }
 }
 
+/// Reserves a private anonymous page range without access.
+///
+/// No memory is committed for the range until pages are made accessible with
+/// [`commit_anon()`]. The range is released with [`release_anon()`].
+///
+/// [`commit_anon()`]: fn.commit_anon.html
+/// [`release_anon()`]: fn.release_anon.html
+pub fn reserve_anon(len: usize) -> Result<*mut u8> {
+    let pg = unsafe { VirtualAlloc(ptr::null_mut(), len as SIZE_T, MEM_RESERVE, PAGE_NOACCESS) };
+    if pg.is_null() {
+        Err(Error::last_os_error(Reserve).with_length(len))
+    } else {
+        Ok(pg as *mut u8)
+    }
+}
+
+/// Commits a page range from a reservation, making it readable and writable.
+///
+/// # Safety
+///
+/// This does not know or care if `pg` or `len` are valid. That is,
+/// it may be null, not at a proper page boundary, point to a size
+/// different from `len`, or worse yet, point to a properly mapped
+/// pointer from some other allocation system.
+///
+/// Generally don't use this unless you are entirely sure you are
+/// doing so correctly.
+pub unsafe fn commit_anon(pg: *mut u8, len: usize) -> Result<()> {
+    if VirtualAlloc(pg as *mut c_void, len as SIZE_T, MEM_COMMIT, PAGE_READWRITE).is_null() {
+        Err(Error::last_os_error(Commit).with_length(len))
+    } else {
+        Ok(())
+    }
+}
+
+/// Decommits a page range of a reservation, returning it to the reserved state.
+///
+/// The pages are discarded with `MEM_DECOMMIT` and the range becomes
+/// inaccessible until it is committed again with [`commit_anon()`], when it
+/// reads as zero. The address space stays reserved.
+///
+/// [`commit_anon()`]: fn.commit_anon.html
+///
+/// # Safety
+///
+/// This does not know or care if `pg` or `len` are valid. That is,
+/// it may be null, not at a proper page boundary, point to a size
+/// different from `len`, or worse yet, point to a properly mapped
+/// pointer from some other allocation system.
+///
+/// Generally don't use this unless you are entirely sure you are
+/// doing so correctly.
+pub unsafe fn decommit_anon(pg: *mut u8, len: usize) -> Result<()> {
+    if VirtualFree(pg as *mut c_void, len as SIZE_T, MEM_DECOMMIT) == 0 {
+        Err(Error::last_os_error(Release).with_length(len))
+    } else {
+        Ok(())
+    }
+}
+
+/// Releases a page range reserved with [`reserve_anon()`].
+///
+/// [`reserve_anon()`]: fn.reserve_anon.html
+///
+/// # Safety
+///
+/// This does not know or care if `pg` or `len` are valid. That is,
+/// it may be null, not at a proper page boundary, point to a size
+/// different from `len`, or worse yet, point to a properly mapped
+/// pointer from some other allocation system.
+///
+/// Generally don't use this unless you are entirely sure you are
+/// doing so correctly.
+pub unsafe fn release_anon(pg: *mut u8, len: usize) -> Result<()> {
+    if VirtualFree(pg as *mut c_void, 0, MEM_RELEASE) == 0 {
+        Err(Error::last_os_error(Release).with_length(len))
+    } else {
+        Ok(())
+    }
+}
+
 /// Unmaps a ring mapping created by `map_ring`.
 ///
 /// # Safety
@@ -207,9 +762,9 @@ pub unsafe fn unmap(pg: *mut u8, _len: usize) -> Result<()> {
 ///
 /// Generally don't use this unless you are entirely sure you are
 /// doing so correctly.
-#[cfg(feature = "io")]
+#[cfg(any(feature = "io", feature = "os"))]
 pub unsafe fn unmap_ring(pg: *mut u8, len: usize) -> Result<()> {
-    if UnmapViewOfFile(pg.offset(len as isize) as *mut c_void) == 0 {
+    if UnmapViewOfFile(pg.add(len) as *mut c_void) == 0 {
         Err(Error::last_os_error(RingDeallocate))
     } else {
         UnmapViewOfFile(pg as *mut c_void);
@@ -229,15 +784,60 @@ pub unsafe fn unmap_ring(pg: *mut u8, len: usize) -> Result<()> {
 /// Generally don't use this unless you are entirely sure you are
 /// doing so correctly.
 pub unsafe fn protect(pg: *mut u8, len: usize, prot: Protect) -> Result<()> {
-    let prot = match prot {
+    let flags = match prot {
         Protect::ReadOnly => PAGE_READONLY,
         Protect::ReadWrite => PAGE_READWRITE,
-        Protect::ReadCopy => PAGE_READWRITE,
+        Protect::ReadCopy => PAGE_WRITECOPY,
         Protect::ReadExec => PAGE_EXECUTE_READ,
     };
     let mut old = 0;
-    if VirtualProtect(pg as *mut c_void, len, prot, &mut old) == 0 {
-        Err(Error::last_os_error(Protect))
+    if VirtualProtect(pg as *mut c_void, len, flags, &mut old) == 0 {
+        Err(Error::last_os_error(Protect)
+            .with_length(len)
+            .with_protect(prot))
+    } else {
+        Ok(())
+    }
+}
+
+/// Makes code written to a page range visible to instruction fetches.
+///
+/// After instructions are stored through a writable mapping, the
+/// instruction cache must be synchronized before they are run. This uses
+/// `FlushInstructionCache`, which is required on ARM and harmless elsewhere.
+///
+/// # Safety
+///
+/// This does not know or care if `pg` or `len` are valid. That is,
+/// it may be null, not at a proper page boundary, point to a size
+/// different from `len`, or worse yet, point to a properly mapped
+/// pointer from some other allocation system.
+///
+/// Generally don't use this unless you are entirely sure you are
+/// doing so correctly.
+pub unsafe fn flush_icache(pg: *mut u8, len: usize) -> Result<()> {
+    if FlushInstructionCache(GetCurrentProcess(), pg as *const c_void, len) == 0 {
+        Err(Error::last_os_error(Protect).with_length(len))
+    } else {
+        Ok(())
+    }
+}
+
+/// Removes all access to a page range so that any access to it faults.
+///
+/// # Safety
+///
+/// This does not know or care if `pg` or `len` are valid. That is,
+/// it may be null, not at a proper page boundary, point to a size
+/// different from `len`, or worse yet, point to a properly mapped
+/// pointer from some other allocation system.
+///
+/// Generally don't use this unless you are entirely sure you are
+/// doing so correctly.
+pub unsafe fn guard(pg: *mut u8, len: usize) -> Result<()> {
+    let mut old = 0;
+    if VirtualProtect(pg as *mut c_void, len, PAGE_NOACCESS, &mut old) == 0 {
+        Err(Error::last_os_error(Protect).with_length(len))
     } else {
         Ok(())
     }
@@ -256,7 +856,7 @@ pub unsafe fn protect(pg: *mut u8, len: usize, prot: Protect) -> Result<()> {
 /// doing so correctly.
 pub unsafe fn flush(pg: *mut u8, file: &File, len: usize, mode: Flush) -> Result<()> {
     if FlushViewOfFile(pg as *mut c_void, len as SIZE_T) == 0 {
-        Err(Error::last_os_error(Flush))
+        Err(Error::last_os_error(Flush).with_length(len))
     } else {
         match mode {
             Flush::Sync => {
@@ -272,6 +872,14 @@ pub unsafe fn flush(pg: *mut u8, file: &File, len: usize, mode: Flush) -> Result
 }
 /// Updates the advise for the page range.
 ///
+/// [`Advise::WillNeed`] prefetches the range with `PrefetchVirtualMemory`,
+/// which requires Windows 8 or later. Every other hint is a no-op on Windows.
+/// In particular, `VirtualUnlock` is not used for [`Advise::WillNotNeed`], as
+/// it would also release any lock held on the pages.
+///
+/// [`Advise::WillNeed`]: ../enum.Advise.html#variant.WillNeed
+/// [`Advise::WillNotNeed`]: ../enum.Advise.html#variant.WillNotNeed
+///
 /// # Safety
 ///
 /// This does not know or care if `pg` or `len` are valid. That is,
@@ -281,12 +889,80 @@ pub unsafe fn flush(pg: *mut u8, file: &File, len: usize, mode: Flush) -> Result
 ///
 /// Generally don't use this unless you are entirely sure you are
 /// doing so correctly.
-pub unsafe fn advise(_pg: *mut u8, _len: usize, _adv: Advise) -> Result<()> {
+pub unsafe fn advise(pg: *mut u8, len: usize, adv: Advise) -> Result<()> {
+    if adv != Advise::WillNeed || len == 0 {
+        return Ok(());
+    }
+    let mut range = WIN32_MEMORY_RANGE_ENTRY {
+        VirtualAddress: pg as *mut c_void,
+        NumberOfBytes: len as SIZE_T,
+    };
+    if PrefetchVirtualMemory(GetCurrentProcess(), 1, &mut range, 0) == 0 {
+        Err(Error::last_os_error(Operation::Advise))
+    } else {
+        Ok(())
+    }
+}
+
+/// Updates the advise for page ranges in the address space of another process.
+///
+/// This is only supported on Linux, and returns an [`Input::Unsupported`]
+/// error elsewhere.
+///
+/// [`Input::Unsupported`]: ../enum.Input.html#variant.Unsupported
+#[cfg(feature = "os")]
+pub fn process_advise(_pid: i32, _ranges: &[(usize, usize)], _adv: Advise) -> Result<()> {
+    Err(Error::input(Advise, Input::Unsupported))
+}
+
+/// Updates the page cache advise for a range of a file.
+///
+/// This is currently a no-op on Windows.
+pub fn fadvise(_file: &File, _off: u64, _len: usize, _adv: FAdvise) -> Result<()> {
+    Ok(())
+}
+
+/// Initiates readahead of a file range into the page cache.
+///
+/// This is currently a no-op on Windows.
+#[cfg(feature = "os")]
+pub fn readahead(_file: &File, _off: u64, _len: usize) -> Result<()> {
     Ok(())
 }
 
+/// Clears the soft-dirty bits of every page in the process.
+///
+/// This is not supported on Windows.
+pub fn clear_soft_dirty() -> Result<()> {
+    Err(Error::input(SoftDirty, Input::Unsupported))
+}
+
+/// Gets the indices of the pages in a range with the soft-dirty bit set.
+///
+/// This is not supported on Windows.
+pub fn soft_dirty_pages(_pg: *const u8, _len: usize) -> Result<Vec<usize>> {
+    Err(Error::input(SoftDirty, Input::Unsupported))
+}
+
+/// Finds the first page in a range that is, or is not, resident in memory.
+///
+/// This is not supported on Windows.
+///
+/// # Safety
+///
+/// This does not dereference `pg`, but is unsafe to match the Unix version.
+pub unsafe fn find_residency(_pg: *mut u8, _len: usize, _resident: bool) -> Result<Option<usize>> {
+    Err(Error::input(Residency, Input::Unsupported))
+}
+
 /// Locks physical pages into memory.
 ///
+/// This uses `VirtualLock`, which can only lock as many pages as fit in the
+/// minimum working set size of the process, less a small overhead. Beyond
+/// that it fails with `ERROR_WORKING_SET_QUOTA`, which is returned as the raw
+/// OS error. The working set may be enlarged with `SetProcessWorkingSetSize`
+/// before locking more pages.
+///
 /// # Safety
 ///
 /// This does not know or care if `pg` or `len` are valid. That is,
@@ -322,3 +998,171 @@ pub unsafe fn unlock(pg: *mut u8, len: usize) -> Result<()> {
         Ok(())
     }
 }
+
+/// Binds the physical pages of a range to a NUMA node.
+///
+/// Windows only supports a preferred node when the memory is first
+/// allocated (i.e. `VirtualAllocExNuma`), so this is a no-op for an
+/// existing mapping.
+///
+/// # Safety
+///
+/// This does not know or care if `pg` or `len` are valid. That is,
+/// it may be null, not at a proper page boundary, point to a size
+/// different from `len`, or worse yet, point to a properly mapped
+/// pointer from some other allocation system.
+///
+/// Generally don't use this unless you are entirely sure you are
+/// doing so correctly.
+pub unsafe fn bind_numa(_pg: *mut u8, _len: usize, _node: u32) -> Result<()> {
+    Ok(())
+}
+
+/// Gets the minor and major page fault counts of the process.
+///
+/// This uses `GetProcessMemoryInfo`, which only reports a single count that
+/// includes both soft faults and faults read from disk. It is returned as the
+/// minor count, and the major count is always zero.
+pub fn page_faults() -> Result<(u64, u64)> {
+    let mut pmc = unsafe { mem::zeroed::<PROCESS_MEMORY_COUNTERS>() };
+    let size = mem::size_of::<PROCESS_MEMORY_COUNTERS>() as DWORD;
+    if unsafe { GetProcessMemoryInfo(GetCurrentProcess(), &mut pmc, size) } == 0 {
+        Err(Error::last_os_error(PageFaults))
+    } else {
+        Ok((pmc.PageFaultCount as u64, 0))
+    }
+}
+
+/// Acquires an advisory lock on an entire file.
+///
+/// This uses `LockFileEx` and blocks until the lock is available. An
+/// exclusive lock excludes all other locks, while any number of shared locks
+/// may be held at once.
+pub fn lock_file(file: &File, exclusive: bool) -> Result<()> {
+    let flags = if exclusive {
+        LOCKFILE_EXCLUSIVE_LOCK
+    } else {
+        0
+    };
+    let ok = unsafe {
+        let mut overlapped = mem::zeroed::<OVERLAPPED>();
+        LockFileEx(file.as_raw_handle(), flags, 0, !0, !0, &mut overlapped)
+    };
+    if ok == 0 {
+        Err(Error::last_os_error(LockFile))
+    } else {
+        Ok(())
+    }
+}
+
+/// Releases an advisory lock acquired with [`lock_file`].
+///
+/// [`lock_file`]: fn.lock_file.html
+pub fn unlock_file(file: &File) -> Result<()> {
+    let ok = unsafe {
+        let mut overlapped = mem::zeroed::<OVERLAPPED>();
+        UnlockFileEx(file.as_raw_handle(), 0, !0, !0, &mut overlapped)
+    };
+    if ok == 0 {
+        Err(Error::last_os_error(UnlockFile))
+    } else {
+        Ok(())
+    }
+}
+
+/// Acquires an advisory lock on a byte range of a file.
+///
+/// This uses `LockFileEx` at an offset and blocks until the lock is
+/// available. An exclusive lock excludes all other locks on any of the
+/// bytes, while any number of shared locks may overlap. `len` must not be
+/// zero.
+pub fn lock_file_range(file: &File, off: u64, len: u64, exclusive: bool) -> Result<()> {
+    if len == 0 {
+        return Err(Error::input(LockFile, Input::InvalidRange));
+    }
+    let flags = if exclusive {
+        LOCKFILE_EXCLUSIVE_LOCK
+    } else {
+        0
+    };
+    let ok = unsafe {
+        let mut overlapped = range_overlapped(off);
+        let (lo, hi) = (len as DWORD, (len >> 32) as DWORD);
+        LockFileEx(file.as_raw_handle(), flags, 0, lo, hi, &mut overlapped)
+    };
+    if ok == 0 {
+        Err(Error::last_os_error(LockFile))
+    } else {
+        Ok(())
+    }
+}
+
+/// Releases an advisory lock acquired with [`lock_file_range`].
+///
+/// [`lock_file_range`]: fn.lock_file_range.html
+pub fn unlock_file_range(file: &File, off: u64, len: u64) -> Result<()> {
+    let ok = unsafe {
+        let mut overlapped = range_overlapped(off);
+        let (lo, hi) = (len as DWORD, (len >> 32) as DWORD);
+        UnlockFileEx(file.as_raw_handle(), 0, lo, hi, &mut overlapped)
+    };
+    if ok == 0 {
+        Err(Error::last_os_error(UnlockFile))
+    } else {
+        Ok(())
+    }
+}
+
+unsafe fn range_overlapped(off: u64) -> OVERLAPPED {
+    let mut overlapped = mem::zeroed::<OVERLAPPED>();
+    let s = overlapped.u.s_mut();
+    s.Offset = off as DWORD;
+    s.OffsetHigh = (off >> 32) as DWORD;
+    overlapped
+}
+
+/// Installs a `SIGBUS` handler to recover from faults in truncated files.
+///
+/// Windows does not allow a file to be truncated while a view of it is
+/// mapped, so this is a no-op.
+pub fn install_sigbus_handler() -> Result<()> {
+    Ok(())
+}
+
+/// Installs the handler that records write faults in tracked maps.
+pub(crate) fn install_write_handler() -> Result<()> {
+    static INSTALL: Once = Once::new();
+    static INSTALLED: AtomicBool = AtomicBool::new(false);
+    INSTALL.call_once(|| {
+        let handler = unsafe { AddVectoredExceptionHandler(1, Some(handle_write)) };
+        INSTALLED.store(!handler.is_null(), Ordering::Release);
+    });
+    if INSTALLED.load(Ordering::Acquire) {
+        Ok(())
+    } else {
+        Err(Error::input(TrackWrites, Input::Unsupported))
+    }
+}
+
+unsafe extern "system" fn handle_write(info: *mut EXCEPTION_POINTERS) -> LONG {
+    let rec = &*(*info).ExceptionRecord;
+    // The first parameter of an access violation is 1 for a write, and the
+    // second is the address that was accessed.
+    if rec.ExceptionCode == EXCEPTION_ACCESS_VIOLATION
+        && rec.NumberParameters >= 2
+        && rec.ExceptionInformation[0] == 1
+        && crate::os::watch::write_fault(rec.ExceptionInformation[1])
+    {
+        EXCEPTION_CONTINUE_EXECUTION
+    } else {
+        EXCEPTION_CONTINUE_SEARCH
+    }
+}
+
+pub(crate) fn register_mapping(_pg: *mut u8, _len: usize) {}
+
+pub(crate) fn unregister_mapping(_pg: *mut u8) {}
+
+pub(crate) fn is_truncated(_ptr: *const u8) -> bool {
+    false
+}
@@ -0,0 +1,170 @@
+use std::cmp;
+use std::ops::{Deref, Range};
+
+use crate::{page_size, ConvertResult, Error, Input, MapMut, Operation, Protect, Result, Size};
+
+/// Writable map with a separate protection for each page.
+///
+/// Pages are given their own protection with [`.protect()`], such as to make
+/// the pages of a copy-on-write arena read-only so that a stray write traps.
+/// The protection of every page is tracked, and mutable access through
+/// [`.get_mut()`] is only given to ranges whose pages are all writable, so
+/// unlike [`MapMut::protect_range()`] this never faults on a write from safe
+/// code. Every page remains readable.
+///
+/// # Examples
+///
+/// ```
+/// use vmap::{MapMut, MapRegion, Protect};
+///
+/// # fn main() -> vmap::Result<()> {
+/// let page = vmap::page_size();
+/// let mut region = MapRegion::new(MapMut::new(4 * page)?);
+/// region.get_mut(0..4)?.copy_from_slice(b"base");
+///
+/// region.protect(0, page, Protect::ReadOnly)?;
+/// assert_eq!(Protect::ReadOnly, region.protection_at(0));
+/// assert!(region.get_mut(0..4).is_err());
+/// assert_eq!(b"base", &region[..4]);
+/// region.get_mut(page..page + 4)?.copy_from_slice(b"next");
+///
+/// let map = region.into_inner().map_err(|(e, _)| e)?;
+/// assert_eq!(Protect::ReadWrite, map.protection());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`.protect()`]: #method.protect
+/// [`.get_mut()`]: #method.get_mut
+/// [`MapMut::protect_range()`]: struct.MapMut.html#method.protect_range
+#[derive(Debug)]
+pub struct MapRegion {
+    map: MapMut,
+    prot: Box<[Protect]>,
+}
+
+impl MapRegion {
+    /// Creates a region over a map, with every page at the protection of
+    /// the map.
+    pub fn new(map: MapMut) -> Self {
+        let lead = Size::page().offset(map.as_ptr() as usize);
+        let prot = vec![map.protection(); (lead + map.len()).div_ceil(page_size())];
+        Self {
+            map,
+            prot: prot.into(),
+        }
+    }
+
+    /// Gets the size of each page.
+    #[inline]
+    pub fn page_size(&self) -> usize {
+        page_size()
+    }
+
+    /// Changes the protection of a range of pages.
+    ///
+    /// The range follows the rules of [`MapMut::protect_range()`]: `off`
+    /// must be at a page boundary, the length is rounded up to whole pages,
+    /// and the protection must suit the sharing of the map. On error, the
+    /// tracked protections are unchanged.
+    ///
+    /// [`MapMut::protect_range()`]: struct.MapMut.html#method.protect_range
+    pub fn protect(&mut self, off: usize, len: usize, prot: Protect) -> Result<()> {
+        // Safety: writes are only made through `get_mut`, which checks the
+        // tracked protection of every page it covers.
+        unsafe { self.map.protect_range(off, len, prot)? };
+        let prot = match (prot, self.map.is_private()) {
+            (Protect::ReadWrite, true) => Protect::ReadCopy,
+            _ => prot,
+        };
+        let pages = self.pages(off..off + len);
+        self.prot[pages].fill(prot);
+        Ok(())
+    }
+
+    /// Gets the protection of the page containing byte `off`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `off` is not less than the length of the map.
+    #[inline]
+    pub fn protection_at(&self, off: usize) -> Protect {
+        assert!(off < self.map.len(), "offset out of range");
+        self.prot[(self.lead() + off) / page_size()]
+    }
+
+    /// Gets a mutable slice of a range of the map.
+    ///
+    /// An [`Input::InvalidRange`] error is returned if the range is not
+    /// within the map, and an [`Input::Unsupported`] error is returned if any
+    /// page of the range is not writable.
+    ///
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    pub fn get_mut(&mut self, range: Range<usize>) -> Result<&mut [u8]> {
+        if range.start > range.end || range.end > self.map.len() {
+            return Err(Error::input(Operation::Write, Input::InvalidRange));
+        }
+        if range.start < range.end {
+            let pages = self.pages(range.clone());
+            let denied = self.prot[pages]
+                .iter()
+                .find(|p| !matches!(p, Protect::ReadWrite | Protect::ReadCopy));
+            if let Some(&prot) = denied {
+                return Err(Error::input(Operation::Write, Input::Unsupported).with_protect(prot));
+            }
+        }
+        Ok(&mut self.map[range])
+    }
+
+    /// Restores the protection of the map to every page and transfers
+    /// ownership of the map back to the caller.
+    ///
+    /// The region is returned along with any error.
+    #[allow(clippy::result_large_err)]
+    pub fn into_inner(mut self) -> ConvertResult<MapMut, Self> {
+        let prot = self.map.protection();
+        let mut idx = 0;
+        while idx < self.prot.len() {
+            if self.prot[idx] == prot {
+                idx += 1;
+                continue;
+            }
+            let end = (idx..self.prot.len())
+                .find(|&i| self.prot[i] == prot)
+                .unwrap_or(self.prot.len());
+            // Only pages that were changed differ, and those start at a
+            // page boundary within the map.
+            let off = idx * page_size() - self.lead();
+            let len = cmp::min((end - idx) * page_size(), self.map.len() - off);
+            if let Err(err) = unsafe { self.map.protect_range(off, len, prot) } {
+                return Err((err, self));
+            }
+            self.prot[idx..end].fill(prot);
+            idx = end;
+        }
+        Ok(self.map)
+    }
+
+    // Gets the indexes of the pages holding the bytes of a non-empty range.
+    #[inline]
+    fn pages(&self, range: Range<usize>) -> Range<usize> {
+        let (lead, page) = (self.lead(), page_size());
+        (lead + range.start) / page..(lead + range.end).div_ceil(page)
+    }
+
+    // Gets the offset of the start of the map within its first page.
+    #[inline]
+    fn lead(&self) -> usize {
+        Size::page().offset(self.map.as_ptr() as usize)
+    }
+}
+
+impl Deref for MapRegion {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        &self.map
+    }
+}
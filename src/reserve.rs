@@ -0,0 +1,252 @@
+use std::collections::BTreeMap;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, MutexGuard};
+use std::{fmt, ptr, slice};
+
+use crate::os::{commit_anon, decommit_anon, release_anon, reserve_anon};
+use crate::{track_map, track_unmap, Error, Input, Operation, Result, Size};
+
+/// Range of address space reserved without committing any memory.
+///
+/// The whole range is reserved up front and stays at the same address, but
+/// only counts against the memory of the process once ranges of it are
+/// committed with [`.commit()`]. This suits arenas and runtimes that need a
+/// large, stable address range while paying only for the pages in use. On
+/// Unix the range is mapped with `PROT_NONE`, along with `MAP_NORESERVE` on
+/// Linux and Android, and on Windows it is reserved with `MEM_RESERVE`.
+///
+/// Each committed range is borrowed from the reservation, so no range can
+/// outlive it, and ranges never overlap. A range is decommitted when it is
+/// dropped, and its pages are given back to the system while the address
+/// space stays reserved. Committing the same pages again yields zeros.
+///
+/// # Examples
+///
+/// ```
+/// use vmap::Reservation;
+///
+/// # fn main() -> vmap::Result<()> {
+/// let page = vmap::page_size();
+/// let res = Reservation::new(1 << 30)?;
+/// let mut a = res.commit(0, 100)?;
+/// let mut b = res.commit(16 * page, 2 * page)?;
+/// a[..4].copy_from_slice(b"head");
+/// b[..4].copy_from_slice(b"tail");
+/// assert_eq!(3 * page, res.committed());
+/// assert!(res.commit(16 * page, page).is_err());
+///
+/// drop(b);
+/// assert_eq!(page, res.committed());
+/// assert_eq!(0, res.commit(16 * page, page)?[0]);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`.commit()`]: #method.commit
+pub struct Reservation {
+    ptr: *mut u8,
+    len: usize,
+    // Committed ranges by their starting offset, mapped to the offset of
+    // the page boundary after their end.
+    ranges: Mutex<BTreeMap<usize, usize>>,
+}
+
+// The committed ranges never overlap, and the table of ranges is only
+// changed while holding the lock.
+unsafe impl Send for Reservation {}
+unsafe impl Sync for Reservation {}
+
+impl Reservation {
+    /// Reserves at least `hint` bytes of address space.
+    ///
+    /// The length is rounded up to the allocation size. No memory is
+    /// committed until a range is taken with [`.commit()`].
+    ///
+    /// [`.commit()`]: #method.commit
+    pub fn new(hint: usize) -> Result<Self> {
+        let len = Size::alloc().round(hint);
+        let ptr = match len {
+            0 => ptr::NonNull::dangling().as_ptr(),
+            _ => reserve_anon(len)?,
+        };
+        if len > 0 {
+            track_map(len);
+        }
+        Ok(Self {
+            ptr,
+            len,
+            ranges: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    /// Gets the length of the reservation in bytes.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Tests if the reservation is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Gets the pointer to the start of the reservation.
+    ///
+    /// The address never changes, and a range committed at offset `off`
+    /// starts `off` bytes after this pointer.
+    #[inline]
+    pub fn as_ptr(&self) -> *const u8 {
+        self.ptr
+    }
+
+    /// Gets the number of bytes currently committed, in whole pages.
+    pub fn committed(&self) -> usize {
+        self.ranges().iter().map(|(start, end)| end - start).sum()
+    }
+
+    /// Commits a range of the reservation for reading and writing.
+    ///
+    /// The range starts at `off`, which must be at a page boundary, and the
+    /// pages holding its `len` bytes are committed. The range reads as zero
+    /// until written. An [`Input::Unaligned`] error is returned if `off` is
+    /// not at a page boundary, an [`Input::InvalidRange`] error is returned
+    /// if the range extends past the reservation, and an
+    /// [`Input::AddressInUse`] error is returned if any of its pages are
+    /// already committed to another range.
+    ///
+    /// [`Input::Unaligned`]: enum.Input.html#variant.Unaligned
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    /// [`Input::AddressInUse`]: enum.Input.html#variant.AddressInUse
+    pub fn commit(&self, off: usize, len: usize) -> Result<ReservationRange<'_>> {
+        let page = Size::page();
+        if !page.is_aligned(off) {
+            return Err(Error::input(Operation::Commit, Input::Unaligned));
+        }
+        let end = off
+            .checked_add(len)
+            .filter(|&end| end <= self.len)
+            .ok_or_else(|| Error::input(Operation::Commit, Input::InvalidRange))?;
+        let end = page.round(end);
+        let ptr = unsafe { self.ptr.add(off) };
+        if len > 0 {
+            let mut ranges = self.ranges();
+            if let Some((_, &prev)) = ranges.range(..end).next_back() {
+                if prev > off {
+                    return Err(Error::input(Operation::Commit, Input::AddressInUse));
+                }
+            }
+            unsafe { commit_anon(ptr, end - off)? };
+            ranges.insert(off, end);
+        }
+        Ok(ReservationRange {
+            res: self,
+            off,
+            ptr,
+            len,
+        })
+    }
+
+    fn ranges(&self) -> MutexGuard<'_, BTreeMap<usize, usize>> {
+        self.ranges.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Decommits the range starting at `off` and forgets it. The range is
+    // forgotten even on error, as the borrow that owned it is gone.
+    fn decommit(&self, off: usize) -> Result<()> {
+        match self.ranges().remove(&off) {
+            Some(end) => unsafe { decommit_anon(self.ptr.add(off), end - off) },
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            track_unmap(self.len);
+            unsafe { release_anon(self.ptr, self.len).unwrap_or_default() };
+        }
+    }
+}
+
+impl fmt::Debug for Reservation {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("Reservation")
+            .field("ptr", &self.ptr)
+            .field("len", &self.len)
+            .field("committed", &self.committed())
+            .finish()
+    }
+}
+
+/// Committed range borrowed from a [`Reservation`].
+///
+/// This dereferences to the bytes of the range and decommits its pages when
+/// dropped.
+///
+/// [`Reservation`]: struct.Reservation.html
+pub struct ReservationRange<'a> {
+    res: &'a Reservation,
+    off: usize,
+    ptr: *mut u8,
+    len: usize,
+}
+
+// A range is an exclusive borrow of its bytes, like `&mut [u8]`.
+unsafe impl Send for ReservationRange<'_> {}
+unsafe impl Sync for ReservationRange<'_> {}
+
+impl ReservationRange<'_> {
+    /// Gets the offset of the range within its reservation.
+    #[inline]
+    pub fn offset(&self) -> usize {
+        self.off
+    }
+
+    /// Decommits the range now and returns any error from doing so.
+    ///
+    /// Dropping the range performs the same step but ignores any error.
+    pub fn decommit(self) -> Result<()> {
+        let res = match self.len {
+            0 => Ok(()),
+            _ => self.res.decommit(self.off),
+        };
+        std::mem::forget(self);
+        res
+    }
+}
+
+impl Deref for ReservationRange<'_> {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl DerefMut for ReservationRange<'_> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+impl Drop for ReservationRange<'_> {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            self.res.decommit(self.off).unwrap_or_default();
+        }
+    }
+}
+
+impl fmt::Debug for ReservationRange<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("ReservationRange")
+            .field("offset", &self.off)
+            .field("ptr", &self.ptr)
+            .field("len", &self.len)
+            .finish()
+    }
+}
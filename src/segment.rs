@@ -0,0 +1,171 @@
+use std::fmt;
+use std::ops::{Deref, Range};
+
+use crate::{ConvertResult, Error, Input, Map, Operation, Result, Span};
+
+/// Owned read-only view of a range of a [`Map`].
+///
+/// A segment holds a clone of the map it was taken from, so the pages stay
+/// mapped for as long as any segment or other clone of the map exists and
+/// are unmapped when the last of them is dropped. Taking or cloning a
+/// segment only increments a reference count and never remaps the file.
+/// This suits handing out independent owned records of a large container
+/// file, such as to other threads.
+///
+/// # Examples
+///
+/// ```
+/// use vmap::Map;
+/// use std::path::PathBuf;
+/// use std::str::from_utf8;
+///
+/// # fn main() -> vmap::Result<()> {
+/// # let tmp = tempdir::TempDir::new("vmap")?;
+/// let path: PathBuf = /* path to file */
+/// # tmp.path().join("example");
+/// # std::fs::write(&path, "A cross-platform library for fast and safe memory-mapped IO in Rust")?;
+/// let (map, _) = Map::with_options().open(&path)?;
+/// let (head, tail) = map.into_split(16).map_err(|(e, _)| e)?;
+/// assert_eq!(Ok("A cross-platform"), from_utf8(&head));
+///
+/// let io = tail.slice(27..40)?;
+/// drop(tail);
+/// assert_eq!(Ok("memory-mapped"), from_utf8(&io));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Map`]: struct.Map.html
+#[derive(Clone)]
+pub struct MapSegment {
+    map: Map,
+    off: usize,
+    len: usize,
+}
+
+impl MapSegment {
+    pub(crate) fn new(map: Map, range: Range<usize>) -> Result<Self> {
+        if range.start > range.end || range.end > map.len() {
+            return Err(Error::input(Operation::Read, Input::InvalidRange));
+        }
+        Ok(Self {
+            map,
+            off: range.start,
+            len: range.end - range.start,
+        })
+    }
+
+    /// Gets the map that the segment is a view of.
+    #[inline]
+    pub fn map(&self) -> &Map {
+        &self.map
+    }
+
+    /// Transfers ownership of the map that the segment is a view of back to
+    /// the caller.
+    #[inline]
+    pub fn into_map(self) -> Map {
+        self.map
+    }
+
+    /// Gets the range of the map covered by the segment.
+    #[inline]
+    pub fn range(&self) -> Range<usize> {
+        self.off..self.off + self.len
+    }
+
+    /// Gets a segment of a range of this segment.
+    ///
+    /// The range is relative to the start of this segment and must lie
+    /// within it, otherwise an [`Input::InvalidRange`] error is returned.
+    ///
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    pub fn slice(&self, range: Range<usize>) -> Result<Self> {
+        if range.start > range.end || range.end > self.len {
+            return Err(Error::input(Operation::Read, Input::InvalidRange));
+        }
+        Ok(Self {
+            map: self.map.clone(),
+            off: self.off + range.start,
+            len: range.end - range.start,
+        })
+    }
+
+    /// Splits the segment into two segments at byte `mid`.
+    ///
+    /// The first segment holds the bytes `0..mid` and the second the bytes
+    /// from `mid` to the end. A `mid` past the end of the segment returns an
+    /// [`Input::InvalidRange`] error along with the original segment.
+    ///
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    pub fn into_split(self, mid: usize) -> ConvertResult<(Self, Self), Self> {
+        if mid > self.len {
+            return Err((Error::input(Operation::Read, Input::InvalidRange), self));
+        }
+        let tail = Self {
+            map: self.map.clone(),
+            off: self.off + mid,
+            len: self.len - mid,
+        };
+        let head = Self { len: mid, ..self };
+        Ok((head, tail))
+    }
+}
+
+impl From<Map> for MapSegment {
+    /// Creates a segment of the whole of a map.
+    fn from(map: Map) -> Self {
+        let len = map.len();
+        Self { map, off: 0, len }
+    }
+}
+
+impl Span for MapSegment {
+    #[inline]
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    fn as_ptr(&self) -> *const u8 {
+        self.map.as_ptr().wrapping_add(self.off)
+    }
+}
+
+impl Deref for MapSegment {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        &self.map[self.off..self.off + self.len]
+    }
+}
+
+impl AsRef<[u8]> for MapSegment {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.deref()
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl From<MapSegment> for bytes::Bytes {
+    /// Converts the segment into `Bytes` without copying.
+    ///
+    /// The `Bytes` holds the map that the segment is a view of, as in the
+    /// conversion from a [`Map`].
+    ///
+    /// [`Map`]: struct.Map.html
+    fn from(seg: MapSegment) -> Self {
+        Self::from_owner(seg)
+    }
+}
+
+impl fmt::Debug for MapSegment {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("MapSegment")
+            .field("ptr", &self.as_ptr())
+            .field("len", &self.len)
+            .finish()
+    }
+}
@@ -0,0 +1,81 @@
+//! Named shared memory for mappings between unrelated processes.
+//!
+//! A [`MapMut::shared()`] map is only visible to processes created with
+//! `fork`. The functions here instead create or open a shared memory object
+//! by name, so any process that knows the name can map the same memory.
+//! On Unix the object is created with `shm_open`, and on Windows it is a
+//! named file mapping backed by the paging file.
+//!
+//! A name must not be empty or contain `/`, `\`, or a nul byte, otherwise an
+//! [`Input::InvalidName`] error is returned. Names share a single namespace
+//! for the user or session, so they should be unique to the application.
+//! Some platforms limit the length of a name, such as to 31 bytes on macOS.
+//!
+//! # Lifetime
+//!
+//! On Unix an object persists until it is removed with [`remove()`], even
+//! after every mapping of it is dropped and the process exits. On Windows the
+//! object is destroyed once the last mapping of it in any process is dropped,
+//! and [`remove()`] has no effect. Portable code should call [`remove()`]
+//! once the object is no longer needed by new processes.
+//!
+//! # Examples
+//!
+//! ```
+//! use vmap::shm;
+//!
+//! # fn main() -> vmap::Result<()> {
+//! # let name = format!("vmap-doc-{}", std::process::id());
+//! let mut map = shm::create(&name, 4096)?;
+//! map[..5].copy_from_slice(b"hello");
+//!
+//! // Typically in another process.
+//! let other = shm::open(&name)?;
+//! assert_eq!(b"hello", &other[..5]);
+//!
+//! shm::remove(&name)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`MapMut::shared()`]: ../struct.MapMut.html#method.shared
+//! [`Input::InvalidName`]: ../enum.Input.html#variant.InvalidName
+//! [`remove()`]: fn.remove.html
+
+use crate::os::{map_shm, unlink_shm};
+use crate::sealed::FromPtr;
+use crate::{track_map, MapFlags, MapMut, Protect, Result, Size};
+
+/// Creates a named shared memory object of `len` bytes and maps it.
+///
+/// The memory starts zeroed. An error is returned if an object with the name
+/// already exists, or with [`Input::InvalidRange`] if `len` is zero.
+///
+/// [`Input::InvalidRange`]: ../enum.Input.html#variant.InvalidRange
+pub fn create(name: &str, len: usize) -> Result<MapMut> {
+    let (ptr, len) = map_shm(name, len, true)?;
+    Ok(from_shm(ptr, len))
+}
+
+/// Opens an existing named shared memory object and maps all of it.
+///
+/// The length of the map is the size of the object. On Windows this is
+/// rounded up to the page size.
+pub fn open(name: &str) -> Result<MapMut> {
+    let (ptr, len) = map_shm(name, 0, false)?;
+    Ok(from_shm(ptr, len))
+}
+
+/// Removes the name of a shared memory object.
+///
+/// Maps of the object remain valid, and the memory is released once the last
+/// of them is dropped, but the name can no longer be opened and may be used
+/// to create a new object. On Windows this only checks the name.
+pub fn remove(name: &str) -> Result<()> {
+    unlink_shm(name)
+}
+
+fn from_shm(ptr: *mut u8, len: usize) -> MapMut {
+    track_map(Size::alloc().round(len));
+    unsafe { MapMut::from_file_ptr(ptr, len, 0, Protect::ReadWrite, MapFlags::NONE) }
+}
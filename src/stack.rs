@@ -0,0 +1,98 @@
+use std::slice;
+
+use crate::{os, MapMut, Result, Size};
+
+/// Anonymous stack with a guard page below its usable region.
+///
+/// The stack grows down, from [`.top()`] toward [`.bottom()`]. The page
+/// immediately below [`.bottom()`] has all access removed, so a stack
+/// overflow faults rather than silently corrupting adjacent memory. This is
+/// intended for the stacks of coroutines, fibers, or threads that are
+/// managed manually.
+///
+/// The stack is private to the process, and its pages are only committed as
+/// they are touched.
+///
+/// # Examples
+///
+/// ```
+/// use vmap::Stack;
+///
+/// # fn main() -> vmap::Result<()> {
+/// let mut stack = Stack::new(64 * 1024)?;
+/// assert!(stack.size() >= 64 * 1024);
+/// assert_eq!(stack.top() as usize - stack.bottom() as usize, stack.size());
+///
+/// // The highest bytes are used first by a stack that grows down.
+/// let buf = stack.as_mut_slice();
+/// let n = buf.len();
+/// buf[n - 8..].copy_from_slice(&[1; 8]);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`.top()`]: #method.top
+/// [`.bottom()`]: #method.bottom
+#[derive(Debug)]
+pub struct Stack {
+    map: MapMut,
+    guard: usize,
+}
+
+impl Stack {
+    /// Creates a stack with at least `size` usable bytes.
+    ///
+    /// The usable size is rounded up to a whole number of pages, and one
+    /// more page is mapped below it as the guard.
+    pub fn new(size: usize) -> Result<Self> {
+        let page = Size::page();
+        let guard = page.size(1);
+        let size = page.round(size.max(1));
+        let mut map = MapMut::with_options().copy().len(guard + size).alloc()?;
+        unsafe { os::guard(map.as_mut_ptr(), guard)? };
+        Ok(Self { map, guard })
+    }
+
+    /// Gets the number of usable bytes in the stack.
+    ///
+    /// This excludes the guard page.
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.map.len() - self.guard
+    }
+
+    /// Gets the number of bytes in the guard page.
+    #[inline]
+    pub fn guard_size(&self) -> usize {
+        self.guard
+    }
+
+    /// Gets a pointer to the lowest usable byte of the stack.
+    ///
+    /// The guard page lies immediately below this address.
+    #[inline]
+    pub fn bottom(&self) -> *const u8 {
+        unsafe { self.map.as_ptr().add(self.guard) }
+    }
+
+    /// Gets a pointer one past the highest usable byte of the stack.
+    ///
+    /// This is the initial stack pointer for a stack that grows down. It is
+    /// aligned to the page size.
+    #[inline]
+    pub fn top(&self) -> *mut u8 {
+        unsafe { self.map.as_ptr().add(self.map.len()) as *mut u8 }
+    }
+
+    /// Get an immutable slice of the usable region.
+    #[inline]
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.bottom(), self.size()) }
+    }
+
+    /// Get a mutable slice of the usable region.
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.bottom() as *mut u8, self.size()) }
+    }
+}
@@ -0,0 +1,137 @@
+use std::convert::TryFrom;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::{mem, slice};
+
+use crate::{ConvertResult, Error, FromBytes, Input, MapMut, Operation, Span, SpanMut};
+
+/// Writable map viewed as a slice of fixed-size values.
+///
+/// The alignment and length of the map are validated once when the typed
+/// map is created, after which it dereferences directly to `[T]`. This suits
+/// files holding an array of homogeneous records, such as a column of a
+/// column store.
+///
+/// A typed map of any type implementing [`FromBytes`], including the
+/// primitive integer and floating point types, is created safely with
+/// [`TryFrom`]. Other types use the unsafe [`TypedMap::new()`].
+///
+/// # Examples
+///
+/// ```
+/// use vmap::{MapMut, TypedMap};
+/// use std::convert::TryFrom;
+///
+/// # fn main() -> vmap::Result<()> {
+/// let map = MapMut::new(4096)?;
+/// let mut values = TypedMap::<u64>::try_from(map).map_err(|(e, _)| e)?;
+/// values[3] = 42;
+/// assert_eq!(values[3], 42);
+/// assert_eq!(values.len(), values.as_map().len() / 8);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`FromBytes`]: trait.FromBytes.html
+/// [`TryFrom`]: https://doc.rust-lang.org/std/convert/trait.TryFrom.html
+/// [`TypedMap::new()`]: #method.new
+#[derive(Debug)]
+pub struct TypedMap<T> {
+    map: MapMut,
+    len: usize,
+    marker: PhantomData<T>,
+}
+
+impl<T: Copy> TypedMap<T> {
+    /// Views a map as a slice of `T`.
+    ///
+    /// The map must be aligned for `T` and its length must be a multiple of
+    /// the size of `T`. Otherwise an [`Input::Unaligned`] error is returned
+    /// along with the original map. A zero-sized `T` is rejected with an
+    /// [`Input::Unsupported`] error.
+    ///
+    /// # Safety
+    ///
+    /// Every bit pattern of the size of `T` must be a valid value of `T`, as
+    /// the contents of the map are not checked. This excludes types such as
+    /// `bool`, `char`, references, and most enums.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{MapMut, TypedMap};
+    ///
+    /// #[derive(Clone, Copy)]
+    /// #[repr(C)]
+    /// struct Point {
+    ///     x: u32,
+    ///     y: u32,
+    /// }
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let map = MapMut::new(4096)?;
+    /// let mut points = unsafe { TypedMap::<Point>::new(map) }.map_err(|(e, _)| e)?;
+    /// points[1] = Point { x: 3, y: 4 };
+    /// assert_eq!(points[1].y, 4);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Input::Unaligned`]: enum.Input.html#variant.Unaligned
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    pub unsafe fn new(map: MapMut) -> ConvertResult<Self, MapMut> {
+        let size = mem::size_of::<T>();
+        if size == 0 {
+            return Err((Error::input(Operation::Cast, Input::Unsupported), map));
+        }
+        if !(map.as_ptr() as usize).is_multiple_of(mem::align_of::<T>())
+            || !map.len().is_multiple_of(size)
+        {
+            return Err((Error::input(Operation::Cast, Input::Unaligned), map));
+        }
+        Ok(Self {
+            len: map.len() / size,
+            map,
+            marker: PhantomData,
+        })
+    }
+}
+
+impl<T> TypedMap<T> {
+    /// Gets the underlying map.
+    #[inline]
+    pub fn as_map(&self) -> &MapMut {
+        &self.map
+    }
+
+    /// Transfers ownership of the underlying map back to the caller.
+    #[inline]
+    pub fn into_inner(self) -> MapMut {
+        self.map
+    }
+}
+
+impl<T: FromBytes> TryFrom<MapMut> for TypedMap<T> {
+    type Error = (Error, MapMut);
+
+    fn try_from(map: MapMut) -> ConvertResult<Self, MapMut> {
+        // Every bit pattern is a valid value of a `FromBytes` type.
+        unsafe { Self::new(map) }
+    }
+}
+
+impl<T> Deref for TypedMap<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.map.as_ptr() as *const T, self.len) }
+    }
+}
+
+impl<T> DerefMut for TypedMap<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.map.as_mut_ptr() as *mut T, self.len) }
+    }
+}
@@ -0,0 +1,414 @@
+use std::fs::{File, OpenOptions};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use std::{cmp, mem, ptr, slice};
+
+use crate::{allocation_size, Error, Flush, FromBytes, Input, MapMut, Operation, Result};
+
+/// Strategy for how far a [`MapVec`] grows when it runs out of capacity.
+///
+/// Each policy picks the new capacity, in values, from the current one. The
+/// result is always raised to fit the values being added, so a policy can
+/// never cause a reserve to fall short. The map of an anonymous vector is
+/// also rounded up to the page size, so its capacity may exceed the choice.
+///
+/// Growing an anonymous vector resizes its map, which on Linux uses `mremap`
+/// and can often extend the mapping in place, while elsewhere it maps a new
+/// range and copies the values. Growing a file-backed vector first extends
+/// the file with `set_len` and then remaps it, and the spare capacity is
+/// stored in the file as zeros until the vector is closed.
+///
+/// [`MapVec`]: struct.MapVec.html
+#[derive(Clone, Copy, Debug, Default)]
+pub enum GrowthPolicy {
+    /// At least doubles the capacity, starting from one allocation size.
+    ///
+    /// Repeated pushes grow the vector only a logarithmic number of times,
+    /// which keeps the number of system calls and copies low, but up to half
+    /// of the capacity may be unused.
+    #[default]
+    Double,
+    /// Adds a fixed number of values to the capacity.
+    ///
+    /// This bounds the unused capacity, and so the zeros stored in the file,
+    /// which suits filesystems that handle sparse or preallocated space
+    /// poorly. Each growth is a system call, and possibly a copy, so the
+    /// increment should be large relative to the rate of pushes.
+    Fixed(usize),
+    /// Computes the new capacity from the current capacity.
+    Custom(fn(usize) -> usize),
+}
+
+impl GrowthPolicy {
+    // Gets the capacity to grow to from `cap` to hold at least `need` values
+    // of `size` bytes.
+    fn grow(self, cap: usize, need: usize, size: usize) -> usize {
+        let next = match self {
+            GrowthPolicy::Double => {
+                let min = cmp::max(allocation_size() / size, 1);
+                cmp::max(cap.saturating_mul(2), min)
+            }
+            GrowthPolicy::Fixed(n) => cap.saturating_add(n),
+            GrowthPolicy::Custom(f) => f(cap),
+        };
+        cmp::max(need, next)
+    }
+}
+
+/// Growable array of values stored in a map.
+///
+/// This works like a `Vec<T>`, but the values live in an anonymous map or,
+/// when opened with [`MapVec::open()`], in a file. The capacity grows by
+/// remapping, which on Linux can extend the mapping in place rather than
+/// copying the values. The vector dereferences to `[T]`, so the values may
+/// be read and modified as a slice.
+///
+/// # Persistence
+///
+/// While a file-backed vector is open, the file is kept at the length of
+/// the capacity, so any spare capacity is stored as zeros. The file is
+/// truncated to the values in use when the vector is dropped or
+/// [`.close()`]d, and an existing file is read as a vector of its whole
+/// length. Changes are written back by the system eventually; use
+/// [`.flush()`], or [`.set_flush_on_drop()`] to flush when closing, where
+/// they must be durable.
+///
+/// # Examples
+///
+/// ```
+/// use vmap::MapVec;
+/// use std::path::PathBuf;
+///
+/// # fn main() -> vmap::Result<()> {
+/// # let tmp = tempdir::TempDir::new("vmap")?;
+/// let path: PathBuf = /* path to file */
+/// # tmp.path().join("example");
+/// let mut values = MapVec::<u64>::open(&path)?;
+/// values.push(1)?;
+/// values.extend_from_slice(&[2, 3])?;
+/// values.close()?;
+///
+/// let mut values = MapVec::<u64>::open(&path)?;
+/// assert_eq!(&[1, 2, 3], &values[..]);
+/// assert_eq!(Some(3), values.pop());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`MapVec::open()`]: #method.open
+/// [`.close()`]: #method.close
+/// [`.flush()`]: #method.flush
+/// [`.set_flush_on_drop()`]: #method.set_flush_on_drop
+#[derive(Debug)]
+pub struct MapVec<T> {
+    map: MapMut,
+    file: Option<File>,
+    len: usize,
+    flush_on_drop: Option<Flush>,
+    policy: GrowthPolicy,
+    marker: PhantomData<T>,
+}
+
+impl<T: FromBytes> MapVec<T> {
+    /// Creates an empty anonymous vector.
+    ///
+    /// Nothing is mapped until the first value is added. A zero-sized `T`
+    /// is rejected with an [`Input::Unsupported`] error.
+    ///
+    /// [`Input::Unsupported`]: enum.Input.html#variant.Unsupported
+    pub fn new() -> Result<Self> {
+        Self::with_capacity(0)
+    }
+
+    /// Creates an empty anonymous vector with room for at least `cap`
+    /// values.
+    pub fn with_capacity(cap: usize) -> Result<Self> {
+        let bytes = Self::bytes(cap)?;
+        Ok(Self::from_parts(MapMut::new(bytes)?, None, 0))
+    }
+
+    /// Opens or creates a file-backed vector.
+    ///
+    /// The file is opened for reading and writing and is created if it does
+    /// not exist. Every value in an existing file is taken as in use, and
+    /// the length of the file must be a multiple of the size of `T`,
+    /// otherwise an [`Input::Unaligned`] error is returned.
+    ///
+    /// [`Input::Unaligned`]: enum.Input.html#variant.Unaligned
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::bytes(0)?;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(|e| Error::io(Operation::MapFile, e))?;
+        let flen = file
+            .metadata()
+            .map_err(|e| Error::io(Operation::MapFile, e))?
+            .len() as usize;
+        if !flen.is_multiple_of(mem::size_of::<T>()) {
+            return Err(
+                Error::input(Operation::MapFile, Input::Unaligned).with_file_len(flen as u64)
+            );
+        }
+        let map = match flen {
+            0 => MapMut::new(0)?,
+            _ => MapMut::with_options().map(&file)?,
+        };
+        Ok(Self::from_parts(
+            map,
+            Some(file),
+            flen / mem::size_of::<T>(),
+        ))
+    }
+
+    /// Appends a value to the end of the vector.
+    ///
+    /// The capacity is grown first if the vector is full. Should that fail,
+    /// the error is returned and the vector is unchanged.
+    pub fn push(&mut self, value: T) -> Result<()> {
+        self.reserve(1)?;
+        unsafe { ptr::write(self.as_mut_ptr().add(self.len), value) };
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Appends every value in a slice to the end of the vector.
+    pub fn extend_from_slice(&mut self, values: &[T]) -> Result<()> {
+        self.reserve(values.len())?;
+        unsafe {
+            let dst = self.as_mut_ptr().add(self.len);
+            ptr::copy_nonoverlapping(values.as_ptr(), dst, values.len());
+        }
+        self.len += values.len();
+        Ok(())
+    }
+
+    /// Reserves capacity for at least `additional` more values.
+    ///
+    /// When the vector grows, the new capacity is chosen by its
+    /// [`GrowthPolicy`], which by default at least doubles it. A file-backed
+    /// vector extends the file to the new capacity. An [`Input::InvalidRange`]
+    /// error is returned if the capacity would overflow.
+    ///
+    /// [`GrowthPolicy`]: enum.GrowthPolicy.html
+    /// [`Input::InvalidRange`]: enum.Input.html#variant.InvalidRange
+    pub fn reserve(&mut self, additional: usize) -> Result<()> {
+        let cap = self.capacity();
+        let need = self
+            .len
+            .checked_add(additional)
+            .ok_or_else(|| Error::input(Operation::Reserve, Input::InvalidRange))?;
+        if need <= cap {
+            return Ok(());
+        }
+        let bytes = Self::bytes(self.policy.grow(cap, need, mem::size_of::<T>()))?;
+        match self.file {
+            Some(ref file) => {
+                file.set_len(bytes as u64)
+                    .map_err(|e| Error::io(Operation::MapFile, e))?;
+                if self.map.is_empty() {
+                    self.map = MapMut::with_options().map(file)?;
+                } else {
+                    self.map.remap(file, 0, bytes)?;
+                }
+                Ok(())
+            }
+            None => self.map.resize(bytes),
+        }
+    }
+}
+
+impl<T> MapVec<T> {
+    fn from_parts(map: MapMut, file: Option<File>, len: usize) -> Self {
+        Self {
+            map,
+            file,
+            len,
+            flush_on_drop: None,
+            policy: GrowthPolicy::Double,
+            marker: PhantomData,
+        }
+    }
+
+    /// Sets the policy used to grow the capacity when the vector is full.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use vmap::{GrowthPolicy, MapVec};
+    ///
+    /// # fn main() -> vmap::Result<()> {
+    /// let mut values = MapVec::<u64>::new()?.with_policy(GrowthPolicy::Fixed(1000));
+    /// values.push(1)?;
+    /// assert!(values.capacity() >= 1000);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_policy(mut self, policy: GrowthPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    // Gets the number of bytes used by `count` values.
+    fn bytes(count: usize) -> Result<usize> {
+        if mem::size_of::<T>() == 0 {
+            return Err(Error::input(Operation::Cast, Input::Unsupported));
+        }
+        count
+            .checked_mul(mem::size_of::<T>())
+            .ok_or_else(|| Error::input(Operation::Reserve, Input::InvalidRange))
+    }
+
+    /// Gets the number of values in the vector.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Tests if the vector holds no values.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Gets the number of values the vector can hold without remapping.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.map.len() / mem::size_of::<T>()
+    }
+
+    /// Removes the last value and returns it, or `None` if it is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        Some(unsafe { ptr::read(self.as_ptr().add(self.len)) })
+    }
+
+    /// Shortens the vector to `len` values.
+    ///
+    /// This has no effect if `len` is not less than the current length. The
+    /// capacity is unchanged.
+    pub fn truncate(&mut self, len: usize) {
+        self.len = cmp::min(self.len, len);
+    }
+
+    /// Removes every value, leaving the capacity unchanged.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Gets a pointer to the first value.
+    #[inline]
+    pub fn as_ptr(&self) -> *const T {
+        self.map.as_ptr() as *const T
+    }
+
+    /// Gets a mutable pointer to the first value.
+    #[inline]
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.map.as_mut_ptr() as *mut T
+    }
+
+    /// Gets the values as a slice.
+    #[inline]
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.as_ptr(), self.len) }
+    }
+
+    /// Gets the values as a mutable slice.
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.as_mut_ptr(), self.len) }
+    }
+
+    /// Gets the file backing the vector, if any.
+    #[inline]
+    pub fn file(&self) -> Option<&File> {
+        self.file.as_ref()
+    }
+
+    /// Writes the values in use back to the file.
+    ///
+    /// This does nothing for an anonymous vector.
+    pub fn flush(&self, mode: Flush) -> Result<()> {
+        match self.file {
+            Some(ref file) if self.len > 0 => {
+                let bytes = self.len * mem::size_of::<T>();
+                self.map.flush_range(file, 0, bytes, mode)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Flushes the values in use to the file whenever the vector is closed.
+    ///
+    /// This has no effect on an anonymous vector. As with unmapping, any
+    /// error in `Drop` is ignored, so call [`.close()`] where the result
+    /// must be known.
+    ///
+    /// [`.close()`]: #method.close
+    pub fn set_flush_on_drop(&mut self, mode: Flush) {
+        self.flush_on_drop = Some(mode);
+    }
+
+    /// Unmaps the vector now and returns any error from doing so.
+    ///
+    /// A file-backed vector is first flushed if requested by
+    /// [`.set_flush_on_drop()`], and the file is then truncated to the
+    /// values in use. Dropping the vector performs the same steps but
+    /// ignores any error.
+    ///
+    /// [`.set_flush_on_drop()`]: #method.set_flush_on_drop
+    pub fn close(mut self) -> Result<()> {
+        self.release()
+    }
+
+    fn release(&mut self) -> Result<()> {
+        let file = match self.file.take() {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+        let bytes = self.len * mem::size_of::<T>();
+        let mut res = Ok(());
+        if let (Some(mode), true) = (self.flush_on_drop, bytes > 0) {
+            res = self.map.flush_range(&file, 0, bytes, mode);
+        }
+        // The file cannot be truncated while mapped on Windows.
+        if !self.map.is_empty() {
+            res = res.and(self.map.remap(&file, 0, 0));
+        }
+        res.and(
+            file.set_len(bytes as u64)
+                .map_err(|e| Error::io(Operation::MapFile, e)),
+        )
+    }
+}
+
+impl<T> Deref for MapVec<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<T> DerefMut for MapVec<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.as_mut_slice()
+    }
+}
+
+impl<T> Drop for MapVec<T> {
+    fn drop(&mut self) {
+        self.release().unwrap_or_default();
+    }
+}